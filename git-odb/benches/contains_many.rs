@@ -0,0 +1,58 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use git_odb::Find;
+
+/// The amount of copies of each of the packs of the fixture, to simulate a repository with many packs.
+const NUM_COPIES_PER_PACK: usize = 30;
+
+/// The amount of ids that don't exist, as sent by a client that has objects the server doesn't know.
+const NUM_UNKNOWN_IDS: usize = 200;
+
+fn handle_with_many_packs() -> (git_odb::Handle, tempfile::TempDir) {
+    let fixture_pack_dir = std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/objects/pack"));
+    let dir = tempfile::tempdir().expect("temp dir can be created");
+    let pack_dir = dir.path().join("pack");
+    std::fs::create_dir(&pack_dir).expect("pack dir can be created");
+    for entry in std::fs::read_dir(fixture_pack_dir).expect("fixture exists") {
+        let path = entry.expect("readable entry").path();
+        if path.extension().map_or(true, |ext| ext != "pack") {
+            continue;
+        }
+        let stem = path.file_stem().expect("named").to_str().expect("UTF-8");
+        for copy in 0..NUM_COPIES_PER_PACK {
+            for ext in ["pack", "idx"] {
+                std::fs::copy(
+                    path.with_extension(ext),
+                    pack_dir.join(format!("{}-{}", stem, copy)).with_extension(ext),
+                )
+                .expect("pack can be copied");
+            }
+        }
+    }
+    let mut handle = git_odb::at(dir.path()).expect("valid object path");
+    handle.refresh_never();
+    handle.packed_object_count().expect("all indices can be loaded");
+    (handle, dir)
+}
+
+fn have_lists(c: &mut Criterion) {
+    let (handle, _dir) = handle_with_many_packs();
+    let mut ids: Vec<_> = handle
+        .iter()
+        .expect("iteration is possible")
+        .map(|id| id.expect("valid id"))
+        .collect();
+    ids.extend(
+        (0..NUM_UNKNOWN_IDS)
+            .map(|n| git_hash::ObjectId::from_hex(format!("{:040x}", n * 7919).as_bytes()).expect("valid hex")),
+    );
+
+    let mut group = c.benchmark_group(format!("existence of {} ids in many packs", ids.len()));
+    group.bench_function("contains() for each id", |b| {
+        b.iter(|| ids.iter().map(|id| handle.contains(id)).collect::<Vec<_>>())
+    });
+    group.bench_function("contains_many()", |b| b.iter(|| handle.contains_many(&ids)));
+    group.finish();
+}
+
+criterion_group!(benches, have_lists);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use git_odb::Find;
+
+/// Ids of objects stored in each of the packs of the fixture.
+const IDS: &[&str] = &[
+    "501b297447a8255d3533c6858bb692575cdefaa0",
+    "0d9726f3fd2359cda58217724f12af273bf440a6",
+    "dd25c539efbb0ab018caa4cda2d133285634e9b5",
+];
+
+/// The amount of lookups of the same object, as done when reading objects that are stored close to each other.
+const NUM_REPEATED_LOOKUPS: usize = 100;
+
+fn handle_with_loaded_indices() -> git_odb::Handle {
+    let handle =
+        git_odb::at(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/objects")).expect("valid object path");
+    handle.packed_object_count().expect("all indices can be loaded");
+    handle
+}
+
+fn repeated_lookups_in_each_pack(c: &mut Criterion) {
+    let ids: Vec<_> = IDS
+        .iter()
+        .map(|hex| git_hash::ObjectId::from_hex(hex.as_bytes()).expect("valid id"))
+        .collect();
+    let handle = handle_with_loaded_indices();
+
+    let mut group = c.benchmark_group("repeated lookups of objects in each pack");
+    group.bench_function("indices in the order of the store", |b| {
+        b.iter_batched(
+            || vec![handle.clone(); NUM_REPEATED_LOOKUPS],
+            |handles| {
+                // Each handle starts out with the order of the store, so each lookup searches the same indices.
+                for id in &ids {
+                    for handle in &handles {
+                        assert!(handle.contains(id));
+                    }
+                }
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.bench_function("indices promoted on hit", |b| {
+        b.iter_batched(
+            || handle.clone(),
+            |handle| {
+                for id in &ids {
+                    for _ in 0..NUM_REPEATED_LOOKUPS {
+                        assert!(handle.contains(id));
+                    }
+                }
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, repeated_lookups_in_each_pack);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use git_odb::Find;
+
+/// Ids of objects stored in each of the packs of the fixture.
+const IDS: &[&str] = &[
+    "501b297447a8255d3533c6858bb692575cdefaa0",
+    "0d9726f3fd2359cda58217724f12af273bf440a6",
+    "dd25c539efbb0ab018caa4cda2d133285634e9b5",
+];
+
+fn store_with_loaded_indices() -> git_odb::Handle {
+    let handle =
+        git_odb::at(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/objects")).expect("valid object path");
+    handle.packed_object_count().expect("all indices can be loaded");
+    handle
+}
+
+fn first_lookups(c: &mut Criterion) {
+    let ids: Vec<_> = IDS
+        .iter()
+        .map(|hex| git_hash::ObjectId::from_hex(hex.as_bytes()).expect("valid id"))
+        .collect();
+    let lookup_all = |handle: git_odb::Handle| {
+        for id in &ids {
+            assert!(handle.contains(id));
+        }
+    };
+
+    let mut group = c.benchmark_group("first lookups after loading all indices");
+    group.bench_function("cold", |b| {
+        b.iter_batched(store_with_loaded_indices, lookup_all, BatchSize::PerIteration)
+    });
+    group.bench_function("warmed", |b| {
+        b.iter_batched(
+            || {
+                let handle = store_with_loaded_indices();
+                handle.store_ref().warm_index_lookup_tables();
+                handle
+            },
+            lookup_all,
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, first_lookups);
+criterion_main!(benches);
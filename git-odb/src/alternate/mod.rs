@@ -6,7 +6,7 @@
 //!
 //! ```text
 //! # a comment, empty lines are also allowed
-//! # relative paths resolve relative to the parent git repository
+//! # relative paths resolve relative to the objects directory containing this file
 //! ../path/relative/to/repo/.git
 //! /absolute/path/to/repo/.git
 //!
@@ -33,42 +33,67 @@ pub enum Error {
     Realpath(#[from] git_path::realpath::Error),
     #[error(transparent)]
     Parse(#[from] parse::Error),
-    #[error("Alternates form a cycle: {} -> {}", .0.iter().map(|p| format!("'{}'", p.display())).collect::<Vec<_>>().join(" -> "), .0.first().expect("more than one directories").display())]
-    Cycle(Vec<PathBuf>),
+    #[error("The alternate object directory at '{}' is not an accessible directory", .0.display())]
+    Inaccessible(PathBuf),
+    #[error("Alternate at '{}' would exceed the maximum of {limit} alternates to follow", .path.display())]
+    TooManyAlternates { path: PathBuf, limit: usize },
 }
 
 /// Given an `objects_directory`, try to resolve alternate object directories possibly located in the
 /// `./info/alternates` file into canonical paths and resolve relative paths with the help of the `current_dir`.
 /// If no alternate object database was resolved, the resulting `Vec` is empty (it is not an error
 /// if there are no alternates).
-/// It is an error once a repository is seen again as it would lead to a cycle.
+///
+/// Relative paths are relative to the objects directory whose `info/alternates` file declares them, and it is an error if
+/// an alternate isn't an accessible directory. Like git, alternates are followed depth-first in the order they are listed,
+/// and object directories that were seen before, including `objects_directory` itself, are skipped. That way cycles are
+/// broken and each object directory is returned only once.
 pub fn resolve(
     objects_directory: impl Into<PathBuf>,
     current_dir: impl AsRef<std::path::Path>,
 ) -> Result<Vec<PathBuf>, Error> {
-    let relative_base = objects_directory.into();
-    let mut dirs = vec![(0, relative_base.clone())];
+    resolve_opts(objects_directory, current_dir, None)
+}
+
+/// Like [`resolve()`], but fails if more than `max_alternates` alternate object directories, direct or indirect, would be followed.
+///
+/// This helps to bound the resources used for repositories whose alternates aren't under our control.
+pub fn resolve_opts(
+    objects_directory: impl Into<PathBuf>,
+    current_dir: impl AsRef<std::path::Path>,
+    max_alternates: Option<usize>,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut dirs = vec![objects_directory.into()];
     let mut out = Vec::new();
     let cwd = current_dir.as_ref();
-    let mut seen = vec![git_path::realpath_opts(&relative_base, cwd, MAX_SYMLINKS)?];
-    while let Some((depth, dir)) = dirs.pop() {
-        match fs::read(dir.join("info").join("alternates")) {
+    let mut seen = Vec::new();
+    while let Some(dir) = dirs.pop() {
+        let dir_canonicalized = git_path::realpath_opts(&dir, cwd, MAX_SYMLINKS)?;
+        if seen.contains(&dir_canonicalized) {
+            continue;
+        }
+        let is_alternate = !seen.is_empty();
+        if is_alternate {
+            if let Some(limit) = max_alternates.filter(|limit| seen.len() > *limit) {
+                return Err(Error::TooManyAlternates { path: dir, limit });
+            }
+            if !dir_canonicalized.is_dir() {
+                return Err(Error::Inaccessible(dir));
+            }
+        }
+        seen.push(dir_canonicalized.clone());
+
+        match fs::read(dir_canonicalized.join("info").join("alternates")) {
             Ok(input) => {
-                for path in parse::content(&input)?.into_iter() {
-                    let path = relative_base.join(path);
-                    let path_canonicalized = git_path::realpath_opts(&path, cwd, MAX_SYMLINKS)?;
-                    if seen.contains(&path_canonicalized) {
-                        return Err(Error::Cycle(seen));
-                    }
-                    seen.push(path_canonicalized);
-                    dirs.push((depth + 1, path));
-                }
+                let alternates = parse::content(&input)?;
+                // the stack is processed from the back, but the first alternate is to be followed first.
+                dirs.extend(alternates.into_iter().rev().map(|path| dir_canonicalized.join(path)));
             }
             Err(err) if err.kind() == io::ErrorKind::NotFound => {}
             Err(err) => return Err(err.into()),
         };
-        if depth != 0 {
-            out.push(dir);
+        if is_alternate {
+            out.push(dir_canonicalized);
         }
     }
     Ok(out)
@@ -99,6 +99,10 @@ use store::types;
 pub struct Store {
     /// The central write lock without which the slotmap index can't be changed.
     write: parking_lot::Mutex<()>,
+    /// The state of the refresh from disk that may currently be in progress, for other threads to wait for and reuse its outcome.
+    pub(crate) refresh: parking_lot::Mutex<types::RefreshState>,
+    /// Notified whenever a refresh from disk completes.
+    pub(crate) refresh_done: parking_lot::Condvar,
 
     /// The source directory from which all content is loaded, and the central write lock for use when a directory refresh is needed.
     pub(crate) path: PathBuf,
@@ -116,9 +120,10 @@ pub struct Store {
 
     /// The below state acts like a slot-map with each slot is mutable when the write lock is held, but readable independently of it.
     /// This allows multiple file to be loaded concurrently if there is multiple handles requesting to load packs or additional indices.
-    /// The map is static and cannot typically change.
+    /// The map never grows or shrinks after creation so slot indices remain valid for as long as the store exists. If there are more
+    /// indices on disk than slots, refreshing fails with [`InsufficientSlots`][store::load_index::Error::InsufficientSlots].
     /// It's read often and changed rarely.
-    pub(crate) files: Vec<types::MutableIndexAndPack>,
+    pub(crate) files: Arc<Vec<types::MutableIndexAndPack>>,
 
     /// The amount of handles that would prevent us from unloading packs or indices
     pub(crate) num_handles_stable: AtomicUsize,
@@ -127,10 +132,51 @@ pub struct Store {
 
     /// The amount of times we re-read the disk state to consolidate our in-memory representation.
     pub(crate) num_disk_state_consolidation: AtomicUsize,
+    /// Cumulative counts of lookups performed by all handles, which can be reset.
+    pub(crate) lookup_counters: types::AtomicLookupCounters,
     /// If true, we are allowed to use multi-pack indices and they must have the `object_hash` or be ignored.
     use_multi_pack_index: bool,
     /// The hash kind to use for some operations
     object_hash: git_hash::Kind,
+    /// An optional name for use in diagnostics only.
+    label: Option<String>,
+    /// If false, the slot map was filled with explicitly given files once and is never reconciled with the state on disk.
+    /// There are no loose object databases either.
+    pub(crate) scan_disk: bool,
+    /// If true, packs are checked to belong to their index when loading them.
+    pub(crate) verify_pack_matches_index_on_load: bool,
+    /// The maximum amount of alternates to follow, if set.
+    pub(crate) max_alternates: Option<usize>,
+    /// If true, indices without objects are not registered.
+    pub(crate) ignore_empty_packs: bool,
+    /// The way files are opened.
+    pub(crate) backend: Arc<dyn store::backend::Backend>,
+    /// The maximum amount of index and pack files to keep loaded, if set, shared with the index loader threads.
+    pub(crate) max_open_files: Option<Arc<types::OpenFilesBudget>>,
+    /// Incremented whenever a pack is handed out by a slot, to learn which slots were used least recently.
+    pub(crate) pack_use_clock: AtomicUsize,
+    /// The size of the buffer to read loose objects through, if set.
+    pub(crate) loose_read_buffer_size: Option<usize>,
+    /// If true, the hash of objects is recomputed and verified when finding them.
+    pub(crate) verify_object_hashes: bool,
+    /// The transformation to apply to the data of objects when reading and writing them, if set.
+    pub(crate) transform: Option<Arc<dyn store::transform::Transform>>,
+    /// The channels to send events about refreshes to, one per subscriber.
+    pub(crate) subscribers: parking_lot::Mutex<Vec<std::sync::mpsc::SyncSender<store::events::Event>>>,
+    /// The callbacks to call when the state of the object database changes, in the order they were registered.
+    pub(crate) state_change_callbacks: ArcSwap<Vec<Arc<store::events::StateChangeCallback>>>,
+    /// The pack counts observed by the most recent refreshes, oldest first.
+    pub(crate) pack_count_history: parking_lot::Mutex<std::collections::VecDeque<store::pressure::Sample>>,
+    /// If true, slots are assigned to new indices in the order of their file names instead of the order they were found in.
+    pub(crate) deterministic_slot_assignment: bool,
+    /// If false, packs and indices kept only for pack id stability aren't used for lookups.
+    pub(crate) garbage_slots_serve_lookups: bool,
+    /// If true, packs found to be corrupt while reading from them are poisoned and skipped by lookups.
+    pub(crate) quarantine_corrupt_packs: bool,
+    /// The order in which indices are searched for objects, before handles reorder them by use.
+    pub(crate) index_sort_order: store::init::IndexSortOrder,
+    /// The threads loading indices in the background, if enabled.
+    pub(crate) index_loaders: Option<store::index_loaders::IndexLoaders>,
 }
 
 /// Create a new cached handle to the object store with support for additional options.
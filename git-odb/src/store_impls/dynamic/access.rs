@@ -1,4 +1,9 @@
-use crate::Store;
+use std::sync::atomic::Ordering;
+
+use crate::{
+    store::{MarkerChange, MarkerDiff, SlotIndexMarker, SlotIndexMarkerGuard, SlotMapIndex},
+    Store,
+};
 
 impl Store {
     /// The root path at which we expect to find all objects and packs, and which is the source of the
@@ -8,6 +13,9 @@ impl Store {
     }
 
     /// The kind of object hash to assume when dealing with pack indices and pack data files.
+    ///
+    /// Use [`len_in_bytes()`][git_hash::Kind::len_in_bytes()] and [`len_in_hex()`][git_hash::Kind::len_in_hex()] to size buffers
+    /// for the object ids the store deals in, instead of assuming the lengths of a particular hash.
     pub fn object_hash(&self) -> git_hash::Kind {
         self.object_hash
     }
@@ -17,8 +25,97 @@ impl Store {
         self.use_multi_pack_index
     }
 
+    /// The label given at construction time to tell this store apart from others in diagnostics, if set.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Return a marker identifying the state of the object database as we currently know it.
+    ///
+    /// It changes whenever the state changes, for example as indices are loaded or changes on disk are picked up.
+    /// Note that it doesn't cause any disk access, so an uninitialized store will return the same marker until it is used.
+    pub fn marker(&self) -> SlotIndexMarker {
+        self.index.load().marker()
+    }
+
+    /// Classify how the state of the object database changed since `marker` was obtained from [`marker()`][Store::marker()].
+    pub fn change_since(&self, marker: SlotIndexMarker) -> MarkerChange {
+        self.marker().change_since(&marker)
+    }
+
+    /// Learn which indices were loaded between the `old` and the `new` marker, both obtained from [`marker()`][Store::marker()],
+    /// to update caches incrementally instead of rebuilding them.
+    ///
+    /// This is computed from the current state of the store, so indices that were removed since `new` was obtained aren't listed.
+    pub fn diff(&self, old: &SlotIndexMarker, new: &SlotIndexMarker) -> MarkerDiff {
+        let index = self.index.load();
+        let current = index.marker();
+        if old.store_id != current.store_id
+            || new.store_id != current.store_id
+            || old.index_load_clock > new.index_load_clock
+            || new.generation != current.generation
+            || old.generation > new.generation
+        {
+            return MarkerDiff::Incomparable;
+        }
+        if old.generation != new.generation {
+            return MarkerDiff::Regenerated;
+        }
+        let mut added: Vec<_> = index
+            .slot_indices
+            .iter()
+            .filter_map(|idx| {
+                let loaded_at = self.files[*idx].index_loaded_at.load(Ordering::SeqCst);
+                (loaded_at > old.index_load_clock && loaded_at <= new.index_load_clock).then(|| (loaded_at, *idx))
+            })
+            .collect();
+        added.sort_by_key(|(loaded_at, _)| *loaded_at);
+        MarkerDiff::Compatible {
+            added: added.into_iter().map(|(_, idx)| idx).collect(),
+        }
+    }
+
     /// An iterator over replacements from object-ids `X` to `X-replaced` as `(X, X-replaced)`, sorted by the original id `X`.
     pub fn replacements(&self) -> impl Iterator<Item = (git_hash::ObjectId, git_hash::ObjectId)> + '_ {
         self.replacements.iter().cloned()
     }
 }
+
+/// A snapshot of the store's configuration and state, for use in diagnostics.
+impl std::fmt::Debug for Store {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Store")
+            .field("path", &self.path)
+            .field("label", &self.label)
+            .field("object_hash", &self.object_hash)
+            .field("metrics", &self.metrics())
+            .field("state", &self.debug_dump())
+            .finish()
+    }
+}
+
+/// Allow code built around `arc-swap` to observe changes to the object database without holding on to the store itself.
+///
+/// Each load returns the [`marker()`][Store::marker()] at the time of the call. It is a copy, so it won't change while the guard
+/// is held, but it may be outdated right after if another thread picks up changes on disk or loads more indices.
+/// A changed marker is a sign to rebuild caches derived from the object database, as it may have gained new objects
+/// or pack ids may have been invalidated.
+impl arc_swap::access::Access<SlotIndexMarker> for Store {
+    type Guard = SlotIndexMarkerGuard;
+
+    fn load(&self) -> Self::Guard {
+        SlotIndexMarkerGuard(self.marker())
+    }
+}
+
+/// Allow code built around `arc-swap` to hold on to the current snapshot of the object database, the same one handles use.
+///
+/// The snapshot doesn't change while the guard is held, even if another thread picks up changes on disk, and loading again yields
+/// the current one.
+impl arc_swap::access::Access<SlotMapIndex> for Store {
+    type Guard = <arc_swap::ArcSwap<SlotMapIndex> as arc_swap::access::Access<SlotMapIndex>>::Guard;
+
+    fn load(&self) -> Self::Guard {
+        arc_swap::access::Access::load(&self.index)
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::SystemTime,
+};
+
+/// A way to open the pack indices, multi-pack indices, reverse indices and pack data files the store reads objects from.
+///
+/// The store still discovers files by scanning directories, but every file it opens, be it for lookups or for maintenance
+/// like verification and statistics, is opened through the backend. This allows to observe or intercept all loads, or to open
+/// files from another location than the one they were discovered at. Use [`Filesystem`] to open them from disk, which is the default.
+///
+/// Files don't have to be opened from the local filesystem, as a backend can also provide their content from memory or a remote
+/// object storage with constructors like [`git_pack::index::File::from_bytes()`]. Note that directories are still scanned to
+/// discover files, and that operations copying or hashing files, like [`copy_pack()`][crate::Store::copy_pack()], read them from disk.
+pub trait Backend: std::fmt::Debug + Send + Sync {
+    /// Open the pack index at `path` whose object ids are of kind `object_hash`.
+    fn open_index(
+        &self,
+        path: &Path,
+        object_hash: git_hash::Kind,
+    ) -> Result<git_pack::index::File, git_pack::index::init::Error>;
+    /// Open the multi-pack index at `path`.
+    fn open_multi_index(&self, path: &Path) -> Result<git_pack::multi_index::File, git_pack::multi_index::init::Error>;
+    /// Open the pack data file at `path` whose object ids are of kind `object_hash`.
+    fn open_pack(
+        &self,
+        path: &Path,
+        object_hash: git_hash::Kind,
+    ) -> Result<git_pack::data::File, git_pack::data::header::decode::Error>;
+    /// Open the reverse index at `path` for the pack index `index`.
+    ///
+    /// Reverse indices are optional, which is why they are read from disk by default, like [`Filesystem`] does.
+    fn open_reverse_index(
+        &self,
+        path: &Path,
+        index: &git_pack::index::File,
+    ) -> Result<git_pack::index::reverse::File, git_pack::index::reverse::Error> {
+        git_pack::index::reverse::File::at(path, index)
+    }
+
+    /// Like [`open_index()`][Backend::open_index()], but return the index in a form that can be handed to multiple stores.
+    ///
+    /// Implement this along with [`open_multi_index_shared()`][Backend::open_multi_index_shared()] and
+    /// [`open_pack_shared()`][Backend::open_pack_shared()] to let stores share files, like [`Shared`] does.
+    fn open_index_shared(
+        &self,
+        path: &Path,
+        object_hash: git_hash::Kind,
+    ) -> Result<Arc<git_pack::index::File>, git_pack::index::init::Error> {
+        self.open_index(path, object_hash).map(Arc::new)
+    }
+    /// Like [`open_multi_index()`][Backend::open_multi_index()], but return the multi-pack index in a form that can be handed to
+    /// multiple stores.
+    fn open_multi_index_shared(
+        &self,
+        path: &Path,
+    ) -> Result<Arc<git_pack::multi_index::File>, git_pack::multi_index::init::Error> {
+        self.open_multi_index(path).map(Arc::new)
+    }
+    /// Like [`open_pack()`][Backend::open_pack()], but return the pack with its [`id`][git_pack::data::File::id] set to `id`
+    /// in a form that can be handed to multiple stores.
+    fn open_pack_shared(
+        &self,
+        path: &Path,
+        object_hash: git_hash::Kind,
+        id: git_pack::data::Id,
+    ) -> Result<Arc<git_pack::data::File>, git_pack::data::header::decode::Error> {
+        self.open_pack(path, object_hash).map(|mut pack| {
+            pack.id = id;
+            Arc::new(pack)
+        })
+    }
+}
+
+/// A [`Backend`] which memory-maps files from the local filesystem.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Filesystem;
+
+impl Backend for Filesystem {
+    fn open_index(
+        &self,
+        path: &Path,
+        object_hash: git_hash::Kind,
+    ) -> Result<git_pack::index::File, git_pack::index::init::Error> {
+        git_pack::index::File::at(path, object_hash)
+    }
+
+    fn open_multi_index(&self, path: &Path) -> Result<git_pack::multi_index::File, git_pack::multi_index::init::Error> {
+        git_pack::multi_index::File::at(path)
+    }
+
+    fn open_pack(
+        &self,
+        path: &Path,
+        object_hash: git_hash::Kind,
+    ) -> Result<git_pack::data::File, git_pack::data::header::decode::Error> {
+        git_pack::data::File::at(path, object_hash)
+    }
+}
+
+/// A [`Backend`] which lets all stores using it share the index and pack files they open, instead of mapping them once per store.
+///
+/// This saves address space and file handles in processes that host many stores which use the same packs, for instance
+/// through shared alternates. Use the same instance as backend of all these stores, and `inner` to actually open files.
+///
+/// Files are identified by their canonical path along with their size and modification time, so a file that changed on disk
+/// is opened again. Only weak references are kept, so a file stays mapped only as long as a store uses it, and limits like
+/// [`max_open_files`][crate::store::init::Options::max_open_files] apply as usual. A store that sees a file deleted stops using
+/// it without affecting other stores which still use it, and the file is unmapped once the last of them let go of it.
+#[derive(Default)]
+pub struct Shared<B = Filesystem> {
+    inner: B,
+    indices: parking_lot::Mutex<HashMap<PathBuf, Cached<git_pack::index::File>>>,
+    multi_indices: parking_lot::Mutex<HashMap<PathBuf, Cached<git_pack::multi_index::File>>>,
+    /// Each store assigns its own ids to packs, which is why there may be multiple instances per pack, all sharing one memory map.
+    packs: parking_lot::Mutex<HashMap<PathBuf, Vec<Cached<git_pack::data::File>>>>,
+}
+
+struct Cached<T> {
+    file: Weak<T>,
+    stamp: Stamp,
+}
+
+/// The size and modification time of a file, to detect if it changed.
+type Stamp = (u64, Option<SystemTime>);
+
+impl<B: Backend> Shared<B> {
+    /// Create a new instance which opens files with `inner`.
+    pub fn new(inner: B) -> Self {
+        Shared {
+            inner,
+            indices: Default::default(),
+            multi_indices: Default::default(),
+            packs: Default::default(),
+        }
+    }
+
+    /// Return the backend used to open files.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+}
+
+impl<B: std::fmt::Debug> std::fmt::Debug for Shared<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shared")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Return the canonical form of `path` along with the stamp of the file it points to.
+fn canonicalize_and_stamp(path: &Path) -> std::io::Result<(PathBuf, Stamp)> {
+    let path = std::fs::canonicalize(path)?;
+    let md = std::fs::metadata(&path)?;
+    Ok((path, (md.len(), md.modified().ok())))
+}
+
+/// Return the file cached for `path` under `map` if it is still in use and didn't change, or open it with `open` and cache it.
+fn get_or_open<T, E>(
+    map: &parking_lot::Mutex<HashMap<PathBuf, Cached<T>>>,
+    path: &Path,
+    open: impl FnOnce() -> Result<T, E>,
+    io_err: impl FnOnce(std::io::Error) -> E,
+) -> Result<Arc<T>, E> {
+    let (key, stamp) = canonicalize_and_stamp(path).map_err(io_err)?;
+    let mut map = map.lock();
+    map.retain(|_, cached| cached.file.strong_count() != 0);
+    if let Some(file) = map
+        .get(&key)
+        .filter(|cached| cached.stamp == stamp)
+        .and_then(|cached| cached.file.upgrade())
+    {
+        return Ok(file);
+    }
+    let file = Arc::new(open()?);
+    map.insert(
+        key,
+        Cached {
+            file: Arc::downgrade(&file),
+            stamp,
+        },
+    );
+    Ok(file)
+}
+
+impl<B: Backend> Backend for Shared<B> {
+    fn open_index(
+        &self,
+        path: &Path,
+        object_hash: git_hash::Kind,
+    ) -> Result<git_pack::index::File, git_pack::index::init::Error> {
+        self.inner.open_index(path, object_hash)
+    }
+
+    fn open_multi_index(&self, path: &Path) -> Result<git_pack::multi_index::File, git_pack::multi_index::init::Error> {
+        self.inner.open_multi_index(path)
+    }
+
+    fn open_pack(
+        &self,
+        path: &Path,
+        object_hash: git_hash::Kind,
+    ) -> Result<git_pack::data::File, git_pack::data::header::decode::Error> {
+        self.inner.open_pack(path, object_hash)
+    }
+
+    fn open_reverse_index(
+        &self,
+        path: &Path,
+        index: &git_pack::index::File,
+    ) -> Result<git_pack::index::reverse::File, git_pack::index::reverse::Error> {
+        self.inner.open_reverse_index(path, index)
+    }
+
+    fn open_index_shared(
+        &self,
+        path: &Path,
+        object_hash: git_hash::Kind,
+    ) -> Result<Arc<git_pack::index::File>, git_pack::index::init::Error> {
+        get_or_open(
+            &self.indices,
+            path,
+            || self.inner.open_index(path, object_hash),
+            |source| git_pack::index::init::Error::Io {
+                source,
+                path: path.to_owned(),
+            },
+        )
+    }
+
+    fn open_multi_index_shared(
+        &self,
+        path: &Path,
+    ) -> Result<Arc<git_pack::multi_index::File>, git_pack::multi_index::init::Error> {
+        get_or_open(
+            &self.multi_indices,
+            path,
+            || self.inner.open_multi_index(path),
+            |source| git_pack::multi_index::init::Error::Io {
+                source,
+                path: path.to_owned(),
+            },
+        )
+    }
+
+    fn open_pack_shared(
+        &self,
+        path: &Path,
+        object_hash: git_hash::Kind,
+        id: git_pack::data::Id,
+    ) -> Result<Arc<git_pack::data::File>, git_pack::data::header::decode::Error> {
+        let (key, stamp) =
+            canonicalize_and_stamp(path).map_err(|source| git_pack::data::header::decode::Error::Io {
+                source,
+                path: path.to_owned(),
+            })?;
+        let mut packs = self.packs.lock();
+        packs.retain(|_, instances| {
+            instances.retain(|cached| cached.file.strong_count() != 0);
+            !instances.is_empty()
+        });
+        let instances = packs.entry(key).or_default();
+        instances.retain(|cached| cached.stamp == stamp);
+        let alive: Vec<_> = instances.iter().filter_map(|cached| cached.file.upgrade()).collect();
+        if let Some(pack) = alive.iter().find(|pack| pack.id == id) {
+            return Ok(Arc::clone(pack));
+        }
+        let pack = Arc::new(match alive.first() {
+            Some(pack) => pack.clone_with_id(id),
+            None => {
+                let mut pack = self.inner.open_pack(path, object_hash)?;
+                pack.id = id;
+                pack
+            }
+        });
+        instances.push(Cached {
+            file: Arc::downgrade(&pack),
+            stamp,
+        });
+        Ok(pack)
+    }
+}
@@ -0,0 +1,59 @@
+use std::ops::Deref;
+
+use git_object::{
+    bstr::{BStr, BString},
+    Data,
+};
+
+use crate::Find;
+
+mod error {
+    use git_object::bstr::BString;
+
+    /// Returned by [`Handle::find_by_ref()`][crate::store::Handle::find_by_ref()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("The reference {name:?} could not be resolved")]
+        Resolve {
+            name: BString,
+            source: Box<dyn std::error::Error + Send + Sync + 'static>,
+        },
+        #[error("The reference {name:?} does not exist")]
+        RefNotFound { name: BString },
+        #[error(transparent)]
+        Find(#[from] crate::store::find::Error),
+    }
+}
+pub use error::Error;
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Resolve the reference `name` to an object id with `resolve_ref` and find the object it points to, storing its data in `buffer`.
+    /// Return the resolved id along with the object, or `None` if the object doesn't exist in this database.
+    ///
+    /// The store doesn't know anything about references, which is why resolving them is entirely delegated to `resolve_ref`.
+    /// It's expected to return the id `name` points to after peeling symbolic references, or `None` if there is no such reference,
+    /// which fails with [`RefNotFound`][Error::RefNotFound].
+    pub fn find_by_ref<'a, E>(
+        &self,
+        name: &BStr,
+        resolve_ref: impl FnOnce(&BStr) -> Result<Option<git_hash::ObjectId>, E>,
+        buffer: &'a mut Vec<u8>,
+    ) -> Result<Option<(git_hash::ObjectId, Data<'a>)>, Error>
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let id = resolve_ref(name)
+            .map_err(|err| Error::Resolve {
+                name: BString::from(name),
+                source: Box::new(err),
+            })?
+            .ok_or_else(|| Error::RefNotFound {
+                name: BString::from(name),
+            })?;
+        Ok(self.try_find(id, buffer)?.map(|data| (id, data)))
+    }
+}
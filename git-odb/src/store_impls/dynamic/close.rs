@@ -0,0 +1,72 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use crate::store::types::SlotMapIndex;
+
+mod error {
+    /// Returned by [`Store::close()`][crate::Store::close()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("Cannot close the store while {count} handle(s) require stable pack ids")]
+        StableHandles { count: usize },
+        #[error(
+            "Would have overflown amount of max possible generations of {}",
+            crate::store::types::Generation::MAX
+        )]
+        GenerationOverflow,
+    }
+}
+pub use error::Error;
+
+impl super::Store {
+    /// Unload all indices and packs, so the memory maps held by the store are released right away instead of once the last
+    /// reference to the store is dropped.
+    ///
+    /// This is useful on systems like Windows, where files can't be deleted while they are mapped. Note that handles keep the
+    /// files they currently use mapped until they are dropped or refresh their view of the store.
+    /// Fails if handles requiring stable pack ids exist, as their pack ids would become invalid.
+    ///
+    /// The store remains usable as all files it knows stay in their slots, and are loaded again when needed, which also holds
+    /// if [index loader threads][crate::store::init::Options::index_loader_threads] are used.
+    pub fn close(&self) -> Result<(), Error> {
+        let _write = self.write.lock();
+        let count = self.num_handles_stable.load(Ordering::SeqCst);
+        if count > 0 {
+            return Err(Error::StableHandles { count });
+        }
+
+        let index = self.index.load();
+        // a new generation makes handles refresh their view instead of trying to use files that are about to be unloaded.
+        let generation = index.generation.checked_add(1).ok_or(Error::GenerationOverflow)?;
+        let mut in_use = vec![false; self.files.len()];
+        for idx in &index.slot_indices {
+            in_use[*idx] = true;
+        }
+        for (slot, in_use) in self.files.iter().zip(in_use) {
+            let _lock = slot.write.lock();
+            if in_use {
+                let mut files = slot.files.load_full();
+                if let Some(files) = Arc::make_mut(&mut files) {
+                    files.unload_all();
+                }
+                slot.files.store(files);
+            } else {
+                // Without stable handles, files that aren't reachable anymore are of no use.
+                slot.files.store(Arc::new(None));
+            }
+            // Not racy due to lock, generation must be set after changing the slot value AND storing it.
+            slot.generation.store(generation, Ordering::SeqCst);
+        }
+        // Background loaders aren't asked to load the indices again, as that would map them right away. Lookups load what they need.
+        self.index.store(Arc::new(SlotMapIndex {
+            slot_indices: index.slot_indices.clone(),
+            loose_dbs: Arc::clone(&index.loose_dbs),
+            generation,
+            next_index_to_load: Default::default(),
+            loaded_indices: Default::default(),
+            num_indices_currently_being_loaded: Default::default(),
+            index_load_clock: Arc::clone(&index.index_load_clock),
+        }));
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use std::{ops::Deref, sync::atomic::Ordering};
+
+use git_hash::{oid, ObjectId};
+
+use crate::store::handle;
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Return whether each of `ids` exists in the object database, in the order of `ids`, like calling
+    /// [`contains()`][crate::Find::contains()] for each of them but more efficiently for many ids.
+    ///
+    /// The ids are sorted and each index is passed over once, checking loose objects only for ids that aren't in any pack.
+    /// All answers come from the same snapshot of the object database, so they are consistent with each other even if
+    /// it is refreshed concurrently. If the snapshot has to change to look for missing objects, all ids are checked again.
+    pub fn contains_many(&self, ids: &[ObjectId]) -> Vec<bool> {
+        let replaced: Vec<&oid> = ids.iter().map(|id| self.replaced_id(id)).collect();
+        let mut by_id: Vec<usize> = (0..ids.len()).collect();
+        by_id.sort_by_key(|idx| replaced[*idx]);
+
+        let mut found = vec![false; ids.len()];
+        let mut snapshot = self.snapshot.borrow_mut();
+        loop {
+            found.iter_mut().for_each(|found| *found = false);
+            let mut num_unknown = by_id.len();
+            for index in snapshot.indices.iter() {
+                if num_unknown == 0 {
+                    break;
+                }
+                let num_objects = index.num_objects();
+                let mut cursor = 0;
+                for &idx in &by_id {
+                    if found[idx] {
+                        continue;
+                    }
+                    let id = replaced[idx];
+                    cursor = lower_bound(index, cursor, num_objects, id);
+                    if cursor == num_objects {
+                        break;
+                    }
+                    if index.oid_at_index(cursor) == id {
+                        self.store.record_pack_hit(index.pack_id_at_index(cursor));
+                        found[idx] = true;
+                        num_unknown -= 1;
+                    }
+                }
+            }
+
+            for &idx in &by_id {
+                if !found[idx] && snapshot.loose_dbs.iter().any(|lodb| lodb.contains(replaced[idx])) {
+                    found[idx] = true;
+                    num_unknown -= 1;
+                }
+            }
+
+            if num_unknown == 0 {
+                break;
+            }
+            match self.store.load_one_index(self.refresh, snapshot.marker) {
+                Ok(Some(new_snapshot)) => {
+                    *snapshot = new_snapshot;
+                    self.clear_cache();
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        let counters = &self.store.lookup_counters;
+        counters.contains.fetch_add(ids.len(), Ordering::Relaxed);
+        counters
+            .misses
+            .fetch_add(found.iter().filter(|found| !**found).count(), Ordering::Relaxed);
+        found
+    }
+}
+
+/// Return the index of the first entry in `index` at or after `start` whose id isn't smaller than `id`, or `end` if there is none.
+fn lower_bound(index: &handle::IndexLookup, mut start: u32, mut end: u32, id: &oid) -> u32 {
+    while start < end {
+        let mid = start + (end - start) / 2;
+        if index.oid_at_index(mid) < id {
+            start = mid + 1;
+        } else {
+            end = mid;
+        }
+    }
+    start
+}
@@ -0,0 +1,216 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use git_features::progress::Progress;
+
+use crate::store::types::{IndexAndPacks, PackId};
+
+mod error {
+    use std::path::PathBuf;
+
+    /// Returned by [`Store::copy_pack()`][crate::Store::copy_pack()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("There is no pack with id {id}")]
+        NotFound { id: git_pack::data::Id },
+        #[error("Could not {message} '{path}'")]
+        Io {
+            source: std::io::Error,
+            message: &'static str,
+            path: PathBuf,
+        },
+        #[error("Could not turn temporary file into persisted file at '{target}'")]
+        Persist {
+            source: tempfile::PersistError,
+            target: PathBuf,
+        },
+        #[error("The checksum of '{path}' is {actual}, but its trailer says it should be {expected}")]
+        ChecksumMismatch {
+            path: PathBuf,
+            expected: git_hash::ObjectId,
+            actual: git_hash::ObjectId,
+        },
+        #[error("'{path}' is too short to contain a checksum")]
+        Truncated { path: PathBuf },
+        #[error("Interrupted")]
+        Interrupted,
+    }
+}
+pub use error::Error;
+
+/// The size of the buffer through which files are copied.
+const BUF_SIZE: usize = 64 * 1024;
+
+impl super::Store {
+    /// Copy the pack with `id` along with its index into the existing directory `destination`, as well as its reverse index and
+    /// bitmap if present, and return the paths of all copied files in the order they were written.
+    ///
+    /// If the pack is covered by a multi-pack index, all packs covered by it along with their indices are copied, followed by the
+    /// reverse index and bitmap of the multi-pack index if present, and the multi-pack index itself. Existing files in `destination` are replaced, and each file only appears there once it is complete,
+    /// with indices written after their packs.
+    ///
+    /// The trailing checksum of each file is verified while copying, which fails with [`ChecksumMismatch`][Error::ChecksumMismatch]
+    /// if the file is corrupt. If `fsync` is true, each copied file is synced to disk before it is moved into place.
+    /// `progress` is advanced by the bytes copied, and `should_interrupt` is checked for each chunk to allow aborting the copy.
+    /// Note that `id` is only valid for the generation of the store it was obtained from, like the pack id of a
+    /// [`Location`][git_pack::data::entry::Location].
+    pub fn copy_pack(
+        &self,
+        id: git_pack::data::Id,
+        destination: impl AsRef<Path>,
+        fsync: bool,
+        mut progress: impl Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let pack_id = PackId::from_intrinsic_pack_id(id);
+        let index = self.index.load();
+        if !index.slot_indices.contains(&pack_id.index) {
+            return Err(Error::NotFound { id });
+        }
+        let files = self.files[pack_id.index].files.load();
+        let sources = match (Option::as_ref(&files), pack_id.multipack_index) {
+            (Some(IndexAndPacks::Index(bundle)), None) => pack_and_companions(bundle.data.path()),
+            (Some(IndexAndPacks::MultiIndex(bundle)), Some(pack_index))
+                if (pack_index as usize) < bundle.data.len() =>
+            {
+                let mut sources: Vec<_> = bundle
+                    .data
+                    .iter()
+                    .flat_map(|pack| pack_and_companions(pack.path()))
+                    .collect();
+                sources.extend(multi_index_companions(&bundle.multi_index));
+                sources.push(bundle.multi_index.path().to_owned());
+                sources
+            }
+            _ => return Err(Error::NotFound { id }),
+        };
+        drop(files);
+
+        let total_bytes = sources
+            .iter()
+            .map(|path| {
+                std::fs::metadata(path).map(|md| md.len()).map_err(|err| Error::Io {
+                    source: err,
+                    message: "obtain metadata of",
+                    path: path.to_owned(),
+                })
+            })
+            .sum::<Result<u64, _>>()?;
+        progress.init(Some(total_bytes as usize), git_features::progress::bytes());
+
+        let destination = destination.as_ref();
+        let mut buf = vec![0; BUF_SIZE];
+        sources
+            .iter()
+            .map(|source| {
+                let target = destination.join(source.file_name().expect("files in pack directories have names"));
+                self.copy_and_verify(source, &target, fsync, &mut buf, &mut progress, should_interrupt)?;
+                Ok(target)
+            })
+            .collect()
+    }
+
+    fn copy_and_verify(
+        &self,
+        source: &Path,
+        target: &Path,
+        fsync: bool,
+        buf: &mut [u8],
+        progress: &mut impl Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<(), Error> {
+        let io_err = |message: &'static str, path: &Path| {
+            let path = path.to_owned();
+            move |err| Error::Io {
+                source: err,
+                message,
+                path,
+            }
+        };
+        let mut input = std::fs::File::open(source).map_err(io_err("open", source))?;
+        let len = input.metadata().map_err(io_err("obtain metadata of", source))?.len();
+        let hash_len = self.object_hash.len_in_bytes();
+        let num_hashed_bytes = len.checked_sub(hash_len as u64).ok_or_else(|| Error::Truncated {
+            path: source.to_owned(),
+        })?;
+        let destination_dir = target.parent().expect("target is in a directory");
+        let mut output =
+            tempfile::NamedTempFile::new_in(destination_dir).map_err(io_err("create tempfile in", destination_dir))?;
+
+        let mut hasher = git_features::hash::hasher(self.object_hash);
+        let mut trailer = Vec::with_capacity(hash_len);
+        let mut pos = 0;
+        loop {
+            if should_interrupt.load(Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+            let num_read = input.read(buf).map_err(io_err("read", source))?;
+            if num_read == 0 {
+                break;
+            }
+            let chunk = &buf[..num_read];
+            output
+                .write_all(chunk)
+                .map_err(io_err("write into tempfile in", destination_dir))?;
+            let num_to_hash = num_hashed_bytes.saturating_sub(pos).min(num_read as u64) as usize;
+            hasher.update(&chunk[..num_to_hash]);
+            trailer.extend_from_slice(&chunk[num_to_hash..]);
+            pos += num_read as u64;
+            progress.inc_by(num_read);
+        }
+        if trailer.len() != hash_len {
+            // The file changed in size while we were reading it.
+            return Err(Error::Truncated {
+                path: source.to_owned(),
+            });
+        }
+        let (expected, actual) = (
+            git_hash::ObjectId::from(trailer.as_slice()),
+            git_hash::ObjectId::from(hasher.digest()),
+        );
+        if expected != actual {
+            return Err(Error::ChecksumMismatch {
+                path: source.to_owned(),
+                expected,
+                actual,
+            });
+        }
+
+        if fsync {
+            output.as_file().sync_all().map_err(io_err("sync", output.path()))?;
+        }
+        output.persist(target).map_err(|err| Error::Persist {
+            source: err,
+            target: target.to_owned(),
+        })?;
+        Ok(())
+    }
+}
+
+/// Return the path of the pack at `pack_path` along with the paths of its reverse index and bitmap if they exist, and its index.
+fn pack_and_companions(pack_path: &Path) -> Vec<PathBuf> {
+    let mut out = vec![pack_path.to_owned()];
+    out.extend(
+        ["rev", "bitmap"]
+            .iter()
+            .map(|extension| pack_path.with_extension(extension))
+            .filter(|path| path.is_file()),
+    );
+    out.push(pack_path.with_extension("idx"));
+    out
+}
+
+/// Return the paths of the reverse index and bitmap of `multi_index` if they exist, which are named after its checksum.
+fn multi_index_companions(multi_index: &git_pack::multi_index::File) -> Vec<PathBuf> {
+    let checksum = multi_index.checksum();
+    let directory = multi_index.path().parent().expect("multi-pack index is in a directory");
+    ["rev", "bitmap"]
+        .iter()
+        .map(|extension| directory.join(format!("multi-pack-index-{}.{}", checksum, extension)))
+        .filter(|path| path.is_file())
+        .collect()
+}
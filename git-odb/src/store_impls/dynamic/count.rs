@@ -0,0 +1,217 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use git_features::progress::Progress;
+
+use crate::{pack, types::IndexAndPacks};
+
+///
+pub mod by_kind {
+    /// Determines how thorough [`Store::count_objects_by_kind()`][crate::Store::count_objects_by_kind()] is.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub enum Mode {
+        /// Only read the entry header of each packed object, which is fast but can't tell the kind of delta objects.
+        ///
+        /// These are counted in [`Outcome::deltas`] instead.
+        DeclaredKindOnly,
+        /// Follow the delta chain of each packed delta object until its base is found to learn its actual kind.
+        ///
+        /// This is considerably slower for packs with long delta chains.
+        ResolveDeltas,
+    }
+
+    impl Default for Mode {
+        fn default() -> Self {
+            Mode::ResolveDeltas
+        }
+    }
+
+    /// Returned by [`Store::count_objects_by_kind()`][crate::Store::count_objects_by_kind()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error(transparent)]
+        IndexOpen(#[from] crate::pack::index::init::Error),
+        #[error(transparent)]
+        MultiIndexOpen(#[from] crate::pack::multi_index::init::Error),
+        #[error(transparent)]
+        PackOpen(#[from] crate::pack::data::init::Error),
+        #[error(transparent)]
+        Decode(#[from] crate::pack::data::decode::Error),
+        #[error(transparent)]
+        LooseHeader(#[from] crate::loose::find::Error),
+        #[error(transparent)]
+        InitializeODB(#[from] crate::store::load_index::Error),
+        #[error("The disk on state changed while performing the operation, and we observed the change.")]
+        NeedsRetryDueToChangeOnDisk,
+        #[error("Interrupted")]
+        Interrupted,
+    }
+
+    /// The amount of objects of each kind, as returned by [`Store::count_objects_by_kind()`][crate::Store::count_objects_by_kind()].
+    ///
+    /// Objects that are stored more than once, for instance in multiple packs or both loose and packed, are counted each time.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Outcome {
+        /// The amount of commits.
+        pub commits: usize,
+        /// The amount of trees.
+        pub trees: usize,
+        /// The amount of blobs.
+        pub blobs: usize,
+        /// The amount of tags.
+        pub tags: usize,
+        /// The amount of delta objects whose kind wasn't determined.
+        ///
+        /// With [`Mode::ResolveDeltas`] this is only non-zero for objects whose base lives outside of their pack.
+        pub deltas: usize,
+    }
+
+    impl Outcome {
+        /// The total amount of objects seen.
+        pub fn total(&self) -> usize {
+            self.commits + self.trees + self.blobs + self.tags + self.deltas
+        }
+
+        pub(crate) fn add(&mut self, kind: git_object::Kind) {
+            use git_object::Kind::*;
+            match kind {
+                Commit => self.commits += 1,
+                Tree => self.trees += 1,
+                Blob => self.blobs += 1,
+                Tag => self.tags += 1,
+            }
+        }
+    }
+}
+
+impl super::Store {
+    /// Count all objects in all packs and loose object databases, including alternates, by their kind, using `mode` to control
+    /// how delta objects are handled.
+    ///
+    /// `progress` is advanced for each object seen, and `should_interrupt` is checked regularly to allow aborting the operation.
+    /// Similar to [`verify_integrity()`][super::Store::verify_integrity()], packs and indices are only loaded temporarily
+    /// if they aren't loaded yet.
+    pub fn count_objects_by_kind(
+        &self,
+        mut progress: impl Progress,
+        should_interrupt: &AtomicBool,
+        mode: by_kind::Mode,
+    ) -> Result<by_kind::Outcome, by_kind::Error> {
+        let mut index = self.index.load();
+        if !index.is_initialized() {
+            self.consolidate_with_disk_state(true, false)?;
+            index = self.index.load();
+        }
+
+        let mut out = by_kind::Outcome::default();
+        progress.init(None, git_features::progress::count("objects"));
+        for slot_index in &index.slot_indices {
+            let slot = &self.files[*slot_index];
+            if slot.generation.load(Ordering::SeqCst) != index.generation {
+                return Err(by_kind::Error::NeedsRetryDueToChangeOnDisk);
+            }
+            let files = slot.files.load();
+            let files = Option::as_ref(&files).ok_or(by_kind::Error::NeedsRetryDueToChangeOnDisk)?;
+
+            match files {
+                IndexAndPacks::Index(bundle) => {
+                    let index = match bundle.index.loaded() {
+                        Some(index) => index.clone(),
+                        None => Arc::new(self.backend.open_index(bundle.index.path(), self.object_hash)?),
+                    };
+                    let data = match bundle.data.loaded() {
+                        Some(pack) => pack.clone(),
+                        None => Arc::new(self.backend.open_pack(bundle.data.path(), self.object_hash)?),
+                    };
+                    for entry in index.iter() {
+                        count_entry(&data, entry.pack_offset, mode, &mut out, |id| {
+                            index.lookup(id).map(|idx| index.pack_offset_at_index(idx))
+                        })?;
+                        progress.inc();
+                        if should_interrupt.load(Ordering::Relaxed) {
+                            return Err(by_kind::Error::Interrupted);
+                        }
+                    }
+                }
+                IndexAndPacks::MultiIndex(bundle) => {
+                    let index = match bundle.multi_index.loaded() {
+                        Some(index) => index.clone(),
+                        None => Arc::new(self.backend.open_multi_index(bundle.multi_index.path())?),
+                    };
+                    let index_dir = index.path().parent().expect("file in a directory").to_owned();
+                    let mut packs = vec![None::<Arc<pack::data::File>>; index.num_indices() as usize];
+                    for entry in index.iter() {
+                        let data = match &mut packs[entry.pack_index as usize] {
+                            Some(pack) => &**pack,
+                            slot @ None => {
+                                let pack_path = index_dir
+                                    .join(index.index_names()[entry.pack_index as usize].with_extension("pack"));
+                                let pack = match bundle.data.get(entry.pack_index as usize).and_then(|f| f.loaded()) {
+                                    Some(pack) => pack.clone(),
+                                    None => Arc::new(self.backend.open_pack(&pack_path, self.object_hash)?),
+                                };
+                                &**slot.get_or_insert(pack)
+                            }
+                        };
+                        count_entry(data, entry.pack_offset, mode, &mut out, |id| {
+                            index.lookup(id).and_then(|idx| {
+                                let (pack_index, pack_offset) = index.pack_id_and_pack_offset_at_index(idx);
+                                (pack_index == entry.pack_index).then(|| pack_offset)
+                            })
+                        })?;
+                        progress.inc();
+                        if should_interrupt.load(Ordering::Relaxed) {
+                            return Err(by_kind::Error::Interrupted);
+                        }
+                    }
+                }
+            }
+        }
+
+        for loose_db in &*index.loose_dbs {
+            for id in loose_db.iter().filter_map(Result::ok) {
+                match loose_db.try_header(id)? {
+                    Some((_size, kind)) => out.add(kind),
+                    None => return Err(by_kind::Error::NeedsRetryDueToChangeOnDisk),
+                }
+                progress.inc();
+                if should_interrupt.load(Ordering::Relaxed) {
+                    return Err(by_kind::Error::Interrupted);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn count_entry(
+    data: &pack::data::File,
+    pack_offset: pack::data::Offset,
+    mode: by_kind::Mode,
+    out: &mut by_kind::Outcome,
+    pack_offset_by_id: impl Fn(&git_hash::oid) -> Option<pack::data::Offset>,
+) -> Result<(), by_kind::Error> {
+    let entry = data.entry(pack_offset);
+    match entry.header.as_kind() {
+        Some(kind) => out.add(kind),
+        None => match mode {
+            by_kind::Mode::DeclaredKindOnly => out.deltas += 1,
+            by_kind::Mode::ResolveDeltas => {
+                match data.decode_header(entry, |id| {
+                    pack_offset_by_id(id)
+                        .map(|offset| pack::data::decode::header::ResolvedBase::InPack(data.entry(offset)))
+                }) {
+                    Ok(header) => out.add(header.kind),
+                    Err(pack::data::decode::Error::DeltaBaseUnresolved(_)) => out.deltas += 1,
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        },
+    }
+    Ok(())
+}
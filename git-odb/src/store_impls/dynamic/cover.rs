@@ -0,0 +1,80 @@
+use std::{collections::HashMap, ops::Deref};
+
+use git_hash::ObjectId;
+
+use crate::store::load_index;
+
+/// Returned by [`Handle::packs_covering()`][crate::store::Handle::packs_covering()].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// The packs needed to obtain all packed objects, along with the amount of the given objects to be obtained from each of them,
+    /// with the pack providing the most objects first.
+    ///
+    /// The pack id is the same as the one in [`Location::pack_id`][git_pack::data::entry::Location::pack_id].
+    pub packs: Vec<(git_pack::data::Id, usize)>,
+    /// Objects that aren't in any pack, but are stored as loose objects.
+    pub loose: Vec<ObjectId>,
+    /// Objects that couldn't be found at all.
+    pub missing: Vec<ObjectId>,
+}
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Find a small set of packs that together contain all of the given `ids`, along with those `ids` which are only
+    /// available as loose objects or aren't present at all.
+    ///
+    /// Objects contained in more than one pack are attributed to the pack that contains the most of the given `ids`, which
+    /// tends to keep the set of packs small without guaranteeing it's minimal.
+    /// All indices are loaded to see a single and consistent state of the object database, and memory usage doesn't
+    /// grow with the amount of `ids` unless these aren't packed.
+    pub fn packs_covering(&self, ids: &[ObjectId]) -> Result<Outcome, load_index::Error> {
+        let mut snapshot = self.snapshot.borrow_mut();
+        *snapshot = self.store.load_all_indices()?;
+        self.clear_cache();
+
+        let mut out = Outcome::default();
+        let mut objects_per_pack = HashMap::<git_pack::data::Id, usize>::new();
+        for id in ids {
+            let mut is_packed = false;
+            for pack_id in snapshot.indices.iter().filter_map(|index| index.pack_id(id)) {
+                *objects_per_pack.entry(pack_id.to_intrinsic_pack_id()).or_default() += 1;
+                is_packed = true;
+            }
+            if !is_packed {
+                if snapshot.loose_dbs.iter().any(|ldb| ldb.contains(id)) {
+                    out.loose.push(*id);
+                } else {
+                    out.missing.push(*id);
+                }
+            }
+        }
+
+        let mut packs_by_objects: Vec<_> = objects_per_pack.into_iter().collect();
+        packs_by_objects.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let rank_by_pack: HashMap<_, _> = packs_by_objects
+            .iter()
+            .enumerate()
+            .map(|(rank, (pack_id, _))| (*pack_id, rank))
+            .collect();
+        let mut objects_per_rank = vec![0; packs_by_objects.len()];
+        for id in ids {
+            if let Some(rank) = snapshot
+                .indices
+                .iter()
+                .filter_map(|index| index.pack_id(id))
+                .map(|pack_id| rank_by_pack[&pack_id.to_intrinsic_pack_id()])
+                .min()
+            {
+                objects_per_rank[rank] += 1;
+            }
+        }
+        out.packs = packs_by_objects
+            .into_iter()
+            .zip(objects_per_rank)
+            .filter_map(|((pack_id, _), num_objects)| (num_objects != 0).then(|| (pack_id, num_objects)))
+            .collect();
+        Ok(out)
+    }
+}
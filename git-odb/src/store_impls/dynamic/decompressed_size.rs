@@ -0,0 +1,127 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use git_features::progress::Progress;
+
+use crate::{
+    pack,
+    store::types::{IndexAndPacks, PackId},
+};
+
+mod error {
+    /// Returned by [`Store::estimate_decompressed_size()`][crate::Store::estimate_decompressed_size()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("There is no pack with id {id}")]
+        NotFound { id: git_pack::data::Id },
+        #[error(transparent)]
+        IndexOpen(#[from] crate::pack::index::init::Error),
+        #[error(transparent)]
+        MultiIndexOpen(#[from] crate::pack::multi_index::init::Error),
+        #[error(transparent)]
+        PackOpen(#[from] crate::pack::data::init::Error),
+        #[error(transparent)]
+        Decode(#[from] crate::pack::data::decode::Error),
+        #[error("Interrupted")]
+        Interrupted,
+    }
+}
+pub use error::Error;
+
+/// Returned by [`Store::estimate_decompressed_size()`][crate::Store::estimate_decompressed_size()].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Outcome {
+    /// The amount of objects in the pack.
+    pub num_objects: usize,
+    /// The sum of the sizes of all objects once decompressed and with their deltas applied.
+    pub total_bytes: u64,
+    /// The size of the largest object once decompressed and with its deltas applied, which is the most memory needed to hold
+    /// a single object.
+    pub largest_object_bytes: u64,
+}
+
+impl super::Store {
+    /// Estimate how many bytes all objects in the pack with `id` take once they are decompressed and their deltas are applied,
+    /// which helps to budget memory or disk space before fully expanding a pack, for instance to convert it.
+    ///
+    /// Only entry headers are read, along with the first few bytes of each delta to learn the size of the object it produces,
+    /// so this is much faster than decoding all objects.
+    /// `progress` is advanced for each object, and `should_interrupt` is checked regularly to allow aborting the operation.
+    /// Similar to [`count_objects_by_kind()`][super::Store::count_objects_by_kind()], the pack and its index are only loaded
+    /// temporarily if they aren't loaded yet. Note that `id` is only valid for the generation of the store it was obtained from,
+    /// like the pack id of a [`Location`][git_pack::data::entry::Location].
+    pub fn estimate_decompressed_size(
+        &self,
+        id: git_pack::data::Id,
+        mut progress: impl Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Outcome, Error> {
+        let pack_id = PackId::from_intrinsic_pack_id(id);
+        let index = self.index.load();
+        if !index.slot_indices.contains(&pack_id.index) {
+            return Err(Error::NotFound { id });
+        }
+        let files = self.files[pack_id.index].files.load();
+        let (data, offsets): (_, Vec<_>) = match (Option::as_ref(&files), pack_id.multipack_index) {
+            (Some(IndexAndPacks::Index(bundle)), None) => {
+                let index = match bundle.index.loaded() {
+                    Some(index) => index.clone(),
+                    None => Arc::new(self.backend.open_index(bundle.index.path(), self.object_hash)?),
+                };
+                let data = match bundle.data.loaded() {
+                    Some(pack) => pack.clone(),
+                    None => Arc::new(self.backend.open_pack(bundle.data.path(), self.object_hash)?),
+                };
+                (data, index.iter().map(|entry| entry.pack_offset).collect())
+            }
+            (Some(IndexAndPacks::MultiIndex(bundle)), Some(pack_index))
+                if (pack_index as usize) < bundle.data.len() =>
+            {
+                let index = match bundle.multi_index.loaded() {
+                    Some(index) => index.clone(),
+                    None => Arc::new(self.backend.open_multi_index(bundle.multi_index.path())?),
+                };
+                let on_disk_pack = &bundle.data[pack_index as usize];
+                let data = match on_disk_pack.loaded() {
+                    Some(pack) => pack.clone(),
+                    None => Arc::new(self.backend.open_pack(on_disk_pack.path(), self.object_hash)?),
+                };
+                let offsets = index
+                    .iter()
+                    .filter(|entry| entry.pack_index == pack_index)
+                    .map(|entry| entry.pack_offset)
+                    .collect();
+                (data, offsets)
+            }
+            _ => return Err(Error::NotFound { id }),
+        };
+        drop(files);
+
+        let mut out = Outcome::default();
+        progress.init(Some(offsets.len()), git_features::progress::count("objects"));
+        for pack_offset in offsets {
+            // The size of a delta object is recorded in the first delta of its chain, so bases of ref-deltas don't have to be
+            // found. Pretending they are elsewhere stops following the chain right there.
+            let size = data
+                .decode_header(data.entry(pack_offset), |_id| {
+                    Some(pack::data::decode::header::ResolvedBase::OutOfPack {
+                        kind: git_object::Kind::Blob,
+                        num_deltas: None,
+                    })
+                })?
+                .object_size;
+            out.num_objects += 1;
+            out.total_bytes += size;
+            out.largest_object_bytes = out.largest_object_bytes.max(size);
+            progress.inc();
+            if should_interrupt.load(Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+        }
+        Ok(out)
+    }
+}
@@ -0,0 +1,103 @@
+use std::ops::Deref;
+
+use git_hash::{oid, ObjectId};
+
+mod error {
+    /// Returned by [`Handle::delta_base_id()`][crate::store::Handle::delta_base_id()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("Object {id} is not stored as delta")]
+        NotADelta { id: git_hash::ObjectId },
+        #[error("Object {id} is stored as loose object, which are never deltas")]
+        Loose { id: git_hash::ObjectId },
+        #[error("Object {id} could not be found")]
+        NotFound { id: git_hash::ObjectId },
+        #[error("The base object of delta object {id} at pack offset {base_pack_offset} isn't listed in its index")]
+        BaseNotInIndex {
+            id: git_hash::ObjectId,
+            base_pack_offset: git_pack::data::Offset,
+        },
+        #[error(transparent)]
+        LoadIndex(#[from] crate::store::load_index::Error),
+        #[error(transparent)]
+        LoadPack(#[from] std::io::Error),
+    }
+}
+pub use error::Error;
+
+/// Returned by [`Handle::delta_kind()`][crate::store::Handle::delta_kind()] to describe how a packed object is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// The object is stored as is.
+    NonDelta,
+    /// The object is a delta against a base in the same pack, referred to by its offset.
+    OffsetDelta {
+        /// The offset of the base object in the pack.
+        base_offset: git_pack::data::Offset,
+        /// The id of the base object.
+        base_oid: ObjectId,
+    },
+    /// The object is a delta against a base referred to by its id.
+    RefDelta {
+        /// The id of the base object.
+        base_oid: ObjectId,
+    },
+}
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Return the id of the object that the packed delta object `id` is based on, or `None` if `id` doesn't exist.
+    ///
+    /// Neither the object nor its base are decoded, which makes this a cheap way to learn about the delta relationships
+    /// within a pack. Objects that aren't deltas, which includes all loose objects, cause an error.
+    /// The id of a base that is referred to by pack offset is looked up like with [`oid_at_pack_offset()`][Self::oid_at_pack_offset()].
+    pub fn delta_base_id(&self, id: impl AsRef<oid>) -> Result<Option<ObjectId>, Error> {
+        let id = id.as_ref();
+        match self.delta_kind(id) {
+            Ok(Kind::OffsetDelta { base_oid, .. } | Kind::RefDelta { base_oid }) => Ok(Some(base_oid)),
+            Ok(Kind::NonDelta) | Err(Error::Loose { .. }) => Err(Error::NotADelta { id: id.to_owned() }),
+            Err(Error::NotFound { .. }) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Return how the packed object `id` is stored, that is if it's a delta and which object it is based on, without decoding it.
+    ///
+    /// Loose objects and objects that don't exist cause an error.
+    /// The id of a base that is referred to by pack offset is looked up like with [`oid_at_pack_offset()`][Self::oid_at_pack_offset()].
+    pub fn delta_kind(&self, id: impl AsRef<oid>) -> Result<Kind, Error> {
+        let id = id.as_ref();
+        let kind = self.find_entry(
+            id,
+            |found| {
+                use git_pack::data::entry::Header::*;
+                let entry = found.pack.entry(found.pack_offset);
+                match entry.header {
+                    RefDelta { base_id } => Ok(Kind::RefDelta { base_oid: base_id }),
+                    OfsDelta { base_distance } => {
+                        let base_offset = entry.base_pack_offset(base_distance);
+                        self.store
+                            .oid_at_pack_offset_in(found.index, found.pack_id, found.marker, base_offset)
+                            .map(|base_oid| Kind::OffsetDelta { base_offset, base_oid })
+                            .ok_or(Error::BaseNotInIndex {
+                                id: id.to_owned(),
+                                base_pack_offset: base_offset,
+                            })
+                    }
+                    Commit | Tree | Blob | Tag => Ok(Kind::NonDelta),
+                }
+            },
+            |lodb, id| {
+                if lodb.contains(id) {
+                    Err(Error::Loose { id: id.to_owned() })
+                } else {
+                    Ok(None)
+                }
+            },
+        )?;
+        kind.ok_or(Error::NotFound { id: id.to_owned() })
+    }
+}
@@ -0,0 +1,228 @@
+use std::{cmp::Ordering, ops::Deref};
+
+use git_hash::{oid, ObjectId};
+use git_object::{
+    bstr::{BStr, BString, ByteSlice, ByteVec},
+    tree::{Entry, EntryMode},
+    CommitRefIter, TreeRefIter,
+};
+
+use crate::Find;
+
+mod error {
+    /// Returned by [`Handle::diff_commits()`][crate::store::Handle::diff_commits()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error(transparent)]
+        Find(#[from] crate::store::find::Error),
+        #[error("Object {id} could not be found")]
+        NotFound { id: git_hash::ObjectId },
+        #[error("Object {id} was expected to be a commit, but is a {actual}")]
+        NotACommit {
+            id: git_hash::ObjectId,
+            actual: git_object::Kind,
+        },
+        #[error("Object {id} was expected to be a tree, but is a {actual}")]
+        NotATree {
+            id: git_hash::ObjectId,
+            actual: git_object::Kind,
+        },
+        #[error(transparent)]
+        Decode(#[from] git_object::decode::Error),
+    }
+}
+pub use error::Error;
+
+/// A change to a blob between two commits, as returned by [`Handle::diff_commits()`][crate::store::Handle::diff_commits()].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub enum Change {
+    /// The blob at `path` didn't exist in the previous commit.
+    Addition {
+        /// The slash-separated path of the blob relative to the root tree.
+        path: BString,
+        /// The id of the added blob.
+        id: ObjectId,
+    },
+    /// The blob at `path` doesn't exist in the current commit anymore.
+    Deletion {
+        /// The slash-separated path of the blob relative to the root tree.
+        path: BString,
+        /// The id of the deleted blob.
+        id: ObjectId,
+    },
+    /// The blob at `path` exists in both commits, but its content or mode changed.
+    Modification {
+        /// The slash-separated path of the blob relative to the root tree.
+        path: BString,
+        /// The id of the blob in the previous commit.
+        previous_id: ObjectId,
+        /// The id of the blob in the current commit, which is the same as `previous_id` if only the mode changed.
+        id: ObjectId,
+    },
+}
+
+impl Change {
+    /// Return the path of the changed blob.
+    pub fn path(&self) -> &BStr {
+        match self {
+            Change::Addition { path, .. } | Change::Deletion { path, .. } | Change::Modification { path, .. } => {
+                path.as_bstr()
+            }
+        }
+    }
+}
+
+/// The side of the diff an entry that only exists on one side is on.
+#[derive(Clone, Copy)]
+enum Side {
+    Previous,
+    Current,
+}
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Return all blobs that were added, deleted or modified between the commits `previous` and `current`, sorted by path.
+    ///
+    /// The root trees of both commits are compared recursively, without descending into subtrees that are the same in both.
+    /// Renames aren't detected and show up as deletion and addition, and a blob that replaces a tree or vice versa shows up as
+    /// deletion of all blobs in the tree and addition of the blob, or the other way around.
+    /// Submodules are ignored as their commits aren't expected to be in this object database.
+    pub fn diff_commits(&self, previous: impl AsRef<oid>, current: impl AsRef<oid>) -> Result<Vec<Change>, Error> {
+        let mut buf = Vec::new();
+        let previous = self.tree_id_of_commit(previous.as_ref(), &mut buf)?;
+        let current = self.tree_id_of_commit(current.as_ref(), &mut buf)?;
+        let mut out = Vec::new();
+        self.diff_trees(&previous, &current, BString::default(), &mut buf, &mut out)?;
+        out.sort_by(|a, b| a.path().cmp(b.path()));
+        Ok(out)
+    }
+
+    fn tree_id_of_commit(&self, id: &oid, buf: &mut Vec<u8>) -> Result<ObjectId, Error> {
+        match self.try_find(id, buf)? {
+            Some(data) if data.kind == git_object::Kind::Commit => Ok(CommitRefIter::from_bytes(data.data).tree_id()?),
+            Some(data) => Err(Error::NotACommit {
+                id: id.to_owned(),
+                actual: data.kind,
+            }),
+            None => Err(Error::NotFound { id: id.to_owned() }),
+        }
+    }
+
+    /// Return the entries of tree `id`, sorted by plain byte-wise comparison of their names to allow merging them.
+    fn tree_entries(&self, id: &oid, buf: &mut Vec<u8>) -> Result<Vec<Entry>, Error> {
+        let mut entries = match self.try_find(id, buf)? {
+            Some(data) if data.kind == git_object::Kind::Tree => TreeRefIter::from_bytes(data.data)
+                .map(|entry| entry.map(Entry::from))
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(data) => {
+                return Err(Error::NotATree {
+                    id: id.to_owned(),
+                    actual: data.kind,
+                })
+            }
+            None => return Err(Error::NotFound { id: id.to_owned() }),
+        };
+        entries.sort_by(|a, b| a.filename.cmp(&b.filename));
+        Ok(entries)
+    }
+
+    fn diff_trees(
+        &self,
+        previous: &oid,
+        current: &oid,
+        path: BString,
+        buf: &mut Vec<u8>,
+        out: &mut Vec<Change>,
+    ) -> Result<(), Error> {
+        if previous == current {
+            return Ok(());
+        }
+        let mut previous = self.tree_entries(previous, buf)?.into_iter().peekable();
+        let mut current = self.tree_entries(current, buf)?.into_iter().peekable();
+        loop {
+            let ordering = match (previous.peek(), current.peek()) {
+                (Some(lhs), Some(rhs)) => lhs.filename.cmp(&rhs.filename),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            match ordering {
+                Ordering::Less => {
+                    let entry = previous.next().expect("peeked");
+                    self.record_all(entry, Side::Previous, path.as_bstr(), buf, out)?;
+                }
+                Ordering::Greater => {
+                    let entry = current.next().expect("peeked");
+                    self.record_all(entry, Side::Current, path.as_bstr(), buf, out)?;
+                }
+                Ordering::Equal => {
+                    let (lhs, rhs) = (previous.next().expect("peeked"), current.next().expect("peeked"));
+                    if lhs.oid == rhs.oid && lhs.mode == rhs.mode {
+                        continue;
+                    }
+                    match (is_leaf(lhs.mode), is_leaf(rhs.mode)) {
+                        (true, true) => out.push(Change::Modification {
+                            path: join(path.as_bstr(), lhs.filename.as_bstr()),
+                            previous_id: lhs.oid,
+                            id: rhs.oid,
+                        }),
+                        _ if lhs.mode.is_tree() && rhs.mode.is_tree() => self.diff_trees(
+                            &lhs.oid,
+                            &rhs.oid,
+                            join(path.as_bstr(), lhs.filename.as_bstr()),
+                            buf,
+                            out,
+                        )?,
+                        _ => {
+                            self.record_all(lhs, Side::Previous, path.as_bstr(), buf, out)?;
+                            self.record_all(rhs, Side::Current, path.as_bstr(), buf, out)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record `entry` in the tree at `path` as deleted or added depending on `side`, or all blobs in it if it's a tree.
+    fn record_all(
+        &self,
+        entry: Entry,
+        side: Side,
+        path: &BStr,
+        buf: &mut Vec<u8>,
+        out: &mut Vec<Change>,
+    ) -> Result<(), Error> {
+        let path = join(path, entry.filename.as_bstr());
+        if entry.mode.is_tree() {
+            for child in self.tree_entries(&entry.oid, buf)? {
+                self.record_all(child, side, path.as_bstr(), buf, out)?;
+            }
+        } else if is_leaf(entry.mode) {
+            out.push(match side {
+                Side::Previous => Change::Deletion { path, id: entry.oid },
+                Side::Current => Change::Addition { path, id: entry.oid },
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Return true if `mode` is for an object that is stored in this database and isn't a tree, i.e. blobs and symlinks.
+fn is_leaf(mode: EntryMode) -> bool {
+    mode.is_no_tree() && mode != EntryMode::Commit
+}
+
+fn join(base: &BStr, name: &BStr) -> BString {
+    let mut path = BString::from(Vec::with_capacity(base.len() + 1 + name.len()));
+    if !base.is_empty() {
+        path.push_str(base);
+        path.push_byte(b'/');
+    }
+    path.push_str(name);
+    path
+}
@@ -0,0 +1,106 @@
+use std::{
+    path::PathBuf,
+    sync::{mpsc, Arc},
+};
+
+use crate::store::SlotIndexMarker;
+
+/// A change to the packs known to a store, as observed when it refreshes its view of the object database on disk, sent to all
+/// receivers created with [`Store::subscribe()`][crate::Store::subscribe()].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Event {
+    /// The [label][crate::Store::label()] of the store that changed, if set.
+    pub label: Option<String>,
+    /// What changed.
+    pub change: Change,
+}
+
+/// The kind of change of an [`Event`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub enum Change {
+    /// The pack index or multi-pack index at the given path was discovered, making the objects in its packs available.
+    PackAdded(PathBuf),
+    /// The pack index or multi-pack index at the given path is gone, or was replaced by a changed version of it which is
+    /// announced with [`PackAdded`][Change::PackAdded] as well.
+    PackRemoved(PathBuf),
+    /// Slots were reused for other indices, which invalidates all pack ids handed out so far.
+    GenerationBumped,
+}
+
+/// A callback registered with [`Store::on_state_change()`][crate::Store::on_state_change()], receiving the
+/// [label][crate::Store::label()] of the store along with its previous and its current marker.
+pub type StateChangeCallback = dyn Fn(Option<&str>, &SlotIndexMarker, &SlotIndexMarker) + Send + Sync;
+
+impl super::Store {
+    /// Return a receiver of the [events][Event] produced each time the store refreshes its view of the object database on disk,
+    /// which is useful to invalidate caches that depend on the packs of the store.
+    ///
+    /// Events are sent after a refresh completed, so the refresh isn't slowed down by receivers. Receivers that are more than
+    /// `capacity` events behind miss those that don't fit, and dropping a receiver ends its subscription.
+    pub fn subscribe(&self, capacity: usize) -> mpsc::Receiver<Event> {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        self.subscribers.lock().push(sender);
+        receiver
+    }
+
+    /// Return true if there is at least one subscriber who would receive events.
+    pub(crate) fn has_subscribers(&self) -> bool {
+        !self.subscribers.lock().is_empty()
+    }
+
+    /// Send `changes` as events to all subscribers without blocking, dropping events for those whose channel is full and forgetting
+    /// those that disconnected.
+    pub(crate) fn publish(&self, changes: Vec<Change>) {
+        if changes.is_empty() {
+            return;
+        }
+        let events: Vec<_> = changes
+            .into_iter()
+            .map(|change| Event {
+                label: self.label.clone(),
+                change,
+            })
+            .collect();
+        self.subscribers.lock().retain(|subscriber| {
+            events.iter().all(|event| match subscriber.try_send(event.clone()) {
+                Ok(()) | Err(mpsc::TrySendError::Full(_)) => true,
+                Err(mpsc::TrySendError::Disconnected(_)) => false,
+            })
+        });
+    }
+
+    /// Call `callback` with our [label][crate::Store::label()] and the previous and the current [marker][SlotIndexMarker] whenever a refresh or the loading of an index
+    /// changes the state of the object database, to learn whether packs were added ([`Updated`][crate::store::MarkerChange::Updated])
+    /// or pack ids were invalidated ([`Regenerated`][crate::store::MarkerChange::Regenerated]) with
+    /// [`change_since()`][SlotIndexMarker::change_since()].
+    ///
+    /// Callbacks are called after the change was made and without holding any lock of the store, so they may use the store, but
+    /// they may be called from multiple threads at the same time. Registering a callback doesn't block lookups or refreshes.
+    pub fn on_state_change(
+        &self,
+        callback: impl Fn(Option<&str>, &SlotIndexMarker, &SlotIndexMarker) + Send + Sync + 'static,
+    ) {
+        let callback: Arc<StateChangeCallback> = Arc::new(callback);
+        self.state_change_callbacks.rcu(|callbacks| {
+            let mut callbacks = Vec::clone(callbacks);
+            callbacks.push(Arc::clone(&callback));
+            callbacks
+        });
+    }
+
+    /// Call all state change callbacks if the current marker differs from `previous`.
+    pub(crate) fn notify_state_change(&self, previous: SlotIndexMarker) {
+        let callbacks = self.state_change_callbacks.load();
+        if callbacks.is_empty() {
+            return;
+        }
+        let current = self.marker();
+        if current != previous {
+            for callback in callbacks.iter() {
+                callback(self.label(), &previous, &current);
+            }
+        }
+    }
+}
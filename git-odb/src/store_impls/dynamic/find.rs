@@ -1,8 +1,8 @@
-use std::{convert::TryInto, ops::Deref};
+use std::{convert::TryInto, ops::Deref, sync::atomic::Ordering};
 
 use git_pack::cache::DecodeEntry;
 
-use crate::store::{handle, load_index};
+use crate::store::{handle, load_index, RefreshMode};
 
 pub(crate) mod error {
     use crate::{loose, pack};
@@ -33,6 +33,25 @@ pub(crate) mod error {
             /// The original object to lookup
             id: git_hash::ObjectId,
         },
+        #[error("Object {expected} was found, but the hash of its content is {actual}")]
+        HashMismatch {
+            /// The id the object was looked up by.
+            expected: git_hash::ObjectId,
+            /// The hash computed from the object's content.
+            actual: git_hash::ObjectId,
+        },
+        #[error("The data of object {id} could not be transformed")]
+        Transform {
+            #[source]
+            err: crate::store::transform::Error,
+            /// The object whose data was transformed.
+            id: git_hash::ObjectId,
+        },
+        #[error("The deadline passed before object {id} could be found")]
+        DeadlineExceeded {
+            /// The object that was looked up when the deadline passed.
+            id: git_hash::ObjectId,
+        },
         #[error("An error occurred when looking up a ref delta base object {} to decode {}", .base_id, .id)]
         DeltaBaseLookup {
             #[source]
@@ -76,7 +95,10 @@ pub(crate) mod error {
 }
 pub use error::Error;
 
-use crate::{store::types::PackId, Find};
+use crate::{
+    store::types::{PackId, SlotIndexMarker},
+    Find,
+};
 
 impl<S> super::Handle<S>
 where
@@ -97,17 +119,12 @@ where
                     id: r.original_id.to_owned(),
                 });
             }
-        } else if !self.ignore_replacements {
-            if let Ok(pos) = self
-                .store
-                .replacements
-                .binary_search_by(|(map_this, _)| map_this.as_ref().cmp(id))
-            {
-                id = self.store.replacements[pos].1.as_ref();
-            }
+        } else {
+            id = self.replaced_id(id);
         }
 
         'outer: loop {
+            self.check_deadline(id)?;
             {
                 let marker = snapshot.marker;
                 for (idx, index) in snapshot.indices.iter_mut().enumerate() {
@@ -117,34 +134,52 @@ where
                         pack: possibly_pack,
                     }) = index.lookup(id)
                     {
+                        if !self.store.serves_lookups(pack_id) {
+                            continue;
+                        }
                         let pack = match possibly_pack {
                             Some(pack) => pack,
-                            None => match self.store.load_pack(pack_id, marker)? {
-                                Some(pack) => {
-                                    *possibly_pack = Some(pack);
-                                    possibly_pack.as_deref().expect("just put it in")
-                                }
-                                None => {
-                                    // The pack wasn't available anymore so we are supposed to try another round with a fresh index
-                                    match self.store.load_one_index(self.refresh, snapshot.marker)? {
-                                        Some(new_snapshot) => {
-                                            *snapshot = new_snapshot;
-                                            self.clear_cache();
-                                            continue 'outer;
-                                        }
-                                        None => {
-                                            // nothing new in the index, kind of unexpected to not have a pack but to also
-                                            // to have no new index yet. We set the new index before removing any slots, so
-                                            // this should be observable.
-                                            return Ok(None);
+                            None => {
+                                self.check_deadline(id)?;
+                                match self.store.load_pack(pack_id, marker)? {
+                                    Some(pack) => {
+                                        *possibly_pack = Some(pack);
+                                        possibly_pack.as_deref().expect("just put it in")
+                                    }
+                                    None => {
+                                        // The pack wasn't available anymore so we are supposed to try another round with a fresh index
+                                        match self.store.load_one_index(self.refresh, marker)? {
+                                            Some(new_snapshot) => {
+                                                *snapshot = new_snapshot;
+                                                self.clear_cache();
+                                                continue 'outer;
+                                            }
+                                            None => {
+                                                // nothing new in the index even though the pack is gone, which happens if it is
+                                                // deleted before its index during a repack. The object may still be stored
+                                                // elsewhere, so keep looking before concluding it doesn't exist.
+                                                continue;
+                                            }
                                         }
                                     }
                                 }
-                            },
+                            }
                         };
+                        if self.store.quarantine_corrupt_packs && pack_offset >= pack.pack_end() as u64 {
+                            self.poison_pack(
+                                pack,
+                                pack_id,
+                                marker,
+                                id,
+                                pack_offset,
+                                "lies beyond the end of the pack",
+                            );
+                            *possibly_pack = None;
+                            continue 'outer;
+                        }
                         let entry = pack.entry(pack_offset);
                         let header_size = entry.header_size();
-                        let res = match pack.decode_entry(
+                        let decoded = pack.decode_entry(
                             entry,
                             buffer,
                             |id, _out| {
@@ -153,7 +188,33 @@ where
                                 })
                             },
                             pack_cache,
-                        ) {
+                        );
+                        if let Err(err @ git_pack::data::decode::Error::ZlibInflate(_)) = &decoded {
+                            if self.store.quarantine_corrupt_packs {
+                                let problem = format!("is malformed: {}", err);
+                                self.poison_pack(pack, pack_id, marker, id, pack_offset, problem);
+                                *possibly_pack = None;
+                                continue 'outer;
+                            }
+                        }
+                        // Truncated entries may decompress without error, so only the hash tells us that the pack is corrupt.
+                        let mut verified = false;
+                        if let Ok(r) = &decoded {
+                            if self.store.quarantine_corrupt_packs && self.store.verify_object_hashes {
+                                let data = git_object::Data {
+                                    kind: r.kind,
+                                    data: buffer.as_slice(),
+                                };
+                                if let Err(err) = self.verify_hash(id, &data) {
+                                    let problem = format!("is corrupt: {}", err);
+                                    self.poison_pack(pack, pack_id, marker, id, pack_offset, problem);
+                                    *possibly_pack = None;
+                                    continue 'outer;
+                                }
+                                verified = true;
+                            }
+                        }
+                        let res = match decoded {
                             Ok(r) => Ok((
                                 git_object::Data {
                                     kind: r.kind,
@@ -188,10 +249,13 @@ where
                                             .map(|r| r.inc_depth())
                                             .or_else(|| error::DeltaBaseRecursion::new(id).into()),
                                     )
-                                    .map_err(|err| Error::DeltaBaseLookup {
-                                        err: Box::new(err),
-                                        base_id,
-                                        id: id.to_owned(),
+                                    .map_err(|err| match err {
+                                        Error::DeadlineExceeded { .. } => err,
+                                        err => Error::DeltaBaseLookup {
+                                            err: Box::new(err),
+                                            base_id,
+                                            id: id.to_owned(),
+                                        },
                                     })?
                                     .ok_or_else(|| Error::DeltaBaseMissing {
                                         base_id,
@@ -272,6 +336,10 @@ where
                         if idx != 0 {
                             snapshot.indices.swap(0, idx);
                         }
+                        if !verified {
+                            self.verify_hash(id, &res.0)?;
+                        }
+                        self.store.record_pack_hit(pack_id);
                         return Ok(Some(res));
                     }
                 }
@@ -280,13 +348,15 @@ where
             for lodb in snapshot.loose_dbs.iter() {
                 // TODO: remove this double-lookup once the borrow checker allows it.
                 if lodb.contains(id) {
-                    return lodb
-                        .try_find(id, buffer)
-                        .map(|obj| obj.map(|obj| (obj, None)))
-                        .map_err(Into::into);
+                    let obj = lodb.try_find(id, buffer)?;
+                    if let Some(obj) = &obj {
+                        self.verify_hash(id, obj)?;
+                    }
+                    return Ok(obj.map(|obj| (obj, None)));
                 }
             }
 
+            self.check_deadline(id)?;
             match self.store.load_one_index(self.refresh, snapshot.marker)? {
                 Some(new_snapshot) => {
                     *snapshot = new_snapshot;
@@ -297,24 +367,58 @@ where
         }
     }
 
+    /// Poison the pack with `pack_id` as its entry for object `id` at `pack_offset` has the given `problem`.
+    fn poison_pack(
+        &self,
+        pack: &git_pack::data::File,
+        pack_id: PackId,
+        marker: SlotIndexMarker,
+        id: &git_hash::oid,
+        pack_offset: git_pack::data::Offset,
+        problem: impl std::fmt::Display,
+    ) {
+        let reason = format!(
+            "The entry of object {} at offset {} in the pack at '{}' {}",
+            id,
+            pack_offset,
+            pack.path().display(),
+            problem
+        );
+        self.store.poison_pack(pack_id, marker, reason);
+    }
+
+    fn check_deadline(&self, id: &git_hash::oid) -> Result<(), Error> {
+        match self.deadline {
+            Some(deadline) if std::time::Instant::now() >= deadline => {
+                Err(Error::DeadlineExceeded { id: id.to_owned() })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn verify_hash(&self, id: &git_hash::oid, data: &git_object::Data<'_>) -> Result<(), Error> {
+        if !self.store.verify_object_hashes {
+            return Ok(());
+        }
+        data.verify_checksum(id).map_err(|err| match err {
+            git_object::data::verify::Error::ChecksumMismatch { desired, actual } => Error::HashMismatch {
+                expected: desired,
+                actual,
+            },
+        })
+    }
+
     pub(crate) fn clear_cache(&self) {
         self.packed_object_count.borrow_mut().take();
     }
-}
-
-impl<S> git_pack::Find for super::Handle<S>
-where
-    S: Deref<Target = super::Store> + Clone,
-{
-    type Error = Error;
 
-    // TODO: probably make this method fallible, but that would mean its own error type.
-    fn contains(&self, id: impl AsRef<git_hash::oid>) -> bool {
-        let id = id.as_ref();
+    pub(crate) fn contains_inner(&self, id: &git_hash::oid, refresh: RefreshMode) -> bool {
+        let id = self.replaced_id(id);
         let mut snapshot = self.snapshot.borrow_mut();
         loop {
             for (idx, index) in snapshot.indices.iter().enumerate() {
-                if index.contains(id) {
+                if let Some(pack_id) = index.pack_id(id) {
+                    self.store.record_pack_hit(pack_id);
                     if idx != 0 {
                         snapshot.indices.swap(0, idx);
                     }
@@ -328,7 +432,7 @@ where
                 }
             }
 
-            match self.store.load_one_index(self.refresh, snapshot.marker) {
+            match self.store.load_one_index(refresh, snapshot.marker) {
                 Ok(Some(new_snapshot)) => {
                     *snapshot = new_snapshot;
                     self.clear_cache();
@@ -338,6 +442,24 @@ where
             }
         }
     }
+}
+
+impl<S> git_pack::Find for super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    type Error = Error;
+
+    // TODO: probably make this method fallible, but that would mean its own error type.
+    fn contains(&self, id: impl AsRef<git_hash::oid>) -> bool {
+        let counters = &self.store.lookup_counters;
+        counters.contains.fetch_add(1, Ordering::Relaxed);
+        let found = self.contains_inner(id.as_ref(), self.refresh);
+        if !found {
+            counters.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        found
+    }
 
     fn try_find_cached<'a>(
         &self,
@@ -347,7 +469,37 @@ where
     ) -> Result<Option<(git_object::Data<'a>, Option<git_pack::data::entry::Location>)>, Self::Error> {
         let id = id.as_ref();
         let mut snapshot = self.snapshot.borrow_mut();
-        self.try_find_cached_inner(id, buffer, pack_cache, &mut snapshot, None)
+        let res = self
+            .try_find_cached_inner(id, &mut *buffer, pack_cache, &mut snapshot, None)
+            .map(|obj| obj.map(|(data, location)| (data.kind, location)));
+        let counters = &self.store.lookup_counters;
+        counters.finds.fetch_add(1, Ordering::Relaxed);
+        match &res {
+            Ok(Some(_)) => {
+                counters.bytes_decompressed.fetch_add(buffer.len(), Ordering::Relaxed);
+            }
+            Ok(None) => {
+                counters.misses.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(_) => {}
+        }
+        match res? {
+            Some((kind, location)) => {
+                if let Some(transform) = self.store.transform.as_deref() {
+                    transform
+                        .decode(kind, buffer)
+                        .map_err(|err| Error::Transform { err, id: id.to_owned() })?;
+                }
+                Ok(Some((
+                    git_object::Data {
+                        kind,
+                        data: buffer.as_slice(),
+                    },
+                    location,
+                )))
+            }
+            None => Ok(None),
+        }
     }
 
     fn location_by_oid(
@@ -360,7 +512,7 @@ where
             "BUG: handle must be configured to `prevent_pack_unload()` before using this method"
         );
 
-        assert!(self.store_ref().replacements.is_empty() || self.ignore_replacements, "Everything related to packing must not use replacements. These are not used here, but it should be turned off for good measure.");
+        assert!(self.replacements().is_empty() || self.ignore_replacements, "Everything related to packing must not use replacements. These are not used here, but it should be turned off for good measure.");
 
         let id = id.as_ref();
         let mut snapshot = self.snapshot.borrow_mut();
@@ -374,6 +526,9 @@ where
                         pack: possibly_pack,
                     }) = index.lookup(id)
                     {
+                        if !self.store.serves_lookups(pack_id) {
+                            continue;
+                        }
                         let pack = match possibly_pack {
                             Some(pack) => pack,
                             None => match self.store.load_pack(pack_id, marker).ok()? {
@@ -383,17 +538,17 @@ where
                                 }
                                 None => {
                                     // The pack wasn't available anymore so we are supposed to try another round with a fresh index
-                                    match self.store.load_one_index(self.refresh, snapshot.marker).ok()? {
+                                    match self.store.load_one_index(self.refresh, marker).ok()? {
                                         Some(new_snapshot) => {
                                             *snapshot = new_snapshot;
                                             self.clear_cache();
                                             continue 'outer;
                                         }
                                         None => {
-                                            // nothing new in the index, kind of unexpected to not have a pack but to also
-                                            // to have no new index yet. We set the new index before removing any slots, so
-                                            // this should be observable.
-                                            return None;
+                                            // nothing new in the index even though the pack is gone, which happens if it is
+                                            // deleted before its index during a repack. The object may still be stored
+                                            // elsewhere, so keep looking before concluding it doesn't exist.
+                                            continue;
                                         }
                                     }
                                 }
@@ -462,6 +617,9 @@ where
             "BUG: handle must be configured to `prevent_pack_unload()` before using this method"
         );
         let pack_id = PackId::from_intrinsic_pack_id(location.pack_id);
+        if !self.store.serves_lookups(pack_id) {
+            return None;
+        }
         let mut snapshot = self.snapshot.borrow_mut();
         let marker = snapshot.marker;
         loop {
@@ -0,0 +1,101 @@
+use std::{ops::Deref, sync::Arc};
+
+use git_hash::oid;
+
+use crate::{
+    loose,
+    store::{handle, types},
+};
+
+/// A packed object found by [`Handle::find_entry()`][super::Handle::find_entry()].
+pub(crate) struct PackedEntry<'a> {
+    /// The index listing the object.
+    pub index: &'a handle::IndexLookup,
+    /// The pack containing the object.
+    pub pack_id: types::PackId,
+    /// The offset at which the object's entry starts in `pack`.
+    pub pack_offset: git_pack::data::Offset,
+    /// The loaded pack containing the object.
+    pub pack: Arc<git_pack::data::File>,
+    /// The marker of the snapshot `index` belongs to.
+    pub marker: types::SlotIndexMarker,
+}
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Find the object `id` after applying replacements and pass it to `packed` if it's in a pack that serves lookups,
+    /// or call `loose` with each loose object database and the id to look for until one returns `Some`.
+    /// Return `None` if the object doesn't exist even after refreshing the snapshot as allowed by our refresh mode.
+    ///
+    /// Packs are loaded as needed, and the snapshot is refreshed if one of them went missing.
+    /// Note that the snapshot is borrowed while the callbacks run, so they must not use methods of this handle that borrow it too.
+    pub(crate) fn find_entry<T, E>(
+        &self,
+        id: &oid,
+        mut packed: impl FnMut(PackedEntry<'_>) -> Result<T, E>,
+        mut loose: impl FnMut(&loose::Store, &oid) -> Result<Option<T>, E>,
+    ) -> Result<Option<T>, E>
+    where
+        E: From<crate::store::load_index::Error> + From<std::io::Error>,
+    {
+        let id = self.replaced_id(id);
+        let mut snapshot = self.snapshot.borrow_mut();
+        'outer: loop {
+            for idx in 0..snapshot.indices.len() {
+                let marker = snapshot.marker;
+                let (pack_id, pack_offset, possibly_pack) = match snapshot.indices[idx].lookup(id) {
+                    Some(handle::index_lookup::Outcome {
+                        object_index: handle::IndexForObjectInPack { pack_id, pack_offset },
+                        pack,
+                        ..
+                    }) => (pack_id, pack_offset, pack),
+                    None => continue,
+                };
+                if !self.store.serves_lookups(pack_id) {
+                    continue;
+                }
+                let pack = match possibly_pack {
+                    Some(pack) => pack.clone(),
+                    None => match self.store.load_pack(pack_id, marker)? {
+                        Some(pack) => {
+                            *possibly_pack = Some(pack.clone());
+                            pack
+                        }
+                        None => match self.store.load_one_index(self.refresh, marker)? {
+                            Some(new_snapshot) => {
+                                *snapshot = new_snapshot;
+                                self.clear_cache();
+                                continue 'outer;
+                            }
+                            None => continue,
+                        },
+                    },
+                };
+                return packed(PackedEntry {
+                    index: &snapshot.indices[idx],
+                    pack_id,
+                    pack_offset,
+                    pack,
+                    marker,
+                })
+                .map(Some);
+            }
+
+            for lodb in snapshot.loose_dbs.iter() {
+                if let Some(res) = loose(lodb, id)? {
+                    return Ok(Some(res));
+                }
+            }
+
+            match self.store.load_one_index(self.refresh, snapshot.marker)? {
+                Some(new_snapshot) => {
+                    *snapshot = new_snapshot;
+                    self.clear_cache();
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
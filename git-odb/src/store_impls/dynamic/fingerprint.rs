@@ -0,0 +1,43 @@
+use crate::store::{handle::SingleOrMultiIndex, load_index};
+
+impl super::Store {
+    /// Return a hash identifying the content of this object database as given by all of its pack indices, identified by their
+    /// file name and checksum, and the canonical location of all of its loose object databases, including alternates.
+    ///
+    /// Unlike the [marker][super::Store::marker()] of the store, which is only valid within this process and changes as
+    /// indices are loaded, the fingerprint only depends on the state on disk. Thus it's the same for all processes
+    /// looking at the same repository, and suitable as key for caches that persist across restarts.
+    ///
+    /// Note that all indices are loaded to compute it, and that changes to loose objects aren't reflected.
+    pub fn fingerprint(&self) -> Result<git_hash::ObjectId, load_index::Error> {
+        let snapshot = self.load_all_indices()?;
+        let mut indices: Vec<_> = snapshot
+            .indices
+            .iter()
+            .map(|index| {
+                let (path, checksum) = match &index.file {
+                    SingleOrMultiIndex::Single { index, .. } => (index.path(), index.index_checksum()),
+                    SingleOrMultiIndex::Multi { index, .. } => (index.path(), index.checksum()),
+                };
+                (path.file_name().unwrap_or_default().to_owned(), checksum)
+            })
+            .collect();
+        indices.sort();
+
+        let mut hasher = git_features::hash::hasher(self.object_hash);
+        for (name, checksum) in indices {
+            hasher.update(b"index ");
+            hasher.update(name.to_string_lossy().as_bytes());
+            hasher.update(checksum.as_bytes());
+        }
+        for loose_db in snapshot.loose_dbs.iter() {
+            // The same directory may be reached through different paths, so only its canonical form identifies it.
+            let path = self.current_dir.join(loose_db.path());
+            let path = std::fs::canonicalize(&path).unwrap_or(path);
+            hasher.update(b"loose ");
+            hasher.update(path.to_string_lossy().as_bytes());
+            hasher.update(b"\n");
+        }
+        Ok(git_hash::ObjectId::from(hasher.digest()))
+    }
+}
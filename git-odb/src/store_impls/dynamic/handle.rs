@@ -2,6 +2,7 @@ use std::{
     cell::RefCell,
     convert::{TryFrom, TryInto},
     ops::Deref,
+    path::PathBuf,
     rc::Rc,
     sync::{atomic::Ordering, Arc},
 };
@@ -120,6 +121,49 @@ pub(crate) mod index_lookup {
             }
         }
 
+        /// Return the id of the pack containing `object_id` if it is present in this index.
+        pub(crate) fn pack_id(&self, object_id: &oid) -> Option<types::PackId> {
+            match &self.file {
+                handle::SingleOrMultiIndex::Single { index, .. } => index.lookup(object_id).map(|_| types::PackId {
+                    index: self.id,
+                    multipack_index: None,
+                }),
+                handle::SingleOrMultiIndex::Multi { index, .. } => {
+                    index.lookup(object_id).map(|entry_index| types::PackId {
+                        index: self.id,
+                        multipack_index: Some(index.pack_id_and_pack_offset_at_index(entry_index).0),
+                    })
+                }
+            }
+        }
+
+        /// Return the id of the object at `pack_offset` in the pack identified by `pack_id`, if it is part of this index.
+        ///
+        /// Note that this is a linear search through all entries of the index.
+        pub(crate) fn oid_at_pack_offset(
+            &self,
+            pack_id: types::PackId,
+            pack_offset: git_pack::data::Offset,
+        ) -> Option<git_hash::ObjectId> {
+            self.iter(pack_id)?
+                .find_map(|entry| (entry.pack_offset == pack_offset).then(|| entry.oid))
+        }
+
+        /// Return the smallest pack offset larger than `pack_offset` in the pack identified by `pack_id`, which is where the
+        /// entry following the one at `pack_offset` begins, or `None` if there is no such entry or the pack isn't part of this index.
+        ///
+        /// Note that this is a linear search through all entries of the index.
+        pub(crate) fn next_pack_offset(
+            &self,
+            pack_id: types::PackId,
+            pack_offset: git_pack::data::Offset,
+        ) -> Option<git_pack::data::Offset> {
+            self.iter(pack_id)?
+                .map(|entry| entry.pack_offset)
+                .filter(|offset| *offset > pack_offset)
+                .min()
+        }
+
         /// Return true if the given object id exists in this index
         pub(crate) fn oid_at_index(&self, entry_index: u32) -> &git_hash::oid {
             match &self.file {
@@ -128,6 +172,19 @@ pub(crate) mod index_lookup {
             }
         }
 
+        /// Return the id of the pack containing the object at `entry_index`.
+        pub(crate) fn pack_id_at_index(&self, entry_index: u32) -> types::PackId {
+            types::PackId {
+                index: self.id,
+                multipack_index: match &self.file {
+                    handle::SingleOrMultiIndex::Single { .. } => None,
+                    handle::SingleOrMultiIndex::Multi { index, .. } => {
+                        Some(index.pack_id_and_pack_offset_at_index(entry_index).0)
+                    }
+                },
+            }
+        }
+
         /// Return the amount of objects contained in the index, essentially the number of object ids.
         pub(crate) fn num_objects(&self) -> u32 {
             match &self.file {
@@ -158,6 +215,19 @@ pub(crate) mod index_lookup {
             Some(res.map(|entry_index| self.oid_at_index(entry_index).to_owned()))
         }
 
+        /// Return a utility to look up pack offsets of objects within the pack identified by `pack_id`, which must be part of this index.
+        pub(crate) fn intra_pack_lookup(&self, pack_id: types::PackId) -> IntraPackLookup<'_> {
+            match &self.file {
+                handle::SingleOrMultiIndex::Single { index, .. } => IntraPackLookup::Single(index),
+                handle::SingleOrMultiIndex::Multi { index, .. } => IntraPackLookup::Multi {
+                    index,
+                    required_pack_index: pack_id.multipack_index.expect(
+                        "BUG: multi-pack index must be set if this is a multi-pack, pack-indices seem unstable",
+                    ),
+                },
+            }
+        }
+
         /// See if the oid is contained in this index, and return its full id for lookup possibly alongside its data file if already
         /// loaded.
         /// Also return the index itself as it's needed to resolve intra-pack ref-delta objects. They are a possibility even though
@@ -256,7 +326,10 @@ impl super::Store {
             store: self.clone(),
             refresh: RefreshMode::default(),
             ignore_replacements: false,
+            follow_replacement_chains: false,
+            deadline: None,
             token: Some(token),
+            replacements: None,
             snapshot: RefCell::new(self.collect_snapshot()),
             max_recursion_depth: Self::INITIAL_MAX_RECURSION_DEPTH,
             packed_object_count: Default::default(),
@@ -272,7 +345,10 @@ impl super::Store {
             store: self.clone(),
             refresh: Default::default(),
             ignore_replacements: false,
+            follow_replacement_chains: false,
+            deadline: None,
             token: Some(token),
+            replacements: None,
             snapshot: RefCell::new(self.collect_snapshot()),
             max_recursion_depth: Self::INITIAL_MAX_RECURSION_DEPTH,
             packed_object_count: Default::default(),
@@ -313,6 +389,37 @@ where
         self.store.clone()
     }
 
+    /// The kind of object hash used by the store, see [`Store::object_hash()`][super::Store::object_hash()].
+    pub fn object_hash(&self) -> git_hash::Kind {
+        self.store.object_hash()
+    }
+
+    /// Return the path to the pack data file identified by `id`, which is useful to tell which pack an error is related to.
+    ///
+    /// For packs in a multi-pack index, this is the path of the member pack, not the one of the multi-pack index.
+    /// `None` is returned if `id` is unknown or stale, as the store started a new generation since this handle last
+    /// looked, possibly reusing the slot for another index.
+    pub fn pack_path(&self, id: types::PackId) -> Option<PathBuf> {
+        let marker = self.snapshot.borrow().marker;
+        if self.store.index.load().generation != marker.generation {
+            return None;
+        }
+        let slot = self.store.files.get(id.index)?;
+        // Pin the value before checking the generation, so we won't see a newer one afterwards.
+        let slot_files = slot.files.load();
+        if slot.generation.load(Ordering::SeqCst) > marker.generation {
+            return None;
+        }
+        let path = match (id.multipack_index, Option::as_ref(&slot_files)?) {
+            (None, types::IndexAndPacks::Index(bundle)) => bundle.data.path(),
+            (Some(pack_index), types::IndexAndPacks::MultiIndex(bundle)) => {
+                bundle.data.get(pack_index as usize)?.path()
+            }
+            _ => return None,
+        };
+        Some(path.to_owned())
+    }
+
     /// Set the handle to never cause ODB refreshes if an object could not be found.
     ///
     /// The latter is the default, as typically all objects referenced in a git repository are contained in the local clone.
@@ -326,6 +433,46 @@ where
     pub fn refresh_mode(&mut self) -> RefreshMode {
         self.refresh
     }
+
+    /// Use `replacements`, pairs of object ids `X` and the id `X` is replaced with, instead of the replacements of the store
+    /// for all lookups through this handle, or use the ones of the store again if `None`.
+    ///
+    /// This allows to see replaced objects in some handles without affecting any other handle of the same store.
+    pub fn set_replacements(
+        &mut self,
+        replacements: Option<impl IntoIterator<Item = (git_hash::ObjectId, git_hash::ObjectId)>>,
+    ) {
+        self.replacements = replacements.map(|replacements| {
+            let mut replacements: Vec<_> = replacements.into_iter().collect();
+            replacements.sort_by(|a, b| a.0.cmp(&b.0));
+            Arc::new(replacements)
+        });
+    }
+
+    /// The replacements used by this handle, as set with [`set_replacements()`][Self::set_replacements()] or the ones of the
+    /// store, sorted by the object id to replace.
+    pub fn replacements(&self) -> &[(git_hash::ObjectId, git_hash::ObjectId)] {
+        self.replacements.as_deref().unwrap_or(&self.store.replacements)
+    }
+
+    /// Return the id of the object `id` is replaced with, or `id` itself if it isn't replaced or replacements are ignored.
+    pub(crate) fn replaced_id<'a>(&'a self, mut id: &'a oid) -> &'a oid {
+        if self.ignore_replacements {
+            return id;
+        }
+        let replacements = self.replacements();
+        // Each replacement can be used only once, so cycles can't keep us here forever.
+        for _ in 0..replacements.len() {
+            match replacements.binary_search_by(|(map_this, _)| map_this.as_ref().cmp(id)) {
+                Ok(pos) => id = replacements[pos].1.as_ref(),
+                Err(_) => break,
+            }
+            if !self.follow_replacement_chains {
+                break;
+            }
+        }
+        id
+    }
 }
 
 impl<S> Drop for super::Handle<S>
@@ -343,6 +490,29 @@ impl TryFrom<&super::Store> for super::Store {
     type Error = std::io::Error;
 
     fn try_from(s: &super::Store) -> Result<Self, Self::Error> {
+        if !s.scan_disk {
+            let index = s.index.load();
+            let mut store = super::Store::at_index_paths(
+                index
+                    .slot_indices
+                    .iter()
+                    .filter_map(|idx| Option::as_ref(&s.files[*idx].files.load()).map(|f| f.index_path().to_owned())),
+                s.object_hash,
+            )?;
+            store.label = s.label.clone();
+            store.verify_pack_matches_index_on_load = s.verify_pack_matches_index_on_load;
+            store.backend = Arc::clone(&s.backend);
+            store.max_open_files = s
+                .max_open_files
+                .as_ref()
+                .map(|budget| Arc::new(crate::store::types::OpenFilesBudget::new(budget.max)));
+            store.loose_read_buffer_size = s.loose_read_buffer_size;
+            store.verify_object_hashes = s.verify_object_hashes;
+            store.transform = s.transform.clone();
+            store.quarantine_corrupt_packs = s.quarantine_corrupt_packs;
+            store.index_sort_order = s.index_sort_order;
+            return Ok(store);
+        }
         super::Store::at_opts(
             s.path(),
             s.replacements(),
@@ -351,6 +521,20 @@ impl TryFrom<&super::Store> for super::Store {
                 object_hash: Default::default(),
                 use_multi_pack_index: false,
                 current_dir: s.current_dir.clone().into(),
+                label: s.label.clone(),
+                verify_pack_matches_index_on_load: s.verify_pack_matches_index_on_load,
+                max_alternates: s.max_alternates,
+                ignore_empty_packs: s.ignore_empty_packs,
+                backend: Arc::clone(&s.backend),
+                max_open_files: s.max_open_files.as_ref().map(|budget| budget.max),
+                loose_read_buffer_size: s.loose_read_buffer_size,
+                verify_object_hashes: s.verify_object_hashes,
+                transform: s.transform.clone(),
+                index_loader_threads: s.index_loaders.as_ref().map(|loaders| loaders.num_threads()),
+                deterministic_slot_assignment: s.deterministic_slot_assignment,
+                garbage_slots_serve_lookups: s.garbage_slots_serve_lookups,
+                quarantine_corrupt_packs: s.quarantine_corrupt_packs,
+                index_sort_order: s.index_sort_order,
             },
         )
     }
@@ -363,6 +547,9 @@ impl super::Handle<Rc<super::Store>> {
         let mut cache = store.to_handle_arc();
         cache.refresh = self.refresh;
         cache.max_recursion_depth = self.max_recursion_depth;
+        cache.deadline = self.deadline;
+        cache.follow_replacement_chains = self.follow_replacement_chains;
+        cache.replacements = self.replacements.clone();
         Ok(cache)
     }
 }
@@ -383,6 +570,8 @@ where
             store: self.store.clone(),
             refresh: self.refresh,
             ignore_replacements: self.ignore_replacements,
+            follow_replacement_chains: self.follow_replacement_chains,
+            deadline: self.deadline,
             token: {
                 let token = self.store.register_handle();
                 match self.token.as_ref().expect("token is always set here ") {
@@ -391,6 +580,7 @@ where
                 }
                 .into()
             },
+            replacements: self.replacements.clone(),
             snapshot: RefCell::new(self.store.collect_snapshot()),
             max_recursion_depth: self.max_recursion_depth,
             packed_object_count: Default::default(),
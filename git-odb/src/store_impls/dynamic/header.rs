@@ -25,14 +25,8 @@ where
                     id: r.original_id.to_owned(),
                 });
             }
-        } else if !self.ignore_replacements {
-            if let Ok(pos) = self
-                .store
-                .replacements
-                .binary_search_by(|(map_this, _)| map_this.as_ref().cmp(id))
-            {
-                id = self.store.replacements[pos].1.as_ref();
-            }
+        } else {
+            id = self.replaced_id(id);
         }
 
         'outer: loop {
@@ -54,17 +48,17 @@ where
                                 }
                                 None => {
                                     // The pack wasn't available anymore so we are supposed to try another round with a fresh index
-                                    match self.store.load_one_index(self.refresh, snapshot.marker)? {
+                                    match self.store.load_one_index(self.refresh, marker)? {
                                         Some(new_snapshot) => {
                                             *snapshot = new_snapshot;
                                             self.clear_cache();
                                             continue 'outer;
                                         }
                                         None => {
-                                            // nothing new in the index, kind of unexpected to not have a pack but to also
-                                            // to have no new index yet. We set the new index before removing any slots, so
-                                            // this should be observable.
-                                            return Ok(None);
+                                            // nothing new in the index even though the pack is gone, which happens if it is
+                                            // deleted before its index during a repack. The object may still be stored
+                                            // elsewhere, so keep looking before concluding it doesn't exist.
+                                            continue;
                                         }
                                     }
                                 }
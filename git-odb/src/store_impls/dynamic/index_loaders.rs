@@ -0,0 +1,108 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::store::{
+    backend::Backend,
+    load_index::load_next_slot,
+    types::{MutableIndexAndPack, OpenFilesBudget, SlotMapIndex},
+};
+
+/// A pool of threads to load the indices of a slot map index in the background, so lookups don't have to.
+///
+/// Each thread claims the next index to load just like lookups would, which is why they can be mixed freely.
+pub(crate) struct IndexLoaders {
+    /// Sends each slot map index whose indices should be loaded once per thread, or `None` once we are shutting down.
+    sender: parking_lot::Mutex<Option<mpsc::Sender<Arc<SlotMapIndex>>>>,
+    /// Tell threads to stop loading indices early.
+    should_interrupt: Arc<AtomicBool>,
+    /// Notified by threads after each attempt to load an index.
+    loaded: Arc<(parking_lot::Mutex<()>, parking_lot::Condvar)>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl IndexLoaders {
+    /// Start `num_threads` threads to load indices of slots in `files` using `backend` while staying within `max_open_files`,
+    /// with `0` threads being treated as `1`.
+    pub(crate) fn start(
+        num_threads: usize,
+        files: &Arc<Vec<MutableIndexAndPack>>,
+        backend: &Arc<dyn Backend>,
+        max_open_files: Option<&Arc<OpenFilesBudget>>,
+        object_hash: git_hash::Kind,
+    ) -> std::io::Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Arc<SlotMapIndex>>();
+        let receiver = Arc::new(parking_lot::Mutex::new(receiver));
+        let should_interrupt = Arc::new(AtomicBool::new(false));
+        let loaded = Arc::new((parking_lot::Mutex::new(()), parking_lot::Condvar::new()));
+        let threads = (0..num_threads.max(1))
+            .map(|thread_id| {
+                let receiver = Arc::clone(&receiver);
+                let should_interrupt = Arc::clone(&should_interrupt);
+                let loaded = Arc::clone(&loaded);
+                let files = Arc::clone(files);
+                let backend = Arc::clone(backend);
+                let max_open_files = max_open_files.cloned();
+                std::thread::Builder::new()
+                    .name(format!("git-odb index loader {}", thread_id))
+                    .spawn(move || loop {
+                        let index = match receiver.lock().recv() {
+                            Ok(index) => index,
+                            Err(_disconnected) => break,
+                        };
+                        while !should_interrupt.load(Ordering::Relaxed)
+                            && load_next_slot(&files, &*backend, max_open_files.as_deref(), object_hash, &index)
+                                .is_some()
+                        {
+                            let _lock = loaded.0.lock();
+                            loaded.1.notify_all();
+                        }
+                    })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        Ok(IndexLoaders {
+            sender: parking_lot::Mutex::new(Some(sender)),
+            should_interrupt,
+            loaded,
+            threads,
+        })
+    }
+
+    /// The amount of threads loading indices.
+    pub(crate) fn num_threads(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Have all threads load the indices of `index` which aren't loaded yet.
+    pub(crate) fn schedule(&self, index: &Arc<SlotMapIndex>) {
+        if let Some(sender) = self.sender.lock().as_ref() {
+            for _ in 0..self.threads.len() {
+                sender.send(Arc::clone(index)).ok();
+            }
+        }
+    }
+
+    /// Block until `is_done()` returns true, which is checked each time an index was loaded, or regularly in case it depends
+    /// on something else.
+    pub(crate) fn wait_until(&self, mut is_done: impl FnMut() -> bool) {
+        let mut lock = self.loaded.0.lock();
+        while !is_done() {
+            self.loaded.1.wait_for(&mut lock, Duration::from_millis(10));
+        }
+    }
+}
+
+impl Drop for IndexLoaders {
+    fn drop(&mut self) {
+        self.should_interrupt.store(true, Ordering::Relaxed);
+        self.sender.lock().take();
+        for thread in self.threads.drain(..) {
+            thread.join().ok();
+        }
+    }
+}
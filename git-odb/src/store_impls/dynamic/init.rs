@@ -3,7 +3,12 @@ use std::{iter::FromIterator, path::PathBuf, sync::Arc};
 use arc_swap::ArcSwap;
 
 use crate::{
-    store::types::{MutableIndexAndPack, SlotMapIndex},
+    store::{
+        backend::{self, Backend},
+        index_loaders::IndexLoaders,
+        transform::Transform,
+        types::{IndexAndPacks, MutableIndexAndPack, OpenFilesBudget, SlotMapIndex},
+    },
     Store,
 };
 
@@ -19,6 +24,84 @@ pub struct Options {
     /// The current directory of the process at the time of instantiation.
     /// If unset, it will be retrieved using `std::env::current_dir()`.
     pub current_dir: Option<std::path::PathBuf>,
+    /// A name to identify the store with in diagnostics, useful if a process hosts many stores at once.
+    ///
+    /// It has no effect on the behaviour of the store.
+    pub label: Option<String>,
+    /// If true, assure that a pack data file belongs to its index when loading it by comparing the pack checksum recorded in the index
+    /// with the one at the end of the pack. Packs that don't match are considered corrupt and won't be read from.
+    ///
+    /// This only applies to packs that are referenced by an index, as multi-pack indices don't record pack checksums.
+    pub verify_pack_matches_index_on_load: bool,
+    /// If set, the maximum amount of alternate object databases to follow, directly or indirectly through other alternates.
+    ///
+    /// Resolving more alternates than that is an error, which is useful if alternates of a repository can't be trusted.
+    pub max_alternates: Option<usize>,
+    /// If true, pack indices without any object won't be registered at all as they can't contribute to lookups.
+    ///
+    /// This avoids using slots and memory maps for them, but note that such packs can be created intentionally by some tools.
+    pub ignore_empty_packs: bool,
+    /// The way pack indices, multi-pack indices and packs are opened, which is from the local filesystem by default.
+    pub backend: Arc<dyn Backend>,
+    /// If set, the maximum amount of index, multi-pack index and pack data files to keep open at a time, to avoid running out
+    /// of file descriptors or memory maps when many stores are used in one process.
+    ///
+    /// Before opening another index or pack, loaded packs are unloaded to stay within the budget, starting with those that no handle
+    /// uses and those that were used least recently. Room is reserved for each file before it is opened, so concurrent handles and
+    /// index loader threads can't exceed the budget. Indices are never unloaded to make room as lookups need them, and multi-pack
+    /// indices opened while refreshing count towards the budget without making room first, so it can be exceeded if there are
+    /// more indices than the budget allows. Note that handles keep the packs they use mapped until they are dropped or refresh
+    /// their view of the store, so this limits the files held by the store itself. A budget of `0` is treated as `1`.
+    ///
+    /// The amount of files currently open is shown by [`Metrics::open_files`][crate::store::Metrics::open_files].
+    /// Defaults to a quarter of the limit of open files of the process if it can be determined, or `None` otherwise.
+    pub max_open_files: Option<usize>,
+    /// If set, loose objects are decompressed while reading their files through a buffer of this many bytes, instead of reading
+    /// each file into memory first.
+    ///
+    /// This bounds the memory needed to decompress a loose object to its own size plus the buffer, trading throughput for a lower
+    /// peak, see [`loose::Store::with_read_buffer_size()`][crate::loose::Store::with_read_buffer_size()].
+    /// Packed objects are decompressed straight from memory-mapped packs and don't use a read buffer.
+    pub loose_read_buffer_size: Option<usize>,
+    /// If true, recompute the hash of each object found from its decoded content and fail with
+    /// [`HashMismatch`][crate::store::find::Error::HashMismatch] if it differs from the id it was looked up by.
+    ///
+    /// This detects corrupted or maliciously crafted objects at the cost of hashing every object read, which is why it's off
+    /// by default. Replaced objects are verified against the id of their replacement.
+    pub verify_object_hashes: bool,
+    /// If set, the data of objects is transformed between the form it's stored in and its actual form when reading
+    /// and writing objects, see [`Transform`] for details.
+    pub transform: Option<Arc<dyn Transform>>,
+    /// If set, the amount of threads to load pack indices and multi-pack indices on in the background, with `0` being treated as `1`.
+    ///
+    /// Indices are then loaded as soon as they are discovered on disk instead of when lookups need them, so these don't have to pay
+    /// the cost of mapping and checking indices. Lookups with [`RefreshMode::AfterAllIndicesLoaded`][crate::store::RefreshMode::AfterAllIndicesLoaded]
+    /// wait for indices that are still loading before declaring an object missing, whereas those with [`RefreshMode::Never`][crate::store::RefreshMode::Never]
+    /// only search the indices loaded so far and may thus miss objects shortly after the store was opened.
+    /// The threads are stopped and joined when the store is dropped.
+    pub index_loader_threads: Option<usize>,
+    /// If true, slots are assigned to newly discovered indices in the order of their file names, and of their paths for indices
+    /// of the same name in different object databases, instead of depending on the order in which they are found on disk.
+    ///
+    /// This makes pack ids the same for object databases with the same files, which helps reproducing problems and asserting on
+    /// pack ids in tests. Indices are still searched in the order they are found in, and slots already in use are never reassigned.
+    pub deterministic_slot_assignment: bool,
+    /// If false, packs and indices that were removed from disk but are kept available for handles that need stable pack ids
+    /// aren't used for lookups anymore, so the objects only they contain can't be found even though pack ids remain valid.
+    ///
+    /// This is useful for callers that must not see objects which are about to disappear, like those pruning objects, and
+    /// affects [locations][git_pack::data::entry::Location] obtained before the removal as well.
+    pub garbage_slots_serve_lookups: bool,
+    /// If true, packs that turn out to be corrupt while reading an object from them, for instance as they were truncated, are
+    /// poisoned and skipped by all lookups instead of failing them, so objects in other packs remain available.
+    ///
+    /// Poisoned packs are counted in [`Metrics::poisoned_packs`][crate::store::Metrics::poisoned_packs] and listed by
+    /// [`Store::corrupt_files()`][super::Store::corrupt_files()]. They are used again once a refresh sees that they changed on disk.
+    pub quarantine_corrupt_packs: bool,
+    /// The order in which indices are searched for objects when a lookup starts, see [`IndexSortOrder`] for details.
+    ///
+    /// It doesn't affect which objects can be found, but how many indices are probed before finding them.
+    pub index_sort_order: IndexSortOrder,
 }
 
 impl Default for Options {
@@ -28,10 +111,47 @@ impl Default for Options {
             object_hash: Default::default(),
             use_multi_pack_index: true,
             current_dir: None,
+            label: None,
+            verify_pack_matches_index_on_load: false,
+            max_alternates: None,
+            ignore_empty_packs: false,
+            backend: Arc::new(backend::Filesystem),
+            max_open_files: default_max_open_files(),
+            loose_read_buffer_size: None,
+            verify_object_hashes: false,
+            transform: None,
+            index_loader_threads: None,
+            deterministic_slot_assignment: false,
+            garbage_slots_serve_lookups: true,
+            quarantine_corrupt_packs: false,
+            index_sort_order: Default::default(),
         }
     }
 }
 
+/// The order in which pack indices and multi-pack indices are searched for objects, which is determined each time the store
+/// learns about new or removed indices.
+///
+/// Handles still move indices that were found to contain an object to the front, so this is the order a lookup starts with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IndexSortOrder {
+    /// The biggest indices come first, which makes it most likely to find an object in the first index that is searched.
+    BySizeDescending,
+    /// The smallest indices come first, which is useful if small packs are read more often, for instance as they contain recent objects.
+    BySizeAscending,
+    /// The most recently modified indices come first, like in `libgit2`.
+    ModifiedTimeNewestFirst,
+    /// Indices are sorted by their file name, and by their path for indices of the same name in different object databases, so the
+    /// order only depends on which files exist.
+    ByNameStable,
+}
+
+impl Default for IndexSortOrder {
+    fn default() -> Self {
+        IndexSortOrder::BySizeDescending
+    }
+}
+
 /// Configures the amount of slots in the index slotmap, which is fixed throughout the existence of the store.
 #[derive(Copy, Clone, Debug)]
 pub enum Slots {
@@ -77,6 +197,20 @@ impl Store {
             object_hash,
             use_multi_pack_index,
             current_dir,
+            label,
+            verify_pack_matches_index_on_load,
+            max_alternates,
+            ignore_empty_packs,
+            backend,
+            max_open_files,
+            loose_read_buffer_size,
+            verify_object_hashes,
+            transform,
+            index_loader_threads,
+            deterministic_slot_assignment,
+            garbage_slots_serve_lookups,
+            quarantine_corrupt_packs,
+            index_sort_order,
         }: Options,
     ) -> std::io::Result<Self> {
         let objects_dir = objects_dir.into();
@@ -90,12 +224,19 @@ impl Store {
         let slot_count = match slots {
             Slots::Given(n) => n as usize,
             Slots::AsNeededByDiskState { multiplier, minimum } => {
-                let mut db_paths = crate::alternate::resolve(&objects_dir, &current_dir)
+                let mut db_paths = crate::alternate::resolve_opts(&objects_dir, &current_dir, max_alternates)
                     .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
                 db_paths.insert(0, objects_dir.clone());
-                let num_slots = super::Store::collect_indices_and_mtime_sorted_by_size(db_paths, None, None)
-                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?
-                    .len();
+                let num_slots = super::Store::collect_indices_and_mtime_sorted(
+                    &*backend,
+                    db_paths,
+                    None,
+                    None,
+                    ignore_empty_packs.then(|| object_hash),
+                    index_sort_order,
+                )
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?
+                .len();
 
                 ((num_slots as f32 * multiplier) as usize).max(minimum)
             }
@@ -109,18 +250,165 @@ impl Store {
         let mut replacements: Vec<_> = replacements.into_iter().collect();
         replacements.sort_by(|a, b| a.0.cmp(&b.0));
 
+        let files = Arc::new(Vec::from_iter(
+            std::iter::repeat_with(MutableIndexAndPack::default).take(slot_count),
+        ));
+        let max_open_files = max_open_files.map(|max| Arc::new(OpenFilesBudget::new(max)));
+        let index_loaders = index_loader_threads
+            .map(|num_threads| IndexLoaders::start(num_threads, &files, &backend, max_open_files.as_ref(), object_hash))
+            .transpose()?;
+
         Ok(Store {
             current_dir,
             write: Default::default(),
+            refresh: Default::default(),
+            refresh_done: Default::default(),
             replacements,
             path: objects_dir,
-            files: Vec::from_iter(std::iter::repeat_with(MutableIndexAndPack::default).take(slot_count)),
+            files,
             index: ArcSwap::new(Arc::new(SlotMapIndex::default())),
             use_multi_pack_index,
             object_hash,
             num_handles_stable: Default::default(),
             num_handles_unstable: Default::default(),
             num_disk_state_consolidation: Default::default(),
+            lookup_counters: Default::default(),
+            subscribers: Default::default(),
+            state_change_callbacks: Default::default(),
+            pack_count_history: Default::default(),
+            pack_use_clock: Default::default(),
+            label,
+            scan_disk: true,
+            verify_pack_matches_index_on_load,
+            max_alternates,
+            ignore_empty_packs,
+            backend,
+            max_open_files,
+            loose_read_buffer_size,
+            verify_object_hashes,
+            transform,
+            deterministic_slot_assignment,
+            garbage_slots_serve_lookups,
+            quarantine_corrupt_packs,
+            index_sort_order,
+            index_loaders,
         })
     }
+
+    /// Open a store which only knows the pack indices at `index_paths`, each with their pack data file right next to them,
+    /// without scanning any directory, and without any loose object databases or alternates.
+    ///
+    /// This is useful to query a standalone pack, like one that was just downloaded. As the given files are all there is,
+    /// the store is ready to use immediately and never looks for changes on disk.
+    /// `object_hash` is the kind of hash used in all indices and packs.
+    pub fn at_index_paths(
+        index_paths: impl IntoIterator<Item = impl Into<PathBuf>>,
+        object_hash: git_hash::Kind,
+    ) -> std::io::Result<Self> {
+        let bundles = index_paths
+            .into_iter()
+            .map(|index_path| {
+                let index_path = index_path.into();
+                let mtime = std::fs::metadata(&index_path)?.modified()?;
+                if !index_path.with_extension("pack").is_file() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("There is no pack data file for index at '{}'", index_path.display()),
+                    ));
+                }
+                Ok(IndexAndPacks::new_single(index_path, mtime))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        if bundles.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "At least one index path must be given",
+            ));
+        }
+        if bundles.len() > crate::store::types::PackId::max_indices() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "Cannot use more than 1^15 slots",
+            ));
+        }
+        let path = bundles[0]
+            .index_path()
+            .parent()
+            .map(ToOwned::to_owned)
+            .unwrap_or_default();
+        let slot_indices = (0..bundles.len()).collect();
+        let files = Arc::new(
+            bundles
+                .into_iter()
+                .map(|bundle| MutableIndexAndPack {
+                    pack_hits: ArcSwap::from_pointee(
+                        std::iter::repeat_with(Default::default)
+                            .take(bundle.num_packs())
+                            .collect(),
+                    ),
+                    files: ArcSwap::from_pointee(Some(bundle)),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+
+        Ok(Store {
+            current_dir: std::env::current_dir()?,
+            write: Default::default(),
+            refresh: Default::default(),
+            refresh_done: Default::default(),
+            replacements: Vec::new(),
+            path,
+            files,
+            index: ArcSwap::new(Arc::new(SlotMapIndex {
+                slot_indices,
+                ..Default::default()
+            })),
+            use_multi_pack_index: false,
+            object_hash,
+            num_handles_stable: Default::default(),
+            num_handles_unstable: Default::default(),
+            num_disk_state_consolidation: Default::default(),
+            lookup_counters: Default::default(),
+            subscribers: Default::default(),
+            state_change_callbacks: Default::default(),
+            pack_count_history: Default::default(),
+            pack_use_clock: Default::default(),
+            label: None,
+            scan_disk: false,
+            verify_pack_matches_index_on_load: false,
+            max_alternates: None,
+            ignore_empty_packs: false,
+            backend: Arc::new(backend::Filesystem),
+            max_open_files: None,
+            loose_read_buffer_size: None,
+            verify_object_hashes: false,
+            transform: None,
+            deterministic_slot_assignment: false,
+            garbage_slots_serve_lookups: true,
+            quarantine_corrupt_packs: false,
+            index_sort_order: Default::default(),
+            index_loaders: None,
+        })
+    }
+}
+
+/// Return a quarter of the soft limit of open files of the process, or `None` if it is unlimited or can't be determined.
+fn default_max_open_files() -> Option<usize> {
+    #[cfg(unix)]
+    {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is valid for writes and `RLIMIT_NOFILE` is a valid resource.
+        #[allow(unsafe_code)]
+        let res = unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) };
+        (res == 0 && limit.rlim_cur != libc::RLIM_INFINITY)
+            .then(|| usize::try_from(limit.rlim_cur / 4).unwrap_or(usize::MAX).max(1))
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
 }
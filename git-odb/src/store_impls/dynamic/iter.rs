@@ -1,4 +1,11 @@
-use std::{ops::Deref, option::Option::None, sync::Arc, vec::IntoIter};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    ops::{Deref, RangeInclusive},
+    option::Option::None,
+    sync::Arc,
+    vec::IntoIter,
+};
 
 use git_hash::ObjectId;
 
@@ -217,6 +224,206 @@ impl Iterator for AllObjects {
     }
 }
 
+/// An iterator over the loose objects of an object store, keeping only those which are either not contained in any of its packs,
+/// or only those which are.
+///
+/// Loose objects that aren't packed are candidates for packing, whereas those that are also packed are redundant and could be deleted.
+/// Packs and loose object databases are taken from a single snapshot of the store, so packs added while iterating are not considered.
+pub struct LooseObjects {
+    indices: Vec<handle::IndexLookup>,
+    loose_dbs: Arc<Vec<loose::Store>>,
+    iter: Option<loose::Iter>,
+    index: usize,
+    packed: bool,
+}
+
+impl LooseObjects {
+    /// Create a new iterator from a dynamic store which yields only loose objects that are also `packed`, or only those which
+    /// aren't packed. It will be forced to load all indices eagerly and in the current thread.
+    pub fn new(db: &dynamic::Store, packed: bool) -> Result<Self, crate::store::load_index::Error> {
+        let snapshot = db.load_all_indices()?;
+        let index = 0;
+        Ok(LooseObjects {
+            iter: snapshot.loose_dbs.get(index).map(|ldb| ldb.iter()),
+            indices: snapshot.indices,
+            loose_dbs: snapshot.loose_dbs,
+            index,
+            packed,
+        })
+    }
+}
+
+impl Iterator for LooseObjects {
+    type Item = Result<ObjectId, loose::iter::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.iter.as_mut()?.next() {
+                Some(Ok(id)) => {
+                    if self.indices.iter().any(|index| index.contains(&id)) == self.packed {
+                        return Some(Ok(id));
+                    }
+                }
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    self.index += 1;
+                    self.iter = self.loose_dbs.get(self.index).map(|ldb| ldb.iter());
+                }
+            }
+        }
+    }
+}
+
+/// An iterator over all objects of an object store in ascending order of their ids, yielding each object only once even if it is
+/// stored multiple times.
+///
+/// It merges the already sorted object ids of all pack indices along with those of loose objects, and thus doesn't allocate
+/// more than one entry per pack index and loose object database.
+/// Packs and loose object databases are taken from a single snapshot of the store, so packs added while iterating are not considered.
+/// Errors that occur while reading loose object databases are returned as they are encountered, and iteration continues after them.
+pub struct ObjectsByHash {
+    indices: Vec<handle::IndexLookup>,
+    next_entry_index: Vec<u32>,
+    /// The index of the first entry past the end of the range for each index.
+    end_entry_index: Vec<u32>,
+    loose: Vec<loose::Iter>,
+    /// The first and last object id to yield, inclusive.
+    range: Option<RangeInclusive<ObjectId>>,
+    loose_started: bool,
+    /// Errors that occurred while advancing loose object databases, to be returned before the next object.
+    loose_errors: VecDeque<loose::iter::Error>,
+    heap: BinaryHeap<Reverse<(ObjectId, usize)>>,
+    last: Option<ObjectId>,
+}
+
+impl ObjectsByHash {
+    /// Create a new iterator from a dynamic store, which will be forced to load all indices eagerly and in the current thread.
+    pub fn new(db: &dynamic::Store) -> Result<Self, crate::store::load_index::Error> {
+        Self::new_inner(db, None)
+    }
+
+    /// Create a new iterator from a dynamic store like [`new()`][ObjectsByHash::new()], which only yields objects whose ids are
+    /// within `range`.
+    ///
+    /// The bounds of the range are found in each pack index by binary search, so only the objects in range are visited.
+    /// Loose object databases are traversed in order until the end of the range.
+    pub fn in_range(
+        db: &dynamic::Store,
+        range: RangeInclusive<ObjectId>,
+    ) -> Result<Self, crate::store::load_index::Error> {
+        Self::new_inner(db, Some(range))
+    }
+
+    fn new_inner(
+        db: &dynamic::Store,
+        range: Option<RangeInclusive<ObjectId>>,
+    ) -> Result<Self, crate::store::load_index::Error> {
+        let snapshot = db.load_all_indices()?;
+        let (next_entry_index, end_entry_index) = snapshot
+            .indices
+            .iter()
+            .map(|index| match &range {
+                Some(range) => (
+                    partition_point(index, |id| id < range.start().as_ref()),
+                    partition_point(index, |id| id <= range.end().as_ref()),
+                ),
+                None => (0, index.num_objects()),
+            })
+            .unzip();
+        let mut iter = ObjectsByHash {
+            next_entry_index,
+            end_entry_index,
+            loose: snapshot.loose_dbs.iter().map(|ldb| ldb.iter_sorted()).collect(),
+            range,
+            heap: BinaryHeap::with_capacity(snapshot.indices.len() + snapshot.loose_dbs.len()),
+            indices: snapshot.indices,
+            loose_started: false,
+            loose_errors: VecDeque::new(),
+            last: None,
+        };
+        for source in 0..iter.indices.len() {
+            iter.advance_index(source);
+        }
+        Ok(iter)
+    }
+
+    /// Put the next object of the index at `source` onto the heap, if there is one.
+    fn advance_index(&mut self, source: usize) {
+        let index = &self.indices[source];
+        let entry_index = &mut self.next_entry_index[source];
+        if *entry_index < self.end_entry_index[source] {
+            self.heap
+                .push(Reverse((index.oid_at_index(*entry_index).to_owned(), source)));
+            *entry_index += 1;
+        }
+    }
+
+    /// Put the next object of the loose object database at `source` onto the heap, if there is one, and queue all errors that
+    /// occur on the way so the remaining objects of the database are still visited.
+    fn advance_loose(&mut self, source: usize) {
+        let id = loop {
+            let id = match self.loose[source - self.indices.len()].next() {
+                Some(Ok(id)) => id,
+                Some(Err(err)) => {
+                    self.loose_errors.push_back(err);
+                    continue;
+                }
+                None => return,
+            };
+            match &self.range {
+                Some(range) if id < *range.start() => continue,
+                Some(range) if id > *range.end() => return,
+                _ => break id,
+            }
+        };
+        self.heap.push(Reverse((id, source)));
+    }
+}
+
+/// Return the index of the first entry in `index` for which `is_before` returns false, assuming it returns true for all entries
+/// before it and false for all that follow.
+fn partition_point(index: &handle::IndexLookup, mut is_before: impl FnMut(&git_hash::oid) -> bool) -> u32 {
+    let (mut low, mut high) = (0, index.num_objects());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if is_before(index.oid_at_index(mid)) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+impl Iterator for ObjectsByHash {
+    type Item = Result<ObjectId, loose::iter::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.loose_started {
+            // loose object databases are only started here as they spawn threads, which we don't want to do on creation.
+            self.loose_started = true;
+            for source in self.indices.len()..self.indices.len() + self.loose.len() {
+                self.advance_loose(source);
+            }
+        }
+        loop {
+            if let Some(err) = self.loose_errors.pop_front() {
+                return Some(Err(err));
+            }
+            let Reverse((id, source)) = self.heap.pop()?;
+            if source < self.indices.len() {
+                self.advance_index(source);
+            } else {
+                self.advance_loose(source);
+            }
+            if self.last != Some(id) {
+                self.last = Some(id);
+                return Some(Ok(id));
+            }
+        }
+    }
+}
+
 impl<S> super::Handle<S>
 where
     S: Deref<Target = super::Store> + Clone,
@@ -226,6 +433,33 @@ where
     pub fn iter(&self) -> Result<AllObjects, dynamic::load_index::Error> {
         AllObjects::new(self.store_ref())
     }
+
+    /// Return an iterator over all objects of all linked databases (via alternates) in ascending order of their ids and without
+    /// duplicates, whether they are packed or loose.
+    pub fn iter_by_hash(&self) -> Result<ObjectsByHash, dynamic::load_index::Error> {
+        ObjectsByHash::new(self.store_ref())
+    }
+
+    /// Like [`iter_by_hash()`][Self::iter_by_hash()], but only yield objects whose ids are within `range`, inclusive, which is
+    /// useful to divide the objects of a database into shards to process or replicate.
+    pub fn iter_by_hash_in_range(
+        &self,
+        range: RangeInclusive<ObjectId>,
+    ) -> Result<ObjectsByHash, dynamic::load_index::Error> {
+        ObjectsByHash::in_range(self.store_ref(), range)
+    }
+
+    /// Return an iterator over all loose objects of all linked databases (via alternates) that aren't stored in any pack.
+    pub fn iter_loose_only(&self) -> Result<LooseObjects, dynamic::load_index::Error> {
+        LooseObjects::new(self.store_ref(), false)
+    }
+
+    /// Return an iterator over all loose objects of all linked databases (via alternates) that are also stored in a pack.
+    ///
+    /// Nothing is deleted, but these loose objects are redundant and can safely be removed.
+    pub fn iter_loose_packed(&self) -> Result<LooseObjects, dynamic::load_index::Error> {
+        LooseObjects::new(self.store_ref(), true)
+    }
 }
 
 impl dynamic::Store {
@@ -233,4 +467,27 @@ impl dynamic::Store {
     pub fn iter(&self) -> Result<AllObjects, dynamic::load_index::Error> {
         AllObjects::new(self)
     }
+
+    /// Like [`Handle::iter_by_hash()`][super::Handle::iter_by_hash()], but accessible directly on the store.
+    pub fn iter_by_hash(&self) -> Result<ObjectsByHash, dynamic::load_index::Error> {
+        ObjectsByHash::new(self)
+    }
+
+    /// Like [`Handle::iter_by_hash_in_range()`][super::Handle::iter_by_hash_in_range()], but accessible directly on the store.
+    pub fn iter_by_hash_in_range(
+        &self,
+        range: RangeInclusive<ObjectId>,
+    ) -> Result<ObjectsByHash, dynamic::load_index::Error> {
+        ObjectsByHash::in_range(self, range)
+    }
+
+    /// Like [`Handle::iter_loose_only()`][super::Handle::iter_loose_only()], but accessible directly on the store.
+    pub fn iter_loose_only(&self) -> Result<LooseObjects, dynamic::load_index::Error> {
+        LooseObjects::new(self, false)
+    }
+
+    /// Like [`Handle::iter_loose_packed()`][super::Handle::iter_loose_packed()], but accessible directly on the store.
+    pub fn iter_loose_packed(&self) -> Result<LooseObjects, dynamic::load_index::Error> {
+        LooseObjects::new(self, true)
+    }
 }
@@ -10,10 +10,10 @@ use std::{
     time::SystemTime,
 };
 
-use crate::store::{handle, types, RefreshMode};
+use crate::store::{events::Change, handle, init::IndexSortOrder, types, RefreshMode};
 
 pub(crate) struct Snapshot {
-    /// Indices ready for object lookup or contains checks, ordered usually by modification data, recent ones first.
+    /// Indices ready for object lookup or contains checks, in the order configured with [`IndexSortOrder`].
     pub(crate) indices: Vec<handle::IndexLookup>,
     /// A set of loose objects dbs to search once packed objects weren't found.
     pub(crate) loose_dbs: Arc<Vec<crate::loose::Store>>,
@@ -57,13 +57,13 @@ mod error {
 
 pub use error::Error;
 
-use crate::store::types::{Generation, IndexAndPacks, MutableIndexAndPack, PackId, SlotMapIndex};
+use crate::store::types::{Generation, IndexAndPacks, MutableIndexAndPack, OpenFilesBudget, PackId, SlotMapIndex};
 
 impl super::Store {
     /// Load all indices, refreshing from disk only if needed.
     pub(crate) fn load_all_indices(&self) -> Result<Snapshot, Error> {
         let mut snapshot = self.collect_snapshot();
-        while let Some(new_snapshot) = self.load_one_index(RefreshMode::Never, snapshot.marker)? {
+        while let Some(new_snapshot) = self.load_one_index_inner(RefreshMode::Never, snapshot.marker, true)? {
             snapshot = new_snapshot
         }
         Ok(snapshot)
@@ -71,10 +71,21 @@ impl super::Store {
 
     /// If `None` is returned, there is new indices and the caller should give up. This is a possibility even if it's allowed to refresh
     /// as here might be no change to pick up.
+    ///
+    /// If indices are loaded in the background, this only waits for them to be loaded if `refresh_mode` allows refreshing.
     pub(crate) fn load_one_index(
         &self,
         refresh_mode: RefreshMode,
         marker: types::SlotIndexMarker,
+    ) -> Result<Option<Snapshot>, Error> {
+        self.load_one_index_inner(refresh_mode, marker, !matches!(refresh_mode, RefreshMode::Never))
+    }
+
+    fn load_one_index_inner(
+        &self,
+        refresh_mode: RefreshMode,
+        marker: types::SlotIndexMarker,
+        wait_for_background_loads: bool,
     ) -> Result<Option<Snapshot>, Error> {
         let index = self.index.load();
         if !index.is_initialized() {
@@ -87,7 +98,9 @@ impl super::Store {
         } else {
             // always compare to the latest state
             // Nothing changed in the mean time, try to load another index…
-            if self.load_next_index(index) {
+            let previous_marker = index.marker();
+            if self.load_next_index(index, wait_for_background_loads) {
+                self.notify_state_change(previous_marker);
                 Ok(Some(self.collect_snapshot()))
             } else {
                 // …and if that didn't yield anything new consider refreshing our disk state.
@@ -96,6 +109,17 @@ impl super::Store {
                     RefreshMode::AfterAllIndicesLoaded => {
                         self.consolidate_with_disk_state(false /* needs init */, true /*load one new index*/)
                     }
+                    RefreshMode::AfterDuration(min_interval) => {
+                        let last_completed_at = self.refresh.lock().last_completed_at;
+                        if last_completed_at.map_or(true, |at| at.elapsed() >= min_interval) {
+                            self.consolidate_with_disk_state(
+                                false, /* needs init */
+                                true,  /*load one new index*/
+                            )
+                        } else {
+                            Ok(None)
+                        }
+                    }
                 }
             }
         }
@@ -104,55 +128,45 @@ impl super::Store {
     /// load a new index (if not yet loaded), and return true if one was indeed loaded (leading to a state_id() change) of the current index.
     /// Note that interacting with the slot-map is inherently racy and we have to deal with it, being conservative in what we even try to load
     /// as our index might already be out-of-date as we try to use it to learn what's next.
-    fn load_next_index(&self, mut index: arc_swap::Guard<Arc<SlotMapIndex>>) -> bool {
+    ///
+    /// If indices are loaded in the background, we don't load them ourselves but wait for one to be loaded if `wait_for_background_loads`
+    /// is true, or return without waiting otherwise.
+    fn load_next_index(&self, mut index: arc_swap::Guard<Arc<SlotMapIndex>>, wait_for_background_loads: bool) -> bool {
         'retry_with_changed_index: loop {
             let previous_state_id = index.state_id();
-            'retry_with_next_slot_index: loop {
-                match index
-                    .next_index_to_load
-                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
-                        (current != index.slot_indices.len()).then(|| current + 1)
-                    }) {
-                    Ok(slot_map_index) => {
-                        // This slot-map index is in bounds and was only given to us.
-                        let _ongoing_operation = IncOnNewAndDecOnDrop::new(&index.num_indices_currently_being_loaded);
-                        let slot = &self.files[index.slot_indices[slot_map_index]];
-                        let _lock = slot.write.lock();
-                        if slot.generation.load(Ordering::SeqCst) > index.generation {
-                            // There is a disk consolidation in progress which just overwrote a slot that cold be disposed with some other
-                            // index, one we didn't intend to load.
-                            // Continue with the next slot index in the hope there is something else we can do…
-                            continue 'retry_with_next_slot_index;
-                        }
-                        let mut bundle = slot.files.load_full();
-                        let bundle_mut = Arc::make_mut(&mut bundle);
-                        if let Some(files) = bundle_mut.as_mut() {
-                            // these are always expected to be set, unless somebody raced us. We handle this later by retrying.
-                            let _loaded_count = IncOnDrop(&index.loaded_indices);
-                            match files.load_index(self.object_hash) {
-                                Ok(_) => {
-                                    slot.files.store(bundle);
-                                    break 'retry_with_next_slot_index;
-                                }
-                                Err(_) => {
-                                    slot.files.store(bundle);
-                                    continue 'retry_with_next_slot_index;
-                                }
-                            }
-                        }
+            match &self.index_loaders {
+                Some(loaders) => {
+                    if wait_for_background_loads {
+                        loaders.wait_until(|| {
+                            index.state_id() != previous_state_id
+                                || !index.has_pending_loads()
+                                || !Arc::ptr_eq(&self.index.load(), &index)
+                        });
                     }
-                    Err(_nothing_more_to_load) => {
-                        // There can be contention as many threads start working at the same time and take all the
-                        // slots to load indices for. Some threads might just be left-over and have to wait for something
-                        // to change.
-                        let num_load_operations = index.num_indices_currently_being_loaded.deref();
-                        // TODO: potentially hot loop - could this be a condition variable?
-                        while num_load_operations.load(Ordering::Relaxed) != 0 {
-                            std::thread::yield_now()
+                }
+                None => loop {
+                    match load_next_slot(
+                        &self.files,
+                        &*self.backend,
+                        self.max_open_files.as_deref(),
+                        self.object_hash,
+                        &index,
+                    ) {
+                        Some(true) => break,
+                        Some(false) => continue,
+                        None => {
+                            // There can be contention as many threads start working at the same time and take all the
+                            // slots to load indices for. Some threads might just be left-over and have to wait for something
+                            // to change.
+                            let num_load_operations = index.num_indices_currently_being_loaded.deref();
+                            // TODO: potentially hot loop - could this be a condition variable?
+                            while num_load_operations.load(Ordering::Relaxed) != 0 {
+                                std::thread::yield_now()
+                            }
+                            break;
                         }
-                        break 'retry_with_next_slot_index;
                     }
-                }
+                },
             }
             if previous_state_id == index.state_id() {
                 let potentially_new_index = self.index.load();
@@ -174,10 +188,63 @@ impl super::Store {
 
     /// refresh and possibly clear out our existing data structures, causing all pack ids to be invalidated.
     /// `load_new_index` is an optimization to at least provide one newly loaded pack after refreshing the slot map.
+    ///
+    /// This holds the store-wide write lock while the disk is scanned. Lookups of objects in already known indices don't take it
+    /// and thus aren't blocked by it, only lookups that miss and want to refresh themselves will wait for the refresh to complete.
+    /// These reuse the outcome of the refresh they waited for instead of scanning the disk once more, so that many threads missing
+    /// objects at the same time cause only a single refresh.
     pub(crate) fn consolidate_with_disk_state(
         &self,
         needs_init: bool,
         load_new_index: bool,
+    ) -> Result<Option<Snapshot>, Error> {
+        self.consolidate_with_disk_state_opts(needs_init, load_new_index, false /* force */)
+    }
+
+    /// Like [`consolidate_with_disk_state()`][Self::consolidate_with_disk_state()], but if `force` is true, the disk is always
+    /// scanned by us even if another refresh is in progress or just completed, as these might have missed recent changes.
+    pub(crate) fn consolidate_with_disk_state_opts(
+        &self,
+        needs_init: bool,
+        load_new_index: bool,
+        force: bool,
+    ) -> Result<Option<Snapshot>, Error> {
+        if !self.scan_disk {
+            // The files we were given are all there is, nothing can change.
+            return Ok(None);
+        }
+        let mut refresh = self.refresh.lock();
+        while refresh.in_progress {
+            let num_completed = refresh.num_completed;
+            while refresh.num_completed == num_completed {
+                self.refresh_done.wait(&mut refresh);
+            }
+            if let Some(changed) = refresh.last_changed.filter(|_| !force) {
+                drop(refresh);
+                return Ok((changed || needs_init).then(|| self.collect_snapshot()));
+            }
+            // The refresh failed or can't be trusted, so do it ourselves unless somebody else is already at it.
+        }
+        refresh.in_progress = true;
+        drop(refresh);
+
+        let previous_marker = self.marker();
+        let mut finish = FinishRefreshOnDrop {
+            store: self,
+            changed: None,
+        };
+        let res = self.consolidate_with_disk_state_inner(needs_init, load_new_index, force);
+        finish.changed = res.as_ref().ok().map(Option::is_some);
+        drop(finish);
+        self.notify_state_change(previous_marker);
+        res
+    }
+
+    fn consolidate_with_disk_state_inner(
+        &self,
+        needs_init: bool,
+        load_new_index: bool,
+        force: bool,
     ) -> Result<Option<Snapshot>, Error> {
         let index = self.index.load();
         let previous_index_state = Arc::as_ptr(&index) as usize;
@@ -188,7 +255,7 @@ impl super::Store {
 
         // Now we know the index isn't going to change anymore, even though threads might still load indices in the meantime.
         let index = self.index.load();
-        if previous_index_state != Arc::as_ptr(&index) as usize {
+        if previous_index_state != Arc::as_ptr(&index) as usize && !force {
             // Someone else took the look before and changed the index. Return it without doing any additional work.
             return Ok(Some(self.collect_snapshot()));
         }
@@ -206,10 +273,16 @@ impl super::Store {
         self.num_disk_state_consolidation.fetch_add(1, Ordering::Relaxed);
 
         let db_paths: Vec<_> = std::iter::once(objects_directory.to_owned())
-            .chain(crate::alternate::resolve(objects_directory, &self.current_dir)?)
+            .chain(crate::alternate::resolve_opts(
+                objects_directory,
+                &self.current_dir,
+                self.max_alternates,
+            )?)
             .collect();
 
         // turn db paths into loose object databases. Reuse what's there, but only if it is in the right order.
+        // If only the alternates changed, existing slots are kept as they are which won't affect the generation. Thus pack ids
+        // handed out previously remain valid.
         let loose_dbs = if was_uninitialized
             || db_paths.len() != index.loose_dbs.len()
             || db_paths
@@ -220,17 +293,30 @@ impl super::Store {
             Arc::new(
                 db_paths
                     .iter()
-                    .map(|path| crate::loose::Store::at(path, self.object_hash))
+                    .map(|path| {
+                        index
+                            .loose_dbs
+                            .iter()
+                            .find(|ldb| &ldb.path == path)
+                            .cloned()
+                            .unwrap_or_else(|| {
+                                crate::loose::Store::at(path, self.object_hash)
+                                    .with_read_buffer_size(self.loose_read_buffer_size)
+                            })
+                    })
                     .collect::<Vec<_>>(),
             )
         } else {
             Arc::clone(&index.loose_dbs)
         };
 
-        let indices_by_modification_time = Self::collect_indices_and_mtime_sorted_by_size(
+        let indices_by_modification_time = Self::collect_indices_and_mtime_sorted(
+            &*self.backend,
             db_paths,
             index.slot_indices.len().into(),
             self.use_multi_pack_index.then(|| self.object_hash),
+            self.ignore_empty_packs.then(|| self.object_hash),
+            self.index_sort_order,
         )?;
         let mut idx_by_index_path: BTreeMap<_, _> = index
             .slot_indices
@@ -245,17 +331,29 @@ impl super::Store {
         let mut index_paths_to_add = was_uninitialized
             .then(|| VecDeque::with_capacity(indices_by_modification_time.len()))
             .unwrap_or_default();
+        // `true` for each slot in `new_slot_map_indices`, to find out whether a slot is taken without searching them all.
+        let mut occupied_slots = vec![false; self.files.len()];
 
         // Figure out this number based on what we see while handling the existing indices
         let mut num_loaded_indices = 0;
-        for (index_info, mtime) in indices_by_modification_time.into_iter().map(|(a, b, _)| (a, b)) {
+        let mut missing_packs_reappeared = false;
+        for (index_info, mtime, size) in indices_by_modification_time {
             match idx_by_index_path.remove(index_info.path()) {
                 Some(slot_idx) => {
                     let slot = &self.files[slot_idx];
                     let files_guard = slot.files.load();
                     let files =
                         Option::as_ref(&files_guard).expect("slot is set or we wouldn't know it points to this file");
-                    if index_info.is_multi_index() && files.mtime() != mtime {
+                    // multi-pack indices may be rewritten in place within the resolution of the modification time, but then
+                    // their size likely changes. Without a mapping there is nothing stale to serve as it's read from disk anyway.
+                    let size_changed = match files {
+                        IndexAndPacks::MultiIndex(bundle) => bundle
+                            .multi_index
+                            .loaded()
+                            .map_or(false, |multi_index| multi_index.data_len() as u64 != size),
+                        IndexAndPacks::Index(_) => false,
+                    };
+                    if index_info.is_multi_index() && (files.mtime() != mtime || size_changed) {
                         // we have a changed multi-pack index. We can't just change the existing slot as it may alter slot indices
                         // that are currently available. Instead we have to move what's there into a new slot, along with the changes,
                         // and later free the slot or dispose of the index in the slot (like we do for removed/missing files).
@@ -268,16 +366,68 @@ impl super::Store {
                         // packs and indices are immutable, so no need to check modification times. Unchanged multi-pack indices also
                         // are handled like this just to be sure they are in the desired state. For these, the only way this could happen
                         // is if somebody deletes and then puts back
+                        // Poisoned packs that changed on disk may have been repaired, so they are worth another try.
+                        if files.keep_files_changed() || files.poisoned_packs_changed() {
+                            let _lock = slot.write.lock();
+                            let mut files = slot.files.load_full();
+                            if let Some(files) = Arc::make_mut(&mut files) {
+                                files.update_keep_files();
+                                files.unpoison_changed_packs();
+                            }
+                            slot.files.store(files);
+                        }
+                        // Snapshots skip indices whose pack was missing, so they must be collected again now that it's back.
+                        missing_packs_reappeared |= files.pack_is_missing();
                         if Self::assure_slot_matches_index(&write, slot, index_info, mtime, index.generation) {
                             num_loaded_indices += 1;
                         }
                         new_slot_map_indices.push(slot_idx);
+                        occupied_slots[slot_idx] = true;
                     }
                 }
                 None => index_paths_to_add.push_back((index_info, mtime, None)),
             }
         }
         let needs_stable_indices = self.maintain_stable_indices(&write);
+        // Files of removed indices were only kept for handles that required stable pack ids. Without these, they can finally be
+        // dropped, which invalidates their pack ids and thus requires a new generation.
+        let mut garbage_slot_indices: Vec<_> = if needs_stable_indices {
+            Vec::new()
+        } else {
+            let mut live_slots = vec![false; self.files.len()];
+            for idx in index.slot_indices.iter() {
+                live_slots[*idx] = true;
+            }
+            self.files
+                .iter()
+                .enumerate()
+                .filter(|(idx, slot)| {
+                    !live_slots[*idx] && Option::as_ref(&slot.files.load()).map_or(false, IndexAndPacks::is_disposable)
+                })
+                .map(|(idx, _)| idx)
+                .collect()
+        };
+
+        // Assign slots in the order of file names if desired, but keep searching new indices in the order they were found in.
+        let num_kept_slot_indices = new_slot_map_indices.len();
+        let mut search_order_of_indices_to_add = Vec::new();
+        if self.deterministic_slot_assignment {
+            let mut indices_to_add: Vec<_> = index_paths_to_add.drain(..).enumerate().collect();
+            indices_to_add.sort_by(|(_, (lhs, ..)), (_, (rhs, ..))| {
+                (lhs.path().file_name(), lhs.path()).cmp(&(rhs.path().file_name(), rhs.path()))
+            });
+            for (search_order, index_info) in indices_to_add {
+                search_order_of_indices_to_add.push(search_order);
+                index_paths_to_add.push_back(index_info);
+            }
+        }
+
+        let mut events = self.has_subscribers().then(|| {
+            index_paths_to_add
+                .iter()
+                .map(|(index_info, ..)| Change::PackAdded(index_info.path().to_owned()))
+                .collect::<Vec<_>>()
+        });
 
         let mut next_possibly_free_index = index
             .slot_indices
@@ -286,7 +436,7 @@ impl super::Store {
             .map(|idx| (idx + 1) % self.files.len())
             .unwrap_or(0);
         let mut num_indices_checked = 0;
-        let mut needs_generation_change = false;
+        let mut needs_generation_change = !garbage_slot_indices.is_empty();
         let mut slot_indices_to_remove: Vec<_> = idx_by_index_path.into_values().collect();
         while let Some((mut index_info, mtime, move_from_slot_idx)) = index_paths_to_add.pop_front() {
             'increment_slot_index: loop {
@@ -300,6 +450,10 @@ impl super::Store {
                 let slot = &self.files[slot_index];
                 next_possibly_free_index = (next_possibly_free_index + 1) % self.files.len();
                 num_indices_checked += 1;
+                if occupied_slots[slot_index] {
+                    // the slot holds a file we keep or was just assigned, overwriting it would make it appear twice.
+                    continue 'increment_slot_index;
+                }
                 match move_from_slot_idx {
                     Some(move_from_slot_idx) => {
                         debug_assert!(index_info.is_multi_index(), "only set for multi-pack indices");
@@ -314,10 +468,12 @@ impl super::Store {
                             mtime,
                             index.generation,
                             needs_stable_indices,
+                            &index.index_load_clock,
                         ) {
                             Ok(dest_was_empty) => {
                                 slot_indices_to_remove.push(move_from_slot_idx);
                                 new_slot_map_indices.push(slot_index);
+                                occupied_slots[slot_index] = true;
                                 // To avoid handling out the wrong pack (due to reassigned pack ids), declare this a new generation.
                                 if !dest_was_empty {
                                     needs_generation_change = true;
@@ -335,9 +491,11 @@ impl super::Store {
                             mtime,
                             index.generation,
                             needs_stable_indices,
+                            &index.index_load_clock,
                         ) {
                             Ok(dest_was_empty) => {
                                 new_slot_map_indices.push(slot_index);
+                                occupied_slots[slot_index] = true;
                                 if !dest_was_empty {
                                     needs_generation_change = true;
                                 }
@@ -355,20 +513,34 @@ impl super::Store {
             0,
             "By this time we have assigned all new files to slots"
         );
+        if !search_order_of_indices_to_add.is_empty() {
+            let added_slot_indices = new_slot_map_indices.split_off(num_kept_slot_indices);
+            let mut added_slot_indices: Vec<_> = search_order_of_indices_to_add
+                .into_iter()
+                .zip(added_slot_indices)
+                .collect();
+            added_slot_indices.sort_by_key(|(search_order, _)| *search_order);
+            new_slot_map_indices.extend(added_slot_indices.into_iter().map(|(_, slot_index)| slot_index));
+        }
+
+        // Slots of removed files may have been reused for new ones already, these must not be cleared.
+        slot_indices_to_remove.retain(|idx| !occupied_slots[*idx]);
+        garbage_slot_indices.retain(|idx| !occupied_slots[*idx]);
 
         let generation = if needs_generation_change {
             index.generation.checked_add(1).ok_or(Error::GenerationOverflow)?
         } else {
             index.generation
         };
-        let index_unchanged = index.slot_indices == new_slot_map_indices;
-        if generation != index.generation {
-            assert!(
-                !index_unchanged,
-                "if the generation changed, the slot index must have changed for sure"
-            );
-        }
-        if !index_unchanged || loose_dbs != index.loose_dbs {
+        assert_eq!(
+            occupied_slots.iter().filter(|occupied| **occupied).count(),
+            new_slot_map_indices.len(),
+            "BUG: slot indices must be unique and point into the slot map, which never changes its size"
+        );
+        // Note that slots may have been reused for different files which changes the generation, but not necessarily the slot indices.
+        let index_unchanged =
+            index.slot_indices == new_slot_map_indices && generation == index.generation && !missing_packs_reappeared;
+        if !index_unchanged || !Arc::ptr_eq(&loose_dbs, &index.loose_dbs) {
             let new_index = Arc::new(SlotMapIndex {
                 slot_indices: new_slot_map_indices,
                 loose_dbs,
@@ -382,8 +554,12 @@ impl super::Store {
                     .then(|| Arc::clone(&index.loaded_indices))
                     .unwrap_or_else(|| Arc::new(num_loaded_indices.into())),
                 num_indices_currently_being_loaded: Default::default(),
+                index_load_clock: Arc::clone(&index.index_load_clock),
             });
-            self.index.store(new_index);
+            self.index.store(Arc::clone(&new_index));
+            if let Some(loaders) = &self.index_loaders {
+                loaders.schedule(&new_index);
+            }
         }
 
         // deleted items - remove their slots AFTER we have set the new index if we may alter indices, otherwise we only declare them garbage.
@@ -392,6 +568,9 @@ impl super::Store {
             let _lock = slot.write.lock();
             let mut files = slot.files.load_full();
             let files_mut = Arc::make_mut(&mut files);
+            if let (Some(events), Some(files)) = (&mut events, files_mut.as_ref()) {
+                events.push(Change::PackRemoved(files.index_path().to_owned()));
+            }
             if needs_stable_indices {
                 if let Some(files) = files_mut.as_mut() {
                     files.trash();
@@ -407,23 +586,46 @@ impl super::Store {
             }
         }
 
+        for slot in garbage_slot_indices.into_iter().map(|idx| &self.files[idx]) {
+            let _lock = slot.write.lock();
+            slot.files.store(Arc::new(None));
+            // Not racy due to lock, generation must be set after unsetting the slot value AND storing it.
+            slot.generation.store(generation, Ordering::SeqCst);
+        }
+
+        if let Some(mut events) = events {
+            if generation != index.generation {
+                events.push(Change::GenerationBumped);
+            }
+            self.publish(events);
+        }
+
         let new_index = self.index.load();
+        self.record_pack_count(&new_index);
         Ok(if index.state_id() == new_index.state_id() {
             // there was no change, and nothing was loaded in the meantime, reflect that in the return value to not get into loops
             None
         } else {
             if load_new_index {
-                self.load_next_index(new_index);
+                self.load_next_index(new_index, true);
             }
             Some(self.collect_snapshot())
         })
     }
 
-    pub(crate) fn collect_indices_and_mtime_sorted_by_size(
+    pub(crate) fn collect_indices_and_mtime_sorted(
+        backend: &dyn super::backend::Backend,
         db_paths: Vec<PathBuf>,
         initial_capacity: Option<usize>,
         multi_pack_index_object_hash: Option<git_hash::Kind>,
+        ignore_empty_indices_with_object_hash: Option<git_hash::Kind>,
+        sort_order: IndexSortOrder,
     ) -> Result<Vec<(Either, SystemTime, u64)>, Error> {
+        // The size of V1 and V2 pack indices without entries, consisting of the fan-out table and the trailing checksums only.
+        let empty_index_sizes = ignore_empty_indices_with_object_hash.map(|hash| {
+            let v1 = (256 * 4 + hash.len_in_bytes() * 2) as u64;
+            [v1, v1 + 8]
+        });
         let mut indices_by_modification_time = Vec::with_capacity(initial_capacity.unwrap_or_default());
         for db_path in db_paths {
             let packs = db_path.join("pack");
@@ -436,9 +638,11 @@ impl super::Store {
                 .filter_map(Result::ok)
                 .filter_map(|e| e.metadata().map(|md| (e.path(), md)).ok())
                 .filter(|(_, md)| md.file_type().is_file())
-                .filter(|(p, _)| {
+                .filter(|(p, md)| {
                     let ext = p.extension();
-                    (ext == Some(OsStr::new("idx")) && p.with_extension("pack").is_file())
+                    (ext == Some(OsStr::new("idx"))
+                        && p.with_extension("pack").is_file()
+                        && !empty_index_sizes.map_or(false, |sizes| sizes.contains(&md.len())))
                         || (multi_pack_index_object_hash.is_some() && ext.is_none() && is_multipack_index(p))
                 })
                 .map(|(p, md)| md.modified().map_err(Error::from).map(|mtime| (p, mtime, md.len())))
@@ -450,9 +654,17 @@ impl super::Store {
                         is_multipack_index(p)
                             .then(|| {
                                 // we always open the multi-pack here to be able to remove indices
-                                git_pack::multi_index::File::at(p)
+                                backend
+                                    .open_multi_index(p)
                                     .ok()
                                     .filter(|midx| midx.object_hash() == hash)
+                                    // A multi-index may be written before its packs are in place, or outlive them. In that
+                                    // case we use the indices of the packs that do exist instead.
+                                    .filter(|midx| {
+                                        midx.index_names()
+                                            .iter()
+                                            .all(|name| p.with_file_name(name).with_extension("pack").is_file())
+                                    })
                                     .map(|midx| (midx, *a, *b))
                             })
                             .flatten()
@@ -492,10 +704,20 @@ impl super::Store {
                 )
             }
         }
-        // Unlike libgit2, do not sort by modification date, but by size and put the biggest indices first. That way
+        // Unlike libgit2, do not sort by modification date by default, but by size and put the biggest indices first. That way
         // the chance to hit an object should be higher. We leave it to the handle to sort by LRU.
         // Git itself doesn't change the order which may safe time, but we want it to be stable which also helps some tests.
-        indices_by_modification_time.sort_by(|l, r| l.2.cmp(&r.2).reverse());
+        let by_name = |l: &Either, r: &Either| (l.path().file_name(), l.path()).cmp(&(r.path().file_name(), r.path()));
+        match sort_order {
+            IndexSortOrder::BySizeDescending => indices_by_modification_time.sort_by(|l, r| l.2.cmp(&r.2).reverse()),
+            IndexSortOrder::BySizeAscending => {
+                indices_by_modification_time.sort_by(|l, r| l.2.cmp(&r.2).then_with(|| by_name(&l.0, &r.0)))
+            }
+            IndexSortOrder::ModifiedTimeNewestFirst => {
+                indices_by_modification_time.sort_by(|l, r| l.1.cmp(&r.1).reverse().then_with(|| by_name(&l.0, &r.0)))
+            }
+            IndexSortOrder::ByNameStable => indices_by_modification_time.sort_by(|l, r| by_name(&l.0, &r.0)),
+        }
         Ok(indices_by_modification_time)
     }
 
@@ -508,6 +730,7 @@ impl super::Store {
         mtime: SystemTime,
         current_generation: Generation,
         needs_stable_indices: bool,
+        index_load_clock: &AtomicUsize,
     ) -> Result<bool, Either> {
         let (dest_slot_was_empty, generation) = match &**dest_slot.files.load() {
             Some(bundle) => {
@@ -537,7 +760,7 @@ impl super::Store {
                 (true, current_generation)
             }
         };
-        Self::set_slot_to_index(lock, dest_slot, index_info, mtime, generation);
+        Self::set_slot_to_index(lock, dest_slot, index_info, mtime, generation, index_load_clock);
         Ok(dest_slot_was_empty)
     }
 
@@ -547,6 +770,7 @@ impl super::Store {
         index_info: Either,
         mtime: SystemTime,
         generation: Generation,
+        index_load_clock: &AtomicUsize,
     ) {
         let _lock = slot.write.lock();
         let mut files = slot.files.load_full();
@@ -556,7 +780,19 @@ impl super::Store {
         // If not, they would fail unable to load a pack or index they need, but that's preferred over returning wrong objects.
         // Safety: can't race as we hold the lock, have to set the generation beforehand to help avoid others to observe the value.
         slot.generation.store(generation, Ordering::SeqCst);
-        *files_mut = Some(index_info.into_index_and_packs(mtime));
+        let index_and_packs = index_info.into_index_and_packs(mtime);
+        slot.pack_hits.store(Arc::new(
+            std::iter::repeat_with(AtomicUsize::default)
+                .take(index_and_packs.num_packs())
+                .collect(),
+        ));
+        // multi-pack indices are already loaded, as they had to be opened to learn which indices they contain.
+        if index_and_packs.index_is_loaded() {
+            tick_index_load_clock(slot, index_load_clock);
+        } else {
+            slot.index_loaded_at.store(0, Ordering::SeqCst);
+        }
+        *files_mut = Some(index_and_packs);
         slot.files.store(files);
     }
 
@@ -624,6 +860,8 @@ impl super::Store {
                 .map(|idx| (*idx, &self.files[*idx]))
                 .filter_map(|(id, file)| {
                     let lookup = match (**file.files.load()).as_ref()? {
+                        // Like git, don't trust an index without its pack, and let lookups find its objects elsewhere.
+                        types::IndexAndPacks::Index(bundle) if bundle.data.is_missing() => return None,
                         types::IndexAndPacks::Index(bundle) => handle::SingleOrMultiIndex::Single {
                             index: bundle.index.loaded()?.clone(),
                             data: bundle.data.loaded().cloned(),
@@ -653,6 +891,60 @@ fn is_multipack_index(path: &Path) -> bool {
     path.file_name() == Some(OsStr::new("multi-pack-index"))
 }
 
+/// Claim the next slot of `index` whose index isn't loaded yet and load it from `files` using `backend`, making room for it within
+/// `max_open_files` first.
+///
+/// Return `Some(true)` if an index was loaded, `Some(false)` if the claimed slot didn't yield one, or `None` if all slots were claimed already.
+pub(crate) fn load_next_slot(
+    files: &[MutableIndexAndPack],
+    backend: &dyn super::backend::Backend,
+    max_open_files: Option<&OpenFilesBudget>,
+    object_hash: git_hash::Kind,
+    index: &SlotMapIndex,
+) -> Option<bool> {
+    let slot_map_index = index
+        .next_index_to_load
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+            (current != index.slot_indices.len()).then(|| current + 1)
+        })
+        .ok()?;
+    // This slot-map index is in bounds and was only given to us.
+    let _ongoing_operation = IncOnNewAndDecOnDrop::new(&index.num_indices_currently_being_loaded);
+    let slot = &files[index.slot_indices[slot_map_index]];
+    // Room has to be made before locking the slot, as the slot itself may have a pack to unload.
+    let _room = Option::as_ref(&slot.files.load())
+        .map_or(false, |bundle| !bundle.index_is_loaded())
+        .then(|| super::load_one::make_room_for_file(files, max_open_files));
+    let _lock = slot.write.lock();
+    if slot.generation.load(Ordering::SeqCst) > index.generation {
+        // There is a disk consolidation in progress which just overwrote a slot that cold be disposed with some other
+        // index, one we didn't intend to load.
+        // Continue with the next slot index in the hope there is something else we can do…
+        return Some(false);
+    }
+    let mut bundle = slot.files.load_full();
+    let bundle_mut = Arc::make_mut(&mut bundle);
+    let index_and_packs = match bundle_mut.as_mut() {
+        Some(index_and_packs) => index_and_packs,
+        // these are always expected to be set, unless somebody raced us. We handle this later by retrying.
+        None => return Some(false),
+    };
+    let _loaded_count = IncOnDrop(&index.loaded_indices);
+    let was_loaded = index_and_packs.index_is_loaded();
+    let loaded = index_and_packs.load_index(backend, object_hash).is_ok();
+    if loaded && !was_loaded {
+        tick_index_load_clock(slot, &index.index_load_clock);
+    }
+    slot.files.store(bundle);
+    Some(loaded)
+}
+
+/// Record in `slot` that its index was just loaded, by advancing the store-wide `clock`.
+fn tick_index_load_clock(slot: &MutableIndexAndPack, clock: &AtomicUsize) {
+    let now = clock.fetch_add(1, Ordering::SeqCst) + 1;
+    slot.index_loaded_at.store(now, Ordering::SeqCst);
+}
+
 struct IncOnNewAndDecOnDrop<'a>(&'a AtomicU16);
 impl<'a> IncOnNewAndDecOnDrop<'a> {
     pub fn new(v: &'a AtomicU16) -> Self {
@@ -666,6 +958,23 @@ impl<'a> Drop for IncOnNewAndDecOnDrop<'a> {
     }
 }
 
+/// Marks the refresh as completed with its outcome, and wakes up all threads waiting for it, even if the refresh panicked.
+struct FinishRefreshOnDrop<'a> {
+    store: &'a super::Store,
+    /// Whether the refresh changed our state, or `None` if it failed.
+    changed: Option<bool>,
+}
+impl<'a> Drop for FinishRefreshOnDrop<'a> {
+    fn drop(&mut self) {
+        let mut refresh = self.store.refresh.lock();
+        refresh.in_progress = false;
+        refresh.num_completed = refresh.num_completed.wrapping_add(1);
+        refresh.last_changed = self.changed;
+        refresh.last_completed_at = Some(std::time::Instant::now());
+        self.store.refresh_done.notify_all();
+    }
+}
+
 struct IncOnDrop<'a>(&'a AtomicUsize);
 impl<'a> Drop for IncOnDrop<'a> {
     fn drop(&mut self) {
@@ -717,3 +1026,54 @@ impl Ord for Either {
         self.path().cmp(other.path())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{Find, Store};
+
+    #[test]
+    fn lookups_are_not_blocked_by_an_ongoing_refresh() {
+        let store = Arc::new(
+            Store::at_opts(
+                std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/objects"),
+                None,
+                Default::default(),
+            )
+            .expect("fixture is valid"),
+        );
+        let ids = [
+            git_hash::ObjectId::from_hex(b"dd25c539efbb0ab018caa4cda2d133285634e9b5").expect("valid"), // packed
+            git_hash::ObjectId::from_hex(b"37d4e6c5c48ba0d245164c4e10d5f41140cab980").expect("valid"), // loose
+        ];
+        let handle = store.to_handle_arc();
+        let mut buf = Vec::new();
+        for id in ids {
+            assert!(handle.contains(id), "all indices and packs we need are loaded");
+            assert!(handle.try_find(id, &mut buf).expect("no error").is_some());
+        }
+
+        // A refresh holds this lock while scanning the disk, which is simulated to be very slow here.
+        let _refresh_in_progress = store.write.lock();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let lookups = std::thread::spawn({
+            let handle = handle.clone();
+            move || {
+                let mut buf = Vec::new();
+                for _round in 0..100 {
+                    for id in ids {
+                        assert!(handle.contains(id));
+                        assert!(handle.try_find(id, &mut buf).expect("no error").is_some());
+                    }
+                }
+                tx.send(()).expect("receiver alive");
+            }
+        });
+        assert!(
+            rx.recv_timeout(Duration::from_secs(10)).is_ok(),
+            "lookups of known objects finish while the refresh is ongoing"
+        );
+        lookups.join().expect("no panic");
+    }
+}
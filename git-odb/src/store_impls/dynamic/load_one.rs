@@ -17,22 +17,20 @@ impl super::Store {
         marker: types::SlotIndexMarker,
     ) -> std::io::Result<Option<Arc<git_pack::data::File>>> {
         let index = self.index.load();
-        if index.generation != marker.generation {
+        if index.generation != marker.generation || !self.serves_lookups(id) {
             return Ok(None);
         }
         fn load_pack(
+            backend: &dyn crate::store::backend::Backend,
             path: &Path,
             id: types::PackId,
             object_hash: git_hash::Kind,
         ) -> std::io::Result<Arc<git_pack::data::File>> {
-            git_pack::data::File::at(path, object_hash)
-                .map(|mut pack| {
-                    pack.id = id.to_intrinsic_pack_id();
-                    Arc::new(pack)
-                })
+            backend
+                .open_pack_shared(path, object_hash, id.to_intrinsic_pack_id())
                 .map_err(|err| match err {
                     git_pack::data::header::decode::Error::Io { source, .. } => source,
-                    other => std::io::Error::new(std::io::ErrorKind::Other, other),
+                    other => types::malformed("pack", path, other),
                 })
         }
 
@@ -45,30 +43,52 @@ impl super::Store {
             // Hope that when the caller returns/retries the new index is set so they can fetch it and retry.
             return Ok(None);
         }
-        match id.multipack_index {
+        let pack = match id.multipack_index {
             None => {
                 match slot_files {
                     Some(types::IndexAndPacks::Index(bundle)) => {
                         match bundle.data.loaded() {
                             Some(pack) => Ok(Some(pack.clone())),
                             None => {
+                                let _room = make_room_for_file(&self.files, self.max_open_files.as_deref());
                                 let _lock = slot.write.lock();
                                 let mut files = slot.files.load_full();
                                 let files_mut = Arc::make_mut(&mut files);
                                 let pack = match files_mut {
-                                    Some(types::IndexAndPacks::Index(bundle)) => bundle
-                                        .data
-                                        .load_with_recovery(|path| load_pack(path, id, self.object_hash))?,
+                                    Some(types::IndexAndPacks::Index(types::IndexFileBundle {
+                                        index, data, ..
+                                    })) => {
+                                        let index = index.loaded();
+                                        data.load_with_recovery(|path| {
+                                            load_pack(&*self.backend, path, id, self.object_hash).and_then(|pack| {
+                                                match index.filter(|_| self.verify_pack_matches_index_on_load) {
+                                                    Some(index) if index.pack_checksum() != pack.checksum() => {
+                                                        Err(std::io::Error::new(
+                                                            std::io::ErrorKind::InvalidData,
+                                                            format!(
+                                                                "The pack at '{}' with checksum {} doesn't belong to its index which expects {}",
+                                                                path.display(),
+                                                                pack.checksum(),
+                                                                index.pack_checksum()
+                                                            ),
+                                                        ))
+                                                    }
+                                                    _ => Ok(pack),
+                                                }
+                                            })
+                                        })
+                                    }
                                     Some(types::IndexAndPacks::MultiIndex(_)) => {
                                         // something changed between us getting the lock, trigger a complete index refresh.
-                                        None
+                                        Ok(None)
                                     }
                                     None => {
                                         unreachable!("BUG: must set this handle to be stable to avoid slots to be cleared/changed")
                                     }
                                 };
+                                // store the state even on error to remember corrupt packs.
                                 slot.files.store(files);
-                                Ok(pack)
+                                pack
                             }
                         }
                     }
@@ -88,25 +108,28 @@ impl super::Store {
                             Some(on_disk_pack) => match on_disk_pack.loaded() {
                                 Some(pack) => Ok(Some(pack.clone())),
                                 None => {
+                                    let _room = make_room_for_file(&self.files, self.max_open_files.as_deref());
                                     let _lock = slot.write.lock();
                                     let mut files = slot.files.load_full();
                                     let files_mut = Arc::make_mut(&mut files);
                                     let pack = match files_mut {
                                         Some(types::IndexAndPacks::Index(_)) => {
                                             // something changed between us getting the lock, trigger a complete index refresh.
-                                            None
+                                            Ok(None)
                                         }
                                         Some(types::IndexAndPacks::MultiIndex(bundle)) => bundle
                                             .data
                                             .get_mut(pack_index as usize)
                                             .expect("BUG: must set this handle to be stable")
-                                            .load_with_recovery(|path| load_pack(path, id, self.object_hash))?,
+                                            .load_with_recovery(|path| {
+                                                load_pack(&*self.backend, path, id, self.object_hash)
+                                            }),
                                         None => {
                                             unreachable!("BUG: must set this handle to be stable to avoid slots to be cleared/changed")
                                         }
                                     };
                                     slot.files.store(files);
-                                    Ok(pack)
+                                    pack
                                 }
                             },
                         }
@@ -119,7 +142,111 @@ impl super::Store {
                     }
                 }
             }
+        };
+        if let Ok(Some(_)) = &pack {
+            let now = self.pack_use_clock.fetch_add(1, Ordering::Relaxed) + 1;
+            slot.last_pack_use.store(now, Ordering::Relaxed);
         }
+        pack
+    }
+
+    /// Return the reverse index of the pack index `index` with `id`, reading it from its `.rev` file or computing it from `index`
+    /// if there is none, or return `None` if `id` is stale or belongs to a multi-pack index.
+    pub(crate) fn load_reverse_index(
+        &self,
+        id: types::PackId,
+        marker: types::SlotIndexMarker,
+        index: &git_pack::index::File,
+    ) -> Option<Arc<git_pack::index::reverse::File>> {
+        if id.multipack_index.is_some() || self.index.load().generation != marker.generation {
+            return None;
+        }
+        let slot = &self.files[id.index];
+        let slot_files = &**slot.files.load();
+        if slot.generation.load(Ordering::SeqCst) > marker.generation {
+            return None;
+        }
+        match slot_files {
+            Some(types::IndexAndPacks::Index(bundle)) => {
+                if let Some(reverse) = bundle.reverse.loaded() {
+                    return Some(reverse.clone());
+                }
+            }
+            Some(types::IndexAndPacks::MultiIndex(_)) | None => return None,
+        }
+
+        let _lock = slot.write.lock();
+        let mut files = slot.files.load_full();
+        let reverse = match Arc::make_mut(&mut files) {
+            Some(types::IndexAndPacks::Index(bundle)) => {
+                let on_disk = bundle.reverse.load_with_recovery(|path| {
+                    self.backend
+                        .open_reverse_index(path, index)
+                        .map(Arc::new)
+                        .map_err(|err| match err {
+                            git_pack::index::reverse::Error::Io { source, .. } => source,
+                            err => types::malformed("reverse index", path, err),
+                        })
+                });
+                match on_disk {
+                    Ok(Some(reverse)) => reverse,
+                    // git computes the reverse index in memory as well if there is none, or if it can't be used.
+                    Ok(None) | Err(_) => {
+                        let reverse = Arc::new(git_pack::index::reverse::File::from_index(index));
+                        bundle.reverse.set_computed(Arc::clone(&reverse));
+                        reverse
+                    }
+                }
+            }
+            // something changed between us getting the lock, the caller has to refresh their index.
+            Some(types::IndexAndPacks::MultiIndex(_)) | None => return None,
+        };
+        slot.files.store(files);
+        Some(reverse)
+    }
+
+    /// Return true if the pack with `id` may be used for lookups, which is always the case unless it was poisoned, or it was removed
+    /// from disk and garbage slots aren't configured to serve lookups.
+    pub(crate) fn serves_lookups(&self, id: types::PackId) -> bool {
+        let garbage_serves_lookups = self.garbage_slots_serve_lookups;
+        match Option::as_ref(&self.files[id.index].files.load()) {
+            Some(types::IndexAndPacks::Index(bundle)) => {
+                !bundle.data.is_poisoned()
+                    && (garbage_serves_lookups || (!bundle.index.is_garbage() && !bundle.data.is_garbage()))
+            }
+            Some(types::IndexAndPacks::MultiIndex(bundle)) => {
+                let pack = id
+                    .multipack_index
+                    .and_then(|pack_index| bundle.data.get(pack_index as usize));
+                !pack.map_or(false, |pack| pack.is_poisoned())
+                    && (garbage_serves_lookups
+                        || (!bundle.multi_index.is_garbage() && !pack.map_or(false, |pack| pack.is_garbage())))
+            }
+            None => true,
+        }
+    }
+
+    /// Poison the pack with `id` for the given `reason`, so it isn't used for lookups anymore until it changes on disk.
+    /// Nothing happens if the slot of `id` was reused since `marker` was obtained.
+    pub(crate) fn poison_pack(&self, id: types::PackId, marker: types::SlotIndexMarker, reason: String) {
+        let slot = &self.files[id.index];
+        let _lock = slot.write.lock();
+        if slot.generation.load(Ordering::SeqCst) > marker.generation {
+            return;
+        }
+        let mut files = slot.files.load_full();
+        let pack = match (Arc::make_mut(&mut files), id.multipack_index) {
+            (Some(types::IndexAndPacks::Index(bundle)), None) => &mut bundle.data,
+            (Some(types::IndexAndPacks::MultiIndex(bundle)), Some(pack_index)) => {
+                match bundle.data.get_mut(pack_index as usize) {
+                    Some(pack) => pack,
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+        pack.poison(reason.into());
+        slot.files.store(files);
     }
 
     /// Similar to `.load_pack()`, but for entire indices, bypassing the index entirely and going solely by marker and id.
@@ -152,3 +279,78 @@ impl super::Store {
         .into()
     }
 }
+
+/// Unload packs in `files` until there is room to open one more file without exceeding `budget` and reserve it, preferring packs
+/// that no handle uses and within these, packs of the slots that were used least recently.
+///
+/// Handles that still use an unloaded pack keep it mapped until they drop it, so this limits the files the store holds on to.
+/// Packs with a `.keep` file are never unloaded, and neither are indices as lookups need them. If there is nothing left to unload,
+/// we wait for files being loaded concurrently as these might be packs to unload, and open the file regardless once there are none.
+/// The returned reservation must be held until the file was loaded, to prevent concurrent loads from exceeding the budget.
+pub(crate) fn make_room_for_file<'a>(
+    files: &[types::MutableIndexAndPack],
+    budget: Option<&'a types::OpenFilesBudget>,
+) -> Option<types::Reservation<'a>> {
+    let budget = budget?;
+    let mut guard = budget.lock.lock();
+    fn packs_mut(files: &mut types::IndexAndPacks) -> Vec<&mut types::OnDiskFile<Arc<git_pack::data::File>>> {
+        match files {
+            types::IndexAndPacks::Index(bundle) => vec![&mut bundle.data],
+            types::IndexAndPacks::MultiIndex(bundle) => bundle.data.iter_mut().collect(),
+        }
+    }
+
+    loop {
+        let num_reserved = budget.num_reserved.load(Ordering::SeqCst);
+        let mut num_open_files: usize = files
+            .iter()
+            .filter_map(|slot| Option::as_ref(&slot.files.load()).map(types::IndexAndPacks::num_open_files))
+            .sum::<usize>()
+            + num_reserved;
+        let mut slots: Vec<_> = files.iter().collect();
+        slots.sort_by_key(|slot| slot.last_pack_use.load(Ordering::Relaxed));
+        for only_unused in [true, false] {
+            for slot in &slots {
+                if num_open_files < budget.max {
+                    return Some(budget.reserve());
+                }
+                let _lock = slot.write.lock();
+                let mut files = slot.files.load_full();
+                // the slot holds the only reference to unused packs, which is why we have to check before copying it.
+                let can_unload = |pack: &types::OnDiskFile<Arc<git_pack::data::File>>| {
+                    !pack.is_garbage()
+                        && !pack.is_kept()
+                        && pack
+                            .loaded()
+                            .map_or(false, |pack| !only_unused || Arc::strong_count(pack) == 1)
+                };
+                let to_unload: Vec<_> = match Option::as_ref(&files) {
+                    Some(types::IndexAndPacks::Index(bundle)) => vec![can_unload(&bundle.data)],
+                    Some(types::IndexAndPacks::MultiIndex(bundle)) => bundle.data.iter().map(can_unload).collect(),
+                    None => continue,
+                };
+                if !to_unload.contains(&true) {
+                    continue;
+                }
+                let files_mut = Option::as_mut(Arc::make_mut(&mut files)).expect("checked to be set");
+                for (pack, _) in packs_mut(files_mut)
+                    .into_iter()
+                    .zip(to_unload)
+                    .filter(|(_, unload)| *unload)
+                {
+                    if num_open_files < budget.max {
+                        break;
+                    }
+                    pack.unload();
+                    num_open_files -= 1;
+                }
+                slot.files.store(files);
+            }
+        }
+        if num_open_files < budget.max || num_reserved == 0 {
+            break;
+        }
+        budget.released.wait(&mut guard);
+    }
+    Some(budget.reserve())
+}
@@ -1,4 +1,8 @@
-use std::sync::atomic::Ordering;
+use std::{
+    mem::size_of,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+};
 
 use crate::store::{types, types::IndexAndPacks};
 
@@ -14,6 +18,8 @@ impl super::Store {
         let mut unused_slots = 0;
         let mut unreachable_indices = 0;
         let mut unreachable_packs = 0;
+        let mut poisoned_packs = 0;
+        let mut open_files = 0;
 
         let index = self.index.load();
         for f in index.slot_indices.iter().map(|idx| &self.files[*idx]) {
@@ -26,6 +32,9 @@ impl super::Store {
                     if bundle.data.is_loaded() {
                         open_packs += 1;
                     }
+                    if bundle.data.is_poisoned() {
+                        poisoned_packs += 1;
+                    }
                     known_packs += 1;
                 }
                 Some(IndexAndPacks::MultiIndex(multi)) => {
@@ -37,6 +46,9 @@ impl super::Store {
                         if pack.is_loaded() {
                             open_packs += 1;
                         }
+                        if pack.is_poisoned() {
+                            poisoned_packs += 1;
+                        }
                         known_packs += 1;
                     }
                 }
@@ -44,12 +56,13 @@ impl super::Store {
             }
         }
 
-        for slot in &self.files {
+        for slot in self.files.iter() {
             match slot.files.load().as_ref() {
                 None => {
                     unused_slots += 1;
                 }
                 Some(bundle) => {
+                    open_files += bundle.num_open_files();
                     if bundle.is_disposable() {
                         unreachable_indices += 1;
                         unreachable_packs += match bundle {
@@ -64,10 +77,12 @@ impl super::Store {
         }
 
         types::Metrics {
+            label: self.label.clone(),
             num_handles: self.num_handles_unstable.load(Ordering::Relaxed)
                 + self.num_handles_stable.load(Ordering::Relaxed),
             num_refreshes: self.num_disk_state_consolidation.load(Ordering::Relaxed),
             open_reachable_packs: open_packs,
+            open_files,
             open_reachable_indices: open_indices,
             known_reachable_indices: known_indices,
             known_packs,
@@ -75,6 +90,196 @@ impl super::Store {
             loose_dbs: index.loose_dbs.len(),
             unreachable_indices,
             unreachable_packs,
+            poisoned_packs,
+        }
+    }
+
+    /// Return the cumulative counts of lookups performed by all handles since the store was created or the counters were last
+    /// reset with [`take_lookup_counters()`][super::Store::take_lookup_counters()].
+    pub fn lookup_counters(&self) -> types::LookupCounters {
+        let counters = &self.lookup_counters;
+        types::LookupCounters {
+            finds: counters.finds.load(Ordering::Relaxed),
+            contains: counters.contains.load(Ordering::Relaxed),
+            misses: counters.misses.load(Ordering::Relaxed),
+            bytes_decompressed: counters.bytes_decompressed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Like [`lookup_counters()`][super::Store::lookup_counters()], but reset all counters to zero at the same time, which is
+    /// useful to report throughput in consecutive windows of time.
+    ///
+    /// Each counter is exchanged with zero atomically, so lookups happening concurrently are counted either in the returned
+    /// counters or in the next ones, but never in both or neither. As the counters are exchanged one after another, a single
+    /// lookup may still show up partially in one window, like its `finds` in the returned counters and its `misses` in the next.
+    pub fn take_lookup_counters(&self) -> types::LookupCounters {
+        let counters = &self.lookup_counters;
+        types::LookupCounters {
+            finds: counters.finds.swap(0, Ordering::Relaxed),
+            contains: counters.contains.swap(0, Ordering::Relaxed),
+            misses: counters.misses.swap(0, Ordering::Relaxed),
+            bytes_decompressed: counters.bytes_decompressed.swap(0, Ordering::Relaxed),
         }
     }
+
+    /// Return an approximation of the memory used by this store, broken down by its main consumers.
+    ///
+    /// Like [`metrics()`][super::Store::metrics()], values are collected in a racy fashion while other threads may load or unload files.
+    pub fn memory_usage(&self) -> types::MemoryUsage {
+        let mut out = types::MemoryUsage {
+            slot_map: self.files.capacity() * size_of::<types::MutableIndexAndPack>(),
+            ..Default::default()
+        };
+        for slot in self.files.iter() {
+            let files = slot.files.load();
+            // An `Arc` allocation stores the strong and weak count next to its value.
+            out.slots += 2 * size_of::<usize>() + size_of::<Option<IndexAndPacks>>();
+            match &**files {
+                Some(IndexAndPacks::Index(bundle)) => {
+                    out.mapped_indices += bundle.index.loaded().map_or(0, |index| index.data_len());
+                    out.mapped_packs += bundle.data.loaded().map_or(0, |pack| pack.data_len());
+                }
+                Some(IndexAndPacks::MultiIndex(bundle)) => {
+                    out.slots += bundle.data.capacity() * size_of::<types::OnDiskFile<Arc<git_pack::data::File>>>();
+                    out.mapped_indices += bundle.multi_index.loaded().map_or(0, |index| index.data_len());
+                    out.mapped_packs += bundle
+                        .data
+                        .iter()
+                        .filter_map(|pack| pack.loaded())
+                        .map(|pack| pack.data_len())
+                        .sum::<usize>();
+                }
+                None => {}
+            }
+        }
+
+        let index = self.index.load();
+        out.loose_dbs = index.loose_dbs.capacity() * size_of::<crate::loose::Store>()
+            + index
+                .loose_dbs
+                .iter()
+                .map(|db| db.path().as_os_str().len())
+                .sum::<usize>();
+        out
+    }
+
+    /// Return the amount of address space it would take to map all reachable index and pack files, as needed when loading all indices
+    /// like [`packed_object_count()`][crate::store::Handle::packed_object_count()] does, and then finding objects in each pack.
+    ///
+    /// File sizes are obtained from the filesystem without mapping any file, and the store learns about the files on disk first
+    /// if it didn't do so yet. This is an upper bound as packs are only mapped once objects are needed from them, and files
+    /// that disappeared in the meantime don't count.
+    pub fn full_load_address_space(&self) -> Result<types::AddressSpace, crate::store::load_index::Error> {
+        if !self.index.load().is_initialized() {
+            self.consolidate_with_disk_state(true /* needs init */, false /* load one new index */)?;
+        }
+        let mut out = types::AddressSpace::default();
+        let index = self.index.load();
+        for f in index.slot_indices.iter().map(|idx| &self.files[*idx]) {
+            match &**f.files.load() {
+                Some(IndexAndPacks::Index(bundle)) => {
+                    out.indices += file_size(bundle.index.path())?;
+                    out.packs += file_size(bundle.data.path())?;
+                }
+                Some(IndexAndPacks::MultiIndex(bundle)) => {
+                    out.indices += file_size(bundle.multi_index.path())?;
+                    for pack in &bundle.data {
+                        out.packs += file_size(pack.path())?;
+                    }
+                }
+                None => {}
+            }
+        }
+        Ok(out)
+    }
+
+    /// Return usage statistics for each reachable pack in the order in which indices are searched, to learn which packs are worth
+    /// keeping loaded.
+    ///
+    /// Hits are counted for each object found or confirmed to exist in a pack since its index was assigned to its slot, so they
+    /// start at zero for packs the store learns about after a refresh. Like [`metrics()`][super::Store::metrics()], values are
+    /// collected in a racy fashion and the disk isn't consulted to learn about packs the store doesn't know yet.
+    pub fn pack_statistics(&self) -> Vec<(types::PackId, types::PackStats)> {
+        let mut out = Vec::new();
+        let index = self.index.load();
+        for (idx, f) in index.slot_indices.iter().map(|idx| (*idx, &self.files[*idx])) {
+            let hits = f.pack_hits.load();
+            let hits_of = |pack_index: usize| hits.get(pack_index).map_or(0, |hits| hits.load(Ordering::Relaxed));
+            let stats_of = |pack: &types::OnDiskFile<Arc<git_pack::data::File>>, hits: usize| types::PackStats {
+                hits,
+                loaded: pack.is_loaded(),
+                size: std::fs::metadata(pack.path()).ok().map(|md| md.len()),
+            };
+            match &**f.files.load() {
+                Some(IndexAndPacks::Index(bundle)) => out.push((
+                    types::PackId {
+                        index: idx,
+                        multipack_index: None,
+                    },
+                    stats_of(&bundle.data, hits_of(0)),
+                )),
+                Some(IndexAndPacks::MultiIndex(bundle)) => {
+                    for (pack_index, pack) in bundle.data.iter().enumerate() {
+                        out.push((
+                            types::PackId {
+                                index: idx,
+                                multipack_index: Some(pack_index as git_pack::multi_index::PackIndex),
+                            },
+                            stats_of(pack, hits_of(pack_index)),
+                        ));
+                    }
+                }
+                None => {}
+            }
+        }
+        out
+    }
+
+    /// Count a hit for the pack with `id`, which is expected to be of the current generation. Otherwise the hit may be counted for
+    /// the wrong pack, which is acceptable for statistics.
+    pub(crate) fn record_pack_hit(&self, id: types::PackId) {
+        if let Some(slot) = self.files.get(id.index) {
+            if let Some(hits) = slot.pack_hits.load().get(id.multipack_index.unwrap_or(0) as usize) {
+                hits.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Return the paths of all reachable index and pack files which exist but couldn't be used, along with a description of the problem.
+    ///
+    /// Such files are skipped during lookups, so objects contained in them can't be found.
+    pub fn corrupt_files(&self) -> Vec<(PathBuf, String)> {
+        let mut out = Vec::new();
+        let index = self.index.load();
+        for f in index.slot_indices.iter().map(|idx| &self.files[*idx]) {
+            let mut push = |file: Option<&str>, path: &Path| {
+                if let Some(reason) = file {
+                    out.push((path.to_owned(), reason.to_owned()));
+                }
+            };
+            match &**f.files.load() {
+                Some(IndexAndPacks::Index(bundle)) => {
+                    push(bundle.index.corruption(), bundle.index.path());
+                    push(bundle.data.corruption(), bundle.data.path());
+                }
+                Some(IndexAndPacks::MultiIndex(bundle)) => {
+                    push(bundle.multi_index.corruption(), bundle.multi_index.path());
+                    for pack in &bundle.data {
+                        push(pack.corruption(), pack.path());
+                    }
+                }
+                None => {}
+            }
+        }
+        out
+    }
+}
+
+/// Return the size of the file at `path`, or 0 if it doesn't exist anymore.
+fn file_size(path: &Path) -> std::io::Result<u64> {
+    match std::fs::metadata(path) {
+        Ok(md) => Ok(md.len()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(err) => Err(err),
+    }
 }
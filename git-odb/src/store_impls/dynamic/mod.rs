@@ -21,8 +21,19 @@ where
 
     /// If true, replacements will not be performed even if these are available.
     pub ignore_replacements: bool,
+    /// If true, replacements are applied to the object they replace an object with as well, until an object is reached that
+    /// isn't replaced. Otherwise, which is the default, an object id is replaced at most once per lookup.
+    pub follow_replacement_chains: bool,
+    /// If set, object lookups still in progress at this point in time fail with
+    /// [`DeadlineExceeded`][find::Error::DeadlineExceeded] instead of continuing to load files or resolve delta bases.
+    ///
+    /// It's checked before loading indices and packs, and at each step of resolving delta bases stored in other packs, but not
+    /// while decoding an entry, so a lookup can exceed the deadline by the time it takes to resolve a single in-pack delta chain.
+    pub deadline: Option<std::time::Instant>,
 
     pub(crate) token: Option<handle::Mode>,
+    /// Replacements to use instead of the ones of the store, sorted by the id to replace, if set.
+    replacements: Option<std::sync::Arc<Vec<(git_hash::ObjectId, git_hash::ObjectId)>>>,
     snapshot: RefCell<load_index::Snapshot>,
     packed_object_count: RefCell<Option<u64>>,
 }
@@ -37,6 +48,12 @@ pub enum RefreshMode {
     /// Use this if you expect a lot of missing objects that shouldn't trigger refreshes even after all packs are loaded.
     /// This comes at the risk of not learning that the packs have changed in the mean time.
     Never,
+    /// Like [`AfterAllIndicesLoaded`][RefreshMode::AfterAllIndicesLoaded], but only check for changes if at least the given
+    /// amount of time passed since the last refresh completed, and act like [`Never`][RefreshMode::Never] otherwise.
+    ///
+    /// This is useful for long-running processes that want to learn about new packs eventually without paying for a refresh
+    /// on each missing object.
+    AfterDuration(std::time::Duration),
 }
 
 impl Default for RefreshMode {
@@ -60,6 +77,10 @@ pub mod prefix;
 
 mod header;
 
+mod contains_many;
+
+mod find_entry;
+
 ///
 pub mod iter;
 
@@ -69,28 +90,109 @@ pub mod write;
 ///
 pub mod init;
 
+///
+pub mod backend;
+
+///
+pub mod transform;
+
 pub(crate) mod types;
-pub use types::Metrics;
+pub use types::{
+    AddressSpace, IndexId, LookupCounters, MarkerChange, MarkerDiff, MemoryUsage, Metrics, PackId, PackStats,
+    SlotIndexMarker, SlotIndexMarkerGuard, SlotMapIndex,
+};
 
 pub(crate) mod handle;
 
+pub(crate) mod index_loaders;
+
 ///
 pub mod load_index;
 
 ///
 pub mod verify;
 
+///
+pub mod count;
+
+///
+pub mod savings;
+
+///
+pub mod overlap;
+
+///
+pub mod wait;
+
+///
+pub mod cover;
+
+///
+pub mod delta;
+
+///
+pub mod stored_size;
+
+///
+pub mod pack_stream;
+
+///
+pub mod prefetch;
+
+///
+pub mod diff;
+
+///
+pub mod tags;
+
+///
+pub mod by_ref;
+
+///
+pub mod copy_pack;
+
+///
+pub mod events;
+
+///
+pub mod pressure;
+
+///
+pub mod decompressed_size;
+
+///
+pub mod close;
+
+///
+pub mod unload;
+
+///
+pub mod reverse;
+
+///
+pub mod refresh;
+
+///
+pub mod object_stream;
+
 mod load_one;
 
 mod metrics;
 
+mod fingerprint;
+
 mod access;
 
 ///
 pub mod structure {
-    use std::path::PathBuf;
+    use std::{path::PathBuf, sync::atomic::Ordering, time::SystemTime};
 
-    use crate::{store::load_index, types::IndexAndPacks, Store};
+    use crate::{
+        pack,
+        store::load_index,
+        types::{IndexAndPacks, PackId},
+        Store,
+    };
 
     /// A record of a structural element of an object database.
     #[derive(Debug, Clone, PartialEq, Eq)]
@@ -134,7 +236,270 @@ pub mod structure {
         Unloaded,
     }
 
+    impl IndexState {
+        fn of(index: &IndexAndPacks) -> Self {
+            if index.is_disposable() {
+                IndexState::Disposable
+            } else if index.index_is_loaded() {
+                IndexState::Loaded
+            } else {
+                IndexState::Unloaded
+            }
+        }
+    }
+
+    /// The format version of a pack index or multi-pack index.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub enum IndexVersion {
+        /// The version of a pack index.
+        Index(pack::index::Version),
+        /// The version of a multi-pack index.
+        MultiIndex(pack::multi_index::Version),
+    }
+
+    /// Information obtained from the header of a loaded index.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct IndexHeader {
+        /// The format version of the index.
+        pub version: IndexVersion,
+        /// The amount of objects in the index.
+        pub num_objects: u32,
+        /// The amount of packs the index refers to, which is always 1 for pack indices.
+        pub num_packs: u32,
+    }
+
+    /// The state of a file known to the store, as part of a [`DebugDump`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub enum FileState {
+        /// The file is on disk and can be loaded from there.
+        Unloaded,
+        /// The file is mapped into memory.
+        Loaded,
+        /// The file was removed from disk, but is kept as handles require pack ids to remain stable.
+        Garbage,
+        /// The file was missing on disk when trying to load it.
+        Missing,
+        /// The file couldn't be used when loading it, for the given reason.
+        Corrupt(String),
+        /// The file turned out to be corrupt when reading from it, for the given reason, and isn't used for lookups.
+        Poisoned(String),
+    }
+
+    /// A file known to the store along with its state, as part of a [`DebugDump`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct FileSummary {
+        /// The last known location of the file.
+        pub path: PathBuf,
+        /// The state of the file.
+        pub state: FileState,
+    }
+
+    /// A slot of the store that holds an index and its packs, as part of a [`DebugDump`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct SlotSummary {
+        /// The position of the slot in the slot map.
+        pub slot: usize,
+        /// The generation the slot was last set in.
+        pub generation: u32,
+        /// The pack index or multi-pack index in the slot, or `None` if the slot was emptied concurrently.
+        pub index: Option<FileSummary>,
+        /// The packs the index refers to, in the order of the multi-pack index if it is one.
+        pub packs: Vec<FileSummary>,
+    }
+
+    /// A summary of the state of all slots of the store as returned by [`Store::debug_dump()`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct DebugDump {
+        /// The generation of the store, which changes when slots are reused.
+        pub generation: u32,
+        /// The slots in use, in the order in which they are searched.
+        pub slots: Vec<SlotSummary>,
+        /// The objects directories of the loose object databases, the first one being our own, followed by the ones of alternates.
+        pub loose_dbs: Vec<PathBuf>,
+    }
+
+    /// A pack index or multi-pack index as returned by [`Store::indices()`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct IndexInfo {
+        /// The location of the index file.
+        pub path: PathBuf,
+        /// Whether this is a multi-pack index.
+        pub is_multi_pack: bool,
+        /// Whether or not the index is mapped into memory.
+        pub state: IndexState,
+        /// Information from the header of the index, or `None` if the index isn't loaded.
+        pub header: Option<IndexHeader>,
+    }
+
+    /// The pack with the oldest objects as returned by [`Store::oldest_pack()`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct OldestPack {
+        /// The id of the pack, the same as the one in [`Location::pack_id`][git_pack::data::entry::Location::pack_id].
+        pub id: git_pack::data::Id,
+        /// The location of the pack data file.
+        pub path: PathBuf,
+        /// The time the index of the pack was last modified when it was last seen on disk.
+        pub mtime: SystemTime,
+        /// The amount of objects in the pack, or `None` if its index isn't loaded.
+        pub num_objects: Option<u32>,
+    }
+
+    /// A recommendation on whether to create a multi-pack index along with the metrics it's based on, as returned by
+    /// [`Store::multi_pack_index_advice()`].
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct MultiPackIndexAdvice {
+        /// If true, creating a multi-pack index covering the single-pack indices is advisable.
+        pub recommended: bool,
+        /// The amount of loaded pack indices, which are searched one after another for objects that aren't in the first ones.
+        pub num_single_pack_indices: usize,
+        /// The amount of objects in all loaded pack indices.
+        pub num_objects_in_single_pack_indices: u64,
+        /// The amount of multi-pack indices known to us, loaded or not.
+        pub num_multi_pack_indices: usize,
+    }
+
     impl Store {
+        /// Return the pack whose index was modified the longest time ago, which is a candidate for expiring its objects,
+        /// or `None` if no pack is known.
+        ///
+        /// Only packs with their own index are considered as packs covered by a multi-pack index don't have a modification
+        /// time of their own. Like [`indices()`][Store::indices()], this doesn't load or scan anything and reflects what's
+        /// currently known to the store.
+        pub fn oldest_pack(&self) -> Option<OldestPack> {
+            let index = self.index.load();
+            index
+                .slot_indices
+                .iter()
+                .filter_map(|idx| {
+                    let files = self.files[*idx].files.load();
+                    match Option::as_ref(&files)? {
+                        IndexAndPacks::Index(bundle) => Some(OldestPack {
+                            id: PackId {
+                                index: *idx,
+                                multipack_index: None,
+                            }
+                            .to_intrinsic_pack_id(),
+                            path: bundle.data.path().into(),
+                            mtime: bundle.index.mtime(),
+                            num_objects: bundle.index.loaded().map(|index| index.num_objects()),
+                        }),
+                        IndexAndPacks::MultiIndex(_) => None,
+                    }
+                })
+                .min_by_key(|pack| pack.mtime)
+        }
+
+        /// Return whether creating a multi-pack index is advisable, which is the case if more than `max_single_pack_indices`
+        /// pack indices are loaded as each lookup of an object that isn't in the first ones has to search them one after another.
+        ///
+        /// Like [`indices()`][Store::indices()], this doesn't load or scan anything and only considers the pack indices that are
+        /// currently loaded, as only those have a known amount of objects. The returned metrics allow to apply a different policy.
+        pub fn multi_pack_index_advice(&self, max_single_pack_indices: usize) -> MultiPackIndexAdvice {
+            let mut out = MultiPackIndexAdvice::default();
+            let index = self.index.load();
+            for slot in index.slot_indices.iter().map(|idx| &self.files[*idx]) {
+                match Option::as_ref(&slot.files.load()) {
+                    Some(IndexAndPacks::Index(bundle)) => {
+                        if let Some(index) = bundle.index.loaded() {
+                            out.num_single_pack_indices += 1;
+                            out.num_objects_in_single_pack_indices += u64::from(index.num_objects());
+                        }
+                    }
+                    Some(IndexAndPacks::MultiIndex(_)) => out.num_multi_pack_indices += 1,
+                    None => {}
+                }
+            }
+            out.recommended = out.num_single_pack_indices > max_single_pack_indices;
+            out
+        }
+
+        /// Return all pack indices and multi-pack indices known to us along with their format version and the amount of objects
+        /// they contain, in the order in which they are searched.
+        ///
+        /// Unlike [`structure()`][Store::structure()], this doesn't load or scan anything and only reports what's currently resident,
+        /// so the [`header`][IndexInfo::header] is only available for loaded indices. If the store didn't read the object
+        /// database yet, the returned list is empty.
+        pub fn indices(&self) -> Vec<IndexInfo> {
+            let index = self.index.load();
+            index
+                .slot_indices
+                .iter()
+                .filter_map(|idx| {
+                    let files = self.files[*idx].files.load();
+                    let files = Option::as_ref(&files)?;
+                    let state = IndexState::of(files);
+                    Some(match files {
+                        IndexAndPacks::Index(bundle) => IndexInfo {
+                            path: bundle.index.path().into(),
+                            is_multi_pack: false,
+                            state,
+                            header: bundle.index.loaded().map(|index| IndexHeader {
+                                version: IndexVersion::Index(index.version()),
+                                num_objects: index.num_objects(),
+                                num_packs: 1,
+                            }),
+                        },
+                        IndexAndPacks::MultiIndex(bundle) => IndexInfo {
+                            path: bundle.multi_index.path().into(),
+                            is_multi_pack: true,
+                            state,
+                            header: bundle.multi_index.loaded().map(|index| IndexHeader {
+                                version: IndexVersion::MultiIndex(index.version()),
+                                num_objects: index.num_objects(),
+                                num_packs: index.num_indices(),
+                            }),
+                        },
+                    })
+                })
+                .collect()
+        }
+
+        /// Return the state of all slots in use, along with the paths of the loose object databases, for debugging lookups
+        /// that don't behave as expected.
+        ///
+        /// Like [`indices()`][Store::indices()], this doesn't load, scan or lock anything and only reports what's currently known,
+        /// which makes it safe to call at any time. It is also part of the `Debug` output of the store.
+        pub fn debug_dump(&self) -> DebugDump {
+            let index = self.index.load();
+            DebugDump {
+                generation: index.generation,
+                slots: index
+                    .slot_indices
+                    .iter()
+                    .map(|idx| {
+                        let slot = &self.files[*idx];
+                        let files = slot.files.load();
+                        let (index, packs) = match Option::as_ref(&files) {
+                            Some(IndexAndPacks::Index(bundle)) => {
+                                (Some(bundle.index.summary()), vec![bundle.data.summary()])
+                            }
+                            Some(IndexAndPacks::MultiIndex(bundle)) => (
+                                Some(bundle.multi_index.summary()),
+                                bundle.data.iter().map(|pack| pack.summary()).collect(),
+                            ),
+                            None => (None, Vec::new()),
+                        };
+                        SlotSummary {
+                            slot: *idx,
+                            generation: slot.generation.load(Ordering::SeqCst),
+                            index,
+                            packs,
+                        }
+                    })
+                    .collect(),
+                loose_dbs: index.loose_dbs.iter().map(|db| db.path.clone()).collect(),
+            }
+        }
+
         /// Return information about all files known to us as well as their loading state.
         ///
         /// Note that this call is expensive as it gathers additional information about loose object databases.
@@ -160,13 +525,7 @@ pub mod structure {
                 let files = slot.files.load();
                 let record = match &**files {
                     Some(index) => {
-                        let state = if index.is_disposable() {
-                            IndexState::Disposable
-                        } else if index.index_is_loaded() {
-                            IndexState::Loaded
-                        } else {
-                            IndexState::Unloaded
-                        };
+                        let state = IndexState::of(index);
                         match index {
                             IndexAndPacks::Index(b) => Record::Index {
                                 path: b.index.path().into(),
@@ -0,0 +1,514 @@
+use std::{
+    fs,
+    io::{self, BufReader, Read},
+    ops::Deref,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use git_features::zlib;
+use git_pack::data;
+
+use crate::{
+    loose,
+    store::handle::IntraPackLookup,
+    store_impls::loose::{hash_path, HEADER_MAX_SIZE},
+    Find,
+};
+
+mod error {
+    use std::path::PathBuf;
+
+    /// Returned by [`Handle::object_stream()`][crate::store::Handle::object_stream()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error(transparent)]
+        Find(#[from] crate::store::find::Error),
+        #[error(transparent)]
+        LoadIndex(#[from] crate::store::load_index::Error),
+        #[error(transparent)]
+        LoadPack(#[from] std::io::Error),
+        #[error("Could not decompress the delta instructions of an entry in the pack at '{path}'")]
+        DecompressDelta {
+            source: git_pack::data::decode::Error,
+            path: PathBuf,
+        },
+        #[error("The delta instructions of an entry in the pack at '{path}' are malformed")]
+        MalformedDelta { path: PathBuf },
+        #[error(transparent)]
+        LooseHeader(#[from] git_object::decode::LooseHeaderDecodeError),
+        #[error("Could not {action} data at '{path}'")]
+        Loose {
+            source: std::io::Error,
+            action: &'static str,
+            path: PathBuf,
+        },
+    }
+}
+pub use error::Error;
+
+/// The size of the buffer through which loose objects are read if the loose database doesn't configure one.
+const LOOSE_READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// A [`Read`] implementation producing the data of a single object, created with [`Handle::object_stream()`][super::Handle::object_stream()].
+///
+/// Loose objects are decompressed as they are read, and so are the entries of packed objects. Deltified objects are produced
+/// by applying the delta instructions of their chain one after another, each reading from the object it is based on as needed,
+/// so only the instructions of each delta are held in memory, but neither the base nor the resulting object.
+/// The pack is kept available for as long as the stream exists, even if it is unloaded from the store in the mean time.
+pub struct ObjectStream {
+    kind: git_object::Kind,
+    size: u64,
+    inner: Inner,
+}
+
+impl ObjectStream {
+    /// The kind of the object.
+    pub fn kind(&self) -> git_object::Kind {
+        self.kind
+    }
+
+    /// The size of the object in bytes, which is the amount of bytes that can be read.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Read for ObjectStream {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Loose(loose) => loose.read(out),
+            Inner::Packed(source) => source.read(out),
+            Inner::Buffered(buf) => buf.read(out),
+        }
+    }
+}
+
+enum Inner {
+    Loose(Loose),
+    Packed(Source),
+    Buffered(io::Cursor<Vec<u8>>),
+}
+
+/// A loose object, decompressed as it is read.
+struct Loose {
+    file: BufReader<fs::File>,
+    inflate: zlib::Inflate,
+    /// Bytes of the object that were decompressed along with the header.
+    pending: Vec<u8>,
+    pending_pos: usize,
+    remaining: u64,
+}
+
+impl Read for Loose {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let max = (out.len() as u64).min(self.remaining) as usize;
+        let out = &mut out[..max];
+        if out.is_empty() {
+            return Ok(0);
+        }
+        let n = if self.pending_pos < self.pending.len() {
+            let n = out.len().min(self.pending.len() - self.pending_pos);
+            out[..n].copy_from_slice(&self.pending[self.pending_pos..][..n]);
+            self.pending_pos += n;
+            n
+        } else {
+            zlib::stream::inflate::read(&mut self.file, &mut self.inflate.state, out)?
+        };
+        if n == 0 {
+            return Err(unexpected_eof());
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// An object in a pack, which can be read sequentially and positioned to any offset.
+enum Source {
+    Base(Inflated),
+    Delta(Box<Delta>),
+}
+
+impl Source {
+    fn position(&self) -> u64 {
+        match self {
+            Source::Base(base) => base.position,
+            Source::Delta(delta) => delta.position,
+        }
+    }
+
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::Base(base) => base.read(out),
+            Source::Delta(delta) => delta.read(out),
+        }
+    }
+
+    /// Position ourselves so that the next read starts at `offset` by skipping over the bytes in between.
+    /// Moving backward isn't possible as the data can only be decompressed front to back, which is why only deltas whose
+    /// copies are [in order][Delta::copies_in_order()] are streamed.
+    fn seek_to(&mut self, offset: u64) -> io::Result<()> {
+        if offset < self.position() {
+            return Err(invalid_delta());
+        }
+        let mut skip_buf = [0_u8; 4096];
+        while self.position() < offset {
+            let n = ((offset - self.position()) as usize).min(skip_buf.len());
+            if self.read(&mut skip_buf[..n])? == 0 {
+                return Err(unexpected_eof());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An undeltified pack entry, decompressed as it is read.
+struct Inflated {
+    pack: Arc<data::File>,
+    data_offset: data::Offset,
+    size: u64,
+    inflate: zlib::Inflate,
+    consumed_in: u64,
+    position: u64,
+}
+
+impl Inflated {
+    fn new(pack: Arc<data::File>, entry: &data::Entry) -> Self {
+        Inflated {
+            pack,
+            data_offset: entry.data_offset,
+            size: entry.decompressed_size,
+            inflate: zlib::Inflate::default(),
+            consumed_in: 0,
+            position: 0,
+        }
+    }
+
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let max = (out.len() as u64).min(self.size - self.position) as usize;
+        let out = &mut out[..max];
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let input = self
+                .pack
+                .entry_slice(self.data_offset + self.consumed_in..self.pack.pack_end() as u64)
+                .ok_or_else(unexpected_eof)?;
+            let (_status, consumed_in, consumed_out) = self
+                .inflate
+                .once(input, out)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            self.consumed_in += consumed_in as u64;
+            if consumed_out != 0 {
+                self.position += consumed_out as u64;
+                return Ok(consumed_out);
+            }
+            if consumed_in == 0 {
+                return Err(unexpected_eof());
+            }
+        }
+    }
+}
+
+/// A deltified pack entry, whose instructions are applied to its `base` as it is read.
+struct Delta {
+    base: Source,
+    instructions: Vec<u8>,
+    /// The position of the first instruction, right past the sizes of the base and the result.
+    instructions_start: usize,
+    cursor: usize,
+    pending: Instruction,
+    size: u64,
+    position: u64,
+}
+
+#[derive(Clone, Copy)]
+enum Instruction {
+    Copy { base_offset: u64, len: u64 },
+    Insert { start: usize, len: usize },
+}
+
+impl Delta {
+    fn new(base: Source, instructions: Vec<u8>) -> Option<Self> {
+        let (_base_size, base_size_len) = decode_size(&instructions)?;
+        let (size, size_len) = decode_size(&instructions[base_size_len..])?;
+        let instructions_start = base_size_len + size_len;
+        Some(Delta {
+            base,
+            instructions,
+            instructions_start,
+            cursor: instructions_start,
+            pending: Instruction::Insert { start: 0, len: 0 },
+            size,
+            position: 0,
+        })
+    }
+
+    /// Return true if each copy instruction starts at or after the end of the previous one, so the base is read front to back
+    /// only once. Malformed instructions count as out of order.
+    fn copies_in_order(&mut self) -> bool {
+        let mut copied_until = 0;
+        let mut in_order = true;
+        while self.cursor != self.instructions.len() {
+            match self.next_instruction() {
+                Some(Instruction::Copy { base_offset, len }) if base_offset >= copied_until => {
+                    copied_until = base_offset + len;
+                }
+                Some(Instruction::Insert { .. }) => {}
+                _ => {
+                    in_order = false;
+                    break;
+                }
+            }
+        }
+        self.cursor = self.instructions_start;
+        in_order
+    }
+
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = match self.pending {
+                Instruction::Copy { base_offset, len } if len != 0 => {
+                    self.base.seek_to(base_offset)?;
+                    let max = (out.len() as u64).min(len) as usize;
+                    let n = self.base.read(&mut out[..max])?;
+                    if n == 0 {
+                        return Err(unexpected_eof());
+                    }
+                    self.pending = Instruction::Copy {
+                        base_offset: base_offset + n as u64,
+                        len: len - n as u64,
+                    };
+                    n
+                }
+                Instruction::Insert { start, len } if len != 0 => {
+                    let n = out.len().min(len);
+                    out[..n].copy_from_slice(&self.instructions[start..][..n]);
+                    self.pending = Instruction::Insert {
+                        start: start + n,
+                        len: len - n,
+                    };
+                    n
+                }
+                _ => {
+                    if self.cursor == self.instructions.len() {
+                        return if self.position == self.size {
+                            Ok(0)
+                        } else {
+                            Err(invalid_delta())
+                        };
+                    }
+                    self.pending = self.next_instruction().ok_or_else(invalid_delta)?;
+                    continue;
+                }
+            };
+            self.position += n as u64;
+            if self.position > self.size {
+                return Err(invalid_delta());
+            }
+            return Ok(n);
+        }
+    }
+
+    /// Decode the instruction at our cursor like git does in `patch_delta()`.
+    fn next_instruction(&mut self) -> Option<Instruction> {
+        let data = &self.instructions;
+        let cmd = *data.get(self.cursor)?;
+        self.cursor += 1;
+        if cmd & 0b1000_0000 != 0 {
+            let mut read_bytes = |bits: u8, num_bytes: usize| -> Option<u64> {
+                let mut value = 0_u64;
+                for byte_idx in 0..num_bytes {
+                    if bits & (1 << byte_idx) != 0 {
+                        value |= (*data.get(self.cursor)? as u64) << (8 * byte_idx);
+                        self.cursor += 1;
+                    }
+                }
+                Some(value)
+            };
+            let base_offset = read_bytes(cmd & 0b1111, 4)?;
+            let len = match read_bytes((cmd >> 4) & 0b111, 3)? {
+                0 => 0x10000,
+                len => len,
+            };
+            Some(Instruction::Copy { base_offset, len })
+        } else if cmd != 0 {
+            let start = self.cursor;
+            let len = cmd as usize;
+            if start + len > data.len() {
+                return None;
+            }
+            self.cursor += len;
+            Some(Instruction::Insert { start, len })
+        } else {
+            None
+        }
+    }
+}
+
+/// Decode a size at the beginning of delta instructions, returning it along with the amount of bytes it took.
+fn decode_size(d: &[u8]) -> Option<(u64, usize)> {
+    let mut size = 0_u64;
+    for (idx, byte) in d.iter().enumerate().take(10) {
+        size |= (*byte as u64 & 0x7f) << (7 * idx);
+        if byte & 0x80 == 0 {
+            return Some((size, idx + 1));
+        }
+    }
+    None
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "The object data ended prematurely")
+}
+
+fn invalid_delta() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "The delta instructions are malformed")
+}
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Return a stream to read the object with `id` from, or `None` if it doesn't exist, to obtain objects too large to be
+    /// held in memory.
+    ///
+    /// Objects are produced as they are read, see [`ObjectStream`] for details.
+    /// Objects are read into memory in full and streamed from there if a [`Transform`][crate::store::Transform] is configured, if object
+    /// hashes are to be verified, or if a ref-delta's base is outside of its pack, as these need the whole object or can't be
+    /// resolved within the pack. The same is done if any delta of the chain copies from an earlier part of its base than a previous
+    /// instruction, as its base would have to be decompressed again up to that point for every such copy.
+    pub fn object_stream(&self, id: impl AsRef<git_hash::oid>) -> Result<Option<ObjectStream>, Error> {
+        let requested_id = id.as_ref();
+        if self.store.transform.is_some() || self.store.verify_object_hashes {
+            return self.buffered_object_stream(requested_id);
+        }
+        let stream = self.find_entry(
+            requested_id,
+            |found| {
+                self.packed_object_stream(
+                    found.pack,
+                    found.pack_offset,
+                    &found.index.intra_pack_lookup(found.pack_id),
+                )
+            },
+            |lodb, id| Ok(loose_object_stream(lodb, id)?.map(Some)),
+        )?;
+        match stream {
+            Some(Some(stream)) => Ok(Some(stream)),
+            Some(None) => self.buffered_object_stream(requested_id),
+            None => Ok(None),
+        }
+    }
+
+    /// Build a stream for the entry at `pack_offset`, or return `None` if its delta chain can't be resolved within `pack`
+    /// or can't be applied front to back.
+    fn packed_object_stream(
+        &self,
+        pack: Arc<data::File>,
+        pack_offset: data::Offset,
+        index_file: &IntraPackLookup<'_>,
+    ) -> Result<Option<ObjectStream>, Error> {
+        let mut deltas = Vec::new();
+        let mut entry = pack.entry(pack_offset);
+        let mut num_ref_deltas = 0;
+        let kind = loop {
+            let base_offset = match entry.header {
+                data::entry::Header::OfsDelta { base_distance } => entry.base_pack_offset(base_distance),
+                data::entry::Header::RefDelta { base_id } => {
+                    num_ref_deltas += 1;
+                    if num_ref_deltas > self.max_recursion_depth {
+                        return Ok(None);
+                    }
+                    match index_file.pack_offset_by_id(&base_id) {
+                        Some(offset) => offset,
+                        None => return Ok(None),
+                    }
+                }
+                header => break header.as_kind().expect("undeltified entries have a kind"),
+            };
+            let base = pack.entry(base_offset);
+            deltas.push(entry);
+            entry = base;
+        };
+
+        let mut size = entry.decompressed_size;
+        let mut source = Source::Base(Inflated::new(pack.clone(), &entry));
+        for delta_entry in deltas.into_iter().rev() {
+            let mut instructions = vec![0; delta_entry.decompressed_size as usize];
+            pack.decompress_entry(&delta_entry, &mut instructions)
+                .map_err(|source| Error::DecompressDelta {
+                    source,
+                    path: pack.path().to_owned(),
+                })?;
+            let mut delta = Delta::new(source, instructions).ok_or_else(|| Error::MalformedDelta {
+                path: pack.path().to_owned(),
+            })?;
+            if !delta.copies_in_order() {
+                return Ok(None);
+            }
+            size = delta.size;
+            source = Source::Delta(Box::new(delta));
+        }
+        Ok(Some(ObjectStream {
+            kind,
+            size,
+            inner: Inner::Packed(source),
+        }))
+    }
+
+    fn buffered_object_stream(&self, id: &git_hash::oid) -> Result<Option<ObjectStream>, Error> {
+        let mut buf = Vec::new();
+        let kind = match self.try_find(id, &mut buf)? {
+            Some(obj) => obj.kind,
+            None => return Ok(None),
+        };
+        Ok(Some(ObjectStream {
+            kind,
+            size: buf.len() as u64,
+            inner: Inner::Buffered(io::Cursor::new(buf)),
+        }))
+    }
+}
+
+fn loose_object_stream(lodb: &loose::Store, id: &git_hash::oid) -> Result<Option<ObjectStream>, Error> {
+    let path: PathBuf = hash_path(id, lodb.path.clone());
+    let file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => {
+            return Err(Error::Loose {
+                source,
+                action: "open",
+                path,
+            })
+        }
+    };
+    let mut file = BufReader::with_capacity(lodb.read_buffer_size.unwrap_or(LOOSE_READ_BUFFER_SIZE).max(1), file);
+    let mut inflate = zlib::Inflate::default();
+    let mut header_buf = [0_u8; HEADER_MAX_SIZE];
+    let consumed_out =
+        zlib::stream::inflate::read(&mut file, &mut inflate.state, &mut header_buf).map_err(|source| Error::Loose {
+            source,
+            action: "deflate",
+            path: path.clone(),
+        })?;
+    let (kind, size, header_size) = git_object::decode::loose_header(&header_buf[..consumed_out])?;
+    let pending = header_buf[header_size..consumed_out.min(header_size + size)].to_vec();
+    Ok(Some(ObjectStream {
+        kind,
+        size: size as u64,
+        inner: Inner::Loose(Loose {
+            file,
+            inflate,
+            pending,
+            pending_pos: 0,
+            remaining: size as u64,
+        }),
+    }))
+}
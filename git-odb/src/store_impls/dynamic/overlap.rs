@@ -0,0 +1,227 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use git_features::progress::Progress;
+
+use crate::{pack, types::IndexAndPacks};
+
+mod error {
+    use std::path::PathBuf;
+
+    /// Returned by [`Store::delta_base_overlap()`][crate::Store::delta_base_overlap()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("The pack at '{}' isn't known to the object database", .path.display())]
+        PackNotFound { path: PathBuf },
+        #[error(transparent)]
+        IndexOpen(#[from] crate::pack::index::init::Error),
+        #[error(transparent)]
+        MultiIndexOpen(#[from] crate::pack::multi_index::init::Error),
+        #[error(transparent)]
+        PackOpen(#[from] crate::pack::data::init::Error),
+        #[error(transparent)]
+        InitializeODB(#[from] crate::store::load_index::Error),
+        #[error("The disk on state changed while performing the operation, and we observed the change.")]
+        NeedsRetryDueToChangeOnDisk,
+        #[error("Interrupted")]
+        Interrupted,
+    }
+}
+pub use error::Error;
+
+/// A pack containing delta bases of objects in the pack passed to [`Store::delta_base_overlap()`][crate::Store::delta_base_overlap()].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Candidate {
+    /// The path to the pack data file.
+    pub path: PathBuf,
+    /// The amount of delta objects whose base is stored in this pack, which is the score by which candidates are ordered.
+    pub delta_objects: usize,
+}
+
+/// Returned by [`Store::delta_base_overlap()`][crate::Store::delta_base_overlap()].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Outcome {
+    /// The amount of objects in the pack.
+    pub num_objects: usize,
+    /// The amount of delta objects whose base isn't stored in the pack itself.
+    pub cross_pack_deltas: usize,
+    /// The amount of delta objects whose base couldn't be found in any other pack, possibly as it is stored as loose object.
+    pub unresolved_bases: usize,
+    /// All packs containing bases of delta objects, the pack with most bases first.
+    pub candidates: Vec<Candidate>,
+}
+
+impl Outcome {
+    /// The pack most of the cross-pack delta objects have their base in, which is the best partner for consolidating the pack with.
+    pub fn nearest(&self) -> Option<&Candidate> {
+        self.candidates.first()
+    }
+}
+
+/// An index of a pack other than the one to analyse, for finding the packs of delta bases.
+enum OtherIndex {
+    Single {
+        index: Arc<pack::index::File>,
+        pack_path: PathBuf,
+    },
+    Multi {
+        index: Arc<pack::multi_index::File>,
+        pack_paths: Vec<PathBuf>,
+    },
+}
+
+impl OtherIndex {
+    /// Return the path to the pack containing `id`, if this index knows it.
+    fn pack_containing(&self, id: &git_hash::oid) -> Option<&Path> {
+        match self {
+            OtherIndex::Single { index, pack_path } => index.lookup(id).map(|_| pack_path.as_path()),
+            OtherIndex::Multi { index, pack_paths } => index.lookup(id).map(|entry_index| {
+                let (pack_index, _) = index.pack_id_and_pack_offset_at_index(entry_index);
+                pack_paths[pack_index as usize].as_path()
+            }),
+        }
+    }
+}
+
+impl super::Store {
+    /// Find the packs that delta objects in the pack at `pack_path` refer to as their base, to learn which pack an incremental pack
+    /// builds upon and would best be consolidated with.
+    ///
+    /// Only delta objects referring to their base by id can have their base outside of the pack, and each is attributed to the
+    /// first other pack containing its base. Entries are streamed, so memory usage is bounded by the size of the indices and the
+    /// amount of candidate packs.
+    /// `pack_path` is the path to a pack data file known to the store, either through its own index or a multi-pack index.
+    ///
+    /// `progress` is advanced for each object in the pack, and `should_interrupt` is checked regularly to allow aborting the operation.
+    /// Like with [`estimate_repack_savings()`][super::Store::estimate_repack_savings()], packs and indices are only loaded
+    /// temporarily if they aren't loaded yet.
+    pub fn delta_base_overlap(
+        &self,
+        pack_path: impl AsRef<Path>,
+        mut progress: impl Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Outcome, Error> {
+        let pack_path = pack_path.as_ref();
+        let mut index = self.index.load();
+        if !index.is_initialized() {
+            self.consolidate_with_disk_state(true, false)?;
+            index = self.index.load();
+        }
+
+        let mut target = None;
+        let mut others = Vec::new();
+        for slot_index in &index.slot_indices {
+            let slot = &self.files[*slot_index];
+            if slot.generation.load(Ordering::SeqCst) != index.generation {
+                return Err(Error::NeedsRetryDueToChangeOnDisk);
+            }
+            let files = slot.files.load();
+            let files = Option::as_ref(&files).ok_or(Error::NeedsRetryDueToChangeOnDisk)?;
+
+            match files {
+                IndexAndPacks::Index(bundle) => {
+                    let index = match bundle.index.loaded() {
+                        Some(index) => index.clone(),
+                        None => Arc::new(self.backend.open_index(bundle.index.path(), self.object_hash)?),
+                    };
+                    if target.is_none() && bundle.data.path() == pack_path {
+                        let data = match bundle.data.loaded() {
+                            Some(pack) => pack.clone(),
+                            None => Arc::new(self.backend.open_pack(bundle.data.path(), self.object_hash)?),
+                        };
+                        target = Some((
+                            data,
+                            OtherIndex::Single {
+                                index,
+                                pack_path: pack_path.to_owned(),
+                            },
+                            None,
+                        ));
+                    } else {
+                        others.push(OtherIndex::Single {
+                            index,
+                            pack_path: bundle.data.path().to_owned(),
+                        });
+                    }
+                }
+                IndexAndPacks::MultiIndex(bundle) => {
+                    let index = match bundle.multi_index.loaded() {
+                        Some(index) => index.clone(),
+                        None => Arc::new(self.backend.open_multi_index(bundle.multi_index.path())?),
+                    };
+                    let index_dir = index.path().parent().expect("file in a directory");
+                    let pack_paths: Vec<_> = index
+                        .index_names()
+                        .iter()
+                        .map(|name| index_dir.join(name.with_extension("pack")))
+                        .collect();
+                    match pack_paths.iter().position(|path| path == pack_path) {
+                        Some(pack_index) if target.is_none() => {
+                            let data = match bundle.data.get(pack_index).and_then(|f| f.loaded()) {
+                                Some(pack) => pack.clone(),
+                                None => Arc::new(self.backend.open_pack(pack_path, self.object_hash)?),
+                            };
+                            target = Some((data, OtherIndex::Multi { index, pack_paths }, Some(pack_index)));
+                        }
+                        _ => others.push(OtherIndex::Multi { index, pack_paths }),
+                    }
+                }
+            }
+        }
+
+        let (data, target_index, pack_index) = target.ok_or_else(|| Error::PackNotFound {
+            path: pack_path.to_owned(),
+        })?;
+        let pack_offsets: Box<dyn Iterator<Item = pack::data::Offset>> = match &target_index {
+            OtherIndex::Single { index, .. } => Box::new(index.iter().map(|e| e.pack_offset)),
+            OtherIndex::Multi { index, .. } => {
+                let pack_index = pack_index.expect("set for multi-pack indices") as pack::multi_index::PackIndex;
+                Box::new(
+                    index
+                        .iter()
+                        .filter(move |e| e.pack_index == pack_index)
+                        .map(|e| e.pack_offset),
+                )
+            }
+        };
+
+        let mut out = Outcome::default();
+        progress.init(None, git_features::progress::count("objects"));
+        for pack_offset in pack_offsets {
+            out.num_objects += 1;
+            if let pack::data::entry::Header::RefDelta { base_id } = data.entry(pack_offset).header {
+                if target_index.pack_containing(&base_id) != Some(pack_path) {
+                    out.cross_pack_deltas += 1;
+                    match std::iter::once(&target_index)
+                        .chain(others.iter())
+                        .find_map(|other| other.pack_containing(&base_id))
+                    {
+                        Some(base_pack) => match out.candidates.iter_mut().find(|c| c.path == base_pack) {
+                            Some(candidate) => candidate.delta_objects += 1,
+                            None => out.candidates.push(Candidate {
+                                path: base_pack.to_owned(),
+                                delta_objects: 1,
+                            }),
+                        },
+                        None => out.unresolved_bases += 1,
+                    }
+                }
+            }
+            progress.inc();
+            if should_interrupt.load(Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+        }
+        out.candidates
+            .sort_by(|a, b| b.delta_objects.cmp(&a.delta_objects).then_with(|| a.path.cmp(&b.path)));
+        Ok(out)
+    }
+}
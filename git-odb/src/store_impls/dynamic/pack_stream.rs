@@ -0,0 +1,140 @@
+use std::{cell::RefCell, convert::Infallible, io::Read, ops::Deref, rc::Rc, sync::atomic::AtomicBool};
+
+use git_features::{parallel::InOrderIter, progress};
+use git_hash::ObjectId;
+use git_pack::data::output;
+
+mod error {
+    /// Returned by [`Handle::pack_stream()`][crate::store::Handle::pack_stream()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("Could not count the objects to put into the pack")]
+        Count(
+            #[from]
+            git_pack::data::output::count::objects::Error<
+                git_pack::find::existing::Error<crate::store::find::Error>,
+                std::convert::Infallible,
+            >,
+        ),
+    }
+}
+pub use error::Error;
+
+type EntryError = output::entry::iter_from_counts::Error<crate::store::find::Error>;
+type Entries = Box<dyn Iterator<Item = Result<Vec<output::Entry>, EntryError>>>;
+
+/// A buffer shared with the pack writer, which is drained by reading from the [`PackStream`].
+#[derive(Default, Clone)]
+struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+impl std::io::Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A [`Read`] implementation producing a pack for a set of objects, created with [`Handle::pack_stream()`][super::Handle::pack_stream()].
+///
+/// Pack entries are produced in chunks as bytes are read, so only the most recently produced chunk of entries is held in memory
+/// along with a small record per object, instead of the whole pack.
+/// The objects to send are determined when the stream is created, and all packs they are copied from are kept available
+/// for as long as the stream exists, even if they are deleted on disk in the mean time.
+pub struct PackStream {
+    pack: output::bytes::FromEntriesIter<Entries, SharedBuf>,
+    buf: SharedBuf,
+    read_pos: usize,
+    num_objects: usize,
+}
+
+impl PackStream {
+    /// The amount of objects in the pack.
+    pub fn num_objects(&self) -> usize {
+        self.num_objects
+    }
+
+    /// Return the checksum of the pack once it was read completely, or `None` otherwise.
+    pub fn digest(&self) -> Option<ObjectId> {
+        self.pack.digest()
+    }
+}
+
+impl Read for PackStream {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            {
+                let buf = self.buf.0.borrow();
+                if self.read_pos < buf.len() {
+                    let n = out.len().min(buf.len() - self.read_pos);
+                    out[..n].copy_from_slice(&buf[self.read_pos..][..n]);
+                    self.read_pos += n;
+                    return Ok(n);
+                }
+            }
+            self.buf.0.borrow_mut().clear();
+            self.read_pos = 0;
+            match self.pack.next() {
+                Some(Ok(_written)) => continue,
+                Some(Err(output::bytes::Error::Io(err))) => return Err(err),
+                Some(Err(output::bytes::Error::Input(err))) => {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, err))
+                }
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone + Send + 'static,
+{
+    /// Return a stream of the bytes of a pack containing all objects in `ids`, suitable for sending it to another process
+    /// without having to write it to disk first.
+    ///
+    /// Packed objects are copied as they are, including deltas, while loose objects are compressed as base objects.
+    /// If `thin` is true, deltas may refer to base objects that are not part of the pack, which is only valid if the receiver
+    /// has them. Otherwise these deltas are stored as base objects.
+    ///
+    /// Note that the stream can't be sent to other threads, and that the packed objects to copy are recorded when it is created.
+    pub fn pack_stream(&self, ids: impl IntoIterator<Item = ObjectId>, thin: bool) -> Result<PackStream, Error> {
+        let mut handle = self.clone();
+        handle.prevent_pack_unload();
+        handle.ignore_replacements = true;
+        let (counts, _outcome) = output::count::objects_unthreaded(
+            handle.clone(),
+            ids.into_iter().map(Ok::<_, Infallible>),
+            progress::Discard,
+            &AtomicBool::default(),
+            output::count::objects::ObjectExpansion::AsIs,
+        )?;
+        let num_objects = counts.len();
+        let entries: Entries = Box::new(InOrderIter::from(output::entry::iter_from_counts(
+            counts,
+            handle,
+            progress::Discard,
+            output::entry::iter_from_counts::Options {
+                allow_thin_pack: thin,
+                ..Default::default()
+            },
+        )));
+        let buf = SharedBuf::default();
+        Ok(PackStream {
+            pack: output::bytes::FromEntriesIter::new(
+                entries,
+                buf.clone(),
+                num_objects as u32,
+                git_pack::data::Version::V2,
+                self.store.object_hash,
+            ),
+            buf,
+            read_pos: 0,
+            num_objects,
+        })
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use git_hash::oid;
+use git_object::TreeRefIter;
+
+use crate::{
+    store::types::{IndexAndPacks, PackId},
+    Find,
+};
+
+mod error {
+    /// Returned by [`Handle::prefetch_tree_children()`][crate::store::Handle::prefetch_tree_children()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error(transparent)]
+        Find(#[from] crate::store::find::Error),
+        #[error("Object {id} was expected to be a tree, but is a {actual}")]
+        NotATree {
+            id: git_hash::ObjectId,
+            actual: git_object::Kind,
+        },
+        #[error(transparent)]
+        Decode(#[from] git_object::decode::Error),
+    }
+}
+pub use error::Error;
+
+/// Returned by [`Handle::prefetch_tree_children()`][crate::store::Handle::prefetch_tree_children()].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    /// The amount of children of the tree, not counting submodules.
+    pub children: usize,
+    /// The amount of packs whose data was warmed as they contain at least one of the children.
+    pub packs_warmed: usize,
+}
+
+/// The amount of bytes to touch at the beginning of each entry, which is about a page and thus the unit of data that is
+/// faulted in.
+const WARM_BYTES_PER_ENTRY: usize = 4096;
+
+/// The size of a page of memory on most systems, which is the unit in which memory maps are faulted in.
+const BYTES_PER_PAGE: usize = 4096;
+
+/// Receives the checksums of warmed memory. Writing to it is a side effect the compiler has to keep, and with it the reads
+/// the checksums are computed from.
+static WARM_SINK: AtomicU32 = AtomicU32::new(0);
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Find the packs containing the immediate children of the tree `id` and load their data, touching the entries of all children
+    /// in parallel so that a recursive traversal descending into them doesn't stall on page faults.
+    ///
+    /// At most `max_packs` packs are warmed, preferring those that contain the most children, and only indices that are already loaded
+    /// are consulted. Packs that can't be loaded are skipped as warming is best-effort.
+    /// Return `None` if `id` doesn't exist.
+    pub fn prefetch_tree_children(&self, id: impl AsRef<oid>, max_packs: usize) -> Result<Option<Outcome>, Error> {
+        let id = id.as_ref();
+        let mut buf = Vec::new();
+        let children = match self.try_find(id, &mut buf)? {
+            Some(data) if data.kind == git_object::Kind::Tree => TreeRefIter::from_bytes(data.data)
+                .filter_map(|entry| match entry {
+                    Ok(entry) if entry.mode == git_object::tree::EntryMode::Commit => None,
+                    Ok(entry) => Some(Ok(entry.oid.to_owned())),
+                    Err(err) => Some(Err(err)),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(data) => {
+                return Err(Error::NotATree {
+                    id: id.to_owned(),
+                    actual: data.kind,
+                })
+            }
+            None => return Ok(None),
+        };
+
+        let mut snapshot = self.snapshot.borrow_mut();
+        let mut offsets_by_pack = HashMap::<git_pack::data::Id, (usize, PackId, Vec<git_pack::data::Offset>)>::new();
+        for child in &children {
+            if let Some((idx, pack_id, pack_offset)) =
+                snapshot.indices.iter_mut().enumerate().find_map(|(idx, index)| {
+                    index
+                        .lookup(child)
+                        .map(|outcome| (idx, outcome.object_index.pack_id, outcome.object_index.pack_offset))
+                })
+            {
+                offsets_by_pack
+                    .entry(pack_id.to_intrinsic_pack_id())
+                    .or_insert_with(|| (idx, pack_id, Vec::new()))
+                    .2
+                    .push(pack_offset);
+            }
+        }
+        let mut packs: Vec<_> = offsets_by_pack.into_iter().collect();
+        packs.sort_by(|a, b| b.1 .2.len().cmp(&a.1 .2.len()).then(a.0.cmp(&b.0)));
+        packs.truncate(max_packs);
+
+        let marker = snapshot.marker;
+        let mut to_warm = Vec::with_capacity(packs.len());
+        for (_, (idx, pack_id, offsets)) in packs {
+            let slot = match snapshot.indices[idx].pack(pack_id) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            let pack = match slot {
+                Some(pack) => pack.clone(),
+                None => match self.store.load_pack(pack_id, marker) {
+                    Ok(Some(pack)) => {
+                        *slot = Some(pack.clone());
+                        pack
+                    }
+                    Ok(None) | Err(_) => continue,
+                },
+            };
+            to_warm.push((pack, offsets));
+        }
+
+        let packs_warmed = to_warm.len();
+        git_features::parallel::threads(|scope| {
+            for (pack, offsets) in to_warm {
+                scope.spawn(move |_| warm(&pack, offsets));
+            }
+        })
+        .ok();
+        Ok(Some(Outcome {
+            children: children.len(),
+            packs_warmed,
+        }))
+    }
+}
+
+impl super::Store {
+    /// Load all indices and multi-pack indices that aren't loaded yet to pay for it before serving lookups, instead of
+    /// loading them lazily when lookups miss.
+    ///
+    /// Only indices are loaded, pack data is left to be loaded by the lookups that need it, which keeps memory usage bounded.
+    /// It's safe to call this from multiple threads at once. Return the metrics of the store after all indices were loaded.
+    pub fn prefetch_indices(&self) -> Result<super::Metrics, crate::store::load_index::Error> {
+        self.load_all_indices()?;
+        Ok(self.metrics())
+    }
+
+    /// Read the pages holding the sorted object ids of all loaded pack indices and multi-pack indices so that the first lookups
+    /// after loading them don't stall on page faults, without reading any pack data.
+    ///
+    /// Fan-out tables are read when an index is loaded already, so the object id tables are all that lookups still fault in.
+    /// Indices that aren't loaded are left alone. Return the amount of indices that were warmed.
+    pub fn warm_index_lookup_tables(&self) -> usize {
+        let index = self.index.load();
+        let mut num_warmed = 0;
+        for slot in index.slot_indices.iter().map(|idx| &self.files[*idx]) {
+            match Option::as_ref(&slot.files.load()) {
+                Some(IndexAndPacks::Index(bundle)) => {
+                    if let Some(index) = bundle.index.loaded() {
+                        let entry_size = match index.version() {
+                            git_pack::index::Version::V1 => index.object_hash().len_in_bytes() + 4,
+                            git_pack::index::Version::V2 => index.object_hash().len_in_bytes(),
+                        };
+                        let checksum = warm_ids(index.num_objects(), entry_size, |entry_index| {
+                            index.oid_at_index(entry_index)
+                        });
+                        WARM_SINK.fetch_xor(checksum, Ordering::Relaxed);
+                        num_warmed += 1;
+                    }
+                }
+                Some(IndexAndPacks::MultiIndex(bundle)) => {
+                    if let Some(index) = bundle.multi_index.loaded() {
+                        let checksum =
+                            warm_ids(index.num_objects(), index.object_hash().len_in_bytes(), |entry_index| {
+                                index.oid_at_index(entry_index)
+                            });
+                        WARM_SINK.fetch_xor(checksum, Ordering::Relaxed);
+                        num_warmed += 1;
+                    }
+                }
+                None => {}
+            }
+        }
+        num_warmed
+    }
+}
+
+/// Read one object id out of each page of a table of `num_objects` entries of `entry_size` bytes, as returned by `oid_at_index`,
+/// and return a checksum of the ids read. The checksum itself isn't needed, but the ids are only read for sure if it's passed
+/// to [`WARM_SINK`].
+fn warm_ids<'a>(num_objects: u32, entry_size: usize, oid_at_index: impl Fn(u32) -> &'a oid) -> u32 {
+    let entries_per_page = (BYTES_PER_PAGE / entry_size).max(1);
+    let last_entry = num_objects.checked_sub(1);
+    (0..num_objects)
+        .step_by(entries_per_page)
+        .chain(last_entry)
+        .fold(0, |checksum, entry_index| {
+            git_features::hash::crc32_update(checksum, oid_at_index(entry_index).as_bytes())
+        })
+}
+
+/// Touch the beginning of each entry at `offsets` in `pack`, skipping those that were touched already along with a previous entry.
+fn warm(pack: &Arc<git_pack::data::File>, mut offsets: Vec<git_pack::data::Offset>) {
+    offsets.sort_unstable();
+    let pack_end = pack.pack_end() as u64;
+    let mut warm_until = 0;
+    for offset in offsets.into_iter().filter(|offset| *offset < pack_end) {
+        if offset < warm_until {
+            continue;
+        }
+        warm_until = (offset + WARM_BYTES_PER_ENTRY as u64).min(pack_end);
+        // the checksum itself isn't needed, but all bytes are only read for sure if it's passed to the sink.
+        let checksum = pack.entry_crc32(offset, (warm_until - offset) as usize);
+        WARM_SINK.fetch_xor(checksum, Ordering::Relaxed);
+    }
+}
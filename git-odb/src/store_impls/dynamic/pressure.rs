@@ -0,0 +1,97 @@
+use std::time::SystemTime;
+
+use crate::store::types::{IndexAndPacks, SlotMapIndex};
+
+/// The amount of samples kept in the pack count history, with the oldest ones being dropped first.
+pub const HISTORY_LEN: usize = 32;
+
+/// The amount of packs and indices observed by a single refresh of the store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sample {
+    /// The time at which the refresh was completed.
+    pub at: SystemTime,
+    /// The amount of reachable packs, including those covered by multi-pack indices.
+    pub num_packs: usize,
+    /// The amount of reachable indices, with each multi-pack index counting as one.
+    pub num_indices: usize,
+}
+
+/// A summary of the [pack count history][crate::Store::pack_count_history()], indicating whether packs accumulate over time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackCountPressure {
+    /// The amount of samples this summary is based on.
+    pub num_samples: usize,
+    /// The amount of packs seen by the oldest sample.
+    pub oldest_num_packs: usize,
+    /// The amount of packs seen by the most recent sample.
+    pub newest_num_packs: usize,
+    /// The amount of refreshes which saw more packs than the one before.
+    pub num_increases: usize,
+    /// The amount of refreshes which saw fewer packs than the one before, typically after a repack.
+    pub num_decreases: usize,
+}
+
+impl PackCountPressure {
+    /// Return true if more packs were added than removed over the course of the history, which suggests that packs are
+    /// received without being repacked.
+    pub fn is_rising(&self) -> bool {
+        self.newest_num_packs > self.oldest_num_packs && self.num_increases > self.num_decreases
+    }
+}
+
+impl super::Store {
+    /// Return the pack counts observed by the most recent refreshes, oldest first, with at most [`HISTORY_LEN`] samples.
+    pub fn pack_count_history(&self) -> Vec<Sample> {
+        self.pack_count_history.lock().iter().copied().collect()
+    }
+
+    /// Summarize the [pack count history][super::Store::pack_count_history()] to learn if packs accumulate, or `None` if the
+    /// store didn't refresh yet.
+    ///
+    /// Unlike [`Metrics::known_packs`][crate::store::Metrics::known_packs], this tells apart a store that has many packs but is
+    /// stable from one that keeps receiving new ones, which helps to decide when to repack or write a multi-pack index.
+    pub fn pack_count_pressure(&self) -> Option<PackCountPressure> {
+        let history = self.pack_count_history.lock();
+        let (oldest, newest) = (history.front()?, history.back()?);
+        let (mut num_increases, mut num_decreases) = (0, 0);
+        for (previous, current) in history.iter().zip(history.iter().skip(1)) {
+            if current.num_packs > previous.num_packs {
+                num_increases += 1;
+            } else if current.num_packs < previous.num_packs {
+                num_decreases += 1;
+            }
+        }
+        Some(PackCountPressure {
+            num_samples: history.len(),
+            oldest_num_packs: oldest.num_packs,
+            newest_num_packs: newest.num_packs,
+            num_increases,
+            num_decreases,
+        })
+    }
+
+    /// Add a sample of the packs reachable through `index` to the history, dropping the oldest sample if it is full.
+    pub(crate) fn record_pack_count(&self, index: &SlotMapIndex) {
+        let num_packs = index
+            .slot_indices
+            .iter()
+            .map(|idx| match &**self.files[*idx].files.load() {
+                Some(IndexAndPacks::Index(_)) => 1,
+                Some(IndexAndPacks::MultiIndex(bundle)) => bundle.data.len(),
+                None => 0,
+            })
+            .sum();
+        let sample = Sample {
+            at: SystemTime::now(),
+            num_packs,
+            num_indices: index.slot_indices.len(),
+        };
+        let mut history = self.pack_count_history.lock();
+        if history.len() == HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(sample);
+    }
+}
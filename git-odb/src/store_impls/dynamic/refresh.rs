@@ -0,0 +1,39 @@
+use crate::store::load_index;
+
+/// Returned by [`Store::refresh()`][crate::Store::refresh()].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Outcome {
+    /// Whether indices or alternate object databases were added, removed or changed since the disk state was last read.
+    pub changed: bool,
+    /// Whether a new generation was started, which happens if slots were reused and invalidates pack ids handed out previously.
+    pub generation_changed: bool,
+    /// The amount of indices and multi-pack indices known to the store after the refresh.
+    pub num_indices: usize,
+}
+
+impl super::Store {
+    /// Read the objects directory and its alternates again and update what we know about them right away, instead of waiting for
+    /// a lookup to miss an object to do so.
+    ///
+    /// This is useful after another process changed the object database, for instance by fetching new packs, which are then
+    /// available to the next lookup of all handles. Unlike the refresh triggered by a miss, this one is never skipped in favor of a
+    /// refresh that is already in progress, as it might have read the directory before it changed. Handles requiring stable pack ids
+    /// are respected like they are for any other refresh.
+    pub fn refresh(&self) -> Result<Outcome, load_index::Error> {
+        let previous_generation = self.index.load().generation;
+        let changed = self
+            .consolidate_with_disk_state_opts(
+                false, /* needs init */
+                false, /* load one new index */
+                true,  /* force */
+            )?
+            .is_some();
+        let index = self.index.load();
+        Ok(Outcome {
+            changed,
+            generation_changed: index.generation != previous_generation,
+            num_indices: index.slot_indices.len(),
+        })
+    }
+}
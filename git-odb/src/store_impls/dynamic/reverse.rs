@@ -0,0 +1,105 @@
+use std::ops::Deref;
+
+use git_hash::ObjectId;
+
+use crate::store::{
+    handle,
+    types::{PackId, SlotIndexMarker},
+};
+
+mod error {
+    /// Returned by [`Handle::oid_at_pack_offset()`][crate::store::Handle::oid_at_pack_offset()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("There is no pack with id {id}")]
+        NotFound { id: git_pack::data::Id },
+    }
+}
+pub use error::Error;
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Return the id of the object whose entry starts at `pack_offset` in the pack with `pack_id`, or `None` if no entry starts
+    /// there. Note that `pack_id` is only valid for the generation of the store it was obtained from, like the pack id of a
+    /// [`Location`][git_pack::data::entry::Location].
+    ///
+    /// For packs with their own index, the reverse index is read from the `.rev` file next to it, or computed from the index
+    /// if there is none, and kept for subsequent calls. Packs of multi-pack indices are searched linearly.
+    pub fn oid_at_pack_offset(
+        &self,
+        pack_id: git_pack::data::Id,
+        pack_offset: git_pack::data::Offset,
+    ) -> Result<Option<ObjectId>, Error> {
+        let id = PackId::from_intrinsic_pack_id(pack_id);
+        let snapshot = self.snapshot.borrow();
+        let marker = snapshot.marker;
+        let not_in_snapshot;
+        let index = match snapshot.indices.iter().find(|index| index.id == id.index) {
+            Some(index) => index,
+            None => {
+                not_in_snapshot = self
+                    .store
+                    .index_by_id(id, marker)
+                    .ok_or(Error::NotFound { id: pack_id })?;
+                &not_in_snapshot
+            }
+        };
+        Ok(self.store.oid_at_pack_offset_in(index, id, marker, pack_offset))
+    }
+}
+
+impl super::Store {
+    /// Return the id of the object whose entry starts at `pack_offset` in the pack with `pack_id` of `index`, or `None` if no
+    /// entry starts there.
+    ///
+    /// Packs with their own index use their reverse index, while packs of multi-pack indices are searched linearly, as is
+    /// `index` if the store changed since `marker` so its reverse index can't be loaded anymore.
+    pub(crate) fn oid_at_pack_offset_in(
+        &self,
+        index: &handle::IndexLookup,
+        pack_id: PackId,
+        marker: SlotIndexMarker,
+        pack_offset: git_pack::data::Offset,
+    ) -> Option<ObjectId> {
+        match &index.file {
+            handle::SingleOrMultiIndex::Single { index: file, .. } => {
+                match self.load_reverse_index(pack_id, marker, file) {
+                    Some(reverse) => reverse
+                        .lookup(file, pack_offset)
+                        .map(|entry_index| file.oid_at_index(entry_index).to_owned()),
+                    None => index.oid_at_pack_offset(pack_id, pack_offset),
+                }
+            }
+            handle::SingleOrMultiIndex::Multi { .. } => index.oid_at_pack_offset(pack_id, pack_offset),
+        }
+    }
+
+    /// Return the offset at which the entry following the one at `pack_offset` begins in the pack with `pack_id` of `index`,
+    /// or `None` if it's the last entry of the pack or no entry starts at `pack_offset`.
+    ///
+    /// This uses the reverse index in the same way as [`oid_at_pack_offset_in()`][Self::oid_at_pack_offset_in()].
+    pub(crate) fn next_pack_offset_in(
+        &self,
+        index: &handle::IndexLookup,
+        pack_id: PackId,
+        marker: SlotIndexMarker,
+        pack_offset: git_pack::data::Offset,
+    ) -> Option<git_pack::data::Offset> {
+        match &index.file {
+            handle::SingleOrMultiIndex::Single { index: file, .. } => {
+                match self.load_reverse_index(pack_id, marker, file) {
+                    Some(reverse) => {
+                        let next_position = reverse.position(file, pack_offset)? + 1;
+                        (next_position < reverse.num_objects())
+                            .then(|| file.pack_offset_at_index(reverse.index_at_position(next_position)))
+                    }
+                    None => index.next_pack_offset(pack_id, pack_offset),
+                }
+            }
+            handle::SingleOrMultiIndex::Multi { .. } => index.next_pack_offset(pack_id, pack_offset),
+        }
+    }
+}
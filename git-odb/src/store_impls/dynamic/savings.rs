@@ -0,0 +1,215 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use git_features::progress::Progress;
+
+use crate::{pack, types::IndexAndPacks};
+
+mod error {
+    /// Returned by [`Store::estimate_repack_savings()`][crate::Store::estimate_repack_savings()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error(transparent)]
+        IndexOpen(#[from] crate::pack::index::init::Error),
+        #[error(transparent)]
+        MultiIndexOpen(#[from] crate::pack::multi_index::init::Error),
+        #[error(transparent)]
+        PackOpen(#[from] crate::pack::data::init::Error),
+        #[error(transparent)]
+        InitializeODB(#[from] crate::store::load_index::Error),
+        #[error("The disk on state changed while performing the operation, and we observed the change.")]
+        NeedsRetryDueToChangeOnDisk,
+        #[error("Interrupted")]
+        Interrupted,
+    }
+}
+pub use error::Error;
+
+/// What a single pack contributes to the [`Outcome`] of [`Store::estimate_repack_savings()`][crate::Store::estimate_repack_savings()].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pack {
+    /// The path to the pack data file.
+    pub path: PathBuf,
+    /// The amount of objects in this pack that were seen in a previous pack already.
+    pub duplicate_objects: usize,
+    /// The amount of bytes the duplicate objects occupy in this pack.
+    pub duplicate_bytes: u64,
+    /// The amount of delta objects in this pack whose base object is stored elsewhere.
+    pub cross_pack_deltas: usize,
+    /// The amount of bytes the cross-pack delta objects occupy in this pack.
+    pub cross_pack_delta_bytes: u64,
+}
+
+/// Returned by [`Store::estimate_repack_savings()`][crate::Store::estimate_repack_savings()].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Outcome {
+    /// The amount of bytes used by objects that are stored more than once, which a full repack would save.
+    pub total_redundant_bytes: u64,
+    /// One entry for each pack that contains duplicate or cross-pack delta objects, in the order in which they were processed.
+    pub packs: Vec<Pack>,
+}
+
+enum SeenIndex {
+    Single(Arc<pack::index::File>),
+    Multi(Arc<pack::multi_index::File>),
+}
+
+impl SeenIndex {
+    fn contains(&self, id: &git_hash::oid) -> bool {
+        match self {
+            SeenIndex::Single(index) => index.lookup(id).is_some(),
+            SeenIndex::Multi(index) => index.lookup(id).is_some(),
+        }
+    }
+}
+
+impl super::Store {
+    /// Estimate how many bytes a full repack would save by visiting all packs and finding objects that are stored more than once,
+    /// along with delta objects whose base lives in another pack.
+    ///
+    /// Of all copies of an object, the one in the first pack visited is assumed to be kept, and all others count as redundant.
+    /// Cross-pack deltas don't waste space by themselves but are reported as they would be stored differently after a repack.
+    /// Loose objects aren't considered.
+    ///
+    /// `progress` is advanced for each packed object seen, and `should_interrupt` is checked regularly to allow aborting the operation.
+    /// Similar to [`count_objects_by_kind()`][super::Store::count_objects_by_kind()], packs and indices are only loaded
+    /// temporarily if they aren't loaded yet, and memory usage is bounded by the size of the largest index.
+    pub fn estimate_repack_savings(
+        &self,
+        mut progress: impl Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Outcome, Error> {
+        let mut index = self.index.load();
+        if !index.is_initialized() {
+            self.consolidate_with_disk_state(true, false)?;
+            index = self.index.load();
+        }
+
+        let mut out = Outcome::default();
+        let mut seen = Vec::<SeenIndex>::new();
+        progress.init(None, git_features::progress::count("objects"));
+        for slot_index in &index.slot_indices {
+            let slot = &self.files[*slot_index];
+            if slot.generation.load(Ordering::SeqCst) != index.generation {
+                return Err(Error::NeedsRetryDueToChangeOnDisk);
+            }
+            let files = slot.files.load();
+            let files = Option::as_ref(&files).ok_or(Error::NeedsRetryDueToChangeOnDisk)?;
+
+            match files {
+                IndexAndPacks::Index(bundle) => {
+                    let index = match bundle.index.loaded() {
+                        Some(index) => index.clone(),
+                        None => Arc::new(self.backend.open_index(bundle.index.path(), self.object_hash)?),
+                    };
+                    let data = match bundle.data.loaded() {
+                        Some(pack) => pack.clone(),
+                        None => Arc::new(self.backend.open_pack(bundle.data.path(), self.object_hash)?),
+                    };
+                    let offsets = index.sorted_offsets();
+                    let mut stats = new_pack(data.path());
+                    for entry in index.iter() {
+                        account_entry(&data, &offsets, entry.oid, entry.pack_offset, &seen, &mut stats, |id| {
+                            index.lookup(id).is_some()
+                        });
+                        progress.inc();
+                        if should_interrupt.load(Ordering::Relaxed) {
+                            return Err(Error::Interrupted);
+                        }
+                    }
+                    out.push(stats);
+                    seen.push(SeenIndex::Single(index));
+                }
+                IndexAndPacks::MultiIndex(bundle) => {
+                    let index = match bundle.multi_index.loaded() {
+                        Some(index) => index.clone(),
+                        None => Arc::new(self.backend.open_multi_index(bundle.multi_index.path())?),
+                    };
+                    let index_dir = index.path().parent().expect("file in a directory").to_owned();
+                    let mut offsets_by_pack = vec![Vec::new(); index.num_indices() as usize];
+                    for entry in index.iter() {
+                        offsets_by_pack[entry.pack_index as usize].push(entry.pack_offset);
+                    }
+                    for (pack_index, offsets) in offsets_by_pack.iter_mut().enumerate() {
+                        offsets.sort_unstable();
+                        let pack_path = index_dir.join(index.index_names()[pack_index].with_extension("pack"));
+                        let data = match bundle.data.get(pack_index).and_then(|f| f.loaded()) {
+                            Some(pack) => pack.clone(),
+                            None => Arc::new(self.backend.open_pack(&pack_path, self.object_hash)?),
+                        };
+                        let mut stats = new_pack(data.path());
+                        for entry in index.iter().filter(|e| e.pack_index as usize == pack_index) {
+                            account_entry(&data, offsets, entry.oid, entry.pack_offset, &seen, &mut stats, |id| {
+                                index
+                                    .lookup(id)
+                                    .map(|idx| index.pack_id_and_pack_offset_at_index(idx).0 == entry.pack_index)
+                                    .unwrap_or(false)
+                            });
+                            progress.inc();
+                            if should_interrupt.load(Ordering::Relaxed) {
+                                return Err(Error::Interrupted);
+                            }
+                        }
+                        out.push(stats);
+                    }
+                    seen.push(SeenIndex::Multi(index));
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Outcome {
+    fn push(&mut self, pack: Pack) {
+        if pack.duplicate_objects != 0 || pack.cross_pack_deltas != 0 {
+            self.total_redundant_bytes += pack.duplicate_bytes;
+            self.packs.push(pack);
+        }
+    }
+}
+
+fn new_pack(path: &std::path::Path) -> Pack {
+    Pack {
+        path: path.to_owned(),
+        duplicate_objects: 0,
+        duplicate_bytes: 0,
+        cross_pack_deltas: 0,
+        cross_pack_delta_bytes: 0,
+    }
+}
+
+/// `sorted_offsets` are all offsets of entries in `data`, used to learn where the entry at `pack_offset` ends.
+fn account_entry(
+    data: &pack::data::File,
+    sorted_offsets: &[pack::data::Offset],
+    id: git_hash::ObjectId,
+    pack_offset: pack::data::Offset,
+    seen: &[SeenIndex],
+    stats: &mut Pack,
+    is_in_same_pack: impl Fn(&git_hash::oid) -> bool,
+) {
+    let entry_end = match sorted_offsets.binary_search(&pack_offset) {
+        Ok(pos) => sorted_offsets.get(pos + 1).copied(),
+        Err(_) => None,
+    }
+    .unwrap_or(data.pack_end() as u64);
+    let size = entry_end - pack_offset;
+    if seen.iter().any(|index| index.contains(&id)) {
+        stats.duplicate_objects += 1;
+        stats.duplicate_bytes += size;
+    } else if let pack::data::entry::Header::RefDelta { base_id } = data.entry(pack_offset).header {
+        if !is_in_same_pack(&base_id) {
+            stats.cross_pack_deltas += 1;
+            stats.cross_pack_delta_bytes += size;
+        }
+    }
+}
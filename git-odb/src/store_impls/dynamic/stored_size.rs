@@ -0,0 +1,90 @@
+use std::ops::Deref;
+
+use git_hash::{oid, ObjectId};
+
+mod error {
+    /// Returned by [`Handle::stored_size()`][crate::store::Handle::stored_size()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error(transparent)]
+        Loose(#[from] crate::loose::find::Error),
+        #[error(transparent)]
+        LoadIndex(#[from] crate::store::load_index::Error),
+        #[error(transparent)]
+        LoadPack(#[from] std::io::Error),
+    }
+}
+pub use error::Error;
+
+/// The way a delta object refers to its base object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeltaBase {
+    /// The base object is referred to by id, and may be stored in the same pack or elsewhere.
+    Id(ObjectId),
+    /// The base object is stored in the same pack at the given offset.
+    PackOffset(git_pack::data::Offset),
+}
+
+/// Returned by [`Handle::stored_size()`][crate::store::Handle::stored_size()].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// The object is stored in a loose object file.
+    Loose {
+        /// The size of the compressed object file in bytes.
+        size: u64,
+    },
+    /// The object is stored in a pack.
+    Packed {
+        /// The id of the pack, the same as in [`Location::pack_id`][git_pack::data::entry::Location::pack_id].
+        pack_id: git_pack::data::Id,
+        /// The amount of bytes the entry occupies in the pack, including its header.
+        size: u64,
+        /// Set if the object is stored as delta, to tell how it refers to its base.
+        delta_base: Option<DeltaBase>,
+    },
+}
+
+impl Outcome {
+    /// The amount of bytes the object occupies on disk.
+    pub fn size(&self) -> u64 {
+        match self {
+            Outcome::Loose { size } | Outcome::Packed { size, .. } => *size,
+        }
+    }
+}
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Return the amount of bytes object `id` occupies on disk in its compressed form, or `None` if it doesn't exist.
+    ///
+    /// Unlike the size obtained by decoding the object, this is the size of the pack entry or of the loose object file, and delta objects
+    /// are reported along with the reference to their base.
+    /// The end of a pack entry is found with the reverse index of its pack, see [`oid_at_pack_offset()`][Self::oid_at_pack_offset()].
+    pub fn stored_size(&self, id: impl AsRef<oid>) -> Result<Option<Outcome>, Error> {
+        self.find_entry(
+            id.as_ref(),
+            |found| {
+                use git_pack::data::entry::Header::*;
+                let entry = found.pack.entry(found.pack_offset);
+                let delta_base = match entry.header {
+                    RefDelta { base_id } => Some(DeltaBase::Id(base_id)),
+                    OfsDelta { base_distance } => Some(DeltaBase::PackOffset(entry.base_pack_offset(base_distance))),
+                    Commit | Tree | Blob | Tag => None,
+                };
+                let entry_end = self
+                    .store
+                    .next_pack_offset_in(found.index, found.pack_id, found.marker, found.pack_offset)
+                    .unwrap_or(found.pack.pack_end() as u64);
+                Ok(Outcome::Packed {
+                    pack_id: found.pack_id.to_intrinsic_pack_id(),
+                    size: entry_end - found.pack_offset,
+                    delta_base,
+                })
+            },
+            |lodb, id| Ok(lodb.stored_size(id)?.map(|size| Outcome::Loose { size })),
+        )
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    ops::Deref,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use git_features::progress::Progress;
+use git_hash::ObjectId;
+use git_object::{bstr::BString, TagRef, TagRefIter};
+
+use crate::{Find, Header};
+
+mod error {
+    /// Returned by [`Handle::peeled_tags()`][crate::store::Handle::peeled_tags()].
+    #[derive(thiserror::Error, Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error(transparent)]
+        InitializeODB(#[from] crate::store::load_index::Error),
+        #[error(transparent)]
+        LooseIter(#[from] crate::loose::iter::Error),
+        #[error(transparent)]
+        Find(#[from] crate::store::find::Error),
+        #[error(transparent)]
+        Decode(#[from] git_object::decode::Error),
+        #[error("Interrupted")]
+        Interrupted,
+    }
+}
+pub use error::Error;
+
+/// An annotated tag object along with the object it ultimately points to, as passed by
+/// [`Handle::peeled_tags()`][crate::store::Handle::peeled_tags()].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tag {
+    /// The id of the tag object.
+    pub id: ObjectId,
+    /// The name of the tag as stored in the tag object.
+    pub name: BString,
+    /// The id of the first object that isn't a tag when following the chain of tags starting at this one.
+    ///
+    /// If the chain ends in a missing object, this is the id of the missing object.
+    pub target: ObjectId,
+    /// The kind of object at `target`, or `None` if it doesn't exist in the object database.
+    pub target_kind: Option<git_object::Kind>,
+}
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Find all annotated tag objects of all linked databases (via alternates) and call `on_tag` with each one of them along with
+    /// the object they point to after peeling, following tags of tags until an object that isn't a tag is found.
+    /// Return the amount of tags seen.
+    ///
+    /// Tags are found in ascending order of their ids by checking the kind of each object, which is why `progress` is advanced
+    /// for each object seen, and `should_interrupt` is checked regularly to allow aborting the operation.
+    pub fn peeled_tags(
+        &self,
+        mut progress: impl Progress,
+        should_interrupt: &AtomicBool,
+        mut on_tag: impl FnMut(Tag),
+    ) -> Result<usize, Error> {
+        progress.init(None, git_features::progress::count("objects"));
+        let mut buf = Vec::new();
+        let mut num_tags = 0;
+        for id in self.iter_by_hash()? {
+            let id = id?;
+            progress.inc();
+            if should_interrupt.load(Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+            if !matches!(self.try_header(id)?, Some(header) if header.kind() == git_object::Kind::Tag) {
+                continue;
+            }
+            let (name, mut target) = match self.try_find(id, &mut buf)? {
+                Some(data) => {
+                    let tag = TagRef::from_bytes(data.data)?;
+                    (tag.name.to_owned(), tag.target())
+                }
+                // The object was removed in the mean time.
+                None => continue,
+            };
+            let target_kind = loop {
+                match self.try_find(target, &mut buf)? {
+                    Some(data) if data.kind == git_object::Kind::Tag => {
+                        target = TagRefIter::from_bytes(data.data).target_id()?;
+                    }
+                    Some(data) => break Some(data.kind),
+                    None => break None,
+                }
+            };
+            num_tags += 1;
+            on_tag(Tag {
+                id,
+                name,
+                target,
+                target_kind,
+            });
+        }
+        Ok(num_tags)
+    }
+}
@@ -0,0 +1,21 @@
+/// The error returned by a [`Transform`], which can be any error as it's entirely up to the implementation.
+pub type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// A way to transform the data of objects between their stored and their actual form, for instance to keep them encrypted at rest.
+///
+/// Objects are written to loose object databases in their stored form as produced by [`encode()`][Transform::encode()],
+/// and the data of objects that are found is turned back into their actual form with [`decode()`][Transform::decode()] after
+/// it was decompressed. As object ids are computed over the stored form, the same object has a different id than it would
+/// have without a transform, and both methods must be deterministic for objects to keep their id when written again.
+/// Packs are read through the transform as well, so they must contain objects in their stored form, like packs created from
+/// loose objects written with the same transform.
+///
+/// Only the data of found objects is transformed, the sizes returned when obtaining object headers are those of the stored form,
+/// and [`verify_object_hashes`][crate::store::init::Options::verify_object_hashes] verifies the stored form.
+pub trait Transform: std::fmt::Debug + Send + Sync {
+    /// Turn `data` of an object of `kind` from its stored form into its actual form, in place.
+    fn decode(&self, kind: git_object::Kind, data: &mut Vec<u8>) -> Result<(), Error>;
+    /// Turn `data` of an object of `kind` from its actual form into its stored form, in place, which must be the inverse of
+    /// [`decode()`][Transform::decode()].
+    fn encode(&self, kind: git_object::Kind, data: &mut Vec<u8>) -> Result<(), Error>;
+}
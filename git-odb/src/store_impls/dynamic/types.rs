@@ -18,7 +18,7 @@ pub(crate) type AtomicGeneration = AtomicU32;
 
 /// A way to indicate which pack indices we have seen already and which of them are loaded, along with an idea
 /// of whether stored `PackId`s are still usable.
-#[derive(Default, Copy, Clone)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct SlotIndexMarker {
     /// The generation the `loaded_until_index` belongs to. Indices of different generations are completely incompatible.
     /// This value changes once the internal representation is compacted, something that may happen only if there is no handle
@@ -27,6 +27,66 @@ pub struct SlotIndexMarker {
     /// A unique id identifying the index state as well as all loose databases we have last observed.
     /// If it changes in any way, the value is different.
     pub(crate) state_id: StateId,
+    /// Identifies the store the marker was obtained from.
+    pub(crate) store_id: usize,
+    /// The value of the index load clock of the store at the time the marker was obtained.
+    pub(crate) index_load_clock: usize,
+}
+
+impl SlotIndexMarker {
+    /// Classify how the state identified by this marker changed compared to the `previous` marker of the same store.
+    pub fn change_since(&self, previous: &SlotIndexMarker) -> MarkerChange {
+        if self.generation != previous.generation {
+            MarkerChange::Regenerated
+        } else if self.state_id != previous.state_id {
+            MarkerChange::Updated
+        } else {
+            MarkerChange::Unchanged
+        }
+    }
+}
+
+/// Describes how the state of an object database changed between two [markers][SlotIndexMarker].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MarkerChange {
+    /// Nothing changed.
+    Unchanged,
+    /// Indices were loaded or changes on disk were picked up, typically new packs or alternates, but all pack ids handed
+    /// out previously are still valid. Caches can be extended.
+    Updated,
+    /// The internal data structures were rebuilt, for instance after a repack, and previous pack ids are invalid.
+    /// Caches that use pack ids must be rebuilt entirely.
+    Regenerated,
+}
+
+/// Describes which indices were loaded between two [markers][SlotIndexMarker], as returned by [`Store::diff()`][crate::Store::diff()].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MarkerDiff {
+    /// The markers are from different stores, the old marker is more recent than the new one, or the store moved on to another
+    /// generation since the new marker was obtained, so it can't tell anymore what changed.
+    Incomparable,
+    /// The internal data structures were rebuilt in the meantime, and all pack ids obtained with the old marker are invalid.
+    Regenerated,
+    /// All pack ids obtained with the old marker are still valid, and the indices with the given ids were loaded since,
+    /// in the order they were loaded.
+    ///
+    /// Ids of indices that aren't multi-pack indices are the same as the [intrinsic pack ids][git_pack::data::Id] of their pack.
+    Compatible {
+        /// The ids of the indices loaded after the old marker was obtained, up to the new marker.
+        added: Vec<IndexId>,
+    },
+}
+
+/// The guard returned when loading a [`SlotIndexMarker`] through [`Access`][arc_swap::access::Access] on a [`Store`][crate::Store].
+#[derive(Debug, Copy, Clone)]
+pub struct SlotIndexMarkerGuard(pub(crate) SlotIndexMarker);
+
+impl std::ops::Deref for SlotIndexMarkerGuard {
+    type Target = SlotIndexMarker;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
 }
 
 /// A way to load and refer to a pack uniquely, namespaced by their indexing mechanism, aka multi-pack or not.
@@ -52,7 +112,7 @@ impl PackId {
     ///
     /// Bit 16 is a marker to tell us if it's a mult-pack or not, the ones before are the index file itself, the ones after
     /// are used to encode the pack index within the multi-pack.
-    pub(crate) fn to_intrinsic_pack_id(self) -> git_pack::data::Id {
+    pub fn to_intrinsic_pack_id(self) -> git_pack::data::Id {
         assert!(self.index < (1 << 15), "There shouldn't be more than 2^15 indices");
         match self.multipack_index {
             None => self.index as git_pack::data::Id,
@@ -66,7 +126,9 @@ impl PackId {
         }
     }
 
-    pub(crate) fn from_intrinsic_pack_id(pack_id: git_pack::data::Id) -> Self {
+    /// Create an instance from an intrinsic `pack_id`, like the one in [`Location::pack_id`][git_pack::data::entry::Location::pack_id],
+    /// the inverse of [`to_intrinsic_pack_id()`][PackId::to_intrinsic_pack_id()].
+    pub fn from_intrinsic_pack_id(pack_id: git_pack::data::Id) -> Self {
         if pack_id & (1 << 15) == 0 {
             PackId {
                 index: (pack_id & 0x7fff) as IndexId,
@@ -82,6 +144,9 @@ impl PackId {
 }
 
 /// An index that changes only if the packs directory changes and its contents is re-read.
+///
+/// It's a snapshot of the indices and loose object databases known to a [`Store`][crate::Store], obtained through
+/// [`Access`][arc_swap::access::Access] on the store, and doesn't change while it's held.
 #[derive(Default)]
 pub struct SlotMapIndex {
     /// The index into the slot map at which we expect an index or pack file. Neither of these might be loaded yet.
@@ -104,6 +169,9 @@ pub struct SlotMapIndex {
     /// The amount of indices that are currently being loaded.
     /// Zero if no loading operation is currently happening, or more otherwise.
     pub(crate) num_indices_currently_being_loaded: Arc<AtomicU16>,
+    /// Incremented each time an index is loaded into a slot.
+    /// Shared across all SlotMapIndex instances of a store so it never goes backwards, which is why its address identifies the store.
+    pub(crate) index_load_clock: Arc<AtomicUsize>,
 }
 
 impl SlotMapIndex {
@@ -114,25 +182,65 @@ impl SlotMapIndex {
         hash::crc32_update(hash, &self.loaded_indices.load(Ordering::SeqCst).to_be_bytes())
     }
 
-    pub(crate) fn marker(self: &Arc<SlotMapIndex>) -> SlotIndexMarker {
+    /// Return a marker identifying this snapshot along with the amount of indices loaded so far, like
+    /// [`Store::marker()`][crate::Store::marker()] did at the time it was current.
+    pub fn marker(self: &Arc<SlotMapIndex>) -> SlotIndexMarker {
         SlotIndexMarker {
             generation: self.generation,
             state_id: self.state_id(),
+            store_id: Arc::as_ptr(&self.index_load_clock) as usize,
+            index_load_clock: self.index_load_clock.load(Ordering::SeqCst),
         }
     }
 
-    /// Returns true if we already know at least one loose object db, a sign of being initialized
+    /// The generation of the store this snapshot belongs to, which changes whenever pack ids are invalidated.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// The amount of indices and multi-pack indices in this snapshot, whether they are loaded or not.
+    pub fn num_indices(&self) -> usize {
+        self.slot_indices.len()
+    }
+
+    /// The amount of loose object databases in this snapshot, which is more than one if alternates are used.
+    pub fn num_loose_dbs(&self) -> usize {
+        self.loose_dbs.len()
+    }
+
+    /// Returns true if we already know at least one loose object db or index, a sign of being initialized
     pub(crate) fn is_initialized(&self) -> bool {
-        !self.loose_dbs.is_empty()
+        !self.loose_dbs.is_empty() || !self.slot_indices.is_empty()
+    }
+
+    /// Returns true if there are indices left to load, or if some are still being loaded.
+    pub(crate) fn has_pending_loads(&self) -> bool {
+        self.next_index_to_load.load(Ordering::SeqCst) < self.slot_indices.len()
+            || self.num_indices_currently_being_loaded.load(Ordering::SeqCst) != 0
     }
 }
 
+/// Tracks refreshes from disk so that threads wanting to refresh while another one does can wait for it instead.
+#[derive(Default)]
+pub(crate) struct RefreshState {
+    /// If true, a thread is currently consolidating our state with the disk state.
+    pub in_progress: bool,
+    /// The amount of refreshes that completed so far, allowing waiting threads to learn that the refresh they waited for is done.
+    pub num_completed: usize,
+    /// Whether the last completed refresh changed our state, or `None` if it failed.
+    pub last_changed: Option<bool>,
+    /// The time at which the last refresh completed, successfully or not.
+    pub last_completed_at: Option<std::time::Instant>,
+}
+
 #[derive(Clone)]
 pub(crate) struct OnDiskFile<T: Clone> {
     /// The last known path of the file
     path: Arc<PathBuf>,
     /// the time the file was last modified
     mtime: SystemTime,
+    /// If true, a `.keep` file exists next to the file, which is only checked for packs. Kept packs are never unloaded.
+    keep: bool,
     state: OnDiskFileState<T>,
 }
 
@@ -143,21 +251,108 @@ pub(crate) enum OnDiskFileState<T: Clone> {
     Loaded(T),
     /// The file was loaded, but appeared to be missing on disk after reconciling our state with what's on disk.
     /// As there were handles that required pack-id stability we had to keep the item to allow finding it on later
-    /// lookups.
+    /// lookups. It is dropped on the first refresh after no handle requires pack-id stability anymore.
     Garbage(T),
     /// File is missing on disk and could not be loaded when we tried or turned missing after reconciling our state.
     Missing,
+    /// The file exists but was found to be unusable when loading it, for instance as it didn't belong to its index or
+    /// was malformed, with the description of the problem.
+    /// It won't be loaded again unless it's put back after reconciling our state with what's on disk.
+    Corrupt(Arc<str>),
+    /// The file was loaded, but turned out to be corrupt when reading objects from it, with the description of the problem and
+    /// its modification time at that moment, if it could be obtained.
+    /// It isn't used for lookups anymore until a refresh sees that its modification time changed.
+    Poisoned {
+        reason: Arc<str>,
+        mtime: Option<SystemTime>,
+    },
 }
 
 impl<T: Clone> OnDiskFile<T> {
     pub fn path(&self) -> &Path {
         &self.path
     }
+    /// The time the file was last modified when we last saw it on disk.
+    pub fn mtime(&self) -> SystemTime {
+        self.mtime
+    }
+    /// Drop our memory map of the file so it will be loaded again when needed, if it is loaded and reachable.
+    pub fn unload(&mut self) {
+        if let OnDiskFileState::Loaded(_) = self.state {
+            self.state = OnDiskFileState::Unloaded;
+        }
+    }
+    /// Use `value` as if it was loaded from our path unless something is loaded already, for files whose content can also be
+    /// computed if they don't exist on disk.
+    pub fn set_computed(&mut self, value: T) {
+        if !self.is_loaded() {
+            self.state = OnDiskFileState::Loaded(value);
+        }
+    }
+    /// Return true if a `.keep` file was seen next to the file when we last looked, which prevents it from being unloaded.
+    pub fn is_kept(&self) -> bool {
+        self.keep
+    }
     /// Return true if we hold a memory map of the file already.
     pub fn is_loaded(&self) -> bool {
         matches!(self.state, OnDiskFileState::Loaded(_) | OnDiskFileState::Garbage(_))
     }
 
+    /// Return the description of why the file couldn't be used if it was found to be corrupt when loading or reading it.
+    pub fn corruption(&self) -> Option<&str> {
+        match &self.state {
+            OnDiskFileState::Corrupt(reason) | OnDiskFileState::Poisoned { reason, .. } => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// Return true if the file was found to be corrupt when reading from it.
+    pub fn is_poisoned(&self) -> bool {
+        matches!(self.state, OnDiskFileState::Poisoned { .. })
+    }
+
+    /// Drop our memory map of the file and don't use it anymore as reading from it failed due to `reason`, until it changes on disk.
+    pub fn poison(&mut self, reason: Arc<str>) {
+        let mtime = std::fs::metadata(self.path.as_path()).and_then(|md| md.modified()).ok();
+        self.state = OnDiskFileState::Poisoned { reason, mtime };
+    }
+
+    /// Return true if the file is poisoned, but its modification time changed since, which makes it worth trying again.
+    pub fn poisoned_file_changed(&self) -> bool {
+        match &self.state {
+            OnDiskFileState::Poisoned { mtime, .. } => {
+                std::fs::metadata(self.path.as_path()).and_then(|md| md.modified()).ok() != *mtime
+            }
+            _ => false,
+        }
+    }
+
+    /// Return our path and state for use in a [`DebugDump`][crate::store::structure::DebugDump].
+    pub fn summary(&self) -> crate::store::structure::FileSummary {
+        use crate::store::structure::FileState;
+        crate::store::structure::FileSummary {
+            path: self.path.as_ref().clone(),
+            state: match &self.state {
+                OnDiskFileState::Unloaded => FileState::Unloaded,
+                OnDiskFileState::Loaded(_) => FileState::Loaded,
+                OnDiskFileState::Garbage(_) => FileState::Garbage,
+                OnDiskFileState::Missing => FileState::Missing,
+                OnDiskFileState::Corrupt(reason) => FileState::Corrupt(reason.to_string()),
+                OnDiskFileState::Poisoned { reason, .. } => FileState::Poisoned(reason.to_string()),
+            },
+        }
+    }
+
+    /// Return true if the file didn't exist when trying to load it.
+    pub fn is_missing(&self) -> bool {
+        matches!(self.state, OnDiskFileState::Missing)
+    }
+
+    /// Return true if the file was loaded, but is only kept to keep pack ids stable as it was removed from disk.
+    pub fn is_garbage(&self) -> bool {
+        matches!(self.state, OnDiskFileState::Garbage(_))
+    }
+
     /// Return true if we are to be collected as garbage
     pub fn is_disposable(&self) -> bool {
         matches!(self.state, OnDiskFileState::Garbage(_) | OnDiskFileState::Missing)
@@ -167,27 +362,33 @@ impl<T: Clone> OnDiskFile<T> {
     pub(crate) fn load_strict(&mut self, load: impl FnOnce(&Path) -> std::io::Result<T>) -> std::io::Result<()> {
         use OnDiskFileState::*;
         match self.state {
-            Unloaded | Missing => match load(&self.path) {
+            Unloaded | Missing | Corrupt(_) => match load(&self.path) {
                 Ok(v) => {
                     self.state = Loaded(v);
                     Ok(())
                 }
+                Err(err) if err.kind() == std::io::ErrorKind::InvalidData => {
+                    self.state = Corrupt(err.to_string().into());
+                    Err(err)
+                }
                 Err(err) => {
-                    // TODO: Should be provide more information? We don't even know what exactly failed right now, degenerating information.
                     self.state = Missing;
                     Err(err)
                 }
             },
-            Loaded(_) | Garbage(_) => Ok(()),
+            Loaded(_) | Garbage(_) | Poisoned { .. } => Ok(()),
         }
     }
     /// If the file is missing, we don't consider this failure but instead return Ok(None) to allow recovery.
     /// when we know that loading is necessary. This also works around borrow check, which is a nice coincidence.
+    ///
+    /// If `load` fails with [`InvalidData`][std::io::ErrorKind::InvalidData], the file is considered corrupt and won't be loaded again.
     pub fn load_with_recovery(&mut self, load: impl FnOnce(&Path) -> std::io::Result<T>) -> std::io::Result<Option<T>> {
         use OnDiskFileState::*;
         match &mut self.state {
             Loaded(v) | Garbage(v) => Ok(Some(v.clone())),
-            Missing => Ok(None),
+            Missing | Poisoned { .. } => Ok(None),
+            Corrupt(reason) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, reason.to_string())),
             Unloaded => match load(&self.path) {
                 Ok(v) => {
                     self.state = OnDiskFileState::Loaded(v.clone());
@@ -197,6 +398,10 @@ impl<T: Clone> OnDiskFile<T> {
                     self.state = OnDiskFileState::Missing;
                     Ok(None)
                 }
+                Err(err) if err.kind() == std::io::ErrorKind::InvalidData => {
+                    self.state = OnDiskFileState::Corrupt(err.to_string().into());
+                    Err(err)
+                }
                 Err(err) => Err(err),
             },
         }
@@ -206,15 +411,17 @@ impl<T: Clone> OnDiskFile<T> {
         use OnDiskFileState::*;
         match &self.state {
             Loaded(v) | Garbage(v) => Some(v),
-            Unloaded | Missing => None,
+            Unloaded | Missing | Corrupt(_) | Poisoned { .. } => None,
         }
     }
 
     pub fn put_back(&mut self) {
         match std::mem::replace(&mut self.state, OnDiskFileState::Missing) {
             OnDiskFileState::Garbage(v) => self.state = OnDiskFileState::Loaded(v),
-            OnDiskFileState::Missing => self.state = OnDiskFileState::Unloaded,
-            other @ OnDiskFileState::Loaded(_) | other @ OnDiskFileState::Unloaded => self.state = other,
+            OnDiskFileState::Missing | OnDiskFileState::Corrupt(_) => self.state = OnDiskFileState::Unloaded,
+            other @ OnDiskFileState::Loaded(_)
+            | other @ OnDiskFileState::Unloaded
+            | other @ OnDiskFileState::Poisoned { .. } => self.state = other,
         }
     }
 
@@ -223,15 +430,43 @@ impl<T: Clone> OnDiskFile<T> {
             OnDiskFileState::Loaded(v) => self.state = OnDiskFileState::Garbage(v),
             other @ OnDiskFileState::Garbage(_)
             | other @ OnDiskFileState::Unloaded
-            | other @ OnDiskFileState::Missing => self.state = other,
+            | other @ OnDiskFileState::Missing
+            | other @ OnDiskFileState::Corrupt(_)
+            | other @ OnDiskFileState::Poisoned { .. } => self.state = other,
         }
     }
 }
 
+impl<T> OnDiskFile<Arc<T>> {
+    /// Return true if we hold a memory map of the file that is reachable and that nobody else refers to.
+    ///
+    /// Note that this must be checked before copying the slot, as copies hold references as well.
+    pub fn is_unused(&self) -> bool {
+        matches!(&self.state, OnDiskFileState::Loaded(v) if Arc::strong_count(v) == 1)
+    }
+}
+
+/// Return true if git placed a `.keep` file next to the pack at `pack_path` to prevent it from being touched.
+fn has_keep_file(pack_path: &Path) -> bool {
+    pack_path.with_extension("keep").is_file()
+}
+
+/// Create an error for a file at `path` of the given `kind` that exists but couldn't be parsed due to `err`, for use in loaders.
+///
+/// Its kind is [`InvalidData`][std::io::ErrorKind::InvalidData] to have the file marked as corrupt.
+pub(crate) fn malformed(kind: &str, path: &Path, err: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("The {} at '{}' is malformed: {}", kind, path.display(), err),
+    )
+}
+
 #[derive(Clone)]
 pub(crate) struct IndexFileBundle {
     pub index: OnDiskFile<Arc<git_pack::index::File>>,
     pub data: OnDiskFile<Arc<git_pack::data::File>>,
+    /// The reverse index, loaded from its `.rev` file only when needed, or computed from the index if there is none.
+    pub reverse: OnDiskFile<Arc<git_pack::index::reverse::File>>,
 }
 
 #[derive(Clone)]
@@ -248,6 +483,14 @@ pub(crate) enum IndexAndPacks {
 }
 
 impl IndexAndPacks {
+    /// The amount of packs this index refers to.
+    pub(crate) fn num_packs(&self) -> usize {
+        match self {
+            IndexAndPacks::Index(_) => 1,
+            IndexAndPacks::MultiIndex(bundle) => bundle.data.len(),
+        }
+    }
+
     pub(crate) fn index_path(&self) -> &Path {
         match self {
             IndexAndPacks::Index(index) => &index.index.path,
@@ -294,6 +537,75 @@ impl IndexAndPacks {
         }
     }
 
+    /// Return true if the presence of `.keep` files next to our packs changed since we last looked.
+    pub(crate) fn keep_files_changed(&self) -> bool {
+        match self {
+            Self::Index(bundle) => bundle.data.keep != has_keep_file(&bundle.data.path),
+            Self::MultiIndex(bundle) => bundle.data.iter().any(|pack| pack.keep != has_keep_file(&pack.path)),
+        }
+    }
+
+    /// Return true if one of our packs is poisoned, but changed on disk since.
+    pub(crate) fn poisoned_packs_changed(&self) -> bool {
+        match self {
+            Self::Index(bundle) => bundle.data.poisoned_file_changed(),
+            Self::MultiIndex(bundle) => bundle.data.iter().any(|pack| pack.poisoned_file_changed()),
+        }
+    }
+
+    /// Return true if this is an index whose pack didn't exist when trying to load it, which makes the whole bundle unusable.
+    pub(crate) fn pack_is_missing(&self) -> bool {
+        matches!(self, Self::Index(bundle) if bundle.data.is_missing())
+    }
+
+    /// Drop the memory maps of the index, its reverse index and all packs so they are loaded again when needed.
+    pub(crate) fn unload_all(&mut self) {
+        match self {
+            Self::Index(bundle) => {
+                bundle.index.unload();
+                bundle.reverse.unload();
+                bundle.data.unload();
+            }
+            Self::MultiIndex(bundle) => {
+                bundle.multi_index.unload();
+                bundle.data.iter_mut().for_each(OnDiskFile::unload);
+            }
+        }
+    }
+
+    /// Allow poisoned packs which changed on disk since to be loaded again.
+    pub(crate) fn unpoison_changed_packs(&mut self) {
+        let packs = match self {
+            Self::Index(bundle) => std::slice::from_mut(&mut bundle.data),
+            Self::MultiIndex(bundle) => bundle.data.as_mut_slice(),
+        };
+        for pack in packs.iter_mut().filter(|pack| pack.poisoned_file_changed()) {
+            pack.state = OnDiskFileState::Unloaded;
+        }
+    }
+
+    /// Record whether or not `.keep` files exist next to our packs.
+    pub(crate) fn update_keep_files(&mut self) {
+        match self {
+            Self::Index(bundle) => bundle.data.keep = has_keep_file(&bundle.data.path),
+            Self::MultiIndex(bundle) => {
+                for pack in &mut bundle.data {
+                    pack.keep = has_keep_file(&pack.path);
+                }
+            }
+        }
+    }
+
+    /// The amount of files of this bundle that are currently loaded, counting the index or multi-pack index and all packs.
+    pub(crate) fn num_open_files(&self) -> usize {
+        match self {
+            Self::Index(bundle) => usize::from(bundle.index.is_loaded()) + usize::from(bundle.data.is_loaded()),
+            Self::MultiIndex(bundle) => {
+                usize::from(bundle.multi_index.is_loaded()) + bundle.data.iter().filter(|pack| pack.is_loaded()).count()
+            }
+        }
+    }
+
     pub(crate) fn index_is_loaded(&self) -> bool {
         match self {
             Self::Index(bundle) => bundle.index.is_loaded(),
@@ -310,24 +622,24 @@ impl IndexAndPacks {
         }
     }
 
-    pub(crate) fn load_index(&mut self, object_hash: git_hash::Kind) -> std::io::Result<()> {
+    pub(crate) fn load_index(
+        &mut self,
+        backend: &dyn crate::store::backend::Backend,
+        object_hash: git_hash::Kind,
+    ) -> std::io::Result<()> {
         match self {
             IndexAndPacks::Index(bundle) => bundle.index.load_strict(|path| {
-                git_pack::index::File::at(path, object_hash)
-                    .map(Arc::new)
-                    .map_err(|err| match err {
-                        git_pack::index::init::Error::Io { source, .. } => source,
-                        err => std::io::Error::new(std::io::ErrorKind::Other, err),
-                    })
+                backend.open_index_shared(path, object_hash).map_err(|err| match err {
+                    git_pack::index::init::Error::Io { source, .. } => source,
+                    err => malformed("pack index", path, err),
+                })
             }),
             IndexAndPacks::MultiIndex(bundle) => {
                 bundle.multi_index.load_strict(|path| {
-                    git_pack::multi_index::File::at(path)
-                        .map(Arc::new)
-                        .map_err(|err| match err {
-                            git_pack::multi_index::init::Error::Io { source, .. } => source,
-                            err => std::io::Error::new(std::io::ErrorKind::Other, err),
-                        })
+                    backend.open_multi_index_shared(path).map_err(|err| match err {
+                        git_pack::multi_index::init::Error::Io { source, .. } => source,
+                        err => malformed("multi-pack index", path, err),
+                    })
                 })?;
                 if let Some(multi_index) = bundle.multi_index.loaded() {
                     bundle.data = Self::index_names_to_pack_paths(multi_index);
@@ -339,17 +651,26 @@ impl IndexAndPacks {
 
     pub(crate) fn new_single(index_path: PathBuf, mtime: SystemTime) -> Self {
         let data_path = index_path.with_extension("pack");
+        let reverse_path = index_path.with_extension("rev");
         Self::Index(IndexFileBundle {
             index: OnDiskFile {
                 path: index_path.into(),
                 state: OnDiskFileState::Unloaded,
+                keep: false,
                 mtime,
             },
             data: OnDiskFile {
+                keep: has_keep_file(&data_path),
                 path: data_path.into(),
                 state: OnDiskFileState::Unloaded,
                 mtime,
             },
+            reverse: OnDiskFile {
+                path: reverse_path.into(),
+                state: OnDiskFileState::Unloaded,
+                keep: false,
+                mtime,
+            },
         })
     }
 
@@ -359,6 +680,7 @@ impl IndexAndPacks {
             multi_index: OnDiskFile {
                 path: Arc::new(multi_index.path().to_owned()),
                 state: OnDiskFileState::Loaded(multi_index),
+                keep: false,
                 mtime,
             },
             data,
@@ -372,10 +694,14 @@ impl IndexAndPacks {
         let data = multi_index
             .index_names()
             .iter()
-            .map(|idx| OnDiskFile {
-                path: parent_dir.join(idx.with_extension("pack")).into(),
-                state: OnDiskFileState::Unloaded,
-                mtime: SystemTime::UNIX_EPOCH,
+            .map(|idx| {
+                let path = parent_dir.join(idx.with_extension("pack"));
+                OnDiskFile {
+                    keep: has_keep_file(&path),
+                    path: path.into(),
+                    state: OnDiskFileState::Unloaded,
+                    mtime: SystemTime::UNIX_EPOCH,
+                }
             })
             .collect();
         data
@@ -390,12 +716,76 @@ pub(crate) struct MutableIndexAndPack {
     /// that has different content under the same id.
     /// Must only be changed when the write lock is held.
     pub(crate) generation: AtomicGeneration,
+    /// The value of the store's pack use clock when a pack of this slot was last handed out, to unload packs of the
+    /// least recently used slots first.
+    pub(crate) last_pack_use: AtomicUsize,
+    /// The value of the index load clock of the store when the index of this slot was loaded, or 0 if it wasn't loaded since
+    /// the slot was set.
+    pub(crate) index_loaded_at: AtomicUsize,
+    /// The amount of objects found in each pack of this slot, indexed by the pack's position in a multi-pack index, or with a
+    /// single counter for a single index. Replaced with fresh counters whenever the slot is set to another index.
+    pub(crate) pack_hits: ArcSwap<Vec<AtomicUsize>>,
+}
+
+/// The maximum amount of files a store keeps open, shared with the threads loading indices in the background.
+pub(crate) struct OpenFilesBudget {
+    /// The maximum amount of index, multi-pack index and pack files to keep open, which is at least 1.
+    pub max: usize,
+    /// Held while making room for a file, so concurrent loads can't claim the same room.
+    pub lock: parking_lot::Mutex<()>,
+    /// Notified whenever a reservation is given back, to let those waiting for room try again.
+    pub released: parking_lot::Condvar,
+    /// The amount of files room was made for, but which may not be loaded yet.
+    pub num_reserved: AtomicUsize,
+}
+
+impl OpenFilesBudget {
+    /// Create a budget for `max` files, with `0` being treated as `1`.
+    pub fn new(max: usize) -> Self {
+        OpenFilesBudget {
+            max: max.max(1),
+            lock: Default::default(),
+            released: Default::default(),
+            num_reserved: Default::default(),
+        }
+    }
+
+    /// Claim room for one more file, which is given back once the returned reservation is dropped.
+    pub fn reserve(&self) -> Reservation<'_> {
+        self.num_reserved.fetch_add(1, Ordering::SeqCst);
+        Reservation(self)
+    }
+}
+
+/// Room for a file within an [`OpenFilesBudget`], to be held until the file was loaded.
+pub(crate) struct Reservation<'a>(&'a OpenFilesBudget);
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.0.num_reserved.fetch_sub(1, Ordering::SeqCst);
+        let _lock = self.0.lock.lock();
+        self.0.released.notify_all();
+    }
+}
+
+/// Usage statistics of a single pack, as returned by [`Store::pack_statistics()`][crate::Store::pack_statistics()].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackStats {
+    /// The amount of times an object was found in this pack or confirmed to exist in it, since its index was assigned to its slot.
+    pub hits: usize,
+    /// Whether the pack is currently memory-mapped.
+    pub loaded: bool,
+    /// The size of the pack data file in bytes, or `None` if it couldn't be obtained, for instance as it was deleted.
+    pub size: Option<u64>,
 }
 
 /// A snapshot about resource usage.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 pub struct Metrics {
+    /// The [label][crate::Store::label()] of the store, to tell apart the metrics of multiple stores.
+    pub label: Option<String>,
     /// The total amount of handles which can be used to access object information.
     pub num_handles: usize,
     /// The amount of refreshes performed to reconcile with the ODB state on disk.
@@ -406,6 +796,9 @@ pub struct Metrics {
     pub known_reachable_indices: usize,
     /// The amount of packs which are open in memory and will be returned to handles.
     pub open_reachable_packs: usize,
+    /// The amount of index, multi-pack index and pack files the store holds open, including unreachable ones, which is what
+    /// counts towards [`max_open_files`][crate::store::init::Options::max_open_files].
+    pub open_files: usize,
     /// The amount of packs that are reachable and will be returned to handles. They aren't open yet.
     pub known_packs: usize,
     /// The amount of slots which are empty.
@@ -420,12 +813,84 @@ pub struct Metrics {
     pub unreachable_indices: usize,
     /// Equivalent to `unreachable_indices`, but for mapped packed data files
     pub unreachable_packs: usize,
+    /// The amount of reachable packs that turned out to be corrupt when reading from them and are skipped during lookups, which
+    /// only happens if [`quarantine_corrupt_packs`][crate::store::init::Options::quarantine_corrupt_packs] is set.
+    pub poisoned_packs: usize,
     /// The amount of loose object databases currently available for object retrieval.
     ///
     /// There may be more than one if 'alternates' are used.
     pub loose_dbs: usize,
 }
 
+/// Cumulative counts of lookups performed by all handles of a store, as returned by
+/// [`Store::lookup_counters()`][crate::Store::lookup_counters()].
+///
+/// Objects served from the object cache of a [`Cache`][crate::Cache] don't reach the handle and aren't counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct LookupCounters {
+    /// The amount of times an object was looked up to obtain its data.
+    pub finds: usize,
+    /// The amount of times the existence of an object was checked.
+    pub contains: usize,
+    /// The amount of finds and existence checks for objects that didn't exist.
+    pub misses: usize,
+    /// The total size of the decompressed data of all objects that were found.
+    pub bytes_decompressed: usize,
+}
+
+/// The atomic counterpart of [`LookupCounters`] to be updated from any thread.
+#[derive(Default)]
+pub(crate) struct AtomicLookupCounters {
+    pub finds: AtomicUsize,
+    pub contains: AtomicUsize,
+    pub misses: AtomicUsize,
+    pub bytes_decompressed: AtomicUsize,
+}
+
+/// An approximation of the memory used by the store, in bytes, as returned by [`Store::memory_usage()`][crate::Store::memory_usage()].
+///
+/// Mapped files are accounted for with their full length, even though only the pages that were accessed are actually resident.
+#[derive(Default, PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone, Copy)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryUsage {
+    /// The bytes allocated for the slot map itself, which has a fixed size.
+    pub slot_map: usize,
+    /// The bytes used by the shared state of all slots, including reference counts and the bookkeeping of multi-pack indices.
+    pub slots: usize,
+    /// The bytes of all mapped index and multi-pack index files, reachable or not.
+    pub mapped_indices: usize,
+    /// The bytes of all mapped pack data files, reachable or not.
+    pub mapped_packs: usize,
+    /// The bytes used by the currently available loose object databases.
+    pub loose_dbs: usize,
+}
+
+impl MemoryUsage {
+    /// The sum of all bytes we know about.
+    pub fn total(&self) -> usize {
+        self.slot_map + self.slots + self.mapped_indices + self.mapped_packs + self.loose_dbs
+    }
+}
+
+/// The bytes of all index and pack files known to the store, as returned by
+/// [`Store::full_load_address_space()`][crate::Store::full_load_address_space()].
+#[derive(Default, PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone, Copy)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct AddressSpace {
+    /// The bytes of all pack index and multi-pack index files.
+    pub indices: u64,
+    /// The bytes of all pack data files.
+    pub packs: u64,
+}
+
+impl AddressSpace {
+    /// The sum of all bytes.
+    pub fn total(&self) -> u64 {
+        self.indices + self.packs
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
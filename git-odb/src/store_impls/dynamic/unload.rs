@@ -0,0 +1,100 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use crate::store::types::{IndexAndPacks, SlotMapIndex};
+
+/// Returned by [`Store::unload_unused()`][crate::Store::unload_unused()].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Outcome {
+    /// The amount of packs whose memory map was released.
+    pub unloaded_packs: usize,
+    /// The amount of indices and multi-pack indices whose memory map was released.
+    pub unloaded_indices: usize,
+}
+
+impl super::Store {
+    /// Release the memory maps of all loaded packs and indices that no handle currently refers to, so a process that touched many
+    /// packs doesn't keep all of them mapped for as long as the store exists. They are loaded again once they are needed.
+    ///
+    /// Packs and indices kept only to keep pack ids stable after they were removed from disk remain untouched, as well as packs with
+    /// a `.keep` file, and indices are only unloaded if no handle requires stable pack ids. Files are unloaded with the lock of their
+    /// slot held, so this can't race with them being loaded, and files that are picked up by a handle while we unload them stay
+    /// mapped until the handle drops them.
+    pub fn unload_unused(&self) -> Outcome {
+        let _write = self.write.lock();
+        let may_unload_indices = self.num_handles_stable.load(Ordering::SeqCst) == 0;
+        let index = self.index.load();
+        let mut out = Outcome::default();
+        for slot in index.slot_indices.iter().map(|idx| &self.files[*idx]) {
+            let _lock = slot.write.lock();
+            let mut files = slot.files.load_full();
+            // the slot holds the only reference to unused files, which is why we have to check before copying it.
+            let (unload_index, unload_packs): (_, Vec<_>) = match Option::as_ref(&files) {
+                Some(IndexAndPacks::Index(bundle)) => (
+                    bundle.index.is_unused(),
+                    vec![bundle.data.is_unused() && !bundle.data.is_kept()],
+                ),
+                Some(IndexAndPacks::MultiIndex(bundle)) => (
+                    bundle.multi_index.is_unused(),
+                    bundle
+                        .data
+                        .iter()
+                        .map(|pack| pack.is_unused() && !pack.is_kept())
+                        .collect(),
+                ),
+                None => continue,
+            };
+            let unload_index = unload_index && may_unload_indices;
+            if !unload_index && !unload_packs.contains(&true) {
+                continue;
+            }
+            match Option::as_mut(Arc::make_mut(&mut files)).expect("checked to be set") {
+                IndexAndPacks::Index(bundle) => {
+                    if unload_index {
+                        bundle.index.unload();
+                    }
+                    if unload_packs[0] {
+                        bundle.data.unload();
+                    }
+                }
+                IndexAndPacks::MultiIndex(bundle) => {
+                    if unload_index {
+                        bundle.multi_index.unload();
+                    }
+                    for (pack, _) in bundle.data.iter_mut().zip(&unload_packs).filter(|(_, unload)| **unload) {
+                        pack.unload();
+                    }
+                }
+            }
+            out.unloaded_indices += usize::from(unload_index);
+            out.unloaded_packs += unload_packs.into_iter().filter(|unload| *unload).count();
+            slot.files.store(files);
+        }
+
+        if out.unloaded_indices != 0 {
+            // Handles only load indices past the ones that were loaded already, so they have to start over to find those we unloaded.
+            // Slots and their generation don't change, and with them all pack ids remain valid.
+            // Index loader threads aren't asked to load them again, as that would undo what we just did.
+            let new_index = Arc::new(SlotMapIndex {
+                slot_indices: index.slot_indices.clone(),
+                loose_dbs: Arc::clone(&index.loose_dbs),
+                generation: index.generation,
+                next_index_to_load: Default::default(),
+                loaded_indices: Arc::new(
+                    index
+                        .slot_indices
+                        .iter()
+                        .filter(|idx| {
+                            Option::as_ref(&self.files[**idx].files.load()).map_or(false, |f| f.index_is_loaded())
+                        })
+                        .count()
+                        .into(),
+                ),
+                num_indices_currently_being_loaded: Default::default(),
+                index_load_clock: Arc::clone(&index.index_load_clock),
+            });
+            self.index.store(new_index);
+        }
+        out
+    }
+}
@@ -1,6 +1,9 @@
 use std::{
     ops::Deref,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::Instant,
 };
 
@@ -83,7 +86,416 @@ pub mod integrity {
     }
 }
 
+///
+pub mod multi_index {
+    use std::path::PathBuf;
+
+    use git_hash::ObjectId;
+
+    use crate::pack;
+
+    /// Returned by [`Store::verify_multi_indices()`][crate::Store::verify_multi_indices()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error(transparent)]
+        IndexOpen(#[from] pack::index::init::Error),
+        #[error(transparent)]
+        MultiIndexOpen(#[from] pack::multi_index::init::Error),
+        #[error(transparent)]
+        PackOpen(#[from] pack::data::init::Error),
+        #[error(transparent)]
+        InitializeODB(#[from] crate::store::load_index::Error),
+        #[error("The disk on state changed while performing the operation, and we observed the change.")]
+        NeedsRetryDueToChangeOnDisk,
+        #[error("Interrupted")]
+        Interrupted,
+        #[error("Could not write a new multi-pack index")]
+        Rebuild(#[from] pack::multi_index::write::Error),
+        #[error("Could not replace a multi-pack index")]
+        Io(#[from] std::io::Error),
+        #[error("Could not move the new multi-pack index into place")]
+        Persist(#[from] tempfile::PersistError),
+    }
+
+    /// Options for use in [`Store::verify_multi_indices_opts()`][crate::Store::verify_multi_indices_opts()].
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct Options {
+        /// If true, each multi-pack index with problems is replaced by one written from the pack indices currently in its directory,
+        /// and the store is refreshed to pick it up. This writes files, which is why problems are only reported by default.
+        ///
+        /// Pack indices whose pack is missing or doesn't belong to them are left out of the new multi-pack index, which is removed
+        /// if no usable pack index is left.
+        pub rebuild_inconsistent: bool,
+    }
+
+    /// An inconsistency between a multi-pack index and its member packs.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub enum Problem {
+        /// The pack index of a member pack doesn't exist.
+        MissingIndex {
+            /// The path at which the index was expected.
+            path: PathBuf,
+        },
+        /// The data file of a member pack doesn't exist.
+        MissingPack {
+            /// The path at which the pack was expected.
+            path: PathBuf,
+        },
+        /// The pack data file doesn't belong to the index of the member pack, as it was replaced.
+        PackMismatch {
+            /// The path to the pack data file.
+            path: PathBuf,
+            /// The checksum of the pack as recorded in its index.
+            expected: ObjectId,
+            /// The actual checksum of the pack.
+            actual: ObjectId,
+        },
+        /// An object refers to a member pack that the multi-pack index doesn't list.
+        UnknownPack {
+            /// The id of the object.
+            id: ObjectId,
+            /// The index of the pack the object refers to.
+            pack_index: pack::multi_index::PackIndex,
+        },
+        /// An object isn't contained in the member pack it is said to be stored in.
+        MissingObject {
+            /// The id of the object.
+            id: ObjectId,
+            /// The path to the index of the member pack.
+            index: PathBuf,
+        },
+        /// An object is stored in its member pack, but at another offset than the one the multi-pack index records.
+        OffsetMismatch {
+            /// The id of the object.
+            id: ObjectId,
+            /// The path to the index of the member pack.
+            index: PathBuf,
+            /// The offset recorded in the index of the member pack.
+            expected: pack::data::Offset,
+            /// The offset recorded in the multi-pack index.
+            actual: pack::data::Offset,
+        },
+    }
+
+    /// The verification result of a single multi-pack index.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct MultiIndex {
+        /// The path to the multi-pack index.
+        pub path: PathBuf,
+        /// All problems found, in the order in which they were found, or an empty list if the multi-pack index is consistent.
+        pub problems: Vec<Problem>,
+        /// True if the multi-pack index was rebuilt as it had problems, see [`Options::rebuild_inconsistent`].
+        pub rebuilt: bool,
+    }
+
+    /// Returned by [`Store::verify_multi_indices()`][crate::Store::verify_multi_indices()].
+    #[derive(Debug, Default, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Outcome {
+        /// One entry for each multi-pack index known to the store.
+        pub multi_indices: Vec<MultiIndex>,
+    }
+
+    impl Outcome {
+        /// Return true if no problem was found in any multi-pack index.
+        pub fn is_consistent(&self) -> bool {
+            self.multi_indices.iter().all(|m| m.problems.is_empty())
+        }
+    }
+}
+
+///
+pub mod index {
+    use std::path::PathBuf;
+
+    use git_hash::ObjectId;
+
+    use crate::{pack, store::IndexId};
+
+    /// Returned by [`Store::verify_index()`][crate::Store::verify_index()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("There is no index with id {0}")]
+        UnknownIndex(IndexId),
+        #[error(transparent)]
+        IndexOpen(#[from] pack::index::init::Error),
+        #[error(transparent)]
+        MultiIndexOpen(#[from] pack::multi_index::init::Error),
+        #[error(transparent)]
+        PackOpen(#[from] pack::data::init::Error),
+        #[error("The checksum of the file at '{}' doesn't match its content", path.display())]
+        Checksum {
+            path: PathBuf,
+            source: pack::verify::checksum::Error,
+        },
+        #[error("The pack at '{}' with checksum {actual} doesn't belong to its index which expects {expected}", path.display())]
+        PackMismatch {
+            path: PathBuf,
+            expected: ObjectId,
+            actual: ObjectId,
+        },
+        #[error("The disk on state changed while performing the operation, and we observed the change.")]
+        NeedsRetryDueToChangeOnDisk,
+        #[error("Interrupted")]
+        Interrupted,
+    }
+}
+
 impl super::Store {
+    /// Check that all multi-pack indices are consistent with their member packs, that is each member pack and its index exist and
+    /// belong together, and each object resolves to the offset recorded in its member pack's index.
+    ///
+    /// This catches multi-pack indices that went stale as a pack was removed or replaced. All problems are collected instead of
+    /// stopping at the first one, which is why files that don't exist are reported as [`Problem`][multi_index::Problem] rather
+    /// than as error.
+    /// `progress` is advanced for each object, and `should_interrupt` is checked regularly to allow aborting the operation.
+    /// Like with [`verify_integrity()`][super::Store::verify_integrity()], files are only loaded temporarily if needed.
+    pub fn verify_multi_indices(
+        &self,
+        progress: impl Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<multi_index::Outcome, multi_index::Error> {
+        self.verify_multi_indices_opts(progress, should_interrupt, Default::default())
+    }
+
+    /// Like [`verify_multi_indices()`][super::Store::verify_multi_indices()], but allows to rebuild inconsistent multi-pack indices
+    /// as configured in `options`.
+    pub fn verify_multi_indices_opts(
+        &self,
+        mut progress: impl Progress,
+        should_interrupt: &AtomicBool,
+        options: multi_index::Options,
+    ) -> Result<multi_index::Outcome, multi_index::Error> {
+        use multi_index::Problem;
+
+        let mut index = self.index.load();
+        if !index.is_initialized() {
+            self.consolidate_with_disk_state(true, false)?;
+            index = self.index.load();
+        }
+
+        let mut out = multi_index::Outcome::default();
+        progress.init(None, git_features::progress::count("objects"));
+        for slot_index in &index.slot_indices {
+            let slot = &self.files[*slot_index];
+            if slot.generation.load(Ordering::SeqCst) != index.generation {
+                return Err(multi_index::Error::NeedsRetryDueToChangeOnDisk);
+            }
+            let files = slot.files.load();
+            let bundle = match Option::as_ref(&files).ok_or(multi_index::Error::NeedsRetryDueToChangeOnDisk)? {
+                IndexAndPacks::MultiIndex(bundle) => bundle,
+                IndexAndPacks::Index(_) => continue,
+            };
+            let multi_index = match bundle.multi_index.loaded() {
+                Some(index) => index.clone(),
+                None => Arc::new(self.backend.open_multi_index(bundle.multi_index.path())?),
+            };
+
+            let index_dir = multi_index.path().parent().expect("file in a directory");
+            let mut problems = Vec::new();
+            let mut member_indices = Vec::with_capacity(multi_index.index_names().len());
+            for (pack_index, index_name) in multi_index.index_names().iter().enumerate() {
+                let index_path = index_dir.join(index_name);
+                let pack_path = index_path.with_extension("pack");
+                let index = if index_path.is_file() {
+                    Some(self.backend.open_index(&index_path, self.object_hash)?)
+                } else {
+                    problems.push(Problem::MissingIndex {
+                        path: index_path.clone(),
+                    });
+                    None
+                };
+                let pack_checksum = match bundle.data.get(pack_index).and_then(|f| f.loaded()) {
+                    Some(pack) => Some(pack.checksum()),
+                    None if pack_path.is_file() => {
+                        Some(self.backend.open_pack(&pack_path, self.object_hash)?.checksum())
+                    }
+                    None => {
+                        problems.push(Problem::MissingPack {
+                            path: pack_path.clone(),
+                        });
+                        None
+                    }
+                };
+                if let (Some(index), Some(actual)) = (&index, pack_checksum) {
+                    if index.pack_checksum() != actual {
+                        problems.push(Problem::PackMismatch {
+                            path: pack_path,
+                            expected: index.pack_checksum(),
+                            actual,
+                        });
+                    }
+                }
+                member_indices.push(index.map(|index| (index, index_path)));
+            }
+
+            for entry in multi_index.iter() {
+                match member_indices.get(entry.pack_index as usize) {
+                    None => problems.push(Problem::UnknownPack {
+                        id: entry.oid,
+                        pack_index: entry.pack_index,
+                    }),
+                    Some(None) => {}
+                    Some(Some((index, index_path))) => match index.lookup(entry.oid) {
+                        None => problems.push(Problem::MissingObject {
+                            id: entry.oid,
+                            index: index_path.clone(),
+                        }),
+                        Some(entry_index) => {
+                            let expected = index.pack_offset_at_index(entry_index);
+                            if expected != entry.pack_offset {
+                                problems.push(Problem::OffsetMismatch {
+                                    id: entry.oid,
+                                    index: index_path.clone(),
+                                    expected,
+                                    actual: entry.pack_offset,
+                                });
+                            }
+                        }
+                    },
+                }
+                progress.inc();
+                if should_interrupt.load(Ordering::Relaxed) {
+                    return Err(multi_index::Error::Interrupted);
+                }
+            }
+            out.multi_indices.push(multi_index::MultiIndex {
+                path: multi_index.path().to_owned(),
+                problems,
+                rebuilt: false,
+            });
+        }
+        drop(index);
+
+        if options.rebuild_inconsistent {
+            let mut any_rebuilt = false;
+            for multi_index in out.multi_indices.iter_mut().filter(|m| !m.problems.is_empty()) {
+                self.rebuild_multi_index(
+                    &multi_index.path,
+                    progress.add_child_with_id("rebuild multi-pack index", git_features::progress::UNKNOWN),
+                    should_interrupt,
+                )?;
+                multi_index.rebuilt = true;
+                any_rebuilt = true;
+            }
+            if any_rebuilt {
+                self.consolidate_with_disk_state(false, false)?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Check that the index with `id` and its pack belong together and weren't altered, by validating the checksum at the end of
+    /// the index and the pack against their content, and comparing the pack checksum recorded in the index with the one of the pack.
+    ///
+    /// For multi-pack indices, the multi-pack index is checked in the same way, and each member pack is checked against the pack
+    /// index it was created from, as multi-pack indices don't record checksums of their packs.
+    /// `progress` is advanced while hashing each file, which can take a while for large packs, and `should_interrupt` is checked
+    /// regularly to allow aborting the operation. Files are only loaded temporarily if needed.
+    pub fn verify_index(
+        &self,
+        id: super::IndexId,
+        mut progress: impl Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<(), index::Error> {
+        let slot_map = self.index.load();
+        let slot = self
+            .files
+            .get(id)
+            .filter(|_| slot_map.slot_indices.contains(&id))
+            .ok_or(index::Error::UnknownIndex(id))?;
+        if slot.generation.load(Ordering::SeqCst) > slot_map.generation {
+            return Err(index::Error::NeedsRetryDueToChangeOnDisk);
+        }
+        let files = slot.files.load();
+        match Option::as_ref(&files).ok_or(index::Error::NeedsRetryDueToChangeOnDisk)? {
+            IndexAndPacks::Index(bundle) => {
+                let index = match bundle.index.loaded() {
+                    Some(index) => index.clone(),
+                    None => Arc::new(self.backend.open_index(bundle.index.path(), self.object_hash)?),
+                };
+                let pack = match bundle.data.loaded() {
+                    Some(pack) => pack.clone(),
+                    None => Arc::new(self.backend.open_pack(bundle.data.path(), self.object_hash)?),
+                };
+                verify_index_and_pack(&index, &pack, &mut progress, should_interrupt)
+            }
+            IndexAndPacks::MultiIndex(bundle) => {
+                let multi_index = match bundle.multi_index.loaded() {
+                    Some(index) => index.clone(),
+                    None => Arc::new(self.backend.open_multi_index(bundle.multi_index.path())?),
+                };
+                multi_index
+                    .verify_checksum(
+                        progress.add_child_with_id(
+                            multi_index.path().display().to_string(),
+                            git_features::progress::UNKNOWN,
+                        ),
+                        should_interrupt,
+                    )
+                    .map_err(|err| checksum_error(multi_index.path(), err))?;
+
+                let index_dir = multi_index.path().parent().expect("file in a directory");
+                for (pack_index, index_name) in multi_index.index_names().iter().enumerate() {
+                    let index = self.backend.open_index(&index_dir.join(index_name), self.object_hash)?;
+                    let pack = match bundle.data.get(pack_index).and_then(|f| f.loaded()) {
+                        Some(pack) => pack.clone(),
+                        None => Arc::new(
+                            self.backend
+                                .open_pack(&index.path().with_extension("pack"), self.object_hash)?,
+                        ),
+                    };
+                    verify_index_and_pack(&index, &pack, &mut progress, should_interrupt)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Replace the multi-pack index at `path` with one covering all usable pack indices in its directory, or remove it if there
+    /// are none.
+    fn rebuild_multi_index(
+        &self,
+        path: &std::path::Path,
+        progress: impl Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<(), multi_index::Error> {
+        let dir = path.parent().expect("file in a directory");
+        let mut index_paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let index_path = entry?.path();
+            let pack_path = index_path.with_extension("pack");
+            if index_path.extension() != Some("idx".as_ref()) || !pack_path.is_file() {
+                continue;
+            }
+            let index = self.backend.open_index(&index_path, self.object_hash)?;
+            if index.pack_checksum() == self.backend.open_pack(&pack_path, self.object_hash)?.checksum() {
+                index_paths.push(index_path);
+            }
+        }
+        if index_paths.is_empty() {
+            std::fs::remove_file(path)?;
+            return Ok(());
+        }
+
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        pack::multi_index::File::write_from_index_paths(
+            index_paths,
+            &mut file,
+            progress,
+            should_interrupt,
+            pack::multi_index::write::Options {
+                object_hash: self.object_hash,
+            },
+        )?;
+        file.persist(path)?;
+        Ok(())
+    }
+
     /// Check the integrity of all objects as per the given `options`.
     ///
     /// Note that this will not not force loading all indices or packs permanently, as we will only use the momentarily loaded disk state.
@@ -137,7 +549,7 @@ impl super::Store {
                     let index = match bundle.index.loaded() {
                         Some(index) => index.deref(),
                         None => {
-                            index = pack::index::File::at(bundle.index.path(), self.object_hash)?;
+                            index = self.backend.open_index(bundle.index.path(), self.object_hash)?;
                             &index
                         }
                     };
@@ -145,7 +557,7 @@ impl super::Store {
                     let data = match bundle.data.loaded() {
                         Some(pack) => pack.deref(),
                         None => {
-                            pack = pack::data::File::at(bundle.data.path(), self.object_hash)?;
+                            pack = self.backend.open_pack(bundle.data.path(), self.object_hash)?;
                             &pack
                         }
                     };
@@ -172,7 +584,7 @@ impl super::Store {
                     let index = match bundle.multi_index.loaded() {
                         Some(index) => index.deref(),
                         None => {
-                            index = pack::multi_index::File::at(bundle.multi_index.path())?;
+                            index = self.backend.open_multi_index(bundle.multi_index.path())?;
                             &index
                         }
                     };
@@ -233,3 +645,42 @@ impl super::Store {
         })
     }
 }
+
+/// Verify the checksums of `index` and `pack`, and that `pack` is the one `index` was created for.
+fn verify_index_and_pack(
+    index: &pack::index::File,
+    pack: &pack::data::File,
+    progress: &mut impl Progress,
+    should_interrupt: &AtomicBool,
+) -> Result<(), index::Error> {
+    index
+        .verify_checksum(
+            progress.add_child_with_id(index.path().display().to_string(), git_features::progress::UNKNOWN),
+            should_interrupt,
+        )
+        .map_err(|err| checksum_error(index.path(), err))?;
+    let actual = pack
+        .verify_checksum(
+            progress.add_child_with_id(pack.path().display().to_string(), git_features::progress::UNKNOWN),
+            should_interrupt,
+        )
+        .map_err(|err| checksum_error(pack.path(), err))?;
+    if index.pack_checksum() != actual {
+        return Err(index::Error::PackMismatch {
+            path: pack.path().to_owned(),
+            expected: index.pack_checksum(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+fn checksum_error(path: &std::path::Path, err: pack::verify::checksum::Error) -> index::Error {
+    match err {
+        pack::verify::checksum::Error::Interrupted => index::Error::Interrupted,
+        err => index::Error::Checksum {
+            path: path.to_owned(),
+            source: err,
+        },
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    ops::Deref,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::store::RefreshMode;
+
+/// The shortest time to wait in between two checks, to avoid refreshing from disk in a busy loop.
+const MIN_BACKOFF: Duration = Duration::from_millis(1);
+/// The longest time to sleep without checking if we should stop waiting.
+const INTERRUPT_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Configure how [`Handle::wait_for_object()`][crate::store::Handle::wait_for_object()] polls for an object to appear.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Options {
+    /// The maximum amount of time to wait for the object to appear.
+    pub timeout: Duration,
+    /// The time to wait after the first unsuccessful check, which is at least a millisecond.
+    pub initial_backoff: Duration,
+    /// The longest time to wait in between two checks, no matter how many checks were done before, which is at least a millisecond.
+    pub max_backoff: Duration,
+    /// The factor by which the time between checks grows after each unsuccessful check.
+    ///
+    /// Values smaller than 1, as well as those that aren't finite, are treated as 1 so the time between checks stays the same.
+    pub backoff_multiplier: f32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            timeout: Duration::from_secs(10),
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            backoff_multiplier: 2.0,
+        }
+    }
+}
+
+impl<S> super::Handle<S>
+where
+    S: Deref<Target = super::Store> + Clone,
+{
+    /// Wait for the object with `id` to become available, for instance as it's about to be written by another process,
+    /// and return `true` if it appeared or `false` if it didn't within the timeout configured in `options`.
+    ///
+    /// Each check refreshes the disk state if the object couldn't be found, even if this handle is configured to [never refresh][RefreshMode::Never],
+    /// with the delay in between checks growing exponentially as configured in `options`.
+    /// Set `should_interrupt` to stop waiting early, which makes this method return `false` as well. It's checked at least every
+    /// few milliseconds, even while waiting for longer in between two checks.
+    pub fn wait_for_object(
+        &self,
+        id: impl AsRef<git_hash::oid>,
+        options: Options,
+        should_interrupt: &AtomicBool,
+    ) -> bool {
+        let id = id.as_ref();
+        let start = Instant::now();
+        let mut backoff = options.initial_backoff.max(MIN_BACKOFF);
+        let max_backoff = options.max_backoff.max(MIN_BACKOFF);
+        let multiplier = match options.backoff_multiplier {
+            m if m.is_finite() && m >= 1.0 => f64::from(m),
+            _ => 1.0,
+        };
+        loop {
+            if self.contains_inner(id, RefreshMode::AfterAllIndicesLoaded) {
+                return true;
+            }
+            let elapsed = start.elapsed();
+            if elapsed >= options.timeout || should_interrupt.load(Ordering::Relaxed) {
+                return false;
+            }
+            let wake_up = Instant::now() + backoff.min(options.timeout - elapsed);
+            loop {
+                let now = Instant::now();
+                if now >= wake_up {
+                    break;
+                }
+                if should_interrupt.load(Ordering::Relaxed) {
+                    return false;
+                }
+                std::thread::sleep((wake_up - now).min(INTERRUPT_CHECK_INTERVAL));
+            }
+            // Multiplying in seconds can't overflow, and limiting the result first keeps it convertible to a duration.
+            let next_backoff = backoff.as_secs_f64() * multiplier;
+            backoff = if next_backoff >= max_backoff.as_secs_f64() {
+                max_backoff
+            } else {
+                Duration::from_secs_f64(next_backoff)
+            };
+        }
+    }
+}
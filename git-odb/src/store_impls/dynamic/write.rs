@@ -18,6 +18,10 @@ mod error {
         LooseWrite(#[from] loose::write::Error),
         #[error(transparent)]
         Io(#[from] std::io::Error),
+        #[error("The object to write could not be transformed")]
+        Transform(#[source] store::transform::Error),
+        #[error("There is no loose object database to write objects to as the store doesn't scan the disk")]
+        NoLooseDb,
     }
 }
 pub use error::Error;
@@ -30,18 +34,23 @@ where
 {
     type Error = Error;
 
-    fn write_stream(&self, kind: Kind, size: u64, from: impl Read) -> Result<ObjectId, Self::Error> {
+    fn write_stream(&self, kind: Kind, size: u64, mut from: impl Read) -> Result<ObjectId, Self::Error> {
         let mut snapshot = self.snapshot.borrow_mut();
-        Ok(match snapshot.loose_dbs.first() {
-            Some(ldb) => ldb.write_stream(kind, size, from)?,
-            None => {
-                let new_snapshot = self
-                    .store
-                    .load_one_index(self.refresh, snapshot.marker)?
-                    .expect("there is always at least one ODB, and this code runs only once for initialization");
+        if snapshot.loose_dbs.is_empty() {
+            // Stores that scan the disk have at least one loose database once initialized, others never have one.
+            if let Some(new_snapshot) = self.store.load_one_index(self.refresh, snapshot.marker)? {
                 *snapshot = new_snapshot;
-                snapshot.loose_dbs[0].write_stream(kind, size, from)?
             }
+        }
+        let ldb = snapshot.loose_dbs.first().ok_or(Error::NoLooseDb)?;
+        Ok(match self.store.transform.as_deref() {
+            Some(transform) => {
+                let mut data = Vec::with_capacity(size as usize);
+                from.read_to_end(&mut data)?;
+                transform.encode(kind, &mut data).map_err(Error::Transform)?;
+                crate::Write::write_buf(ldb, kind, &data)?
+            }
+            None => ldb.write_stream(kind, size, from)?,
         })
     }
 }
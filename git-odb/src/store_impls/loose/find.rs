@@ -1,4 +1,10 @@
-use std::{cmp::Ordering, collections::HashSet, fs, io::Read, path::PathBuf};
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fs,
+    io::{BufReader, Read},
+    path::PathBuf,
+};
 
 use git_features::zlib;
 
@@ -39,6 +45,20 @@ impl Store {
         hash_path(id.as_ref(), self.path.clone()).is_file()
     }
 
+    /// Return the size in bytes of the compressed file storing the object `id`, or `None` if it doesn't exist.
+    pub fn stored_size(&self, id: impl AsRef<git_hash::oid>) -> Result<Option<u64>, Error> {
+        let path = hash_path(id.as_ref(), self.path.clone());
+        match fs::metadata(&path) {
+            Ok(meta) => Ok(Some(meta.len())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::Io {
+                source: err,
+                action: "query metadata of",
+                path,
+            }),
+        }
+    }
+
     /// Given a `prefix`, find an object that matches it uniquely within this loose object
     /// database as `Ok(Some(Ok(<oid>)))`.
     /// If there is more than one object matching the object `Ok(Some(Err(()))` is returned.
@@ -182,6 +202,9 @@ impl Store {
 
     fn find_inner<'a>(&self, id: &git_hash::oid, buf: &'a mut Vec<u8>) -> Result<git_object::Data<'a>, Error> {
         let path = hash_path(id, self.path.clone());
+        if let Some(read_buffer_size) = self.read_buffer_size {
+            return Self::find_buffered(path, read_buffer_size, buf);
+        }
 
         let mut inflate = zlib::Inflate::default();
         let ((status, consumed_in, consumed_out), bytes_read) = {
@@ -259,4 +282,45 @@ impl Store {
         buf.resize(size, 0);
         Ok(git_object::Data { kind, data: buf })
     }
+
+    /// Like `find_inner()`, but decompresses while reading the file at `path` through a buffer of `read_buffer_size` bytes so that
+    /// only the decompressed object is kept in `buf`.
+    fn find_buffered(path: PathBuf, read_buffer_size: usize, buf: &mut Vec<u8>) -> Result<git_object::Data<'_>, Error> {
+        let istream = fs::File::open(&path).map_err(|e| Error::Io {
+            source: e,
+            action: Self::OPEN_ACTION,
+            path: path.to_owned(),
+        })?;
+        let mut istream = BufReader::with_capacity(read_buffer_size.max(1), istream);
+        let mut inflate = zlib::Inflate::default();
+        let mut read = |out: &mut [u8]| {
+            zlib::stream::inflate::read(&mut istream, &mut inflate.state, out).map_err(|e| Error::Io {
+                source: e,
+                action: "deflate",
+                path: path.to_owned(),
+            })
+        };
+
+        let mut header_buf = [0_u8; HEADER_MAX_SIZE];
+        let consumed_out = read(&mut header_buf)?;
+        let (kind, size, header_size) = git_object::decode::loose_header(&header_buf[..consumed_out])?;
+        let body_bytes_in_header_buf = consumed_out.saturating_sub(header_size).min(size);
+        buf.clear();
+        buf.resize(size, 0);
+        buf[..body_bytes_in_header_buf]
+            .copy_from_slice(&header_buf[header_size..header_size + body_bytes_in_header_buf]);
+        let num_decompressed_bytes = if consumed_out == header_size + body_bytes_in_header_buf {
+            consumed_out + read(&mut buf[body_bytes_in_header_buf..])?
+        } else {
+            consumed_out
+        };
+        if num_decompressed_bytes != size + header_size {
+            return Err(Error::SizeMismatch {
+                expected: size + header_size,
+                actual: num_decompressed_bytes,
+                path,
+            });
+        }
+        Ok(git_object::Data { kind, data: buf })
+    }
 }
@@ -78,4 +78,21 @@ impl loose::Store {
             hash_hex_len: self.object_hash.len_in_hex(),
         }
     }
+
+    /// Like [`iter()`][loose::Store::iter()], but returns objects in ascending order of their ids, which is stable across runs.
+    pub fn iter_sorted(&self) -> loose::Iter {
+        loose::Iter {
+            inner: fs::walkdir_sorted_new(
+                &self.path,
+                fs::walkdir::Parallelism::ThreadPoolPerTraversal {
+                    thread_name: "git_odb::loose::Store::iter_sorted: fs-walk",
+                },
+            )
+            .min_depth(2)
+            .max_depth(3)
+            .follow_links(false)
+            .into_iter(),
+            hash_hex_len: self.object_hash.len_in_hex(),
+        }
+    }
 }
@@ -1,6 +1,6 @@
 //! An object database storing each object in a zlib compressed file with its hash in the path
 /// The maximum size that an object header can have. `git2` says 64, and `git` says 32 but also mentions it can be larger.
-const HEADER_MAX_SIZE: usize = 64;
+pub(crate) const HEADER_MAX_SIZE: usize = 64;
 use std::path::{Path, PathBuf};
 
 use git_features::fs;
@@ -12,6 +12,8 @@ pub struct Store {
     pub(crate) path: PathBuf,
     /// The kind of hash we should assume during iteration and when writing new objects.
     pub(crate) object_hash: git_hash::Kind,
+    /// If set, the size of the buffer through which object files are read while decompressing them.
+    pub(crate) read_buffer_size: Option<usize>,
 }
 
 /// Initialization
@@ -26,9 +28,21 @@ impl Store {
         Store {
             path: objects_directory.into(),
             object_hash,
+            read_buffer_size: None,
         }
     }
 
+    /// Read object files through a buffer of `size` bytes while decompressing them, instead of reading each file into memory
+    /// before decompressing it, which is the default if `size` is `None`.
+    ///
+    /// This bounds the memory used by a lookup to the size of the object plus `size`, instead of the size of the object plus
+    /// the size of its compressed file, at the cost of more system calls and thus lower throughput, especially with small buffers.
+    /// Object headers are always read in full, so buffers smaller than a couple of bytes are just slow.
+    pub fn with_read_buffer_size(mut self, size: Option<usize>) -> Self {
+        self.read_buffer_size = size;
+        self
+    }
+
     /// Return the path to our `objects` directory.
     pub fn path(&self) -> &Path {
         &self.path
@@ -40,7 +54,7 @@ impl Store {
     }
 }
 
-fn hash_path(id: &git_hash::oid, mut root: PathBuf) -> PathBuf {
+pub(crate) fn hash_path(id: &git_hash::oid, mut root: PathBuf) -> PathBuf {
     let mut hex = git_hash::Kind::hex_buf();
     let hex_len = id.hex_to_buf(hex.as_mut());
     let buf = std::str::from_utf8(&hex[..hex_len]).expect("ascii only in hex");
@@ -5,7 +5,10 @@ use std::{
 
 use git_features::progress::Progress;
 
-use crate::{loose::Store, Write};
+use crate::{
+    loose::{hash_path, Store},
+    Write,
+};
 
 ///
 pub mod integrity {
@@ -40,6 +43,43 @@ pub mod integrity {
     }
 }
 
+///
+pub mod names {
+    use std::path::PathBuf;
+
+    /// The error returned by [`verify_object_names()`][super::Store::verify_object_names()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error(transparent)]
+        Find(#[from] crate::loose::find::Error),
+        #[error("Interrupted")]
+        Interrupted,
+    }
+
+    /// A loose object whose content doesn't hash to the id its path suggests.
+    #[derive(Debug, PartialEq, Eq, Hash, Ord, PartialOrd, Clone)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Mismatch {
+        /// The path to the object file.
+        pub path: PathBuf,
+        /// The id of the object as derived from its path.
+        pub expected: git_hash::ObjectId,
+        /// The id of the object as computed from its content.
+        pub actual: git_hash::ObjectId,
+    }
+
+    /// The outcome returned by [`verify_object_names()`][super::Store::verify_object_names()].
+    #[derive(Debug, PartialEq, Eq, Hash, Ord, PartialOrd, Clone)]
+    #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Outcome {
+        /// The amount of loose objects we checked.
+        pub num_objects: usize,
+        /// All objects whose content doesn't match their path, in the order in which they were found.
+        pub mismatches: Vec<Mismatch>,
+    }
+}
+
 impl Store {
     /// Check all loose objects for their integrity checking their hash matches the actual data and by decoding them fully.
     pub fn verify_integrity(
@@ -83,4 +123,48 @@ impl Store {
 
         Ok(integrity::Statistics { num_objects })
     }
+
+    /// Check that the content of each loose object hashes to the id its path suggests, and return all objects for which it
+    /// doesn't, which happens if object files were damaged or renamed.
+    ///
+    /// Unlike [`verify_integrity()`][Store::verify_integrity()], all mismatches are collected instead of stopping at the
+    /// first one, and objects aren't decoded. Mismatching files are left in place. Objects that are deleted while checking are
+    /// skipped, but those that can't be read fail the operation.
+    /// `progress` is advanced for each object, and `should_interrupt` is checked for each object to allow aborting the operation.
+    pub fn verify_object_names(
+        &self,
+        mut progress: impl Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<names::Outcome, names::Error> {
+        let mut buf = Vec::new();
+        let sink = crate::sink(self.object_hash);
+
+        let mut out = names::Outcome {
+            num_objects: 0,
+            mismatches: Vec::new(),
+        };
+        let start = Instant::now();
+        progress.init(None, git_features::progress::count("loose objects"));
+        for id in self.iter().filter_map(Result::ok) {
+            if should_interrupt.load(Ordering::SeqCst) {
+                return Err(names::Error::Interrupted);
+            }
+            let object = match self.try_find(id, &mut buf)? {
+                Some(object) => object,
+                None => continue,
+            };
+            let actual = sink.write_buf(object.kind, object.data).expect("sink never fails");
+            if actual != id {
+                out.mismatches.push(names::Mismatch {
+                    path: hash_path(&id, self.path.clone()),
+                    expected: id,
+                    actual,
+                });
+            }
+            progress.inc();
+            out.num_objects += 1;
+        }
+        progress.show_throughput(start);
+        Ok(out)
+    }
 }
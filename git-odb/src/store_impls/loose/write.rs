@@ -47,7 +47,7 @@ impl crate::traits::Write for Store {
 
     /// Write the given buffer in `from` to disk in one syscall at best.
     ///
-    /// This will cost at least 4 IO operations.
+    /// This will cost at least 4 IO operations. If the object exists already, it is left untouched.
     fn write_buf(&self, kind: git_object::Kind, from: &[u8]) -> Result<git_hash::ObjectId, Self::Error> {
         let mut to = self.dest()?;
         to.write_all(&git_object::encode::loose_header(kind, from.len()))
@@ -68,7 +68,7 @@ impl crate::traits::Write for Store {
 
     /// Write the given stream in `from` to disk with at least one syscall.
     ///
-    /// This will cost at least 4 IO operations.
+    /// This will cost at least 4 IO operations. If the object exists already, it is left untouched.
     fn write_stream(
         &self,
         kind: git_object::Kind,
@@ -116,6 +116,10 @@ impl Store {
     ) -> Result<git_hash::ObjectId, Error> {
         let id = git_hash::ObjectId::from(hash.digest());
         let object_path = loose::hash_path(&id, self.path.clone());
+        if object_path.is_file() {
+            // objects are immutable, so an existing one must have the same content and the temporary file is discarded.
+            return Ok(id);
+        }
         let object_dir = object_path
             .parent()
             .expect("each object path has a 1 hex-bytes directory");
@@ -126,10 +130,14 @@ impl Store {
             }
         }
         let file = file.into_inner();
-        file.persist(&object_path).map_err(|err| Error::Persist {
-            source: err,
-            target: object_path,
-        })?;
-        Ok(id)
+        match file.persist(&object_path) {
+            Ok(_) => Ok(id),
+            // another writer may have just persisted the same object, which fails on some platforms.
+            Err(_) if object_path.is_file() => Ok(id),
+            Err(err) => Err(Error::Persist {
+                source: err,
+                target: object_path,
+            }),
+        }
     }
 }
@@ -0,0 +1,21 @@
+#!/bin/bash
+set -eu -o pipefail
+
+# top -> middle -> base, each referring to the next with a path relative to its own objects directory.
+git init -q base
+(cd base
+  echo base > file
+  git add file
+  git commit -qm "base"
+)
+
+git init -q middle
+echo "../../../base/.git/objects" > middle/.git/objects/info/alternates
+(cd middle
+  echo middle > file
+  git add file
+  git commit -qm "middle"
+)
+
+git init -q top
+echo "../../../middle/.git/objects" > top/.git/objects/info/alternates
@@ -3,7 +3,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use git_odb::alternate;
+use git_odb::{alternate, FindExt};
 
 pub fn alternate(
     objects_at: impl Into<PathBuf>,
@@ -50,14 +50,14 @@ fn alternate_with_content(
 }
 
 #[test]
-fn circular_alternates_are_detected_with_relative_paths() -> crate::Result {
+fn circular_alternates_with_relative_paths_are_skipped() -> crate::Result {
     let tmp = git_testtools::tempfile::TempDir::new()?;
     let tmp = tmp.path().join("sub-dir");
     std::fs::create_dir(&tmp)?;
     let (from, _) = alternate(tmp.join("a"), tmp.join("b"))?;
     alternate_with_content(
         tmp.join("b"),
-        tmp.join("..").join("a"),
+        tmp.join("a"),
         Path::new("..")
             .join("a")
             .to_str()
@@ -67,18 +67,89 @@ fn circular_alternates_are_detected_with_relative_paths() -> crate::Result {
         None,
     )?;
 
-    match alternate::resolve(from, std::env::current_dir()?) {
-        Err(alternate::Error::Cycle(chain)) => {
-            assert_eq!(
-                chain
-                    .into_iter()
-                    .map(|p| p.file_name().expect("non-root").to_str().expect("utf8").to_owned())
-                    .collect::<Vec<_>>(),
-                vec!["a", "b"]
-            );
-        }
+    assert_eq!(
+        alternate::resolve(from, std::env::current_dir()?)?,
+        vec![tmp.join("b").canonicalize()?],
+        "'b' points back to 'a' relative to itself, which was seen already"
+    );
+    Ok(())
+}
+
+#[test]
+fn alternates_referring_to_their_own_objects_directory_are_skipped() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let (from, _) = alternate_with_content(tmp.path().join("a"), tmp.path().join("a"), b".".to_vec(), None)?;
+    assert!(alternate::resolve(from, std::env::current_dir()?)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn alternates_reachable_by_multiple_routes_are_returned_once_in_discovery_order() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let path = |name: &str| tmp.path().join(name);
+    let lines = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| path(name).to_str().expect("valid UTF-8").to_owned())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into_bytes()
+    };
+    alternate_with_content(path("a"), path("b"), lines(&["b", "c", "d"]), None)?;
+    alternate_with_content(path("b"), path("d"), lines(&["d"]), None)?;
+    alternate_with_content(path("c"), path("d"), lines(&["d"]), None)?;
+
+    assert_eq!(
+        alternate::resolve(path("a"), std::env::current_dir()?)?,
+        vec![
+            path("b").canonicalize()?,
+            path("d").canonicalize()?,
+            path("c").canonicalize()?
+        ],
+        "'d' is followed first through 'b', and isn't listed again when reached through 'c' or 'a'"
+    );
+    Ok(())
+}
+
+#[test]
+fn nested_relative_alternates_are_relative_to_the_objects_directory_declaring_them() -> crate::Result {
+    let dir = git_testtools::scripted_fixture_read_only("make_nested_relative_alternates.sh")?;
+    let objects_dir = |repo: &str| dir.join(repo).join(".git").join("objects");
+
+    assert_eq!(
+        alternate::resolve(objects_dir("top"), std::env::current_dir()?)?,
+        vec![
+            objects_dir("middle").canonicalize()?,
+            objects_dir("base").canonicalize()?
+        ]
+    );
+
+    let handle = git_odb::at(objects_dir("top"))?;
+    let mut buf = Vec::new();
+    for (id, content) in [
+        ("ea1a2ae6cdab2b53dee8943ca555c4312a0b798a", "middle\n"),
+        ("df967b96a579e45a18b8251732d16804b2e56a55", "base\n"),
+    ] {
+        let blob = handle.find_blob(git_testtools::hex_to_id(id), &mut buf)?;
+        assert_eq!(blob.data, content.as_bytes(), "objects of all alternates are found");
+    }
+    Ok(())
+}
+
+#[test]
+fn alternates_that_are_not_accessible_directories_are_an_error() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let (from, to) = alternate(tmp.path().join("a"), tmp.path().join("b"))?;
+    std::fs::remove_dir(&to)?;
+
+    match alternate::resolve(&from, std::env::current_dir()?) {
+        Err(alternate::Error::Inaccessible(path)) => assert_eq!(path, to),
         res => unreachable!("should be a specific kind of error: {:?}", res),
     }
+    assert!(
+        git_odb::at(&from).is_err(),
+        "a store can't be opened with an inaccessible alternate either"
+    );
     Ok(())
 }
 
@@ -90,7 +161,37 @@ fn single_link_with_comment_before_path_and_ansi_c_escape() -> crate::Result {
     let (from, to) = alternate_with(tmp.path().join("a"), non_alternate, Some("# comment\n"))?;
     let alternates = alternate::resolve(from, std::env::current_dir()?)?;
     assert_eq!(alternates.len(), 1);
-    assert_eq!(alternates[0], to);
+    assert_eq!(alternates[0], to.canonicalize()?, "alternates are canonicalized");
+    Ok(())
+}
+
+#[test]
+fn quoted_paths_with_escapes_are_unquoted_and_comments_and_blank_lines_are_skipped() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let to = tmp.path().join("with space and \"quote\" and ü");
+    let mut quoted = b"\"".to_vec();
+    for b in to.to_str().expect("valid UTF-8").bytes() {
+        match b {
+            b'"' => quoted.extend_from_slice(b"\\\""),
+            b'\\' => quoted.extend_from_slice(b"\\\\"),
+            b if b.is_ascii() => quoted.push(b),
+            b => quoted.extend_from_slice(format!("\\{:03o}", b).as_bytes()),
+        }
+    }
+    quoted.push(b'"');
+    let (from, to) = alternate_with_content(
+        tmp.path().join("a"),
+        to,
+        quoted,
+        Some("# a comment, followed by blank lines\n\n\n"),
+    )?;
+
+    let alternates = alternate::resolve(from, std::env::current_dir()?)?;
+    assert_eq!(
+        alternates,
+        vec![to.canonicalize()?],
+        "the single quoted path is unescaped"
+    );
     Ok(())
 }
 
@@ -100,3 +201,35 @@ fn no_alternate_in_first_objects_dir() -> crate::Result {
     assert!(alternate::resolve(tmp.path(), std::env::current_dir()?)?.is_empty());
     Ok(())
 }
+
+#[test]
+fn the_amount_of_alternates_to_follow_can_be_limited() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let (from, _) = alternate(tmp.path().join("a"), tmp.path().join("b"))?;
+    alternate(tmp.path().join("b"), tmp.path().join("c"))?;
+
+    assert_eq!(
+        alternate::resolve_opts(&from, std::env::current_dir()?, Some(2))?.len(),
+        2,
+        "the limit is inclusive"
+    );
+    match alternate::resolve_opts(&from, std::env::current_dir()?, Some(1)) {
+        Err(alternate::Error::TooManyAlternates { path, limit }) => {
+            assert_eq!(limit, 1);
+            assert_eq!(path, tmp.path().join("c"), "indirect alternates count as well");
+        }
+        res => unreachable!("should be a specific kind of error: {:?}", res),
+    }
+
+    let err = git_odb::Store::at_opts(
+        &from,
+        None,
+        git_odb::store::init::Options {
+            max_alternates: Some(1),
+            ..Default::default()
+        },
+    )
+    .expect_err("the limit is enforced when initializing a store");
+    assert!(err.to_string().contains("maximum of 1 alternates"), "{}", err);
+    Ok(())
+}
@@ -37,6 +37,455 @@ fn db_with_all_object_sources() -> crate::Result<(git_odb::Handle, tempfile::Tem
     Ok((git_odb::at(objects_dir.path())?, objects_dir))
 }
 
+#[test]
+fn indices_without_pack_are_ignored() -> crate::Result {
+    let objects_dir = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &objects_dir)?;
+    std::fs::copy(
+        fixture_path("objects/pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b.idx"),
+        objects_dir
+            .path()
+            .join("pack/pack-0000000000000000000000000000000000000000.idx"),
+    )?;
+
+    let handle = git_odb::at(objects_dir.path())?;
+    handle.packed_object_count()?;
+    let metrics = handle.store_ref().metrics();
+    assert_eq!(metrics.known_reachable_indices, 3, "the index without pack isn't known");
+    assert_eq!(metrics.known_packs, 3);
+    assert!(handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")));
+    Ok(())
+}
+
+#[test]
+fn indices_whose_pack_disappeared_are_skipped_by_lookups() -> crate::Result {
+    let objects_dir = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &objects_dir)?;
+    let pack_path = objects_dir
+        .path()
+        .join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack");
+    let ids_in_pack: Vec<_> = git_odb::pack::index::File::at(pack_path.with_extension("idx"), git_hash::Kind::Sha1)?
+        .iter()
+        .map(|entry| entry.oid)
+        .collect();
+    let also_loose = ids_in_pack[0];
+    let mut buf = Vec::new();
+    let (expected_kind, expected_data) = {
+        let obj = db().find(also_loose, &mut buf)?;
+        (obj.kind, obj.data.to_owned())
+    };
+
+    let mut handle = git_odb::at(objects_dir.path())?;
+    handle.refresh_never();
+    handle.packed_object_count()?;
+    let moved_pack_path = objects_dir.path().join("moved.pack");
+    std::fs::rename(&pack_path, &moved_pack_path)?;
+    handle.write_buf(expected_kind, &expected_data)?;
+
+    let obj = handle.find(also_loose, &mut buf)?;
+    assert_eq!(
+        (obj.kind, obj.data),
+        (expected_kind, expected_data.as_slice()),
+        "objects are found elsewhere if the pack of an index is missing"
+    );
+    let mut unavailable = Vec::new();
+    for id in &ids_in_pack {
+        if handle.try_find(id, &mut buf)?.is_none() {
+            unavailable.push(*id);
+        }
+    }
+    assert!(
+        !unavailable.is_empty(),
+        "objects only in the missing pack are missing, without error"
+    );
+
+    let mut new_handle = handle.store().to_cache();
+    new_handle.refresh_never();
+    assert!(
+        !new_handle.contains(unavailable[0]),
+        "new handles don't use the index as its pack is known to be missing"
+    );
+
+    std::fs::rename(&moved_pack_path, &pack_path)?;
+    handle.store_ref().refresh()?;
+    for id in &ids_in_pack {
+        assert!(
+            new_handle.try_find(id, &mut buf)?.is_some(),
+            "the pack is used again once it is back"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn multi_indices_with_missing_packs_are_ignored() -> crate::Result {
+    let (handle, tmp) = db_with_all_object_sources()?;
+    let stem = tmp.path().join("pack/pack-a2bf8e71d8c18879e499335762dd95119d93d9f1");
+    std::fs::remove_file(stem.with_extension("pack"))?;
+
+    handle.packed_object_count()?;
+    let metrics = handle.store_ref().metrics();
+    assert_eq!(
+        metrics.known_reachable_indices, 2,
+        "the single indices of the remaining packs are used instead of the multi-index"
+    );
+    assert_eq!(metrics.known_packs, 2);
+    assert!(handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")));
+    Ok(())
+}
+
+#[test]
+fn single_indices_covered_by_a_multi_index_are_not_loaded_separately() -> crate::Result {
+    let (handle, _tmp) = db_with_all_object_sources()?;
+    handle.packed_object_count()?;
+    let metrics = handle.store_ref().metrics();
+    assert_eq!(
+        metrics.known_reachable_indices, 2,
+        "the multi-index and the index of the single pack it doesn't cover"
+    );
+    assert_eq!(metrics.open_reachable_indices, 2);
+    assert_eq!(metrics.known_packs, 3, "all packs are still reachable");
+    Ok(())
+}
+
+#[test]
+fn indices() -> crate::Result {
+    use git_odb::{
+        pack,
+        store::structure::{IndexHeader, IndexState, IndexVersion},
+    };
+    let (handle, _tmp) = db_with_all_object_sources()?;
+    let store = handle.store_ref();
+    assert!(
+        store.indices().is_empty(),
+        "nothing is known before the store is initialized"
+    );
+
+    store.structure()?;
+    let indices = store.indices();
+    assert_eq!(indices.len(), 2);
+    assert!(
+        indices
+            .iter()
+            .all(|index| index.header.is_none() == (index.state == IndexState::Unloaded)),
+        "headers are only available for loaded indices"
+    );
+    assert!(
+        indices.iter().any(|index| index.header.is_none()),
+        "indices aren't loaded to obtain their header"
+    );
+
+    handle.packed_object_count()?;
+    let mut headers: Vec<_> = store
+        .indices()
+        .into_iter()
+        .map(|index| (index.is_multi_pack, index.state, index.header))
+        .collect();
+    headers.sort_by_key(|(is_multi_pack, ..)| *is_multi_pack);
+    assert_eq!(
+        headers,
+        vec![
+            (
+                false,
+                IndexState::Loaded,
+                Some(IndexHeader {
+                    version: IndexVersion::Index(pack::index::Version::V2),
+                    num_objects: 30,
+                    num_packs: 1
+                })
+            ),
+            (
+                true,
+                IndexState::Loaded,
+                Some(IndexHeader {
+                    version: IndexVersion::MultiIndex(pack::multi_index::Version::V1),
+                    num_objects: 42 + 67,
+                    num_packs: 2
+                })
+            )
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn copy_pack() -> crate::Result {
+    use std::{io::Write, sync::atomic::AtomicBool};
+
+    use git_features::progress;
+    use git_odb::store::copy_pack::Error;
+    let (handle, tmp) = db_with_all_object_sources()?;
+    let store = handle.store_ref();
+    let mut buf = Vec::new();
+    let mut pack_id_of = |id: &str| {
+        git_odb::pack::Find::location_by_oid(&handle, hex_to_id(id), &mut buf)
+            .expect("packed")
+            .pack_id
+    };
+    let (in_single_pack, in_multi_index) = (
+        pack_id_of("501b297447a8255d3533c6858bb692575cdefaa0"),
+        pack_id_of("0d9726f3fd2359cda58217724f12af273bf440a6"),
+    );
+    let file_names = |paths: &[std::path::PathBuf]| {
+        paths
+            .iter()
+            .map(|path| path.file_name().expect("named").to_str().expect("UTF-8").to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    let destination = git_testtools::tempfile::tempdir()?;
+    let copied = store.copy_pack(
+        in_single_pack,
+        destination.path(),
+        true,
+        progress::Discard,
+        &AtomicBool::default(),
+    )?;
+    assert_eq!(
+        file_names(&copied),
+        [
+            "pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack",
+            "pack-11fdfa9e156ab73caae3b6da867192221f2089c2.idx"
+        ],
+        "packs are copied before their index"
+    );
+    for path in &copied {
+        assert_eq!(
+            std::fs::read(path)?,
+            std::fs::read(tmp.path().join("pack").join(path.file_name().expect("named")))?
+        );
+    }
+
+    let destination = git_testtools::tempfile::tempdir()?;
+    let copied = store.copy_pack(
+        in_multi_index,
+        destination.path(),
+        false,
+        progress::Discard,
+        &AtomicBool::default(),
+    )?;
+    assert_eq!(
+        file_names(&copied),
+        [
+            "pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.pack",
+            "pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.idx",
+            "pack-c0438c19fb16422b6bbcce24387b3264416d485b.pack",
+            "pack-c0438c19fb16422b6bbcce24387b3264416d485b.idx",
+            "multi-pack-index"
+        ],
+        "all packs of a multi-pack index are copied along with it"
+    );
+
+    let multi_index_checksum =
+        git_odb::pack::multi_index::File::at(tmp.path().join("pack/multi-pack-index"))?.checksum();
+    for extension in ["rev", "bitmap"] {
+        let mut companion = git_features::hash::Write::new(Vec::new(), git_hash::Kind::Sha1);
+        companion.write_all(format!("{} of the multi-pack index", extension).as_bytes())?;
+        let checksum = companion.hash.digest();
+        let mut data = companion.inner;
+        data.extend_from_slice(&checksum);
+        std::fs::write(
+            tmp.path()
+                .join("pack")
+                .join(format!("multi-pack-index-{}.{}", multi_index_checksum, extension)),
+            data,
+        )?;
+    }
+    let destination = git_testtools::tempfile::tempdir()?;
+    let copied = store.copy_pack(
+        in_multi_index,
+        destination.path(),
+        false,
+        progress::Discard,
+        &AtomicBool::default(),
+    )?;
+    assert_eq!(
+        file_names(&copied)[4..],
+        [
+            format!("multi-pack-index-{}.rev", multi_index_checksum),
+            format!("multi-pack-index-{}.bitmap", multi_index_checksum),
+            "multi-pack-index".into()
+        ],
+        "the reverse index and bitmap of a multi-pack index are copied before it"
+    );
+    for path in &copied {
+        assert_eq!(
+            std::fs::read(path)?,
+            std::fs::read(tmp.path().join("pack").join(path.file_name().expect("named")))?
+        );
+    }
+
+    assert!(matches!(
+        store.copy_pack(
+            in_single_pack,
+            destination.path(),
+            false,
+            progress::Discard,
+            &AtomicBool::new(true)
+        ),
+        Err(Error::Interrupted)
+    ));
+
+    drop(handle);
+    let pack_path = tmp
+        .path()
+        .join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack");
+    let mut data = std::fs::read(&pack_path)?;
+    let last_byte_before_trailer = data.len() - 21;
+    data[last_byte_before_trailer] ^= 0xff;
+    std::fs::write(&pack_path, data)?;
+    let handle = git_odb::at(tmp.path())?;
+    let in_corrupt_pack =
+        git_odb::pack::Find::location_by_oid(&handle, hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut buf)
+            .expect("the index is intact")
+            .pack_id;
+    assert!(matches!(
+        handle.store_ref().copy_pack(
+            in_corrupt_pack,
+            destination.path(),
+            false,
+            progress::Discard,
+            &AtomicBool::default()
+        ),
+        Err(Error::ChecksumMismatch { path, .. }) if path.file_name() == pack_path.file_name()
+    ));
+    Ok(())
+}
+
+#[test]
+fn estimate_decompressed_size() -> crate::Result {
+    use std::sync::atomic::AtomicBool;
+
+    use git_features::progress;
+    use git_odb::store::decompressed_size::{Error, Outcome};
+    let (handle, _tmp) = db_with_all_object_sources()?;
+    let mut buf = Vec::new();
+    for (pack_name, id_in_pack) in [
+        (
+            "pack-11fdfa9e156ab73caae3b6da867192221f2089c2",
+            "501b297447a8255d3533c6858bb692575cdefaa0",
+        ),
+        (
+            "pack-a2bf8e71d8c18879e499335762dd95119d93d9f1",
+            "0d9726f3fd2359cda58217724f12af273bf440a6",
+        ),
+    ] {
+        let pack_id = git_odb::pack::Find::location_by_oid(&handle, hex_to_id(id_in_pack), &mut buf)
+            .expect("packed")
+            .pack_id;
+        let index = git_odb::pack::index::File::at(
+            fixture_path("objects/pack").join(pack_name).with_extension("idx"),
+            git_hash::Kind::Sha1,
+        )?;
+        let mut expected = Outcome::default();
+        for entry in index.iter() {
+            let size = handle.find(entry.oid, &mut buf)?.data.len() as u64;
+            expected.num_objects += 1;
+            expected.total_bytes += size;
+            expected.largest_object_bytes = expected.largest_object_bytes.max(size);
+        }
+
+        let store = handle.store_ref();
+        assert_eq!(
+            store.estimate_decompressed_size(pack_id, progress::Discard, &AtomicBool::default())?,
+            expected,
+            "the estimate matches the size of the decoded objects exactly"
+        );
+        assert!(matches!(
+            store.estimate_decompressed_size(pack_id, progress::Discard, &AtomicBool::new(true)),
+            Err(Error::Interrupted)
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn multi_pack_index_advice() -> crate::Result {
+    use git_odb::store::structure::MultiPackIndexAdvice;
+    let handle = db();
+    let store = handle.store_ref();
+    assert_eq!(
+        store.multi_pack_index_advice(2),
+        MultiPackIndexAdvice::default(),
+        "nothing is loaded initially"
+    );
+
+    assert_eq!(handle.packed_object_count()?, 139);
+    assert_eq!(
+        store.multi_pack_index_advice(2),
+        MultiPackIndexAdvice {
+            recommended: true,
+            num_single_pack_indices: 3,
+            num_objects_in_single_pack_indices: 139,
+            num_multi_pack_indices: 0,
+        }
+    );
+    assert!(!store.multi_pack_index_advice(3).recommended);
+
+    let (handle, _tmp) = db_with_all_object_sources()?;
+    handle.packed_object_count()?;
+    assert_eq!(
+        handle.store_ref().multi_pack_index_advice(0),
+        MultiPackIndexAdvice {
+            recommended: true,
+            num_single_pack_indices: 1,
+            num_objects_in_single_pack_indices: 30,
+            num_multi_pack_indices: 1,
+        },
+        "packs covered by a multi-pack index don't count"
+    );
+    Ok(())
+}
+
+#[test]
+fn oldest_pack() -> crate::Result {
+    let dir = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &dir)?;
+    let pack_dir = dir.path().join("pack");
+    for (name, seconds) in [
+        ("pack-11fdfa9e156ab73caae3b6da867192221f2089c2", 1000),
+        ("pack-a2bf8e71d8c18879e499335762dd95119d93d9f1", 2000),
+        ("pack-c0438c19fb16422b6bbcce24387b3264416d485b", 3000),
+    ] {
+        filetime::set_file_mtime(
+            pack_dir.join(name).with_extension("idx"),
+            filetime::FileTime::from_unix_time(seconds, 0),
+        )?;
+    }
+    let handle = git_odb::at(dir.path())?;
+    let store = handle.store_ref();
+    assert_eq!(
+        store.oldest_pack(),
+        None,
+        "nothing is known before the store is initialized"
+    );
+
+    store.structure()?;
+    let oldest = store.oldest_pack().expect("packs are known");
+    assert_eq!(
+        oldest.path,
+        pack_dir.join("pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack")
+    );
+    assert_eq!(
+        oldest.mtime,
+        std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1000)
+    );
+    assert_eq!(oldest.num_objects, None, "indices aren't loaded to count objects");
+
+    handle.packed_object_count()?;
+    let oldest = store.oldest_pack().expect("packs are known");
+    assert_eq!(oldest.num_objects, Some(30));
+    assert_eq!(
+        handle
+            .packs_covering(&[hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0")])?
+            .packs[0]
+            .0,
+        oldest.id,
+        "the id is the one used to refer to packs elsewhere"
+    );
+    Ok(())
+}
+
 #[test]
 fn multi_index_access() -> crate::Result {
     let dir = git_testtools::scripted_fixture_writable("make_repo_multi_index.sh")?;
@@ -45,16 +494,19 @@ fn multi_index_access() -> crate::Result {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 0,
             open_reachable_indices: 0,
             known_reachable_indices: 0,
             open_reachable_packs: 0,
+            open_files: 0,
             known_packs: 0,
             unused_slots: 32,
             loose_dbs: 0,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "it starts out knowing nothing, it's completely lazy"
     );
@@ -77,16 +529,19 @@ fn multi_index_access() -> crate::Result {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 1,
             open_reachable_indices: 1,
             known_reachable_indices: 1,
             open_reachable_packs: 15,
+            open_files: 16,
             known_packs: 15,
             unused_slots: 31,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "it opened only a single multi-index and its pack - hard to see it's actually a multi-index as it's just one index anyway…"
     );
@@ -97,16 +552,19 @@ fn multi_index_access() -> crate::Result {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 2,
             open_reachable_indices: 1,
             known_reachable_indices: 1,
             open_reachable_packs: 15,
+            open_files: 16,
             known_packs: 15,
             unused_slots: 31,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "A miss means just another refresh with no other change"
     );
@@ -120,16 +578,19 @@ fn multi_index_access() -> crate::Result {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 2 + 1 /*legit refresh with changes*/ + 1 /*a refresh attempt with no changes, causing 'contains()' to give up*/,
             open_reachable_indices: 1,
             known_reachable_indices: 1,
             open_reachable_packs: 0,
+            open_files: 1,
             known_packs: 15,
             unused_slots: 31,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "everything seems to remain as it was, even though we moved our multi-index to a new slot and removed the old one"
     );
@@ -138,6 +599,81 @@ fn multi_index_access() -> crate::Result {
     Ok(())
 }
 
+#[test]
+fn multi_index_packs_are_known_before_they_are_loaded() -> crate::Result {
+    let dir = git_testtools::scripted_fixture_writable("make_repo_multi_index.sh")?;
+    let objects_dir = dir.path().join(".git/objects");
+    let handle = git_odb::at(&objects_dir)?;
+    let store = handle.store_ref();
+
+    let address_space = store.full_load_address_space()?;
+    let metrics = store.metrics();
+    assert_eq!(metrics.known_packs, 15, "each pack of the multi-pack index is known");
+    assert_eq!(metrics.open_reachable_packs, 0, "but none of them was mapped");
+
+    let mut pack_sizes = 0;
+    for entry in std::fs::read_dir(objects_dir.join("pack"))? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "pack") {
+            pack_sizes += std::fs::metadata(path)?.len();
+        }
+    }
+    assert_eq!(
+        address_space.packs, pack_sizes,
+        "the pack paths are derived from the pack names in the multi-pack index"
+    );
+    Ok(())
+}
+
+#[test]
+fn multi_index_rewritten_in_place_is_picked_up_even_if_its_modification_time_is_unchanged() -> crate::Result {
+    let objects_dir = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &objects_dir)?;
+    let pack_dir = objects_dir.path().join("pack");
+    let added_pack = "pack-c0438c19fb16422b6bbcce24387b3264416d485b";
+    for ext in ["idx", "pack"] {
+        std::fs::remove_file(pack_dir.join(format!("{}.{}", added_pack, ext)))?;
+    }
+    let multi_index_path = pack_dir.join("multi-pack-index");
+    let write_multi_index = |index_names: &[&str]| -> crate::Result {
+        git_odb::pack::multi_index::File::write_from_index_paths(
+            index_names
+                .iter()
+                .map(|name| pack_dir.join(format!("{}.idx", name)))
+                .collect(),
+            std::fs::File::create(&multi_index_path)?,
+            git_features::progress::Discard,
+            &std::sync::atomic::AtomicBool::default(),
+            git_odb::pack::multi_index::write::Options {
+                object_hash: git_hash::Kind::Sha1,
+            },
+        )?;
+        Ok(())
+    };
+    write_multi_index(&["pack-a2bf8e71d8c18879e499335762dd95119d93d9f1"])?;
+
+    let handle = git_odb::at(objects_dir.path())?;
+    let in_added_pack = hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+    assert!(handle.contains(hex_to_id("0d9726f3fd2359cda58217724f12af273bf440a6")));
+    assert!(!handle.contains(in_added_pack));
+
+    for ext in ["idx", "pack"] {
+        std::fs::copy(
+            fixture_path(format!("objects/pack/{}.{}", added_pack, ext)),
+            pack_dir.join(format!("{}.{}", added_pack, ext)),
+        )?;
+    }
+    let mtime = filetime::FileTime::from_last_modification_time(&std::fs::metadata(&multi_index_path)?);
+    write_multi_index(&["pack-a2bf8e71d8c18879e499335762dd95119d93d9f1", added_pack])?;
+    filetime::set_file_mtime(&multi_index_path, mtime)?;
+
+    assert!(
+        handle.contains(in_added_pack),
+        "the multi-pack index changed its size, and is read again to see the pack it now contains"
+    );
+    Ok(())
+}
+
 #[test]
 fn multi_index_keep_open() -> crate::Result {
     let dir = git_testtools::scripted_fixture_writable("make_repo_multi_index.sh")?;
@@ -152,16 +688,19 @@ fn multi_index_keep_open() -> crate::Result {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 2,
             num_refreshes: 1,
             open_reachable_indices: 1,
             known_reachable_indices: 1,
             open_reachable_packs: 0,
+            open_files: 1,
             known_packs: 15,
             unused_slots: 31,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "it opened the multi-pack index for iteration"
     );
@@ -181,16 +720,19 @@ fn multi_index_keep_open() -> crate::Result {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 2,
             num_refreshes: 3,
             open_reachable_indices: 1,
             known_reachable_indices: 1,
             open_reachable_packs: 0, /*no pack is open anymore at least as seen from the index*/
+            open_files: 3,
             known_packs: 15,
             unused_slots: 30,
             loose_dbs: 1,
             unreachable_indices: 1,
-            unreachable_packs: 1
+            unreachable_packs: 1,
+            poisoned_packs: 0
         },
         "now there is an unreachable index and pack which is still loaded, but whose pack hasn't been loaded"
     );
@@ -215,6 +757,137 @@ fn write() -> crate::Result {
     Ok(())
 }
 
+#[test]
+fn object_hash() {
+    let handle = db();
+    let store = handle.store_ref();
+    assert_eq!(store.object_hash(), git_hash::Kind::Sha1);
+    assert_eq!(handle.object_hash(), store.object_hash(), "handles know it as well");
+    assert_eq!(store.object_hash().len_in_bytes(), 20);
+    assert_eq!(store.object_hash().len_in_hex(), 40);
+
+    let debug = format!("{:?}", store);
+    assert!(
+        debug.contains("object_hash: Sha1"),
+        "it's part of the debug snapshot: {}",
+        debug
+    );
+    assert!(debug.contains("metrics: Metrics"), "{}", debug);
+}
+
+#[test]
+fn debug_dump() -> crate::Result {
+    use git_odb::store::structure::{FileState, FileSummary};
+    let objects_dir = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &objects_dir)?;
+    let mut handle = git_odb::at(objects_dir.path())?;
+    handle.refresh_never();
+    handle.packed_object_count()?;
+
+    let file = |name: &str, ext: &str, state: FileState| FileSummary {
+        path: objects_dir.path().join("pack").join(name).with_extension(ext),
+        state,
+    };
+    let slots = |dump: git_odb::store::structure::DebugDump| {
+        assert!(dump.slots.iter().all(|slot| slot.generation == dump.generation));
+        dump.slots
+            .into_iter()
+            .map(|slot| (slot.index.expect("slots are set"), slot.packs))
+            .collect::<Vec<_>>()
+    };
+    // Indices are searched by size, largest first.
+    let (largest, medium, smallest) = (
+        "pack-c0438c19fb16422b6bbcce24387b3264416d485b",
+        "pack-a2bf8e71d8c18879e499335762dd95119d93d9f1",
+        "pack-11fdfa9e156ab73caae3b6da867192221f2089c2",
+    );
+
+    let dump = handle.store_ref().debug_dump();
+    assert_eq!(dump.loose_dbs, vec![objects_dir.path().to_owned()]);
+    assert_eq!(
+        slots(dump),
+        vec![
+            (
+                file(largest, "idx", FileState::Loaded),
+                vec![file(largest, "pack", FileState::Unloaded)]
+            ),
+            (
+                file(medium, "idx", FileState::Loaded),
+                vec![file(medium, "pack", FileState::Unloaded)]
+            ),
+            (
+                file(smallest, "idx", FileState::Loaded),
+                vec![file(smallest, "pack", FileState::Unloaded)]
+            ),
+        ],
+        "all indices are loaded, but none of the packs"
+    );
+
+    let mut buf = Vec::new();
+    handle.find(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut buf)?;
+    std::fs::remove_file(objects_dir.path().join("pack").join(medium).with_extension("pack"))?;
+    assert!(handle
+        .try_find(hex_to_id("0d9726f3fd2359cda58217724f12af273bf440a6"), &mut buf)?
+        .is_none());
+    assert_eq!(
+        slots(handle.store_ref().debug_dump()),
+        vec![
+            (
+                file(largest, "idx", FileState::Loaded),
+                vec![file(largest, "pack", FileState::Unloaded)]
+            ),
+            (
+                file(medium, "idx", FileState::Loaded),
+                vec![file(medium, "pack", FileState::Missing)]
+            ),
+            (
+                file(smallest, "idx", FileState::Loaded),
+                vec![file(smallest, "pack", FileState::Loaded)]
+            ),
+        ],
+        "packs are loaded as needed, and missing ones are noticed when trying to load them"
+    );
+
+    let debug = format!("{:?}", handle.store_ref());
+    assert!(debug.contains("state: DebugDump"), "{}", debug);
+    Ok(())
+}
+
+#[test]
+fn label() -> crate::Result {
+    let handle = git_odb::at_opts(
+        fixture_path("objects"),
+        None,
+        git_odb::store::init::Options {
+            label: Some("fixture".into()),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(handle.store_ref().label(), Some("fixture"));
+    assert_eq!(handle.store_ref().metrics().label.as_deref(), Some("fixture"));
+    assert_eq!(db().store_ref().label(), None, "stores aren't labelled by default");
+
+    let events = handle.store_ref().subscribe(16);
+    let labels = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+    handle.store_ref().on_state_change({
+        let labels = labels.clone();
+        move |label, _, _| labels.lock().push(label.map(ToOwned::to_owned))
+    });
+    assert!(handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")));
+    let events: Vec<_> = events.try_iter().collect();
+    assert!(!events.is_empty());
+    assert!(
+        events.iter().all(|event| event.label.as_deref() == Some("fixture")),
+        "events tell which store they are from"
+    );
+    assert!(!labels.lock().is_empty());
+    assert!(
+        labels.lock().iter().all(|label| label.as_deref() == Some("fixture")),
+        "and so do state change callbacks"
+    );
+    Ok(())
+}
+
 #[test]
 fn object_replacement() -> crate::Result {
     let dir = git_testtools::scripted_fixture_read_only("make_replaced_history.sh")?;
@@ -286,6 +959,102 @@ fn object_replacement() -> crate::Result {
     Ok(())
 }
 
+#[test]
+fn object_replacement_per_handle() -> crate::Result {
+    let mut handle = db();
+    let unaffected = handle.clone();
+    let a = hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+    let b = hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0");
+    let c = hex_to_id("0d9726f3fd2359cda58217724f12af273bf440a6");
+    let missing = hex_to_id("ffffffffffffffffffffffffffffffffffffffff");
+    let data = |id: ObjectId| -> crate::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        Ok(unaffected.find(id, &mut buf)?.data.to_vec())
+    };
+
+    handle.set_replacements(Some(vec![(b, c), (a, b), (missing, a)]));
+    assert_eq!(
+        handle.replacements(),
+        &[(a, b), (b, c), (missing, a)],
+        "replacements are sorted"
+    );
+    assert!(unaffected.replacements().is_empty(), "the store isn't changed");
+
+    let mut buf = Vec::new();
+    assert_eq!(
+        handle.find(a, &mut buf)?.data,
+        data(b)?,
+        "the replacement is returned in place of the replaced object"
+    );
+    assert_eq!(
+        handle.find(b, &mut buf)?.data,
+        data(c)?,
+        "replacements are applied only once per lookup by default"
+    );
+    assert_eq!(
+        unaffected.find(a, &mut buf)?.data,
+        data(a)?,
+        "other handles of the same store see the original"
+    );
+    assert!(handle.contains(missing), "objects are present if their replacement is");
+    assert!(!unaffected.contains(missing));
+    assert_eq!(
+        handle.try_header(missing)?.expect("replaced").size(),
+        data(a)?.len() as u64
+    );
+
+    handle.follow_replacement_chains = true;
+    assert_eq!(
+        handle.find(a, &mut buf)?.data,
+        data(c)?,
+        "chains are followed to their end if enabled"
+    );
+    handle.set_replacements(Some(vec![(a, b), (b, a)]));
+    assert_eq!(
+        handle.find(a, &mut buf)?.data,
+        data(a)?,
+        "cycles end once each replacement was used"
+    );
+
+    handle.ignore_replacements = true;
+    assert_eq!(handle.find(b, &mut buf)?.data, data(b)?);
+    handle.ignore_replacements = false;
+
+    handle.set_replacements(None::<Vec<_>>);
+    assert!(
+        handle.replacements().is_empty(),
+        "the replacements of the store are used again"
+    );
+    assert_eq!(handle.find(a, &mut buf)?.data, data(a)?);
+    Ok(())
+}
+
+#[test]
+fn contains_many() -> crate::Result {
+    let (handle, tmp) = db_with_all_object_sources()?;
+    let mut ids = handle.iter()?.collect::<Result<Vec<_>, _>>()?;
+    ids.reverse();
+    ids.push(hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab980")); // loose object
+    ids.push(ids[0]);
+    ids.insert(1, hex_to_id("0000000000000000000000000000000000000001"));
+    ids.push(hex_to_id("ffffffffffffffffffffffffffffffffffffffff"));
+
+    let new_handle = git_odb::at(tmp.path())?;
+    let expected: Vec<_> = ids.iter().map(|id| handle.contains(id)).collect();
+    assert_eq!(
+        new_handle.contains_many(&ids),
+        expected,
+        "indices are loaded as needed, and the answers are in the order of the input"
+    );
+    assert_eq!(
+        expected.iter().filter(|found| !**found).count(),
+        2,
+        "only the made-up ids are missing"
+    );
+    assert!(handle.contains_many(&[]).is_empty());
+    Ok(())
+}
+
 #[test]
 fn contains() {
     let handle = db();
@@ -294,16 +1063,19 @@ fn contains() {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 1,
             open_reachable_indices: 0,
             known_reachable_indices: 3,
             open_reachable_packs: 0,
+            open_files: 0,
             known_packs: 3,
             unused_slots: 29,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "it only refreshed the file list, yielding the loose db to find this object, but no pack was opened yet"
     );
@@ -314,16 +1086,19 @@ fn contains() {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 1,
             open_reachable_indices: 1,
             known_reachable_indices: 3,
             open_reachable_packs: 0,
+            open_files: 1,
             known_packs: 3,
             unused_slots: 29,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "it loaded the biggest back only, which is the first in the list"
     );
@@ -336,16 +1111,19 @@ fn contains() {
     assert_eq!(
         new_handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 2,
             num_refreshes: 1,
             open_reachable_indices: 3,
             known_reachable_indices: 3,
             open_reachable_packs: 0,
+            open_files: 3,
             known_packs: 3,
             unused_slots: 29,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "when asking for an object in the smallest pack, all in between packs are also loaded."
     );
@@ -354,16 +1132,19 @@ fn contains() {
     assert_eq!(
         new_handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 2,
             num_refreshes: 2,
             open_reachable_indices: 3,
             known_reachable_indices: 3,
             open_reachable_packs: 0,
+            open_files: 3,
             known_packs: 3,
             unused_slots: 29,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "trigger refreshes each time there is an object miss"
     );
@@ -373,16 +1154,19 @@ fn contains() {
     assert_eq!(
         new_handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 2,
             num_refreshes: 2,
             open_reachable_indices: 3,
             known_reachable_indices: 3,
             open_reachable_packs: 0,
+            open_files: 3,
             known_packs: 3,
             unused_slots: 29,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "if no refreshes are allowed, there is no additional refresh"
     );
@@ -408,16 +1192,19 @@ fn lookup() {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 0,
             open_reachable_indices: 0,
             known_reachable_indices: 0,
             open_reachable_packs: 0,
+            open_files: 0,
             known_packs: 0,
             unused_slots: 32,
             loose_dbs: 0,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "nothing happened yet, the store is totally lazy"
     );
@@ -428,16 +1215,19 @@ fn lookup() {
     can_locate(&handle, "dd25c539efbb0ab018caa4cda2d133285634e9b5"); // pack c043
 
     let mut all_loaded = git_odb::store::Metrics {
+        label: None,
         num_handles: 1,
         num_refreshes: 1,
         open_reachable_indices: 3,
         known_reachable_indices: 3,
         open_reachable_packs: 3,
+        open_files: 6,
         known_packs: 3,
         unused_slots: 29,
         loose_dbs: 1,
         unreachable_indices: 0,
         unreachable_packs: 0,
+        poisoned_packs: 0,
     };
     assert_eq!(
         handle.store_ref().metrics(),
@@ -475,16 +1265,19 @@ fn assert_all_indices_loaded(handle: &git_odb::Handle, num_refreshes: usize, ope
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes,
             open_reachable_indices,
             known_reachable_indices: 2,
             open_reachable_packs: 0,
+            open_files: open_reachable_indices,
             known_packs: 3,
             unused_slots: 30,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "all indices must be loaded and searched to assure unambiguous object ids"
     );
@@ -559,10 +1352,12 @@ mod disambiguate_prefix {
         assert_eq!(
             handle.store_ref().metrics(),
             git_odb::store::Metrics {
+                label: None,
                 num_handles: 1,
                 num_refreshes: 1,
                 open_reachable_indices: 1,
                 known_reachable_indices: 2,
+                open_files: 1,
                 known_packs: 3,
                 unused_slots: 30,
                 loose_dbs: 1,
@@ -617,7 +1412,194 @@ mod iter {
         }
         Ok(())
     }
-}
+
+    #[test]
+    fn loose_objects_by_whether_they_are_packed() -> crate::Result {
+        use git_odb::{FindExt, Write};
+        let (mut handle, tmp) = db_with_all_object_sources()?;
+        handle.prevent_pack_unload();
+        let packed = git_testtools::hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+        let mut buf = Vec::new();
+        let data = handle.find(packed, &mut buf)?.data.to_owned();
+        handle.write_buf(git_object::Kind::Blob, &data)?;
+
+        let (mut expected_packed, mut expected_loose_only): (Vec<_>, Vec<_>) =
+            git_odb::loose::Store::at(tmp.path(), git_hash::Kind::Sha1)
+                .iter()
+                .map(Result::unwrap)
+                .partition(|id| git_odb::pack::Find::location_by_oid(&handle, id, &mut buf).is_some());
+        expected_packed.sort();
+        expected_loose_only.sort();
+        assert!(
+            !expected_loose_only.is_empty(),
+            "the fixture has loose objects which aren't packed"
+        );
+        assert_eq!(expected_packed, vec![packed]);
+
+        let mut actual: Vec<_> = handle.iter_loose_only()?.map(Result::unwrap).collect();
+        actual.sort();
+        assert!(
+            !actual.contains(&packed),
+            "loose objects that are also packed are excluded"
+        );
+        assert_eq!(actual, expected_loose_only);
+
+        let actual: Vec<_> = handle.iter_loose_packed()?.map(Result::unwrap).collect();
+        assert_eq!(actual, expected_packed, "only redundant loose objects are returned");
+        Ok(())
+    }
+
+    #[test]
+    fn iteration_by_hash_is_sorted_and_yields_each_object_once() -> crate::Result {
+        use git_odb::{FindExt, Write};
+        let (mut handle, _tmp) = db_with_all_object_sources()?;
+        handle.prevent_pack_unload();
+        let packed = git_testtools::hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+        let mut buf = Vec::new();
+        let data = handle.find(packed, &mut buf)?.data.to_owned();
+        handle.write_buf(git_object::Kind::Blob, &data)?;
+
+        let mut expected: Vec<_> = handle.iter()?.map(Result::unwrap).collect();
+        expected.sort();
+        expected.dedup();
+
+        let actual: Vec<_> = handle.iter_by_hash()?.map(Result::unwrap).collect();
+        assert_eq!(
+            actual, expected,
+            "all objects are returned in order, and the object stored both loose and packed only once"
+        );
+        assert_eq!(
+            handle.store_ref().iter_by_hash()?.count(),
+            expected.len(),
+            "the store provides the same iterator"
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn iteration_by_hash_continues_after_errors_in_loose_object_databases() -> crate::Result {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = git_testtools::tempfile::tempdir()?;
+        let (objects, alternate) = (tmp.path().join("objects"), tmp.path().join("alternate"));
+        let create_object = |objects: &std::path::Path, hex: &str| -> std::io::Result<git_hash::ObjectId> {
+            let dir = objects.join(&hex[..2]);
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(dir.join(&hex[2..]), b"")?;
+            Ok(git_testtools::hex_to_id(hex))
+        };
+        let expected = vec![
+            create_object(&objects, "00aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")?,
+            create_object(&alternate, "11aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")?,
+            create_object(&objects, "ffaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")?,
+        ];
+        std::fs::create_dir_all(objects.join("info"))?;
+        std::fs::write(objects.join("info/alternates"), alternate.to_str().expect("UTF-8"))?;
+        let unreadable = objects.join("0a");
+        std::fs::create_dir(&unreadable)?;
+        std::fs::set_permissions(&unreadable, std::fs::Permissions::from_mode(0o000))?;
+        if std::fs::read_dir(&unreadable).is_ok() {
+            // We are privileged and can read anything, so there is no error.
+            return Ok(());
+        }
+
+        let handle = git_odb::at(&objects)?;
+        let (mut ids, mut num_errors) = (Vec::new(), 0);
+        for res in handle.iter_by_hash()? {
+            match res {
+                Ok(id) => ids.push(id),
+                Err(_) => num_errors += 1,
+            }
+        }
+        std::fs::set_permissions(&unreadable, std::fs::Permissions::from_mode(0o755))?;
+        assert_eq!(num_errors, 1);
+        assert_eq!(
+            ids, expected,
+            "the loose object database with the error is still traversed to its end, as is the alternate"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn iteration_by_hash_yields_the_objects_git_knows() -> crate::Result {
+        use git_odb::{FindExt, Write};
+        let tmp = git_testtools::tempfile::TempDir::new()?;
+        assert!(
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(tmp.path())
+                .arg("init")
+                .arg("--bare")
+                .status()?
+                .success(),
+            "git should work"
+        );
+        git_testtools::copy_recursively_into_existing_dir(
+            git_testtools::fixture_path("objects"),
+            tmp.path().join("objects"),
+        )?;
+        let handle = git_odb::at(tmp.path().join("objects"))?;
+        let mut buf = Vec::new();
+        let data = handle
+            .find(
+                git_testtools::hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"),
+                &mut buf,
+            )?
+            .data
+            .to_owned();
+        handle.write_buf(git_object::Kind::Blob, &data)?;
+
+        let out = std::process::Command::new("git")
+            .arg("-C")
+            .arg(tmp.path())
+            .args(["cat-file", "--batch-all-objects", "--batch-check=%(objectname)"])
+            .output()?;
+        assert!(out.status.success(), "git should work");
+        let expected = std::str::from_utf8(&out.stdout)?
+            .lines()
+            .map(|line| git_hash::ObjectId::from_hex(line.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let actual: Vec<_> = handle.iter_by_hash()?.collect::<Result<_, _>>()?;
+        assert_eq!(
+            actual, expected,
+            "objects stored both loose and packed are listed once, just like git does"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn iteration_by_hash_in_range_yields_objects_within_inclusive_bounds() -> crate::Result {
+        let (handle, _tmp) = db_with_all_object_sources()?;
+        let all: Vec<_> = handle.iter_by_hash()?.map(Result::unwrap).collect();
+        let (start, end) = (all[10], all[all.len() - 10]);
+
+        let actual: Vec<_> = handle.iter_by_hash_in_range(start..=end)?.map(Result::unwrap).collect();
+        assert_eq!(
+            actual,
+            all[10..=all.len() - 10],
+            "packed and loose objects are yielded in order, including the bounds"
+        );
+
+        let (before_start, after_end) = (
+            git_testtools::hex_to_id("0000000000000000000000000000000000000000"),
+            git_testtools::hex_to_id("ffffffffffffffffffffffffffffffffffffffff"),
+        );
+        assert_eq!(
+            handle
+                .store_ref()
+                .iter_by_hash_in_range(before_start..=after_end)?
+                .count(),
+            all.len(),
+            "the range can extend beyond all objects"
+        );
+        assert_eq!(
+            handle.iter_by_hash_in_range(end..=start)?.count(),
+            0,
+            "empty ranges yield nothing"
+        );
+        Ok(())
+    }
+}
 
 mod lookup_prefix {
     use std::collections::HashSet;
@@ -706,16 +1688,19 @@ fn missing_objects_triggers_everything_is_loaded() {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 2,
             open_reachable_indices: 3,
             known_reachable_indices: 3,
             open_reachable_packs: 0,
+            open_files: 3,
             known_packs: 3,
             unused_slots: 29,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "first refresh triggered by on-disk check, second refresh triggered to see if something changed, contains() only sees indices"
     );
@@ -728,16 +1713,19 @@ fn missing_objects_triggers_everything_is_loaded() {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 3,
             open_reachable_indices: 3,
             known_reachable_indices: 3,
             open_reachable_packs: 0,
+            open_files: 3,
             known_packs: 3,
             unused_slots: 29,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "there are still no packs opened as no index contained the object"
     );
@@ -799,16 +1787,19 @@ fn auto_refresh_with_and_without_id_stability() -> crate::Result {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 1,
             open_reachable_indices: 1,
             known_reachable_indices: 1,
             open_reachable_packs: 1,
+            open_files: 2,
             known_packs: 1,
             unused_slots: 31,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "one pack was opened"
     );
@@ -824,16 +1815,19 @@ fn auto_refresh_with_and_without_id_stability() -> crate::Result {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 2,
             open_reachable_indices: 1,
             known_reachable_indices: 1,
             open_reachable_packs: 1,
+            open_files: 2,
             known_packs: 1,
             unused_slots: 31,
             loose_dbs: 1,
             unreachable_indices: 0,
-            unreachable_packs: 0
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
         "the old pack was removed, the new was loaded"
     );
@@ -862,16 +1856,19 @@ fn auto_refresh_with_and_without_id_stability() -> crate::Result {
         assert_eq!(
             handle.store_ref().metrics(),
             git_odb::store::Metrics {
+                label: None,
                 num_handles: 2,
                 num_refreshes: 3,
                 open_reachable_indices: 1,
                 known_reachable_indices: 1,
                 open_reachable_packs: 1,
+                open_files: 4,
                 known_packs: 1,
                 unused_slots: 30,
                 loose_dbs: 1,
                 unreachable_indices: 1,
-                unreachable_packs: 1
+                unreachable_packs: 1,
+                poisoned_packs: 0
             },
             "the removed pack is still loaded"
         );
@@ -897,73 +1894,3875 @@ fn auto_refresh_with_and_without_id_stability() -> crate::Result {
     assert_eq!(
         handle.store_ref().metrics(),
         git_odb::store::Metrics {
+            label: None,
             num_handles: 1,
             num_refreshes: 4,
             open_reachable_indices: 1,
             known_reachable_indices: 1,
             open_reachable_packs: 1,
+            open_files: 2,
             known_packs: 1,
-            unused_slots: 30,
+            unused_slots: 31,
             loose_dbs: 1,
-            unreachable_indices: 1,
-            unreachable_packs: 1
+            unreachable_indices: 0,
+            unreachable_packs: 0,
+            poisoned_packs: 0
         },
-        "garbaged slots aren't reclaimed until there is the need. Keeping indices open despite them not being accessible anymore."
+        "garbaged slots are reclaimed on refresh once no handle requires stable pack ids anymore"
     );
     Ok(())
 }
 
-mod verify {
-    use std::sync::atomic::AtomicBool;
-
-    use git_features::progress;
-    use git_testtools::fixture_path;
+#[test]
+fn garbage_slots_serve_lookups_only_if_configured() -> crate::Result {
+    use git_pack::Find;
+    for serve_garbage in [true, false] {
+        let tmp = git_testtools::tempfile::TempDir::new()?;
+        git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), tmp.path())?;
+        let mut handle = git_odb::at_opts(
+            tmp.path(),
+            None,
+            git_odb::store::init::Options {
+                garbage_slots_serve_lookups: serve_garbage,
+                ..Default::default()
+            },
+        )?;
+        handle.prevent_pack_unload();
+        let mut buf = Vec::new();
+        let location = handle
+            .location_by_oid(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut buf)
+            .expect("object exists");
+        assert!(handle.entry_by_location(&location).is_some());
 
-    use crate::store::dynamic::db;
+        let stem = tmp.path().join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2");
+        std::fs::remove_file(stem.with_extension("idx"))?;
+        std::fs::remove_file(stem.with_extension("pack"))?;
+        assert!(
+            handle
+                .location_by_oid(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), &mut buf)
+                .is_none(),
+            "the miss triggers a refresh which keeps the removed pack as garbage"
+        );
+        assert_eq!(handle.store_ref().metrics().unreachable_packs, 1);
 
-    #[test]
-    fn integrity() {
-        let handle = db();
-        let outcome = handle
-            .store_ref()
-            .verify_integrity(progress::Discard, &AtomicBool::new(false), Default::default())
-            .unwrap();
-        assert_eq!(outcome.index_statistics.len(), 3, "there are only three packs to check");
         assert_eq!(
-            outcome.index_statistics[0].path,
-            fixture_path("objects/pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b.idx")
+            handle.entry_by_location(&location).is_some(),
+            serve_garbage,
+            "the removed pack is still mapped, but only serves lookups if configured"
         );
         assert_eq!(
-            outcome.index_statistics[1].path,
-            fixture_path("objects/pack/pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.idx")
+            handle.clone().entry_by_location(&location).is_some(),
+            serve_garbage,
+            "the same is true for handles which don't know the pack yet"
         );
-        assert_eq!(
-            outcome.index_statistics[2].path,
-            fixture_path("objects/pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.idx")
+    }
+    Ok(())
+}
+
+#[test]
+fn deleted_packs_are_reclaimed_on_refresh() -> crate::Result {
+    for keep_deleted_packs_available in [false, true] {
+        let tmp = git_testtools::tempfile::TempDir::new()?;
+        git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), tmp.path())?;
+        let mut handle = git_odb::at(tmp.path())?;
+        if keep_deleted_packs_available {
+            handle.prevent_pack_unload();
+        }
+        let in_removed_pack = hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0");
+        let mut buf = Vec::new();
+        assert!(handle.find(in_removed_pack, &mut buf).is_ok());
+
+        let stem = tmp.path().join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2");
+        std::fs::remove_file(stem.with_extension("idx"))?;
+        std::fs::remove_file(stem.with_extension("pack"))?;
+        assert!(
+            !handle.contains(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")),
+            "the miss triggers a refresh"
         );
-        assert_eq!(
-            outcome.loose_object_stores,
-            vec![git_odb::store::verify::integrity::LooseObjectStatistics {
-                path: fixture_path("objects"),
-                statistics: git_odb::loose::verify::integrity::Statistics { num_objects: 7 }
-            }]
+        assert!(
+            !handle.contains(in_removed_pack),
+            "objects of the removed pack can't be found anymore"
+        );
+        assert!(
+            handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")),
+            "objects in other packs are still found"
+        );
+
+        let metrics = handle.store_ref().metrics();
+        assert_eq!(metrics.known_packs, 2);
+        if keep_deleted_packs_available {
+            assert_eq!(
+                (metrics.unreachable_indices, metrics.unreachable_packs),
+                (1, 1),
+                "the slot is kept for handles that rely on stable pack ids"
+            );
+        } else {
+            assert_eq!(
+                (metrics.unreachable_indices, metrics.unreachable_packs),
+                (0, 0),
+                "the slot is cleared right away"
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn garbage_is_freed_once_no_handle_requires_stable_pack_ids() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), tmp.path())?;
+    let mut stable_handle = git_odb::at(tmp.path())?;
+    stable_handle.prevent_pack_unload();
+    let mut buf = Vec::new();
+    stable_handle.find(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut buf)?;
+
+    let stem = tmp.path().join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2");
+    std::fs::remove_file(stem.with_extension("idx"))?;
+    std::fs::remove_file(stem.with_extension("pack"))?;
+    let store = stable_handle.store();
+    let outcome = store.refresh()?;
+    assert!(outcome.changed);
+    assert!(!outcome.generation_changed, "pack ids have to remain valid");
+    let metrics = store.metrics();
+    assert_eq!((metrics.unreachable_indices, metrics.unreachable_packs), (1, 1));
+
+    drop(stable_handle);
+    let outcome = store.refresh()?;
+    assert!(
+        outcome.generation_changed,
+        "the slot of the removed pack is freed, which invalidates its pack ids"
+    );
+    assert_eq!(outcome.num_indices, 2, "the remaining indices are still known");
+    let after = store.metrics();
+    assert_eq!((after.unreachable_indices, after.unreachable_packs), (0, 0));
+    assert_eq!(after.unused_slots, metrics.unused_slots + 1);
+
+    assert!(
+        !store.refresh()?.generation_changed,
+        "without garbage, the generation stays the same"
+    );
+    Ok(())
+}
+
+#[test]
+fn refresh_after_duration_throttles_refreshes() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), tmp.path())?;
+    let aside = git_testtools::tempfile::TempDir::new()?;
+    let stem = tmp.path().join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2");
+    for extension in ["idx", "pack"] {
+        std::fs::rename(stem.with_extension(extension), aside.path().join(extension))?;
+    }
+
+    let min_interval = std::time::Duration::from_millis(500);
+    let mut handle = git_odb::at(tmp.path())?;
+    handle.refresh = store::RefreshMode::AfterDuration(min_interval);
+    let in_hidden_pack = hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0");
+    assert!(
+        !handle.contains(in_hidden_pack),
+        "the initial refresh can't see the pack"
+    );
+    for extension in ["idx", "pack"] {
+        std::fs::rename(aside.path().join(extension), stem.with_extension(extension))?;
+    }
+
+    assert!(
+        !handle.contains(in_hidden_pack),
+        "it's too early to refresh again, so the pack isn't seen yet"
+    );
+    assert_eq!(handle.store_ref().metrics().num_refreshes, 1);
+
+    std::thread::sleep(min_interval);
+    assert!(
+        handle.contains(in_hidden_pack),
+        "enough time passed for the miss to trigger a refresh"
+    );
+    assert_eq!(handle.store_ref().metrics().num_refreshes, 2);
+    Ok(())
+}
+
+#[test]
+fn alternates_changes_keep_pack_ids_stable() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let objects_dir = tmp.path().join("objects");
+    let alternate_dir = tmp.path().join("alternate");
+    std::fs::create_dir_all(objects_dir.join("info"))?;
+    std::fs::create_dir_all(objects_dir.join("pack"))?;
+    std::fs::create_dir_all(&alternate_dir)?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects/pack"), objects_dir.join("pack"))?;
+
+    let mut handle = git_odb::at(&objects_dir)?;
+    handle.prevent_pack_unload();
+    let mut buf = Vec::new();
+    let location =
+        git_odb::pack::Find::location_by_oid(&handle, hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"), &mut buf)
+            .expect("object is packed");
+
+    let alternate_id = git_odb::loose::Store::at(&alternate_dir, git_hash::Kind::Sha1)
+        .write_buf(git_object::Kind::Blob, b"alternate")?;
+    assert!(
+        !handle.contains(alternate_id),
+        "the alternate isn't known yet, but it triggers a refresh"
+    );
+    std::fs::write(
+        objects_dir.join("info/alternates"),
+        alternate_dir.to_str().expect("valid UTF-8"),
+    )?;
+    assert!(
+        handle.contains(alternate_id),
+        "the new alternate was picked up with a refresh"
+    );
+    assert_eq!(handle.store_ref().metrics().loose_dbs, 2);
+    assert!(
+        git_odb::pack::Find::entry_by_location(&handle, &location).is_some(),
+        "the pack id is still valid as only the alternates changed"
+    );
+    Ok(())
+}
+
+mod wait_for_object {
+    use std::{sync::atomic::AtomicBool, time::Duration};
+
+    use git_odb::{store::wait, Write};
+    use git_testtools::hex_to_id;
+
+    #[test]
+    fn returns_true_once_the_object_was_written_by_someone_else() -> crate::Result {
+        let dir = git_testtools::tempfile::tempdir()?;
+        let mut handle = git_odb::at(dir.path())?;
+        handle.refresh_never();
+        let id = hex_to_id("95d09f2b10159347eece71399a7e2e907ea3df4f");
+        assert!(!git_odb::Find::contains(&handle, id));
+
+        let writer = std::thread::spawn({
+            let objects_dir = dir.path().to_owned();
+            move || {
+                std::thread::sleep(Duration::from_millis(50));
+                git_odb::loose::Store::at(objects_dir, git_hash::Kind::Sha1)
+                    .write_buf(git_object::Kind::Blob, b"hello world")
+                    .expect("write works")
+            }
+        });
+        assert!(
+            handle.wait_for_object(id, wait::Options::default(), &AtomicBool::default()),
+            "the handle refreshes despite its refresh mode"
+        );
+        assert_eq!(writer.join().expect("no panic"), id);
+        Ok(())
+    }
+
+    #[test]
+    fn returns_false_on_timeout_or_interrupt() -> crate::Result {
+        let dir = git_testtools::tempfile::tempdir()?;
+        let handle = git_odb::at(dir.path())?;
+        let id = hex_to_id("95d09f2b10159347eece71399a7e2e907ea3df4f");
+        let options = wait::Options {
+            timeout: Duration::from_millis(20),
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        assert!(!handle.wait_for_object(id, options, &AtomicBool::default()));
+        assert!(!handle.wait_for_object(
+            id,
+            wait::Options {
+                timeout: Duration::from_secs(60),
+                ..options
+            },
+            &AtomicBool::new(true)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn zero_backoffs_do_not_cause_a_busy_loop() -> crate::Result {
+        let dir = git_testtools::tempfile::tempdir()?;
+        let handle = git_odb::at(dir.path())?;
+        let id = hex_to_id("95d09f2b10159347eece71399a7e2e907ea3df4f");
+        let options = wait::Options {
+            timeout: Duration::from_millis(50),
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            ..Default::default()
+        };
+        let before = handle.store_ref().metrics().num_refreshes;
+        assert!(!handle.wait_for_object(id, options, &AtomicBool::default()));
+        assert!(
+            handle.store_ref().metrics().num_refreshes - before <= 2 * 51,
+            "there is at least a millisecond between checks"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn interrupts_are_noticed_while_waiting_in_between_checks() -> crate::Result {
+        let dir = git_testtools::tempfile::tempdir()?;
+        let handle = git_odb::at(dir.path())?;
+        let id = hex_to_id("95d09f2b10159347eece71399a7e2e907ea3df4f");
+        let options = wait::Options {
+            timeout: Duration::from_secs(60),
+            initial_backoff: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let should_interrupt = std::sync::Arc::new(AtomicBool::default());
+        let interrupter = std::thread::spawn({
+            let should_interrupt = should_interrupt.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(20));
+                should_interrupt.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        });
+        let start = std::time::Instant::now();
+        assert!(!handle.wait_for_object(id, options, &should_interrupt));
+        assert!(
+            start.elapsed() < Duration::from_secs(30),
+            "the backoff isn't slept through in full"
         );
+        interrupter.join().expect("no panic");
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_backoff_multipliers_keep_the_backoff_unchanged() -> crate::Result {
+        let dir = git_testtools::tempfile::tempdir()?;
+        let handle = git_odb::at(dir.path())?;
+        let id = hex_to_id("95d09f2b10159347eece71399a7e2e907ea3df4f");
+        for backoff_multiplier in [-2.0, 0.5, f32::NAN, f32::INFINITY, f32::MAX] {
+            let options = wait::Options {
+                timeout: Duration::from_millis(10),
+                initial_backoff: Duration::from_millis(1),
+                backoff_multiplier,
+                ..Default::default()
+            };
+            assert!(
+                !handle.wait_for_object(id, options, &AtomicBool::default()),
+                "{} doesn't cause a panic",
+                backoff_multiplier
+            );
+        }
+        Ok(())
+    }
+}
 
+mod at_index_paths {
+    use git_odb::{Find, FindExt};
+    use git_testtools::{fixture_path, hex_to_id};
+
+    #[test]
+    fn objects_can_be_found_in_given_packs_only() -> crate::Result {
+        let store = git_odb::Store::at_index_paths(
+            Some(fixture_path(
+                "objects/pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b.idx",
+            )),
+            git_hash::Kind::Sha1,
+        )?;
+        let handle = git_odb::Cache::from(git_features::threading::OwnShared::new(store).to_handle());
+        let mut buf = Vec::new();
+        assert!(handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")));
+        assert_eq!(
+            handle
+                .find(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"), &mut buf)?
+                .kind,
+            git_object::Kind::Blob
+        );
+        assert!(
+            !handle.contains(hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab980")),
+            "loose objects are unknown"
+        );
         assert_eq!(
             handle.store_ref().metrics(),
             git_odb::store::Metrics {
+                label: None,
                 num_handles: 1,
-                num_refreshes: 1,
-                open_reachable_indices: 0,
-                known_reachable_indices: 3,
-                open_reachable_packs: 0,
-                known_packs: 3,
-                unused_slots: 29,
-                loose_dbs: 1,
+                num_refreshes: 0,
+                open_reachable_indices: 1,
+                known_reachable_indices: 1,
+                open_reachable_packs: 1,
+                open_files: 2,
+                known_packs: 1,
+                unused_slots: 0,
+                loose_dbs: 0,
                 unreachable_indices: 0,
-                unreachable_packs: 0
+                unreachable_packs: 0,
+                poisoned_packs: 0
             },
-            "verification only discovers files on disk but won't cause them to be opened permanently"
+            "the disk is never scanned"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn objects_can_be_found_after_closing_the_store() -> crate::Result {
+        let store = git_odb::Store::at_index_paths(
+            Some(fixture_path(
+                "objects/pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b.idx",
+            )),
+            git_hash::Kind::Sha1,
+        )?;
+        let store = git_features::threading::OwnShared::new(store);
+        let handle = store.to_cache();
+        let id = hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+        let mut buf = Vec::new();
+        handle.find(id, &mut buf)?;
+
+        store.close()?;
+        assert_eq!(store.memory_usage().mapped_packs, 0);
+        assert_eq!(
+            handle.find(id, &mut buf)?.kind,
+            git_object::Kind::Blob,
+            "the given index is used again even though the disk is never scanned"
+        );
+        assert_eq!(
+            store.to_cache().find(id, &mut buf)?.kind,
+            git_object::Kind::Blob,
+            "new handles can be created"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn writes_fail_as_there_is_no_loose_object_database() -> crate::Result {
+        use git_odb::Write;
+        let store = git_odb::Store::at_index_paths(
+            Some(fixture_path(
+                "objects/pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b.idx",
+            )),
+            git_hash::Kind::Sha1,
+        )?;
+        let handle = git_odb::Cache::from(git_features::threading::OwnShared::new(store).to_handle());
+        for _attempt in 0..2 {
+            assert!(matches!(
+                handle.write_buf(git_object::Kind::Blob, b"hello world"),
+                Err(git_odb::store::write::Error::NoLooseDb)
+            ));
+        }
+        assert!(
+            handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")),
+            "the handle remains usable"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn at_least_one_index_with_pack_is_required() {
+        assert!(git_odb::Store::at_index_paths(None::<std::path::PathBuf>, git_hash::Kind::Sha1).is_err());
+        assert!(git_odb::Store::at_index_paths(
+            Some(fixture_path("objects/pack/does-not-exist.idx")),
+            git_hash::Kind::Sha1
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn packs_not_matching_their_index_are_rejected_if_configured() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let pack_dir = tmp.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects/pack"), &pack_dir)?;
+    let (good, other) = (
+        pack_dir.join("pack-c0438c19fb16422b6bbcce24387b3264416d485b.pack"),
+        pack_dir.join("pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.pack"),
+    );
+    std::fs::copy(&other, &good)?;
+
+    let handle = git_odb::at_opts(
+        tmp.path(),
+        None,
+        git_odb::store::init::Options {
+            verify_pack_matches_index_on_load: true,
+            ..Default::default()
+        },
+    )?;
+    let id = hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+    let mut buf = Vec::new();
+    for _attempt in 0..2 {
+        let err = handle.try_find(id, &mut buf).unwrap_err();
+        assert!(
+            matches!(err, git_odb::store::find::Error::LoadPack(ref err) if err.kind() == std::io::ErrorKind::InvalidData),
+            "the mismatch is detected each time: {:?}",
+            err
         );
     }
+    assert!(
+        handle
+            .try_find(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut buf)?
+            .is_some(),
+        "other packs are unaffected"
+    );
+    Ok(())
+}
+
+#[test]
+fn malformed_files_are_reported_with_their_path() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let pack_dir = tmp.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects/pack"), &pack_dir)?;
+    let (bad_signature, bad_version) = (
+        pack_dir.join("pack-c0438c19fb16422b6bbcce24387b3264416d485b.pack"),
+        pack_dir.join("pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.idx"),
+    );
+    let overwrite = |path: &std::path::Path, ofs: usize, bytes: &[u8]| -> std::io::Result<()> {
+        let mut data = std::fs::read(path)?;
+        data[ofs..][..bytes.len()].copy_from_slice(bytes);
+        std::fs::write(path, data)
+    };
+    overwrite(&bad_signature, 0, b"PACX")?;
+    overwrite(&bad_version, 4, &9u32.to_be_bytes())?;
+
+    let handle = git_odb::at(tmp.path())?;
+    let mut buf = Vec::new();
+    for _attempt in 0..2 {
+        let err = handle
+            .try_find(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"), &mut buf)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(&bad_signature.display().to_string()) && err.contains("\"PACX\""),
+            "the error names the file and the problem each time: {}",
+            err
+        );
+    }
+    assert!(
+        handle
+            .try_find(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut buf)?
+            .is_some(),
+        "other packs are unaffected"
+    );
+    assert!(
+        handle
+            .try_find(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), &mut buf)?
+            .is_none(),
+        "malformed indices are skipped"
+    );
+
+    let mut corrupt = handle.store_ref().corrupt_files();
+    corrupt.sort();
+    assert_eq!(corrupt.len(), 2, "{:?}", corrupt);
+    assert_eq!(corrupt[0].0, bad_version);
+    assert!(
+        corrupt[0].1.contains(&bad_version.display().to_string())
+            && corrupt[0].1.ends_with("Unsupported index version: 9"),
+        "{}",
+        corrupt[0].1
+    );
+    assert_eq!(corrupt[1].0, bad_signature);
+    Ok(())
+}
+
+#[test]
+fn packs_found_to_be_corrupt_while_reading_are_quarantined_if_configured() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), tmp.path())?;
+    let handle = git_odb::at_opts(
+        tmp.path(),
+        None,
+        git_odb::store::init::Options {
+            quarantine_corrupt_packs: true,
+            ..Default::default()
+        },
+    )?;
+    let pack_path = tmp
+        .path()
+        .join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack");
+    let original = std::fs::read(&pack_path)?;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(&pack_path)?
+        .set_len(original.len() as u64 / 2)?;
+
+    let beyond_truncation = hex_to_id("15926d8d6d17d1cbdf7f03c457e8ff983270f363");
+    let mut buf = Vec::new();
+    assert!(
+        handle.try_find(beyond_truncation, &mut buf)?.is_none(),
+        "the object is only contained in the truncated pack, which is skipped instead of failing the lookup"
+    );
+    assert_eq!(handle.store_ref().metrics().poisoned_packs, 1);
+    let corrupt = handle.store_ref().corrupt_files();
+    assert_eq!(corrupt.len(), 1);
+    assert_eq!(corrupt[0].0, pack_path);
+    assert!(
+        corrupt[0].1.contains("lies beyond the end of the pack"),
+        "{}",
+        corrupt[0].1
+    );
+
+    assert!(
+        handle
+            .try_find(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"), &mut buf)?
+            .is_some(),
+        "objects in other packs remain available"
+    );
+    assert!(
+        handle
+            .store()
+            .to_handle()
+            .try_find(hex_to_id("0ead45fc727edcf5cadca25ef922284f32bb6fc1"), &mut buf)?
+            .is_none(),
+        "the poisoned pack isn't used by any handle, even for objects it could still provide"
+    );
+
+    std::fs::write(&pack_path, &original)?;
+    filetime::set_file_mtime(&pack_path, filetime::FileTime::from_unix_time(1_000_000_000, 0))?;
+    handle.store_ref().refresh()?;
+    assert_eq!(
+        handle.store_ref().metrics().poisoned_packs,
+        0,
+        "the poison is lifted as the pack changed on disk"
+    );
+    assert_eq!(
+        handle.find(beyond_truncation, &mut buf)?.kind,
+        git_object::Kind::Blob,
+        "the repaired pack is used again"
+    );
+    Ok(())
+}
+
+#[test]
+fn objects_are_found_elsewhere_if_their_pack_vanishes_before_its_index() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let pack_dir = tmp.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects/pack"), &pack_dir)?;
+    let (id, only_in_vanished_pack) = (
+        hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"),
+        hex_to_id("036bd66fe9b6591e959e6df51160e636ab1a682e"),
+    );
+    let mut buf = Vec::new();
+    let data = db().find(id, &mut buf)?.data.to_owned();
+
+    let handle = git_odb::at(tmp.path())?;
+    assert!(handle.contains(id), "the index was loaded, but the pack wasn't");
+    std::fs::remove_file(pack_dir.join("pack-c0438c19fb16422b6bbcce24387b3264416d485b.pack"))?;
+    handle.write_buf(git_object::Kind::Blob, &data)?;
+
+    for _attempt in 0..2 {
+        let obj = handle.try_find(id, &mut buf)?.expect("found as loose object");
+        assert_eq!(obj.data, data);
+        assert_eq!(handle.try_header(id)?.map(|h| h.kind()), Some(git_object::Kind::Blob));
+        assert!(
+            handle.try_find(only_in_vanished_pack, &mut buf)?.is_none(),
+            "objects that were only in the vanished pack are now missing, without error"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn refresh_events_are_sent_to_subscribers() -> crate::Result {
+    use git_odb::store::events::{Change, Event};
+    let tmp = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &tmp)?;
+    let handle = git_odb::at(tmp.path())?;
+    let events = handle.store_ref().subscribe(16);
+    let lagging = handle.store_ref().subscribe(1);
+    drop(handle.store_ref().subscribe(16));
+
+    let file_names = |events: Vec<Event>| {
+        let mut names: Vec<_> = events
+            .into_iter()
+            .map(|event| match event.change {
+                Change::PackAdded(path) => format!("+{}", path.file_name().expect("named").to_string_lossy()),
+                Change::PackRemoved(path) => format!("-{}", path.file_name().expect("named").to_string_lossy()),
+                Change::GenerationBumped => "generation".into(),
+            })
+            .collect();
+        names.sort();
+        names
+    };
+    assert!(handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")));
+    assert_eq!(
+        file_names(events.try_iter().collect()),
+        [
+            "+pack-11fdfa9e156ab73caae3b6da867192221f2089c2.idx",
+            "+pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.idx",
+            "+pack-c0438c19fb16422b6bbcce24387b3264416d485b.idx"
+        ],
+        "all indices are discovered with the first refresh"
+    );
+    assert_eq!(
+        lagging.try_iter().count(),
+        1,
+        "events that don't fit are dropped instead of blocking the refresh"
+    );
+
+    for extension in ["pack", "idx"] {
+        std::fs::remove_file(
+            tmp.path()
+                .join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2")
+                .with_extension(extension),
+        )?;
+    }
+    assert!(!handle.contains(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")));
+    assert_eq!(
+        file_names(events.try_iter().collect()),
+        ["-pack-11fdfa9e156ab73caae3b6da867192221f2089c2.idx"]
+    );
+    Ok(())
+}
+
+#[test]
+fn state_change_callbacks_receive_the_previous_and_current_marker() -> crate::Result {
+    let objects_dir = git_testtools::tempfile::tempdir()?;
+    let pack_dir = objects_dir.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    let write_pack = |name: &str| -> std::io::Result<()> {
+        for ext in ["idx", "pack"] {
+            let file_name = format!("pack-{}.{}", name, ext);
+            std::fs::copy(fixture_path("objects/pack").join(&file_name), pack_dir.join(file_name))?;
+        }
+        Ok(())
+    };
+    write_pack("11fdfa9e156ab73caae3b6da867192221f2089c2")?;
+    let handle = git_odb::at(objects_dir.path())?;
+    let in_new_pack = hex_to_id("0d9726f3fd2359cda58217724f12af273bf440a6");
+    assert!(!handle.contains(in_new_pack), "all indices are loaded");
+
+    let store = handle.store();
+    let changes = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+    for _ in 0..2 {
+        let changes = changes.clone();
+        store.on_state_change(move |label, previous, current| {
+            assert_eq!(label, None, "the store isn't labelled");
+            changes.lock().push((*previous, *current))
+        });
+    }
+    let previous = store.marker();
+    write_pack("a2bf8e71d8c18879e499335762dd95119d93d9f1")?;
+    assert!(handle.contains(in_new_pack), "the miss picks up the new pack");
+
+    let current = store.marker();
+    assert_eq!(
+        *changes.lock(),
+        vec![(previous, current); 2],
+        "each callback is called exactly once, with the markers before and after the refresh"
+    );
+    assert_eq!(current.change_since(&previous), store::MarkerChange::Updated);
+    Ok(())
+}
+
+#[test]
+fn pack_count_pressure() -> crate::Result {
+    let tmp = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &tmp)?;
+    let aside = git_testtools::tempfile::tempdir()?;
+    let pack = "pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2";
+    for extension in ["pack", "idx"] {
+        std::fs::rename(
+            tmp.path().join(pack).with_extension(extension),
+            aside.path().join(extension),
+        )?;
+    }
+    let handle = git_odb::at(tmp.path())?;
+    assert!(
+        handle.store_ref().pack_count_pressure().is_none(),
+        "nothing was observed yet"
+    );
+
+    assert!(handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")));
+    let history = handle.store_ref().pack_count_history();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].num_packs, 2);
+    assert_eq!(history[0].num_indices, 2);
+
+    for extension in ["pack", "idx"] {
+        std::fs::rename(
+            aside.path().join(extension),
+            tmp.path().join(pack).with_extension(extension),
+        )?;
+    }
+    assert!(handle.contains(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0")));
+    let pressure = handle.store_ref().pack_count_pressure().expect("refreshed");
+    assert_eq!(pressure.num_samples, handle.store_ref().pack_count_history().len());
+    assert_eq!(pressure.oldest_num_packs, 2);
+    assert_eq!(pressure.newest_num_packs, 3);
+    assert_eq!(pressure.num_increases, 1);
+    assert_eq!(pressure.num_decreases, 0);
+    assert!(pressure.is_rising(), "a pack was added without any being removed");
+    Ok(())
+}
+
+#[test]
+fn packs_covering() -> crate::Result {
+    let mut handle = db();
+    handle.prevent_pack_unload();
+    let (packed_big, packed_small, loose, missing) = (
+        hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"),
+        hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"),
+        hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab980"),
+        hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"),
+    );
+    let outcome = handle.packs_covering(&[packed_big, loose, packed_small, missing, packed_big])?;
+    assert_eq!(outcome.loose, vec![loose]);
+    assert_eq!(outcome.missing, vec![missing]);
+    assert_eq!(outcome.packs.len(), 2, "each packed object is in a different pack");
+    assert_eq!(
+        outcome.packs[0].1, 2,
+        "the pack providing the most objects comes first, and duplicate ids are counted"
+    );
+    assert_eq!(outcome.packs[1].1, 1);
+
+    let mut buf = Vec::new();
+    let location = git_odb::pack::Find::location_by_oid(&handle, packed_big, &mut buf).expect("packed");
+    assert_eq!(
+        outcome.packs[0].0, location.pack_id,
+        "pack ids are the same as in pack locations"
+    );
+    Ok(())
+}
+
+#[test]
+fn slots_of_removed_packs_can_be_reused_for_new_packs() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let pack_dir = tmp.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    let copy_pack = |name: &str| -> std::io::Result<()> {
+        for ext in ["idx", "pack"] {
+            let file_name = format!("pack-{}.{}", name, ext);
+            std::fs::copy(fixture_path("objects/pack").join(&file_name), pack_dir.join(file_name))?;
+        }
+        Ok(())
+    };
+    copy_pack("c0438c19fb16422b6bbcce24387b3264416d485b")?;
+    let handle = git_odb::at_opts(
+        tmp.path(),
+        None,
+        git_odb::store::init::Options {
+            slots: git_odb::store::init::Slots::Given(1),
+            ..Default::default()
+        },
+    )?;
+    assert!(handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")));
+
+    for ext in ["idx", "pack"] {
+        std::fs::remove_file(pack_dir.join(format!("pack-c0438c19fb16422b6bbcce24387b3264416d485b.{}", ext)))?;
+    }
+    copy_pack("11fdfa9e156ab73caae3b6da867192221f2089c2")?;
+    let in_new_pack = hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0");
+    assert!(
+        handle.contains(in_new_pack),
+        "the only slot is reused for the new pack within the same refresh that removes the old one"
+    );
+    assert_eq!(
+        handle.store_ref().metrics().known_packs,
+        1,
+        "the reused slot isn't cleared as the old pack is removed"
+    );
+    let mut buf = Vec::new();
+    assert!(
+        handle.try_find(in_new_pack, &mut buf)?.is_some(),
+        "the new pack can be loaded from its slot"
+    );
+    Ok(())
+}
+
+#[test]
+fn delta_base_id() -> crate::Result {
+    use git_odb::store::delta::Error;
+    let handle = db();
+    assert_eq!(
+        handle.delta_base_id(hex_to_id("2c1e59ee54facb7d72c0061d06b9fe3889f357a9"))?,
+        Some(hex_to_id("1dfd336d2290794b0b1f80d98af33f725da6f42d")),
+        "the base of a delta object is obtained without resolving it"
+    );
+    for not_a_delta in [
+        "501b297447a8255d3533c6858bb692575cdefaa0",
+        "37d4e6c5c48ba0d245164c4e10d5f41140cab980",
+    ] {
+        assert!(
+            matches!(
+                handle.delta_base_id(hex_to_id(not_a_delta)),
+                Err(Error::NotADelta { .. })
+            ),
+            "packed base objects and loose objects aren't deltas"
+        );
+    }
+    assert_eq!(
+        handle.delta_base_id(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"))?,
+        None,
+        "missing objects have no base"
+    );
+    Ok(())
+}
+
+#[test]
+fn prefetch_tree_children() -> crate::Result {
+    use git_odb::store::prefetch::Error;
+    let handle = db();
+    let tree = hex_to_id("2c1e59ee54facb7d72c0061d06b9fe3889f357a9");
+    let mut buf = Vec::new();
+    let num_children = git_object::TreeRefIter::from_bytes(handle.find(tree, &mut buf)?.data)
+        .filter_map(Result::ok)
+        .count();
+
+    let outcome = handle.prefetch_tree_children(tree, 0)?.expect("present");
+    assert_eq!(outcome.children, num_children);
+    assert_eq!(
+        outcome.packs_warmed, 0,
+        "the fan-out bounds the amount of packs to warm"
+    );
+
+    let outcome = handle.prefetch_tree_children(tree, 1)?.expect("present");
+    assert_eq!(outcome.packs_warmed, 1);
+
+    let outcome = handle.prefetch_tree_children(tree, 5)?.expect("present");
+    assert_eq!(outcome.children, num_children);
+    assert_eq!(outcome.packs_warmed, 2, "the children are spread over two packs");
+
+    assert!(
+        handle
+            .prefetch_tree_children(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), 5)?
+            .is_none(),
+        "missing trees can't be prefetched"
+    );
+    assert!(
+        matches!(
+            handle.prefetch_tree_children(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), 5),
+            Err(Error::NotATree { .. })
+        ),
+        "only trees have children"
+    );
+    Ok(())
+}
+
+#[test]
+fn diff_commits() -> crate::Result {
+    use git_object::tree::{Entry, EntryMode};
+    use git_odb::store::diff::{Change, Error};
+    let dir = git_testtools::tempfile::tempdir()?;
+    let handle = git_odb::at(dir.path())?;
+    let blob = |data: &str| handle.write_buf(git_object::Kind::Blob, data.as_bytes());
+    let tree = |entries: Vec<(&str, EntryMode, git_hash::ObjectId)>| {
+        handle.write(git_object::Tree {
+            entries: entries
+                .into_iter()
+                .map(|(filename, mode, oid)| Entry {
+                    mode,
+                    filename: filename.into(),
+                    oid,
+                })
+                .collect(),
+        })
+    };
+    let commit = |tree: git_hash::ObjectId| {
+        handle.write(git_object::Commit {
+            tree,
+            parents: Default::default(),
+            author: git_actor::Signature::empty(),
+            committer: git_actor::Signature::empty(),
+            encoding: None,
+            message: "message".into(),
+            extra_headers: Vec::new(),
+        })
+    };
+    let (a, b, b2, c) = (blob("a")?, blob("b")?, blob("b2")?, blob("c")?);
+    let same = tree(vec![("c", EntryMode::Blob, c)])?;
+    let previous = commit(tree(vec![
+        ("a", EntryMode::Blob, a),
+        (
+            "dir",
+            EntryMode::Tree,
+            tree(vec![("b", EntryMode::Blob, b), ("same", EntryMode::Tree, same)])?,
+        ),
+        ("file-then-dir", EntryMode::Blob, c),
+    ])?)?;
+    let current = commit(tree(vec![
+        (
+            "dir",
+            EntryMode::Tree,
+            tree(vec![
+                ("b", EntryMode::Blob, b2),
+                ("new", EntryMode::Blob, a),
+                ("same", EntryMode::Tree, same),
+            ])?,
+        ),
+        ("file-then-dir", EntryMode::Tree, tree(vec![("x", EntryMode::Blob, a)])?),
+        ("z", EntryMode::BlobExecutable, a),
+    ])?)?;
+
+    assert_eq!(
+        handle.diff_commits(previous, current)?,
+        vec![
+            Change::Deletion {
+                path: "a".into(),
+                id: a
+            },
+            Change::Modification {
+                path: "dir/b".into(),
+                previous_id: b,
+                id: b2
+            },
+            Change::Addition {
+                path: "dir/new".into(),
+                id: a
+            },
+            Change::Deletion {
+                path: "file-then-dir".into(),
+                id: c
+            },
+            Change::Addition {
+                path: "file-then-dir/x".into(),
+                id: a
+            },
+            Change::Addition {
+                path: "z".into(),
+                id: a
+            },
+        ],
+        "unchanged subtrees are skipped, and blobs replacing trees are a deletion and an addition"
+    );
+    assert_eq!(handle.diff_commits(current, current)?, Vec::new(), "no changes");
+    assert!(
+        matches!(handle.diff_commits(a, current), Err(Error::NotACommit { id, .. }) if id == a),
+        "only commits can be diffed"
+    );
+    Ok(())
+}
+
+#[test]
+fn peeled_tags() -> crate::Result {
+    use std::sync::atomic::AtomicBool;
+
+    use git_features::progress;
+    use git_odb::store::tags::{Error, Tag};
+    let (handle, _tmp) = db_with_all_object_sources()?;
+    let tag = hex_to_id("722fe60ad4f0276d5a8121970b5bb9dccdad4ef9");
+    let commit = hex_to_id("ffa700b4aca13b80cb6b98a078e7c96804f8e0ec");
+    let tag_of_tag = handle.write(git_object::Tag {
+        target: tag,
+        target_kind: git_object::Kind::Tag,
+        name: "nested".into(),
+        tagger: None,
+        message: "message".into(),
+        pgp_signature: None,
+    })?;
+
+    let mut tags = Vec::new();
+    let num_tags = handle.peeled_tags(progress::Discard, &AtomicBool::new(false), |tag| tags.push(tag))?;
+    assert_eq!(num_tags, tags.len());
+    tags.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(
+        tags,
+        vec![
+            Tag {
+                id: tag,
+                name: "1.0.0".into(),
+                target: commit,
+                target_kind: Some(git_object::Kind::Commit),
+            },
+            Tag {
+                id: tag_of_tag,
+                name: "nested".into(),
+                target: commit,
+                target_kind: Some(git_object::Kind::Commit),
+            },
+        ],
+        "tags of tags are peeled until they point to an object that isn't a tag"
+    );
+
+    assert!(matches!(
+        handle.peeled_tags(progress::Discard, &AtomicBool::new(true), |_| {}),
+        Err(Error::Interrupted)
+    ));
+    Ok(())
+}
+
+#[test]
+fn find_by_ref() -> crate::Result {
+    use git_object::bstr::BStr;
+    use git_odb::store::by_ref::Error;
+    let handle = db();
+    let commit = hex_to_id("ffa700b4aca13b80cb6b98a078e7c96804f8e0ec");
+    let missing = hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    let resolve = |name: &BStr| match &name[..] {
+        b"refs/heads/main" => Ok(Some(commit)),
+        b"refs/heads/dangling" => Ok(Some(missing)),
+        b"refs/heads/broken" => Err(std::io::Error::new(std::io::ErrorKind::Other, "corrupt ref")),
+        _ => Ok(None),
+    };
+
+    let mut buf = Vec::new();
+    let (id, data) = handle
+        .find_by_ref("refs/heads/main".into(), resolve, &mut buf)?
+        .expect("present");
+    assert_eq!(id, commit);
+    assert_eq!(data.kind, git_object::Kind::Commit);
+    assert!(handle
+        .find_by_ref("refs/heads/dangling".into(), resolve, &mut buf)?
+        .is_none());
+    assert!(matches!(
+        handle.find_by_ref("refs/heads/unknown".into(), resolve, &mut buf),
+        Err(Error::RefNotFound { name }) if name == "refs/heads/unknown"
+    ));
+    assert!(matches!(
+        handle.find_by_ref("refs/heads/broken".into(), resolve, &mut buf),
+        Err(Error::Resolve { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn objects_in_packs_with_v1_index_are_found_at_their_offsets() -> crate::Result {
+    use git_odb::HeaderExt;
+    let index = git_odb::pack::index::File::at(
+        fixture_path("objects/pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b.idx"),
+        git_hash::Kind::Sha1,
+    )?;
+    assert_eq!(index.version(), git_odb::pack::index::Version::V1, "fixture is v1");
+
+    let mut handle = db();
+    handle.prevent_pack_unload();
+    let mut buf = Vec::new();
+    for entry in index.iter() {
+        let location = git_odb::pack::Find::location_by_oid(&handle, entry.oid, &mut buf).expect("present");
+        assert_eq!(
+            location.pack_offset, entry.pack_offset,
+            "offsets are read from the v1 layout"
+        );
+        let kind = handle.find(entry.oid, &mut buf)?.kind;
+        assert_eq!(
+            handle.header(entry.oid)?.kind(),
+            kind,
+            "deltas are resolved to the same kind"
+        );
+        let actual = git_odb::sink(git_hash::Kind::Sha1).write_buf(kind, &buf)?;
+        assert_eq!(actual, entry.oid, "the object decodes correctly");
+    }
+    Ok(())
+}
+
+#[test]
+fn stored_size() -> crate::Result {
+    use git_odb::store::stored_size::{DeltaBase, Outcome};
+    let mut handle = db();
+    let (delta, base) = (
+        hex_to_id("2c1e59ee54facb7d72c0061d06b9fe3889f357a9"),
+        hex_to_id("1dfd336d2290794b0b1f80d98af33f725da6f42d"),
+    );
+    let (pack_id, size, base_offset) = match handle.stored_size(delta)?.expect("present") {
+        Outcome::Packed {
+            pack_id,
+            size,
+            delta_base: Some(DeltaBase::PackOffset(offset)),
+        } => (pack_id, size, offset),
+        other => unreachable!("expected ofs-delta, got {:?}", other),
+    };
+
+    let pack_path = fixture_path("objects/pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack");
+    let index = git_odb::pack::index::File::at(pack_path.with_extension("idx"), git_hash::Kind::Sha1)?;
+    let pack = git_odb::pack::data::File::at(&pack_path, git_hash::Kind::Sha1)?;
+    let entry_index = index.lookup(delta).expect("delta is in this pack");
+    assert_eq!(
+        pack.entry_crc32(index.pack_offset_at_index(entry_index), size as usize),
+        index.crc32_at_index(entry_index).expect("v2"),
+        "the stored size spans exactly the pack entry"
+    );
+    assert_eq!(
+        index.pack_offset_at_index(index.lookup(base).expect("base is in same pack")),
+        base_offset
+    );
+    match handle.stored_size(base)?.expect("present") {
+        Outcome::Packed {
+            pack_id: base_pack_id,
+            delta_base,
+            ..
+        } => {
+            assert_eq!(base_pack_id, pack_id);
+            assert_eq!(delta_base, None, "bases aren't deltas");
+        }
+        other => unreachable!("expected packed object, got {:?}", other),
+    }
+
+    let loose = hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab980");
+    assert_eq!(
+        handle.stored_size(loose)?,
+        Some(Outcome::Loose {
+            size: std::fs::metadata(fixture_path("objects/37/d4e6c5c48ba0d245164c4e10d5f41140cab980"))?.len()
+        })
+    );
+    assert_eq!(
+        handle.stored_size(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"))?,
+        None
+    );
+
+    handle.set_replacements(Some(vec![(base, loose)]));
+    assert_eq!(
+        handle.stored_size(base)?,
+        handle.stored_size(loose)?,
+        "replacements apply like when finding objects"
+    );
+    Ok(())
+}
+
+#[test]
+fn oid_at_pack_offset_with_and_without_reverse_index_file() -> crate::Result {
+    let objects_dir = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &objects_dir)?;
+    let pack_name = "pack-11fdfa9e156ab73caae3b6da867192221f2089c2";
+    let pack_dir = objects_dir.path().join("pack");
+    assert!(
+        Command::new("git")
+            .current_dir(&pack_dir)
+            .args(["index-pack", "--rev-index"])
+            .arg(format!("{}.pack", pack_name))
+            .output()?
+            .status
+            .success(),
+        "git should work"
+    );
+    let index = git_odb::pack::index::File::at(pack_dir.join(format!("{}.idx", pack_name)), git_hash::Kind::Sha1)?;
+    let on_disk = git_odb::pack::index::reverse::File::at(pack_dir.join(format!("{}.rev", pack_name)), &index)?;
+    let computed = git_odb::pack::index::reverse::File::from_index(&index);
+    assert!(
+        (0..index.num_objects()).all(|n| on_disk.index_at_position(n) == computed.index_at_position(n)),
+        "git computes the same reverse index as we do"
+    );
+
+    let mut buf = Vec::new();
+    for mut handle in [git_odb::at(objects_dir.path())?, db()] {
+        handle.prevent_pack_unload();
+        let pack_id = git_odb::pack::Find::location_by_oid(&handle, index.oid_at_index(0), &mut buf)
+            .expect("present")
+            .pack_id;
+        for entry in index.iter() {
+            assert_eq!(handle.oid_at_pack_offset(pack_id, entry.pack_offset)?, Some(entry.oid));
+            assert_eq!(
+                handle.oid_at_pack_offset(pack_id, entry.pack_offset + 1)?,
+                None,
+                "no entry starts there"
+            );
+        }
+    }
+
+    let (mut handle, _tmp) = db_with_all_object_sources()?;
+    handle.prevent_pack_unload();
+    let id = hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+    let location = git_odb::pack::Find::location_by_oid(&handle, id, &mut buf).expect("present");
+    assert_eq!(
+        handle.oid_at_pack_offset(location.pack_id, location.pack_offset)?,
+        Some(id),
+        "packs of multi-pack indices work as well"
+    );
+    Ok(())
+}
+
+#[test]
+fn reverse_index_files_with_entries_out_of_range_are_rejected() -> crate::Result {
+    let objects_dir = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &objects_dir)?;
+    let pack_name = "pack-11fdfa9e156ab73caae3b6da867192221f2089c2";
+    let pack_dir = objects_dir.path().join("pack");
+    assert!(
+        Command::new("git")
+            .current_dir(&pack_dir)
+            .args(["index-pack", "--rev-index"])
+            .arg(format!("{}.pack", pack_name))
+            .output()?
+            .status
+            .success(),
+        "git should work"
+    );
+    let index = git_odb::pack::index::File::at(pack_dir.join(format!("{}.idx", pack_name)), git_hash::Kind::Sha1)?;
+    let rev_path = pack_dir.join(format!("{}.rev", pack_name));
+    let original = std::fs::read(&rev_path)?;
+
+    let mut of_other_pack = original.clone();
+    let pack_checksum_start = of_other_pack.len() - 2 * 20;
+    of_other_pack[pack_checksum_start] ^= 0xff;
+    std::fs::write(&rev_path, &of_other_pack)?;
+    assert!(
+        matches!(
+            git_odb::pack::index::reverse::File::at(&rev_path, &index),
+            Err(git_odb::pack::index::reverse::Error::Corrupt { .. })
+        ),
+        "reverse indices of other packs are rejected"
+    );
+
+    let mut out_of_range = original;
+    let first_entry = 4 /*signature*/ + 4 /*version*/ + 4 /*hash id*/;
+    out_of_range[first_entry..][..4].copy_from_slice(&index.num_objects().to_be_bytes());
+    std::fs::write(&rev_path, &out_of_range)?;
+    assert!(matches!(
+        git_odb::pack::index::reverse::File::at(&rev_path, &index),
+        Err(git_odb::pack::index::reverse::Error::Corrupt { .. })
+    ));
+
+    let mut handle = git_odb::at(objects_dir.path())?;
+    handle.prevent_pack_unload();
+    let mut buf = Vec::new();
+    let pack_id = git_odb::pack::Find::location_by_oid(&handle, index.oid_at_index(0), &mut buf)
+        .expect("present")
+        .pack_id;
+    for entry in index.iter() {
+        assert_eq!(
+            handle.oid_at_pack_offset(pack_id, entry.pack_offset)?,
+            Some(entry.oid),
+            "the reverse index is computed instead"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn delta_kind() -> crate::Result {
+    use git_odb::store::delta::{Error, Kind};
+    let mut handle = db();
+    handle.prevent_pack_unload();
+    match handle.delta_kind(hex_to_id("2c1e59ee54facb7d72c0061d06b9fe3889f357a9"))? {
+        Kind::OffsetDelta { base_offset, base_oid } => {
+            assert_eq!(base_oid, hex_to_id("1dfd336d2290794b0b1f80d98af33f725da6f42d"));
+            let mut buf = Vec::new();
+            let location = git_odb::pack::Find::location_by_oid(&handle, base_oid, &mut buf).expect("packed");
+            assert_eq!(location.pack_offset, base_offset);
+        }
+        other => unreachable!("expected an offset delta, got {:?}", other),
+    }
+    assert_eq!(
+        handle.delta_kind(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"))?,
+        Kind::NonDelta
+    );
+    assert!(matches!(
+        handle.delta_kind(hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab980")),
+        Err(Error::Loose { .. })
+    ));
+    assert!(matches!(
+        handle.delta_kind(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")),
+        Err(Error::NotFound { .. })
+    ));
+    Ok(())
+}
+
+mod backend {
+    use std::{
+        path::Path,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use git_odb::{
+        pack,
+        store::{
+            backend::{Backend, Filesystem, Shared},
+            find,
+        },
+        Find, FindExt,
+    };
+    use git_testtools::{fixture_path, hex_to_id};
+
+    #[derive(Debug, Default)]
+    struct Counting {
+        indices: AtomicUsize,
+        packs: AtomicUsize,
+        fail_packs: bool,
+        delay: Duration,
+    }
+
+    impl Backend for Counting {
+        fn open_index(
+            &self,
+            path: &Path,
+            object_hash: git_hash::Kind,
+        ) -> Result<pack::index::File, pack::index::init::Error> {
+            self.indices.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(self.delay);
+            Filesystem.open_index(path, object_hash)
+        }
+
+        fn open_multi_index(&self, path: &Path) -> Result<pack::multi_index::File, pack::multi_index::init::Error> {
+            self.indices.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(self.delay);
+            Filesystem.open_multi_index(path)
+        }
+
+        fn open_pack(
+            &self,
+            path: &Path,
+            object_hash: git_hash::Kind,
+        ) -> Result<pack::data::File, pack::data::header::decode::Error> {
+            self.packs.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(self.delay);
+            if self.fail_packs {
+                return Err(pack::data::header::decode::Error::Io {
+                    source: std::io::ErrorKind::PermissionDenied.into(),
+                    path: path.to_owned(),
+                });
+            }
+            Filesystem.open_pack(path, object_hash)
+        }
+    }
+
+    /// Provides files from memory by their name, like a backend reading them from remote object storage would.
+    #[derive(Debug, Default)]
+    struct InMemory {
+        files: std::collections::HashMap<std::ffi::OsString, Arc<[u8]>>,
+    }
+
+    impl InMemory {
+        fn bytes(&self, path: &Path) -> std::io::Result<Arc<[u8]>> {
+            path.file_name()
+                .and_then(|name| self.files.get(name))
+                .cloned()
+                .ok_or_else(|| std::io::ErrorKind::NotFound.into())
+        }
+    }
+
+    impl Backend for InMemory {
+        fn open_index(
+            &self,
+            path: &Path,
+            object_hash: git_hash::Kind,
+        ) -> Result<pack::index::File, pack::index::init::Error> {
+            let data = self.bytes(path).map_err(|source| pack::index::init::Error::Io {
+                source,
+                path: path.to_owned(),
+            })?;
+            pack::index::File::from_bytes(data, path, object_hash)
+        }
+
+        fn open_multi_index(&self, path: &Path) -> Result<pack::multi_index::File, pack::multi_index::init::Error> {
+            let data = self.bytes(path).map_err(|source| pack::multi_index::init::Error::Io {
+                source,
+                path: path.to_owned(),
+            })?;
+            pack::multi_index::File::from_bytes(data, path)
+        }
+
+        fn open_pack(
+            &self,
+            path: &Path,
+            object_hash: git_hash::Kind,
+        ) -> Result<pack::data::File, pack::data::header::decode::Error> {
+            let data = self
+                .bytes(path)
+                .map_err(|source| pack::data::header::decode::Error::Io {
+                    source,
+                    path: path.to_owned(),
+                })?;
+            pack::data::File::from_bytes(data, path, object_hash)
+        }
+
+        fn open_reverse_index(
+            &self,
+            path: &Path,
+            index: &pack::index::File,
+        ) -> Result<pack::index::reverse::File, pack::index::reverse::Error> {
+            let data = self.bytes(path).map_err(|source| pack::index::reverse::Error::Io {
+                source,
+                path: path.to_owned(),
+            })?;
+            pack::index::reverse::File::from_bytes(data, path, index)
+        }
+    }
+
+    fn db_with(backend: Arc<Counting>) -> crate::Result<git_odb::Handle> {
+        Ok(git_odb::at_opts(
+            fixture_path("objects"),
+            None,
+            git_odb::store::init::Options {
+                backend,
+                ..Default::default()
+            },
+        )?)
+    }
+
+    #[test]
+    fn maintenance_opens_files_through_the_backend() -> crate::Result {
+        use git_odb::store::count::by_kind;
+        let should_interrupt = std::sync::atomic::AtomicBool::new(false);
+        let backend = Arc::new(Counting::default());
+        let handle = db_with(backend.clone())?;
+        handle.store_ref().count_objects_by_kind(
+            git_features::progress::Discard,
+            &should_interrupt,
+            by_kind::Mode::ResolveDeltas,
+        )?;
+        assert_eq!(backend.indices.load(Ordering::SeqCst), 3, "each index was opened once");
+        assert_eq!(backend.packs.load(Ordering::SeqCst), 3, "as well as each pack");
+
+        let handle = db_with(Arc::new(Counting {
+            fail_packs: true,
+            ..Default::default()
+        }))?;
+        assert!(
+            handle
+                .store_ref()
+                .count_objects_by_kind(
+                    git_features::progress::Discard,
+                    &should_interrupt,
+                    by_kind::Mode::ResolveDeltas
+                )
+                .is_err(),
+            "packs aren't opened from disk directly"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn files_can_be_provided_from_memory() -> crate::Result {
+        let tmp = git_testtools::tempfile::TempDir::new()?;
+        let pack_dir = tmp.path().join("pack");
+        std::fs::create_dir(&pack_dir)?;
+        let mut backend = InMemory::default();
+        for entry in std::fs::read_dir(fixture_path("objects/pack"))? {
+            let path = entry?.path();
+            let name = path.file_name().expect("named");
+            backend.files.insert(name.to_owned(), std::fs::read(&path)?.into());
+            // Files are still discovered on disk, but their content is only ever read from memory.
+            std::fs::write(pack_dir.join(name), b"")?;
+        }
+        let handle = git_odb::at_opts(
+            tmp.path(),
+            None,
+            git_odb::store::init::Options {
+                backend: Arc::new(backend),
+                ..Default::default()
+            },
+        )?;
+
+        let expected = crate::odb::db();
+        let (mut buf, mut expected_buf) = (Vec::new(), Vec::new());
+        for id in [
+            "501b297447a8255d3533c6858bb692575cdefaa0",
+            "4dac9989f96bc5b5b1263b582c08f0c5f0b58542",
+            "dd25c539efbb0ab018caa4cda2d133285634e9b5",
+        ] {
+            let id = hex_to_id(id);
+            let object = handle.find(id, &mut buf)?;
+            let expected_object = expected.find(id, &mut expected_buf)?;
+            assert_eq!(object.kind, expected_object.kind);
+            assert_eq!(
+                object.data, expected_object.data,
+                "objects are decoded from packs in memory"
+            );
+        }
+        assert_eq!(
+            handle.packed_object_count()?,
+            expected.packed_object_count()?,
+            "all indices are read from memory as well"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deadlines_abort_lookups_waiting_for_slow_files() -> crate::Result {
+        let packed = hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0");
+        let backend = Arc::new(Counting {
+            delay: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let mut handle = db_with(backend.clone())?;
+        handle.deadline = Some(Instant::now() + Duration::from_millis(10));
+        let mut buf = Vec::new();
+        match handle.try_find(packed, &mut buf) {
+            Err(find::Error::DeadlineExceeded { id }) => assert_eq!(id, packed),
+            res => unreachable!(
+                "the first slow index load exceeds the deadline: {:?}",
+                res.map(|o| o.is_some())
+            ),
+        }
+        assert_eq!(backend.packs.load(Ordering::SeqCst), 0, "it stops before loading more");
+
+        handle.deadline = Some(Instant::now() + Duration::from_secs(60));
+        assert!(
+            handle.try_find(packed, &mut buf)?.is_some(),
+            "lookups finishing in time succeed"
+        );
+
+        handle.deadline = Some(Instant::now());
+        assert!(
+            matches!(
+                handle.try_find(hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab980"), &mut buf),
+                Err(find::Error::DeadlineExceeded { .. })
+            ),
+            "passed deadlines fail lookups even if all files are available"
+        );
+        handle.deadline = None;
+        assert!(handle.try_find(packed, &mut buf)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_refreshes_are_coalesced() -> crate::Result {
+        use std::sync::Barrier;
+        let tmp = git_testtools::tempfile::TempDir::new()?;
+        let pack_dir = tmp.path().join("pack");
+        std::fs::create_dir(&pack_dir)?;
+        let copy_pack = |name: &str| -> std::io::Result<()> {
+            for ext in ["idx", "pack"] {
+                let file_name = format!("{}.{}", name, ext);
+                std::fs::copy(fixture_path("objects/pack").join(&file_name), pack_dir.join(file_name))?;
+            }
+            Ok(())
+        };
+        copy_pack("pack-c0438c19fb16422b6bbcce24387b3264416d485b")?;
+        let backend = Arc::new(Counting {
+            delay: Duration::from_millis(100),
+            ..Default::default()
+        });
+        let handle = git_odb::at_opts(
+            tmp.path(),
+            None,
+            git_odb::store::init::Options {
+                backend,
+                ..Default::default()
+            },
+        )?
+        .into_arc()?;
+        handle.packed_object_count()?;
+        let refreshes_before = handle.store_ref().metrics().num_refreshes;
+
+        copy_pack("pack-11fdfa9e156ab73caae3b6da867192221f2089c2")?;
+        let num_threads = 8;
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let threads: Vec<_> = (0..num_threads)
+            .map(|_| {
+                std::thread::spawn({
+                    let handle = handle.clone();
+                    let barrier = Arc::clone(&barrier);
+                    move || {
+                        let mut buf = Vec::new();
+                        barrier.wait();
+                        handle
+                            .try_find(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut buf)
+                            .map(|obj| obj.is_some())
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            assert!(
+                thread.join().expect("no panic")?,
+                "all threads find the object in the new pack"
+            );
+        }
+        assert_eq!(
+            handle.store_ref().metrics().num_refreshes,
+            refreshes_before + 1,
+            "all threads missing the object share a single refresh"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn is_used_to_open_all_files() -> crate::Result {
+        let backend = Arc::new(Counting::default());
+        let handle = db_with(backend.clone())?;
+        let mut buf = Vec::new();
+        handle.find(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut buf)?;
+        assert!(backend.indices.load(Ordering::SeqCst) > 0);
+        assert_eq!(
+            backend.packs.load(Ordering::SeqCst),
+            1,
+            "only the pack with the object was opened"
+        );
+
+        let handle = db_with(backend.clone())?.into_inner().into_arc()?;
+        let before = backend.indices.load(Ordering::SeqCst);
+        handle.packed_object_count()?;
+        assert!(
+            backend.indices.load(Ordering::SeqCst) > before,
+            "thread-safe copies of the store use the same backend"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn shared_backends_open_files_once_for_all_stores_using_them() -> crate::Result {
+        let backend = Arc::new(Shared::new(Counting::default()));
+        let open = || {
+            git_odb::at_opts(
+                fixture_path("objects"),
+                None,
+                git_odb::store::init::Options {
+                    backend: backend.clone(),
+                    ..Default::default()
+                },
+            )
+        };
+        let counts = || {
+            (
+                backend.inner().indices.load(Ordering::SeqCst),
+                backend.inner().packs.load(Ordering::SeqCst),
+            )
+        };
+        let packed = hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0");
+        let mut buf = Vec::new();
+
+        let (first, second) = (open()?, open()?);
+        first.find(packed, &mut buf)?;
+        let opened_by_first = counts();
+        assert_eq!(opened_by_first.1, 1);
+        second.find(packed, &mut buf)?;
+        assert_eq!(
+            counts(),
+            opened_by_first,
+            "the second store uses the files of the first one"
+        );
+
+        drop((first, second));
+        open()?.find(packed, &mut buf)?;
+        assert_eq!(counts().1, 2, "files are opened again once no store uses them anymore");
+        Ok(())
+    }
+
+    #[test]
+    fn errors_are_handled_like_those_of_the_filesystem() -> crate::Result {
+        let backend = Arc::new(Counting {
+            fail_packs: true,
+            ..Default::default()
+        });
+        let handle = db_with(backend)?;
+        let mut buf = Vec::new();
+        assert!(
+            handle
+                .try_find(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut buf)
+                .is_err(),
+            "packs that can't be opened aren't silently skipped"
+        );
+        Ok(())
+    }
+}
+
+#[test]
+fn deterministic_slot_assignment_follows_file_names() -> crate::Result {
+    let index_names = [
+        "pack-11fdfa9e156ab73caae3b6da867192221f2089c2.idx",
+        "pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.idx",
+        "pack-c0438c19fb16422b6bbcce24387b3264416d485b.idx",
+    ];
+    let first_object_of_each_pack = index_names
+        .iter()
+        .map(|name| {
+            git_odb::pack::index::File::at(fixture_path("objects/pack").join(name), git_hash::Kind::Sha1)
+                .map(|index| index.oid_at_index(0).to_owned())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut slot_maps = Vec::new();
+    for _ in 0..2 {
+        let dir = git_testtools::tempfile::tempdir()?;
+        git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &dir)?;
+        let handle = git_odb::at_opts(
+            dir.path(),
+            None,
+            git_odb::store::init::Options {
+                deterministic_slot_assignment: true,
+                ..Default::default()
+            },
+        )?;
+        let mut buf = Vec::new();
+        let pack_ids = first_object_of_each_pack
+            .iter()
+            .map(|id| {
+                git_odb::pack::Find::location_by_oid(&handle, id, &mut buf)
+                    .expect("present")
+                    .pack_id
+            })
+            .collect::<Vec<_>>();
+        slot_maps.push(pack_ids);
+    }
+    assert_eq!(
+        slot_maps,
+        vec![vec![0, 1, 2], vec![0, 1, 2]],
+        "slots are assigned in the order of index file names, the same way for the same files"
+    );
+    Ok(())
+}
+
+#[test]
+fn index_sort_order_determines_the_order_indices_are_searched_in() -> crate::Result {
+    use git_odb::store::{init::IndexSortOrder, structure::Record};
+    let dir = git_testtools::tempfile::tempdir()?;
+    let pack_dir = dir.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    // Index sizes grow from 11fd to a2bf to c043, names are chosen to not follow either and modification times neither.
+    let packs = [
+        ("pack-11fdfa9e156ab73caae3b6da867192221f2089c2", "pack-3", 3),
+        ("pack-a2bf8e71d8c18879e499335762dd95119d93d9f1", "pack-1", 1),
+        ("pack-c0438c19fb16422b6bbcce24387b3264416d485b", "pack-2", 2),
+    ];
+    let mut first_object_of_each_pack = Vec::new();
+    for (fixture_name, name, seconds) in packs {
+        for ext in ["pack", "idx"] {
+            let path = pack_dir.join(name).with_extension(ext);
+            std::fs::copy(
+                fixture_path("objects/pack").join(fixture_name).with_extension(ext),
+                &path,
+            )?;
+            filetime::set_file_mtime(&path, filetime::FileTime::from_unix_time(seconds, 0))?;
+        }
+        let index = git_odb::pack::index::File::at(pack_dir.join(name).with_extension("idx"), git_hash::Kind::Sha1)?;
+        first_object_of_each_pack.push(index.oid_at_index(0).to_owned());
+    }
+
+    for (sort_order, expected) in [
+        (IndexSortOrder::BySizeDescending, ["pack-2", "pack-1", "pack-3"]),
+        (IndexSortOrder::BySizeAscending, ["pack-3", "pack-1", "pack-2"]),
+        (IndexSortOrder::ModifiedTimeNewestFirst, ["pack-3", "pack-2", "pack-1"]),
+        (IndexSortOrder::ByNameStable, ["pack-1", "pack-2", "pack-3"]),
+    ] {
+        let handle = git_odb::at_opts(
+            dir.path(),
+            None,
+            git_odb::store::init::Options {
+                index_sort_order: sort_order,
+                ..Default::default()
+            },
+        )?;
+        let order: Vec<_> = handle
+            .store_ref()
+            .structure()?
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::Index { path, .. } => path.file_stem().map(|stem| stem.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(order, expected, "{:?}", sort_order);
+
+        let mut buf = Vec::new();
+        for id in first_object_of_each_pack.iter().rev() {
+            assert!(
+                handle.find(id, &mut buf).is_ok(),
+                "the order only affects how lookups probe indices, not what they find"
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn max_open_files_limits_the_files_held_by_the_store() -> crate::Result {
+    let handle = git_odb::at_opts(
+        fixture_path("objects"),
+        None,
+        git_odb::store::init::Options {
+            max_open_files: Some(3 /* indices */ + 1),
+            ..Default::default()
+        },
+    )?;
+    let (in_first_pack, in_second_pack) = (
+        hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"),
+        hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"),
+    );
+    let mut buf = Vec::new();
+    handle.find(in_first_pack, &mut buf)?;
+    assert_eq!(handle.store_ref().metrics().open_reachable_packs, 1);
+    handle.packed_object_count()?;
+    handle.find(in_second_pack, &mut buf)?;
+    let metrics = handle.store_ref().metrics();
+    assert_eq!(
+        (metrics.open_reachable_packs, metrics.open_files),
+        (1, 4),
+        "the first pack was unloaded to make room"
+    );
+    handle.find(in_first_pack, &mut buf)?;
+
+    let store = handle.store();
+    drop(handle);
+    let handle = store.to_cache();
+    handle.find(in_first_pack, &mut buf)?;
+    handle.find(in_second_pack, &mut buf)?;
+    assert_eq!(handle.store_ref().metrics().open_reachable_packs, 1);
+    Ok(())
+}
+
+#[test]
+fn max_open_files_unloads_least_recently_used_packs_first() -> crate::Result {
+    let store = git_odb::at_opts(
+        fixture_path("objects"),
+        None,
+        git_odb::store::init::Options {
+            max_open_files: Some(3 /* indices */ + 2),
+            ..Default::default()
+        },
+    )?
+    .store();
+    let pack_len = |name: &str| -> std::io::Result<usize> {
+        Ok(std::fs::metadata(fixture_path(format!("objects/pack/pack-{}.pack", name)))?.len() as usize)
+    };
+    let mut buf = Vec::new();
+    for id in [
+        "501b297447a8255d3533c6858bb692575cdefaa0",
+        "0d9726f3fd2359cda58217724f12af273bf440a6",
+        "501b297447a8255d3533c6858bb692575cdefaa0",
+        "dd25c539efbb0ab018caa4cda2d133285634e9b5",
+    ] {
+        store.to_cache().find(hex_to_id(id), &mut buf)?;
+    }
+    assert_eq!(store.metrics().open_reachable_packs, 2);
+    assert_eq!(
+        store.memory_usage().mapped_packs,
+        pack_len("11fdfa9e156ab73caae3b6da867192221f2089c2")? + pack_len("c0438c19fb16422b6bbcce24387b3264416d485b")?,
+        "the pack used before the most recently used one was unloaded"
+    );
+    Ok(())
+}
+
+#[test]
+fn max_open_files_is_respected_by_concurrent_handles_and_index_loader_threads() -> crate::Result {
+    let handle = git_odb::at_opts(
+        fixture_path("objects"),
+        None,
+        git_odb::store::init::Options {
+            max_open_files: Some(3 /* indices */ + 1),
+            index_loader_threads: Some(2),
+            ..Default::default()
+        },
+    )?
+    .into_arc()?;
+    let store = handle.store();
+    let threads: Vec<_> = (0..4)
+        .map(|thread_idx| {
+            std::thread::spawn({
+                let store = store.clone();
+                move || -> Result<(), git_odb::store::find::Error> {
+                    let mut buf = Vec::new();
+                    let ids = [
+                        "501b297447a8255d3533c6858bb692575cdefaa0",
+                        "0d9726f3fd2359cda58217724f12af273bf440a6",
+                        "dd25c539efbb0ab018caa4cda2d133285634e9b5",
+                    ];
+                    for round in 0..20 {
+                        let handle = store.to_handle_arc();
+                        let id = hex_to_id(ids[(thread_idx + round) % ids.len()]);
+                        assert!(handle.try_find(id, &mut buf)?.is_some());
+                    }
+                    Ok(())
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().expect("no panic")?;
+    }
+    let metrics = store.metrics();
+    assert_eq!(
+        (metrics.open_reachable_packs, metrics.open_files),
+        (1, 4),
+        "concurrent loads of indices and packs never exceed the limit"
+    );
+    Ok(())
+}
+
+#[test]
+fn max_open_files_counts_indices_and_unloads_packs_to_make_room_for_them() -> crate::Result {
+    let handle = git_odb::at_opts(
+        fixture_path("objects"),
+        None,
+        git_odb::store::init::Options {
+            max_open_files: Some(2),
+            ..Default::default()
+        },
+    )?;
+    let mut buf = Vec::new();
+    handle.find(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut buf)?;
+    let metrics = handle.store_ref().metrics();
+    assert_eq!(metrics.open_reachable_packs, 1);
+    assert_eq!(
+        metrics.open_files,
+        metrics.open_reachable_indices + 1,
+        "the index of the pack counts as well"
+    );
+
+    handle.packed_object_count()?;
+    let metrics = handle.store_ref().metrics();
+    assert_eq!(
+        (metrics.open_reachable_packs, metrics.open_reachable_indices, metrics.open_files),
+        (0, 3, 3),
+        "the pack was unloaded to make room for the remaining indices, which are loaded regardless as lookups need them"
+    );
+    Ok(())
+}
+
+#[test]
+fn loose_read_buffer_size_is_used_for_loose_objects() -> crate::Result {
+    let handle = git_odb::at_opts(
+        fixture_path("objects"),
+        None,
+        git_odb::store::init::Options {
+            loose_read_buffer_size: Some(3),
+            ..Default::default()
+        },
+    )?;
+    let (mut expected, mut actual) = (Vec::new(), Vec::new());
+    for id in crate::odb::store::loose::object_ids() {
+        let expected = db().find(id, &mut expected)?;
+        assert_eq!(handle.find(id, &mut actual)?, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn index_loader_threads_load_all_indices_in_the_background() -> crate::Result {
+    let handle = git_odb::at_opts(
+        fixture_path("objects"),
+        None,
+        git_odb::store::init::Options {
+            index_loader_threads: Some(2),
+            ..Default::default()
+        },
+    )?;
+    let mut buf = Vec::new();
+    handle.find(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"), &mut buf)?;
+    assert!(
+        !handle.contains(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")),
+        "missing objects are still reported as such after waiting for all indices"
+    );
+
+    let start = std::time::Instant::now();
+    loop {
+        let metrics = handle.store_ref().metrics();
+        if metrics.open_reachable_indices == metrics.known_reachable_indices {
+            break;
+        }
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(10),
+            "all indices are eventually loaded without lookups asking for them"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(handle.packed_object_count()?, db().packed_object_count()?);
+    drop(handle);
+    Ok(())
+}
+
+#[test]
+fn verify_object_hashes_rejects_objects_not_matching_their_id() -> crate::Result {
+    use git_odb::store::find::Error;
+    let dir = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &dir)?;
+    let blob = hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab980");
+    let corrupt = hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    std::fs::create_dir(dir.path().join("aa"))?;
+    std::fs::copy(
+        dir.path().join("37/d4e6c5c48ba0d245164c4e10d5f41140cab980"),
+        dir.path().join("aa/aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"),
+    )?;
+
+    let mut buf = Vec::new();
+    let handle = git_odb::at(dir.path())?;
+    assert_eq!(
+        handle.find(corrupt, &mut buf)?.kind,
+        git_object::Kind::Blob,
+        "by default, objects are returned as they are stored"
+    );
+
+    let handle = git_odb::at_opts(
+        dir.path(),
+        None,
+        git_odb::store::init::Options {
+            verify_object_hashes: true,
+            ..Default::default()
+        },
+    )?;
+    match handle.try_find(corrupt, &mut buf) {
+        Err(Error::HashMismatch { expected, actual }) => {
+            assert_eq!(expected, corrupt);
+            assert_eq!(actual, blob, "the hash of the content is the one of the copied object");
+        }
+        res => unreachable!("the mismatch is detected: {:?}", res.map(|o| o.is_some())),
+    }
+    for id in handle.iter()? {
+        let id = id?;
+        if id != corrupt {
+            handle.find(id, &mut buf)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn transform_round_trip() -> crate::Result {
+    use git_odb::store::transform::{self, Transform};
+
+    #[derive(Debug)]
+    struct Invert;
+    impl Transform for Invert {
+        fn decode(&self, _kind: git_object::Kind, data: &mut Vec<u8>) -> Result<(), transform::Error> {
+            data.iter_mut().for_each(|b| *b = !*b);
+            Ok(())
+        }
+        fn encode(&self, kind: git_object::Kind, data: &mut Vec<u8>) -> Result<(), transform::Error> {
+            self.decode(kind, data)
+        }
+    }
+
+    let dir = git_testtools::tempfile::tempdir()?;
+    let handle = git_odb::at_opts(
+        dir.path(),
+        None,
+        git_odb::store::init::Options {
+            transform: Some(std::sync::Arc::new(Invert)),
+            verify_object_hashes: true,
+            ..Default::default()
+        },
+    )?;
+    let id = handle.write_buf(git_object::Kind::Blob, b"hello world")?;
+    assert_ne!(
+        id,
+        hex_to_id("95d09f2b10159347eece71399a7e2e907ea3df4f"),
+        "the id is computed over the stored form"
+    );
+    let mut buf = Vec::new();
+    let obj = handle.find(id, &mut buf)?;
+    assert_eq!(obj.kind, git_object::Kind::Blob);
+    assert_eq!(obj.data, b"hello world", "objects are read in their actual form");
+    assert_eq!(
+        handle.write_buf(git_object::Kind::Blob, b"hello world")?,
+        id,
+        "writing the same object again yields the same id"
+    );
+
+    let plain = git_odb::at(dir.path())?;
+    let stored: Vec<u8> = b"hello world".iter().map(|b| !b).collect();
+    assert_eq!(
+        plain.find(id, &mut buf)?.data,
+        stored.as_slice(),
+        "without transform, the stored form is returned"
+    );
+    Ok(())
+}
+
+#[test]
+fn close_releases_all_files_held_by_the_store() -> crate::Result {
+    let handle = db();
+    let id = hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+    let mut buf = Vec::new();
+    handle.find(id, &mut buf)?;
+    assert!(handle.store_ref().memory_usage().mapped_packs > 0);
+
+    let mut stable = handle.clone();
+    stable.prevent_pack_unload();
+    assert!(
+        matches!(
+            handle.store_ref().close(),
+            Err(git_odb::store::close::Error::StableHandles { count: 1 })
+        ),
+        "pack ids of stable handles must remain valid"
+    );
+    drop(stable);
+
+    handle.store_ref().close()?;
+    let usage = handle.store_ref().memory_usage();
+    assert_eq!(usage.mapped_indices, 0, "all indices were unloaded");
+    assert_eq!(usage.mapped_packs, 0, "and all packs");
+    let metrics = handle.store_ref().metrics();
+    assert_eq!(metrics.known_packs, 3, "all files are still known");
+    assert_eq!(metrics.open_reachable_indices, 0);
+    assert_eq!(metrics.open_reachable_packs, 0);
+    assert_eq!(metrics.num_refreshes, 1, "nothing was read from disk");
+
+    let store = handle.store();
+    drop(handle);
+    let handle = store.to_cache();
+    handle.find(id, &mut buf)?;
+    assert!(
+        handle.store_ref().memory_usage().mapped_packs > 0,
+        "the store remains usable and loads files again"
+    );
+    Ok(())
+}
+
+#[test]
+fn close_does_not_have_index_loader_threads_load_indices_again() -> crate::Result {
+    let handle = git_odb::at_opts(
+        fixture_path("objects"),
+        None,
+        git_odb::store::init::Options {
+            index_loader_threads: Some(1),
+            ..Default::default()
+        },
+    )?;
+    let mut buf = Vec::new();
+    handle.find(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"), &mut buf)?;
+    let store = handle.store();
+    drop(handle);
+    wait_for_index_loader_threads(&store);
+    store.close()?;
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert_eq!(
+        store.metrics().open_reachable_indices,
+        0,
+        "indices stay unloaded until lookups need them"
+    );
+    Ok(())
+}
+
+#[test]
+fn unload_unused_does_not_have_index_loader_threads_load_indices_again() -> crate::Result {
+    let handle = git_odb::at_opts(
+        fixture_path("objects"),
+        None,
+        git_odb::store::init::Options {
+            index_loader_threads: Some(1),
+            ..Default::default()
+        },
+    )?;
+    let mut buf = Vec::new();
+    handle.find(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"), &mut buf)?;
+    let store = handle.store();
+    drop(handle);
+    wait_for_index_loader_threads(&store);
+    assert_ne!(store.unload_unused().unloaded_indices, 0);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert_eq!(
+        store.metrics().open_reachable_indices,
+        0,
+        "indices stay unloaded until lookups need them"
+    );
+    Ok(())
+}
+
+fn wait_for_index_loader_threads(store: &git_odb::Store) {
+    let start = std::time::Instant::now();
+    loop {
+        let metrics = store.metrics();
+        if metrics.known_reachable_indices != 0 && metrics.open_reachable_indices == metrics.known_reachable_indices {
+            break;
+        }
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(10),
+            "all indices are eventually loaded without lookups asking for them"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn unload_unused_releases_files_no_handle_uses() -> crate::Result {
+    let handle = db();
+    let mut stable = handle.clone();
+    stable.prevent_pack_unload();
+    let mut buf = Vec::new();
+    for id in [
+        "501b297447a8255d3533c6858bb692575cdefaa0",
+        "0d9726f3fd2359cda58217724f12af273bf440a6",
+        "dd25c539efbb0ab018caa4cda2d133285634e9b5",
+    ] {
+        handle.find(hex_to_id(id), &mut buf)?;
+    }
+    let store = handle.store();
+    let before = store.metrics();
+    assert_eq!(before.open_reachable_packs, 3);
+
+    assert_eq!(
+        store.unload_unused(),
+        git_odb::store::unload::Outcome::default(),
+        "the handle still refers to all files it used"
+    );
+
+    drop(handle);
+    let outcome = store.unload_unused();
+    assert_eq!(
+        outcome.unloaded_packs, 3,
+        "packs are unloaded even if pack ids must remain stable"
+    );
+    assert_eq!(outcome.unloaded_indices, 0, "but indices are not");
+    drop(stable);
+    let outcome = store.unload_unused();
+    assert_eq!(outcome.unloaded_packs, 0);
+    assert_eq!(outcome.unloaded_indices, before.open_reachable_indices);
+
+    let after = store.metrics();
+    assert_eq!(after.open_reachable_packs, 0);
+    assert_eq!(after.open_reachable_indices, 0);
+    assert_eq!(after.known_packs, before.known_packs, "nothing is forgotten");
+    assert_eq!(after.num_refreshes, before.num_refreshes);
+
+    let handle = store.to_cache();
+    handle.find(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"), &mut buf)?;
+    assert_eq!(
+        handle.store_ref().metrics().num_refreshes,
+        before.num_refreshes,
+        "unloaded files are loaded again"
+    );
+    Ok(())
+}
+
+#[test]
+fn header_of_deltified_objects_is_obtained_without_decompressing_them() -> crate::Result {
+    let handle = db();
+    let mut deepest = None;
+    for oid in handle.iter()? {
+        let oid = oid?;
+        let hdr = handle.header(oid)?;
+        if hdr.num_deltas()
+            > deepest
+                .as_ref()
+                .and_then(|(_, hdr): &(_, git_odb::find::Header)| hdr.num_deltas())
+        {
+            deepest = Some((oid, hdr));
+        }
+    }
+    let (oid, hdr) = deepest.expect("objects exist");
+    assert_eq!(
+        hdr.num_deltas(),
+        Some(6),
+        "the fixture has objects deep in a delta chain"
+    );
+    assert_eq!(
+        handle.store_ref().lookup_counters().bytes_decompressed,
+        0,
+        "only entry headers and the sizes at the start of deltas were decoded"
+    );
+
+    let mut buf = Vec::new();
+    let obj = handle.find(oid, &mut buf)?;
+    assert_eq!(obj.kind, hdr.kind());
+    assert_eq!(
+        obj.data.len() as u64,
+        hdr.size(),
+        "the size is the one of the resolved object"
+    );
+    Ok(())
+}
+
+#[test]
+fn packs_with_keep_file_are_not_unloaded() -> crate::Result {
+    let objects_dir = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &objects_dir)?;
+    let keep_file = objects_dir
+        .path()
+        .join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.keep");
+    std::fs::write(&keep_file, b"")?;
+
+    let handle = git_odb::at(objects_dir.path())?;
+    let mut buf = Vec::new();
+    for id in [
+        "501b297447a8255d3533c6858bb692575cdefaa0",
+        "0d9726f3fd2359cda58217724f12af273bf440a6",
+        "dd25c539efbb0ab018caa4cda2d133285634e9b5",
+    ] {
+        handle.find(hex_to_id(id), &mut buf)?;
+    }
+    let store = handle.store();
+    drop(handle);
+    assert_eq!(store.unload_unused().unloaded_packs, 2);
+    assert_eq!(store.metrics().open_reachable_packs, 1, "the kept pack remains loaded");
+
+    std::fs::remove_file(&keep_file)?;
+    assert!(
+        !store
+            .to_cache()
+            .contains(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")),
+        "refresh to learn about the removed keep file"
+    );
+    assert_eq!(store.unload_unused().unloaded_packs, 1);
+    assert_eq!(store.metrics().open_reachable_packs, 0);
+    Ok(())
+}
+
+#[test]
+fn refresh_makes_packs_written_by_other_processes_available_without_a_miss() -> crate::Result {
+    let objects_dir = git_testtools::tempfile::tempdir()?;
+    let pack_dir = objects_dir.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    let write_pack = |name: &str| -> std::io::Result<()> {
+        for ext in ["idx", "pack"] {
+            let file_name = format!("pack-{}.{}", name, ext);
+            std::fs::copy(fixture_path("objects/pack").join(&file_name), pack_dir.join(file_name))?;
+        }
+        Ok(())
+    };
+    write_pack("11fdfa9e156ab73caae3b6da867192221f2089c2")?;
+
+    let mut handle = git_odb::at(objects_dir.path())?;
+    handle.refresh_never();
+    let id = hex_to_id("0d9726f3fd2359cda58217724f12af273bf440a6");
+    assert!(!handle.contains(id));
+
+    write_pack("a2bf8e71d8c18879e499335762dd95119d93d9f1")?;
+    assert!(
+        !handle.contains(id),
+        "a handle that never refreshes doesn't see the new pack by itself"
+    );
+
+    let store = handle.store();
+    assert_eq!(
+        store.refresh()?,
+        store::refresh::Outcome {
+            changed: true,
+            generation_changed: false,
+            num_indices: 2
+        },
+        "the new pack is put into a free slot, which leaves existing pack ids valid"
+    );
+    assert!(handle.contains(id), "the next lookup finds objects in the new pack");
+    assert!(
+        !store.refresh()?.changed,
+        "refreshing always reads the disk, but nothing changed"
+    );
+    Ok(())
+}
+
+#[test]
+fn lookup_counters() -> crate::Result {
+    use git_odb::store::LookupCounters;
+    let handle = db();
+    let store = handle.store_ref();
+    assert_eq!(store.lookup_counters(), LookupCounters::default());
+
+    let (existing, missing) = (
+        hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab980"),
+        hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"),
+    );
+    let mut buf = Vec::new();
+    let size = handle.find(existing, &mut buf)?.data.len();
+    assert!(handle.try_find(missing, &mut buf)?.is_none());
+    assert!(handle.contains(existing));
+    assert!(!handle.contains(missing));
+
+    let expected = LookupCounters {
+        finds: 2,
+        contains: 2,
+        misses: 2,
+        bytes_decompressed: size,
+    };
+    assert_eq!(store.lookup_counters(), expected, "observing doesn't reset");
+    assert_eq!(store.take_lookup_counters(), expected);
+    assert_eq!(
+        store.lookup_counters(),
+        LookupCounters::default(),
+        "taking the counters resets them"
+    );
+    Ok(())
+}
+
+#[test]
+fn pack_statistics() -> crate::Result {
+    let dir = git_testtools::tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &dir)?;
+    let handle = git_odb::at(dir.path())?;
+    // Packs are told apart by their size, which differs for each of them.
+    let hits_by_pack_size = || {
+        let mut stats: Vec<_> = handle
+            .store_ref()
+            .pack_statistics()
+            .into_iter()
+            .map(|(_, stats)| (stats.size.expect("pack exists"), stats.hits, stats.loaded))
+            .collect();
+        stats.sort_unstable();
+        stats
+    };
+    assert!(
+        handle.store_ref().pack_statistics().is_empty(),
+        "the store doesn't know any pack before its first use"
+    );
+
+    let mut buf = Vec::new();
+    let in_c043 = hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+    let in_11fd = hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0");
+    let in_a2bf = hex_to_id("0d9726f3fd2359cda58217724f12af273bf440a6");
+    handle.find(in_c043, &mut buf)?;
+    handle.find(in_c043, &mut buf)?;
+    assert!(handle.contains(in_11fd));
+    assert!(handle.contains(in_a2bf));
+    handle.find(in_a2bf, &mut buf)?;
+    assert!(!handle.contains(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")));
+    assert_eq!(
+        hits_by_pack_size(),
+        vec![(3732, 2, true), (49113, 2, true), (51875, 1, false)],
+        "finds and existence checks count as hits, but only finds load packs"
+    );
+
+    let pack_path = dir.path().join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2");
+    let moved_away = dir.path().join("11fd");
+    std::fs::create_dir(&moved_away)?;
+    for ext in ["idx", "pack"] {
+        std::fs::rename(pack_path.with_extension(ext), moved_away.join(ext))?;
+    }
+    handle.store_ref().refresh()?;
+    assert_eq!(
+        hits_by_pack_size(),
+        vec![(3732, 2, true), (49113, 2, true)],
+        "removed packs aren't listed"
+    );
+    for ext in ["idx", "pack"] {
+        std::fs::rename(moved_away.join(ext), pack_path.with_extension(ext))?;
+    }
+    handle.store_ref().refresh()?;
+    assert_eq!(
+        hits_by_pack_size(),
+        vec![(3732, 2, true), (49113, 2, true), (51875, 0, false)],
+        "packs start counting from scratch once they are assigned to a slot again"
+    );
+    Ok(())
+}
+
+#[test]
+fn memory_usage() -> crate::Result {
+    let handle = db();
+    let before = handle.store_ref().memory_usage();
+    assert_eq!(before.mapped_indices, 0, "nothing is loaded initially");
+    assert_eq!(before.mapped_packs, 0);
+    assert!(before.slot_map > 0, "the slot map is allocated upfront");
+
+    let mut buf = Vec::new();
+    handle.find(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"), &mut buf)?;
+    let after = handle.store_ref().memory_usage();
+    assert!(after.mapped_indices > 0, "indices were loaded to find the object");
+    assert!(after.mapped_packs > 0, "and the pack containing it");
+    assert!(after.loose_dbs > 0);
+    assert_eq!(after.slot_map, before.slot_map, "the slot map never grows");
+    assert_eq!(
+        after.total(),
+        after.slot_map + after.slots + after.mapped_indices + after.mapped_packs + after.loose_dbs
+    );
+    Ok(())
+}
+
+#[test]
+fn prefetch_indices() -> crate::Result {
+    let (handle, _tmp) = db_with_all_object_sources()?;
+    let store = handle.into_arc()?.store();
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            std::thread::spawn({
+                let store = store.clone();
+                move || store.prefetch_indices()
+            })
+        })
+        .collect();
+    for thread in threads {
+        let metrics = thread.join().expect("no panic")?;
+        assert_eq!(metrics.open_reachable_indices, metrics.known_reachable_indices);
+    }
+
+    let metrics = store.prefetch_indices()?;
+    assert_eq!(metrics.known_reachable_indices, 2, "a multi-pack index and an index");
+    assert_eq!(
+        metrics.open_reachable_indices, metrics.known_reachable_indices,
+        "all indices are loaded"
+    );
+    assert_eq!(metrics.open_reachable_packs, 0, "but none of the packs");
+    Ok(())
+}
+
+#[test]
+fn warm_index_lookup_tables() -> crate::Result {
+    let handle = db();
+    let store = handle.store_ref();
+    assert_eq!(
+        store.warm_index_lookup_tables(),
+        0,
+        "indices that aren't loaded are skipped"
+    );
+
+    handle.packed_object_count()?;
+    assert_eq!(store.warm_index_lookup_tables(), 3);
+    assert_eq!(
+        store.memory_usage().mapped_packs,
+        0,
+        "pack data isn't needed to warm indices"
+    );
+
+    let (handle, _tmp) = db_with_all_object_sources()?;
+    handle.packed_object_count()?;
+    assert_eq!(
+        handle.store_ref().warm_index_lookup_tables(),
+        2,
+        "multi-pack indices are warmed as well"
+    );
+    Ok(())
+}
+
+#[test]
+fn full_load_address_space() -> crate::Result {
+    let handle = db();
+    let space = handle.store_ref().full_load_address_space()?;
+    assert_eq!(
+        handle.store_ref().memory_usage().mapped_indices,
+        0,
+        "nothing is mapped to learn about the file sizes"
+    );
+    assert!(space.indices > 0 && space.packs > 0);
+    assert_eq!(space.total(), space.indices + space.packs);
+
+    let mut expected = git_odb::store::AddressSpace::default();
+    for entry in std::fs::read_dir(fixture_path("objects/pack"))? {
+        let path = entry?.path();
+        let len = std::fs::metadata(&path)?.len();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("idx") => expected.indices += len,
+            Some("pack") => expected.packs += len,
+            _ => {}
+        }
+    }
+    assert_eq!(
+        space, expected,
+        "all single-pack indices and their packs are accounted for"
+    );
+    Ok(())
+}
+
+#[test]
+fn object_stream() -> crate::Result {
+    use std::io::Read;
+    fn read_in_small_chunks(mut stream: impl Read) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut chunk = [0u8; 7];
+        loop {
+            let n = stream.read(&mut chunk)?;
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&chunk[..n]);
+        }
+        Ok(out)
+    }
+
+    let handle = db();
+    let (mut num_deltified, mut num_loose) = (0, 0);
+    let mut largest = (0, ObjectId::null(git_hash::Kind::Sha1));
+    let mut buf = Vec::new();
+    for oid in handle.iter()? {
+        let oid = oid?;
+        let obj = handle.find(oid, &mut buf)?;
+        let stream = handle.object_stream(oid)?.expect("present");
+        assert_eq!(stream.kind(), obj.kind);
+        assert_eq!(stream.size(), obj.data.len() as u64);
+        assert_eq!(
+            read_in_small_chunks(stream)?,
+            obj.data,
+            "{} streams exactly what find produces",
+            oid
+        );
+        match handle.try_header(oid)?.expect("present") {
+            git_odb::find::Header::Packed(out) if out.num_deltas > 0 => num_deltified += 1,
+            git_odb::find::Header::Packed(_) => {}
+            git_odb::find::Header::Loose { .. } => num_loose += 1,
+        }
+        largest = largest.max((obj.data.len(), oid));
+    }
+    assert!(num_deltified > 0, "delta chains are resolved while streaming");
+    assert!(num_loose > 0, "loose objects are streamed as well");
+    assert!(handle.object_stream(ObjectId::null(git_hash::Kind::Sha1))?.is_none());
+
+    let (size, oid) = largest;
+    let expected = handle.find(oid, &mut buf)?.data.to_vec();
+    let mut stream = handle.object_stream(oid)?.expect("present");
+    let mut start = [0u8; 100];
+    stream.read_exact(&mut start)?;
+    drop(handle);
+    let mut rest = read_in_small_chunks(stream)?;
+    rest.splice(0..0, start);
+    assert_eq!(rest.len(), size);
+    assert_eq!(
+        rest, expected,
+        "the stream keeps its pack alive even if the store is gone"
+    );
+    Ok(())
+}
+
+#[test]
+fn object_stream_of_delta_copying_out_of_order() -> crate::Result {
+    use std::io::{Read, Write};
+    fn entry(header: git_odb::pack::data::entry::Header, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut entry = Vec::new();
+        header.write_to(data.len() as u64, &mut entry)?;
+        let mut compressed = git_features::zlib::stream::deflate::Write::new(Vec::new());
+        compressed.write_all(data)?;
+        compressed.flush()?;
+        entry.extend(compressed.into_inner());
+        Ok(entry)
+    }
+
+    let base = entry(git_odb::pack::data::entry::Header::Blob, b"0123456789abcdef")?;
+    let delta = entry(
+        git_odb::pack::data::entry::Header::OfsDelta {
+            base_distance: base.len() as u64,
+        },
+        // base size, result size, copy 8 bytes from offset 8, then copy 8 bytes from offset 0
+        &[0x10, 0x10, 0x91, 0x08, 0x08, 0x90, 0x08],
+    )?;
+    let mut pack = git_features::hash::Write::new(Vec::new(), git_hash::Kind::Sha1);
+    pack.write_all(b"PACK")?;
+    pack.write_all(&2_u32.to_be_bytes())?;
+    pack.write_all(&2_u32.to_be_bytes())?;
+    pack.write_all(&base)?;
+    pack.write_all(&delta)?;
+    let pack_checksum = pack.hash.digest();
+    let mut pack = pack.inner;
+    pack.extend_from_slice(&pack_checksum);
+
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let pack_dir = tmp.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    git_odb::pack::Bundle::write_to_directory(
+        &pack[..],
+        Some(&pack_dir),
+        git_features::progress::Discard,
+        &std::sync::atomic::AtomicBool::default(),
+        None,
+        Default::default(),
+    )?;
+
+    let handle = git_odb::at(tmp.path())?;
+    let id = git_odb::Write::write_buf(
+        &git_odb::sink(git_hash::Kind::Sha1),
+        git_object::Kind::Blob,
+        b"89abcdef01234567",
+    )?;
+    let mut stream = handle.object_stream(id)?.expect("present");
+    assert_eq!(stream.kind(), git_object::Kind::Blob);
+    assert_eq!(stream.size(), 16);
+    let mut data = Vec::new();
+    stream.read_to_end(&mut data)?;
+    assert_eq!(
+        data, b"89abcdef01234567",
+        "copies reading from an earlier part of the base are applied as well"
+    );
+    Ok(())
+}
+
+#[test]
+fn pack_stream() -> crate::Result {
+    use std::io::Read;
+    let handle = db().into_arc()?;
+    let ids = [
+        hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"),
+        hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"),
+        hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab980"),
+        hex_to_id("2c1e59ee54facb7d72c0061d06b9fe3889f357a9"),
+    ];
+    let mut stream = handle.pack_stream(ids, false /* thin */)?;
+    assert_eq!(stream.num_objects(), ids.len());
+    assert_eq!(stream.digest(), None, "the pack wasn't read yet");
+
+    let mut pack = Vec::new();
+    let mut chunk = [0u8; 100];
+    loop {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        pack.extend_from_slice(&chunk[..n]);
+    }
+    assert!(
+        stream.digest().is_some(),
+        "the checksum is known once the pack was read"
+    );
+
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let pack_dir = tmp.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    let outcome = git_odb::pack::Bundle::write_to_directory(
+        &pack[..],
+        Some(&pack_dir),
+        git_features::progress::Discard,
+        &std::sync::atomic::AtomicBool::default(),
+        None,
+        Default::default(),
+    )?;
+    assert_eq!(
+        outcome.index.num_objects,
+        ids.len() as u32,
+        "the pack is complete and deltas don't refer to objects outside of it"
+    );
+    assert_eq!(Some(outcome.index.data_hash), stream.digest());
+
+    let received = git_odb::at(tmp.path())?;
+    let (mut expected, mut actual) = (Vec::new(), Vec::new());
+    for id in ids {
+        assert_eq!(
+            received.find(id, &mut actual)?.data,
+            handle.find(id, &mut expected)?.data,
+            "all objects are in the pack, including the loose one"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn fingerprint() -> crate::Result {
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), tmp.path())?;
+    let (a, b) = (
+        git_odb::Store::at_opts(tmp.path(), None, Default::default())?,
+        git_odb::Store::at_opts(tmp.path(), None, Default::default())?,
+    );
+    let fingerprint = a.fingerprint()?;
+    assert_ne!(
+        a.marker(),
+        b.marker(),
+        "markers are specific to each instance as all indices are loaded now"
+    );
+    assert_eq!(
+        b.fingerprint()?,
+        fingerprint,
+        "fingerprints depend only on the state on disk"
+    );
+    assert_eq!(a.fingerprint()?, fingerprint, "it's stable");
+
+    std::fs::remove_file(
+        tmp.path()
+            .join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.idx"),
+    )?;
+    assert_ne!(
+        git_odb::Store::at_opts(tmp.path(), None, Default::default())?.fingerprint()?,
+        fingerprint,
+        "removing a pack changes the fingerprint"
+    );
+    Ok(())
+}
+
+#[test]
+fn fingerprint_is_independent_of_the_path_used_to_open_the_store() -> crate::Result {
+    let relative = fixture_path("objects");
+    assert!(relative.is_relative());
+    let fingerprint = git_odb::Store::at_opts(&relative, None, Default::default())?.fingerprint()?;
+    for path in [
+        std::env::current_dir()?.join(&relative),
+        fixture_path("../fixtures/objects"),
+    ] {
+        assert_eq!(
+            git_odb::Store::at_opts(&path, None, Default::default())?.fingerprint()?,
+            fingerprint,
+            "{:?} is the same repository",
+            path
+        );
+    }
+    Ok(())
+}
+
+mod empty_packs {
+    use git_odb::{Find, Header};
+    use git_testtools::{fixture_path, hex_to_id};
+
+    fn objects_dir_with_empty_pack() -> crate::Result<git_testtools::tempfile::TempDir> {
+        let tmp = git_testtools::tempfile::TempDir::new()?;
+        let pack_dir = tmp.path().join("pack");
+        std::fs::create_dir(&pack_dir)?;
+        git_testtools::copy_recursively_into_existing_dir(fixture_path("objects/pack"), &pack_dir)?;
+
+        let mut pack = Vec::new();
+        let no_entries = std::iter::empty::<Result<Vec<git_odb::pack::data::output::Entry>, std::io::Error>>();
+        for res in git_odb::pack::data::output::bytes::FromEntriesIter::new(
+            no_entries,
+            &mut pack,
+            0,
+            git_odb::pack::data::Version::V2,
+            git_hash::Kind::Sha1,
+        ) {
+            res?;
+        }
+        let outcome = git_odb::pack::Bundle::write_to_directory(
+            &pack[..],
+            Some(&pack_dir),
+            git_features::progress::Discard,
+            &std::sync::atomic::AtomicBool::default(),
+            None,
+            Default::default(),
+        )?;
+        assert_eq!(outcome.index.num_objects, 0);
+        Ok(tmp)
+    }
+
+    #[test]
+    fn do_not_affect_lookups() -> crate::Result {
+        let tmp = objects_dir_with_empty_pack()?;
+        for ignore_empty_packs in [false, true] {
+            let mut handle = git_odb::at_opts(
+                tmp.path(),
+                None,
+                git_odb::store::init::Options {
+                    ignore_empty_packs,
+                    ..Default::default()
+                },
+            )?;
+            handle.prevent_pack_unload();
+            let mut buf = Vec::new();
+            assert!(handle
+                .try_find(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), &mut buf)?
+                .is_none());
+            for id in [
+                hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5"),
+                hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"),
+            ] {
+                assert!(handle.try_find(id, &mut buf)?.is_some());
+                assert!(handle.try_header(id)?.is_some());
+                assert!(git_odb::pack::Find::location_by_oid(&handle, id, &mut buf).is_some());
+            }
+            assert_eq!(handle.packed_object_count()?, 139);
+            assert_eq!(handle.iter()?.count(), 139);
+            assert!(handle
+                .lookup_prefix(
+                    git_hash::Prefix::new(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), 7)?,
+                    None
+                )?
+                .is_none());
+
+            let metrics = handle.store_ref().metrics();
+            assert_eq!(
+                metrics.known_packs,
+                if ignore_empty_packs { 3 } else { 4 },
+                "empty packs can be skipped entirely"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn marker_access_reflects_state_changes() -> crate::Result {
+    use arc_swap::access::Access;
+    fn marker(access: &impl Access<store::SlotIndexMarker>) -> store::SlotIndexMarker {
+        *access.load()
+    }
+
+    let handle = db();
+    let store = handle.store();
+    let initial = marker(&store);
+    assert_eq!(initial, handle.store_ref().marker());
+    assert_eq!(marker(&store), initial, "nothing changes without use");
+
+    assert!(handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")));
+    let after_use = marker(&store);
+    assert_ne!(after_use, initial, "the store was initialized and an index was loaded");
+    assert_eq!(marker(&store), after_use);
+    Ok(())
+}
+
+#[test]
+fn snapshot_access_does_not_change_while_held() -> crate::Result {
+    use arc_swap::access::Access;
+    fn snapshot(access: &impl Access<store::SlotMapIndex>) -> impl std::ops::Deref<Target = store::SlotMapIndex> {
+        access.load()
+    }
+
+    let handle = db();
+    let store = handle.store();
+    let initial = snapshot(&store);
+    assert_eq!(initial.num_indices(), 0, "nothing is known before the store is used");
+
+    assert!(handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")));
+    assert_eq!(initial.num_indices(), 0, "the snapshot held doesn't change");
+    let current = snapshot(&store);
+    assert_eq!(current.num_indices(), 3, "the current one knows all indices");
+    assert_eq!(current.num_loose_dbs(), 1);
+    assert_eq!(current.generation(), initial.generation());
+    Ok(())
+}
+
+#[test]
+fn change_since_marker() -> crate::Result {
+    use git_odb::store::MarkerChange;
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let pack_dir = tmp.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    let copy_pack = |name: &str| -> std::io::Result<()> {
+        for ext in ["idx", "pack"] {
+            let file_name = format!("{}.{}", name, ext);
+            std::fs::copy(fixture_path("objects/pack").join(&file_name), pack_dir.join(file_name))?;
+        }
+        Ok(())
+    };
+    copy_pack("pack-c0438c19fb16422b6bbcce24387b3264416d485b")?;
+    let handle = git_odb::at_opts(
+        tmp.path(),
+        None,
+        git_odb::store::init::Options {
+            slots: git_odb::store::init::Slots::Given(1),
+            ..Default::default()
+        },
+    )?;
+    let store = handle.store_ref();
+    let initial = store.marker();
+    assert_eq!(store.change_since(initial), MarkerChange::Unchanged);
+
+    assert!(handle.contains(hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5")));
+    assert_eq!(
+        store.change_since(initial),
+        MarkerChange::Updated,
+        "the index was loaded, which keeps pack ids"
+    );
+    let loaded = store.marker();
+    assert_eq!(store.change_since(loaded), MarkerChange::Unchanged);
+
+    for ext in ["idx", "pack"] {
+        std::fs::remove_file(pack_dir.join(format!("pack-c0438c19fb16422b6bbcce24387b3264416d485b.{}", ext)))?;
+    }
+    copy_pack("pack-11fdfa9e156ab73caae3b6da867192221f2089c2")?;
+    assert!(handle.contains(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0")));
+    assert_eq!(
+        store.change_since(loaded),
+        MarkerChange::Regenerated,
+        "the only slot had to be reused for a different pack"
+    );
+    Ok(())
+}
+
+#[test]
+fn diff_between_markers_lists_the_indices_loaded_in_between() -> crate::Result {
+    use git_odb::store::MarkerDiff;
+    use git_pack::Find;
+    let mut handle = git_odb::at(fixture_path("objects"))?;
+    handle.prevent_pack_unload();
+    let store = handle.store();
+    let mut buf = Vec::new();
+    let mut markers = vec![store.marker()];
+    let mut index_ids = Vec::new();
+    // in the order in which indices are loaded, largest first
+    for id in [
+        "dd25c539efbb0ab018caa4cda2d133285634e9b5",
+        "0d9726f3fd2359cda58217724f12af273bf440a6",
+        "501b297447a8255d3533c6858bb692575cdefaa0",
+    ] {
+        let location = handle.location_by_oid(hex_to_id(id), &mut buf).expect("object exists");
+        index_ids.push(location.pack_id as git_odb::store::IndexId);
+        markers.push(store.marker());
+    }
+
+    for (markers, index_id) in markers.windows(2).zip(&index_ids) {
+        assert_eq!(
+            store.diff(&markers[0], &markers[1]),
+            MarkerDiff::Compatible { added: vec![*index_id] },
+            "each lookup loaded only the index of the pack containing the object"
+        );
+    }
+    assert_eq!(
+        store.diff(&markers[0], &markers[3]),
+        MarkerDiff::Compatible {
+            added: index_ids.clone()
+        },
+        "indices are listed in the order they were loaded"
+    );
+    assert_eq!(
+        store.diff(&markers[3], &markers[3]),
+        MarkerDiff::Compatible { added: Vec::new() }
+    );
+    assert_eq!(
+        store.diff(&markers[3], &markers[0]),
+        MarkerDiff::Incomparable,
+        "the old marker can't be more recent than the new one"
+    );
+    assert_eq!(
+        db().store_ref().diff(&markers[0], &markers[3]),
+        MarkerDiff::Incomparable,
+        "markers of other stores can't be compared"
+    );
+
+    drop(handle);
+    store.close()?;
+    assert_eq!(store.diff(&markers[3], &store.marker()), MarkerDiff::Regenerated);
+    assert_eq!(
+        store.diff(&markers[0], &markers[3]),
+        MarkerDiff::Incomparable,
+        "the store can't tell what changed in previous generations"
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+fn multi_index_entries_with_offsets_beyond_4gb_are_resolved_using_large_offsets() -> crate::Result {
+    use std::io::{Seek, SeekFrom, Write};
+
+    use git_pack::Find;
+    // A blob stored as undeltified entry in one of the fixture packs, which we copy into a sparse pack past the 4GB mark.
+    let id = hex_to_id("2dad8b277db3a95919bd904133d7e7cc3e323cb9");
+    let (fixture_pack_offset, entry_size) = (38056, 42);
+    let large_offset = u32::MAX as u64 + 1 + 12;
+
+    let fixture_pack = std::fs::read(fixture_path(
+        "objects/pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack",
+    ))?;
+    let entry = &fixture_pack[fixture_pack_offset..][..entry_size];
+
+    let objects_dir = git_testtools::tempfile::tempdir()?;
+    let pack_dir = objects_dir.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    let pack_path = pack_dir.join("pack-1111111111111111111111111111111111111111.pack");
+    {
+        let mut pack = std::fs::File::create(&pack_path)?;
+        pack.write_all(b"PACK")?;
+        pack.write_all(&2u32.to_be_bytes())?;
+        pack.write_all(&1u32.to_be_bytes())?;
+        pack.seek(SeekFrom::Start(large_offset))?;
+        pack.write_all(entry)?;
+        pack.write_all(&[0; 20])?;
+    }
+    let index_path = pack_path.with_extension("idx");
+    {
+        let mut index = Vec::new();
+        index.extend_from_slice(b"\xfftOc");
+        index.extend_from_slice(&2u32.to_be_bytes());
+        for first_byte in 0..256u32 {
+            let count = u32::from(first_byte >= u32::from(id.first_byte()));
+            index.extend_from_slice(&count.to_be_bytes());
+        }
+        index.extend_from_slice(id.as_bytes());
+        index.extend_from_slice(&git_features::hash::crc32(entry).to_be_bytes());
+        index.extend_from_slice(&(1u32 << 31).to_be_bytes());
+        index.extend_from_slice(&large_offset.to_be_bytes());
+        index.extend_from_slice(&[0; 20 * 2]);
+        std::fs::write(&index_path, index)?;
+    }
+    git_odb::pack::multi_index::File::write_from_index_paths(
+        vec![index_path],
+        std::fs::File::create(pack_dir.join("multi-pack-index"))?,
+        git_features::progress::Discard,
+        &std::sync::atomic::AtomicBool::default(),
+        git_odb::pack::multi_index::write::Options {
+            object_hash: git_hash::Kind::Sha1,
+        },
+    )?;
+
+    let mut handle = git_odb::at(objects_dir.path())?;
+    handle.prevent_pack_unload();
+    let mut buf = Vec::new();
+    let location = handle.location_by_oid(id, &mut buf).expect("object exists");
+    assert_eq!(location.pack_offset, large_offset, "the 64 bit offset is used as is");
+    assert_eq!(location.entry_size, entry_size);
+
+    let pack = git_odb::pack::data::File::at(&pack_path, git_hash::Kind::Sha1)?;
+    assert_eq!(
+        pack.entry_slice(location.entry_range(location.pack_offset)),
+        Some(entry),
+        "the location refers to the entry in the member pack"
+    );
+
+    let mut expected = Vec::new();
+    let expected = db().find(id, &mut expected)?;
+    let actual = handle.find(id, &mut buf)?;
+    assert_eq!(actual.kind, expected.kind);
+    assert_eq!(
+        actual.data, expected.data,
+        "the decoded object is the same as the one in the fixture"
+    );
+    Ok(())
+}
+
+mod pack_path {
+    use git_odb::store::PackId;
+    use git_pack::Find;
+    use git_testtools::{fixture_path, hex_to_id};
+
+    use crate::store::dynamic::db_with_all_object_sources;
+
+    #[test]
+    fn single_index() -> crate::Result {
+        let mut handle = git_odb::at(fixture_path("objects"))?;
+        handle.prevent_pack_unload();
+        let location = handle
+            .location_by_oid(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut Vec::new())
+            .expect("object exists");
+        assert_eq!(
+            handle.pack_path(PackId::from_intrinsic_pack_id(location.pack_id)),
+            Some(fixture_path(
+                "objects/pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack"
+            ))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn multi_index() -> crate::Result {
+        let (mut handle, tmp) = db_with_all_object_sources()?;
+        handle.prevent_pack_unload();
+        for (id, pack_name) in [
+            (
+                "dd25c539efbb0ab018caa4cda2d133285634e9b5",
+                "pack-c0438c19fb16422b6bbcce24387b3264416d485b.pack",
+            ),
+            (
+                "0d9726f3fd2359cda58217724f12af273bf440a6",
+                "pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.pack",
+            ),
+        ] {
+            let location = handle
+                .location_by_oid(hex_to_id(id), &mut Vec::new())
+                .expect("object exists");
+            let pack_id = PackId::from_intrinsic_pack_id(location.pack_id);
+            assert_eq!(
+                handle.pack_path(pack_id),
+                Some(tmp.path().join("pack").join(pack_name)),
+                "the path of the member pack is returned"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn stale_generation() -> crate::Result {
+        let tmp = git_testtools::tempfile::TempDir::new()?;
+        git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), tmp.path())?;
+        let mut stable_handle = git_odb::at(tmp.path())?;
+        stable_handle.prevent_pack_unload();
+        let location = stable_handle
+            .location_by_oid(hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0"), &mut Vec::new())
+            .expect("object exists");
+        let pack_id = PackId::from_intrinsic_pack_id(location.pack_id);
+        let store = stable_handle.store();
+        let handle = store.to_handle();
+        assert!(handle.pack_path(pack_id).is_some());
+
+        let stem = tmp.path().join("pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2");
+        std::fs::remove_file(stem.with_extension("idx"))?;
+        std::fs::remove_file(stem.with_extension("pack"))?;
+        drop(stable_handle);
+        assert!(store.refresh()?.generation_changed);
+
+        assert_eq!(
+            handle.pack_path(pack_id),
+            None,
+            "the handle still sees the previous generation"
+        );
+        assert_eq!(
+            store.to_handle().pack_path(pack_id),
+            None,
+            "the slot of the pack was freed"
+        );
+        Ok(())
+    }
+}
+
+#[test]
+fn running_out_of_slots_keeps_existing_slots_usable_for_concurrent_lookups() -> crate::Result {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+    let tmp = git_testtools::tempfile::TempDir::new()?;
+    let pack_dir = tmp.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+    let copy_pack = |name: &str| -> std::io::Result<()> {
+        for ext in ["idx", "pack"] {
+            let file_name = format!("{}.{}", name, ext);
+            std::fs::copy(fixture_path("objects/pack").join(&file_name), pack_dir.join(file_name))?;
+        }
+        Ok(())
+    };
+    copy_pack("pack-c0438c19fb16422b6bbcce24387b3264416d485b")?;
+    let handle = git_odb::at_opts(
+        tmp.path(),
+        None,
+        git_odb::store::init::Options {
+            slots: git_odb::store::init::Slots::Given(2),
+            ..Default::default()
+        },
+    )?
+    .into_arc()?;
+    let packed = hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+    assert!(handle.contains(packed));
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let reader = std::thread::spawn({
+        let mut handle = handle.clone();
+        handle.refresh_never();
+        let stop = Arc::clone(&stop);
+        move || -> usize {
+            let mut buf = Vec::new();
+            let mut lookups = 0;
+            while !stop.load(Ordering::Relaxed) || lookups == 0 {
+                handle
+                    .find(packed, &mut buf)
+                    .expect("objects in existing slots stay available");
+                lookups += 1;
+            }
+            lookups
+        }
+    });
+
+    copy_pack("pack-11fdfa9e156ab73caae3b6da867192221f2089c2")?;
+    copy_pack("pack-a2bf8e71d8c18879e499335762dd95119d93d9f1")?;
+    let err = handle
+        .try_find(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), &mut Vec::new())
+        .expect_err("there is more packs than slots");
+    assert!(
+        matches!(
+            err,
+            git_odb::store::find::Error::LoadIndex(git_odb::store::load_index::Error::InsufficientSlots {
+                current: 2,
+                ..
+            })
+        ),
+        "the slot map can't grow, as this would invalidate slot indices held by handles: {:?}",
+        err
+    );
+    assert!(handle.contains(packed));
+
+    stop.store(true, Ordering::Relaxed);
+    assert!(reader.join().expect("no panic") > 0);
+    assert_eq!(
+        handle.store_ref().metrics().known_reachable_indices,
+        1,
+        "the failed refresh didn't change the slot map"
+    );
+    Ok(())
+}
+
+mod verify {
+    use std::sync::atomic::AtomicBool;
+
+    use git_features::progress;
+    use git_odb::store::verify::{index, multi_index};
+    use git_testtools::fixture_path;
+
+    use crate::store::dynamic::{db, db_with_all_object_sources};
+
+    #[test]
+    fn integrity() {
+        let handle = db();
+        let outcome = handle
+            .store_ref()
+            .verify_integrity(progress::Discard, &AtomicBool::new(false), Default::default())
+            .unwrap();
+        assert_eq!(outcome.index_statistics.len(), 3, "there are only three packs to check");
+        assert_eq!(
+            outcome.index_statistics[0].path,
+            fixture_path("objects/pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b.idx")
+        );
+        assert_eq!(
+            outcome.index_statistics[1].path,
+            fixture_path("objects/pack/pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.idx")
+        );
+        assert_eq!(
+            outcome.index_statistics[2].path,
+            fixture_path("objects/pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.idx")
+        );
+        assert_eq!(
+            outcome.loose_object_stores,
+            vec![git_odb::store::verify::integrity::LooseObjectStatistics {
+                path: fixture_path("objects"),
+                statistics: git_odb::loose::verify::integrity::Statistics { num_objects: 7 }
+            }]
+        );
+
+        assert_eq!(
+            handle.store_ref().metrics(),
+            git_odb::store::Metrics {
+                label: None,
+                num_handles: 1,
+                num_refreshes: 1,
+                open_reachable_indices: 0,
+                known_reachable_indices: 3,
+                open_reachable_packs: 0,
+                open_files: 0,
+                known_packs: 3,
+                unused_slots: 29,
+                loose_dbs: 1,
+                unreachable_indices: 0,
+                unreachable_packs: 0,
+                poisoned_packs: 0
+            },
+            "verification only discovers files on disk but won't cause them to be opened permanently"
+        );
+    }
+
+    #[test]
+    fn multi_indices_without_problems() -> crate::Result {
+        let (handle, tmp) = db_with_all_object_sources()?;
+        let outcome = handle
+            .store_ref()
+            .verify_multi_indices(progress::Discard, &AtomicBool::new(false))?;
+        assert!(outcome.is_consistent());
+        assert_eq!(
+            outcome.multi_indices,
+            vec![multi_index::MultiIndex {
+                path: tmp.path().join("pack/multi-pack-index"),
+                problems: Vec::new(),
+                rebuilt: false,
+            }]
+        );
+        assert_eq!(
+            db().store_ref()
+                .verify_multi_indices(progress::Discard, &AtomicBool::new(false))?
+                .multi_indices
+                .len(),
+            0,
+            "stores without multi-pack indices have nothing to verify"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn multi_indices_with_replaced_and_missing_packs() -> crate::Result {
+        let (handle, tmp) = db_with_all_object_sources()?;
+        let pack_dir = tmp.path().join("pack");
+        let replaced = pack_dir.join("pack-c0438c19fb16422b6bbcce24387b3264416d485b");
+        let missing = pack_dir.join("pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.pack");
+        let other = pack_dir.join("pack-11fdfa9e156ab73caae3b6da867192221f2089c2");
+        std::fs::copy(other.with_extension("pack"), replaced.with_extension("pack"))?;
+        std::fs::remove_file(&missing)?;
+
+        let outcome = handle
+            .store_ref()
+            .verify_multi_indices(progress::Discard, &AtomicBool::new(false))?;
+        assert!(!outcome.is_consistent());
+        let problems = &outcome.multi_indices[0].problems;
+        let pack_checksum = |path: &std::path::Path| -> crate::Result<git_hash::ObjectId> {
+            Ok(git_odb::pack::data::File::at(path, git_hash::Kind::Sha1)?.checksum())
+        };
+        assert_eq!(
+            problems,
+            &[
+                multi_index::Problem::MissingPack { path: missing },
+                multi_index::Problem::PackMismatch {
+                    path: replaced.with_extension("pack"),
+                    expected: pack_checksum(&fixture_path(
+                        "objects/pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b.pack"
+                    ))?,
+                    actual: pack_checksum(&other.with_extension("pack"))?,
+                }
+            ],
+            "all problems are reported, but objects are still found in the indices of their packs"
+        );
+
+        std::fs::copy(other.with_extension("idx"), replaced.with_extension("idx"))?;
+        let outcome = handle
+            .store_ref()
+            .verify_multi_indices(progress::Discard, &AtomicBool::new(false))?;
+        let problems = &outcome.multi_indices[0].problems;
+        assert_eq!(
+            problems[0],
+            multi_index::Problem::MissingPack {
+                path: pack_dir.join("pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.pack")
+            }
+        );
+        assert_eq!(
+            problems.len(),
+            1 + 67,
+            "each object of the replaced pack is reported as missing"
+        );
+        assert!(problems[1..]
+            .iter()
+            .all(|p| matches!(p, multi_index::Problem::MissingObject { index, .. } if *index == replaced.with_extension("idx"))));
+        Ok(())
+    }
+
+    #[test]
+    fn inconsistent_multi_indices_can_be_rebuilt() -> crate::Result {
+        use git_odb::Find;
+        let (handle, tmp) = db_with_all_object_sources()?;
+        let pack_dir = tmp.path().join("pack");
+        std::fs::copy(
+            pack_dir.join("pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack"),
+            pack_dir.join("pack-c0438c19fb16422b6bbcce24387b3264416d485b.pack"),
+        )?;
+        std::fs::remove_file(pack_dir.join("pack-a2bf8e71d8c18879e499335762dd95119d93d9f1.pack"))?;
+        let multi_index_path = pack_dir.join("multi-pack-index");
+        let before = std::fs::read(&multi_index_path)?;
+
+        let outcome = handle
+            .store_ref()
+            .verify_multi_indices(progress::Discard, &AtomicBool::new(false))?;
+        assert_eq!(outcome.multi_indices[0].problems.len(), 2);
+        assert!(
+            !outcome.multi_indices[0].rebuilt,
+            "problems are only reported by default"
+        );
+        assert_eq!(std::fs::read(&multi_index_path)?, before);
+
+        let outcome = handle.store_ref().verify_multi_indices_opts(
+            progress::Discard,
+            &AtomicBool::new(false),
+            multi_index::Options {
+                rebuild_inconsistent: true,
+            },
+        )?;
+        assert_eq!(
+            outcome.multi_indices[0].problems.len(),
+            2,
+            "problems before the rebuild"
+        );
+        assert!(outcome.multi_indices[0].rebuilt);
+
+        let rebuilt = git_odb::pack::multi_index::File::at(&multi_index_path)?;
+        assert_eq!(
+            rebuilt.index_names(),
+            &[std::path::PathBuf::from(
+                "pack-11fdfa9e156ab73caae3b6da867192221f2089c2.idx"
+            )],
+            "only the pack that is present and belongs to its index remains"
+        );
+        assert!(handle
+            .store_ref()
+            .verify_multi_indices(progress::Discard, &AtomicBool::new(false))?
+            .is_consistent());
+        assert!(
+            handle.contains(git_testtools::hex_to_id("501b297447a8255d3533c6858bb692575cdefaa0")),
+            "the store uses the rebuilt multi-pack index"
+        );
+        Ok(())
+    }
+
+    /// Load all indices of the store of `handle` and return their ids.
+    fn all_index_ids(handle: &git_odb::Handle) -> crate::Result<Vec<git_odb::store::IndexId>> {
+        let store = handle.store_ref();
+        let before = store.marker();
+        handle.packed_object_count()?;
+        match store.diff(&before, &store.marker()) {
+            git_odb::store::MarkerDiff::Compatible { added } => Ok(added),
+            other => unreachable!("nothing else changed: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn index_matching_its_pack() -> crate::Result {
+        let (handle, _tmp) = db_with_all_object_sources()?;
+        let ids = all_index_ids(&handle)?;
+        assert_eq!(ids.len(), 2, "the multi-pack index and the index not covered by it");
+        for id in ids {
+            handle
+                .store_ref()
+                .verify_index(id, progress::Discard, &AtomicBool::new(false))?;
+        }
+
+        assert!(matches!(
+            handle
+                .store_ref()
+                .verify_index(31, progress::Discard, &AtomicBool::new(false)),
+            Err(index::Error::UnknownIndex(31))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn index_not_matching_its_pack_or_checksum() -> crate::Result {
+        let tmp = git_testtools::tempfile::TempDir::new()?;
+        git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), tmp.path())?;
+        let pack_dir = tmp.path().join("pack");
+        let replaced = pack_dir.join("pack-c0438c19fb16422b6bbcce24387b3264416d485b.pack");
+        let other = pack_dir.join("pack-11fdfa9e156ab73caae3b6da867192221f2089c2");
+        std::fs::copy(other.with_extension("pack"), &replaced)?;
+        let mut index_data = std::fs::read(other.with_extension("idx"))?;
+        *index_data.last_mut().expect("not empty") ^= 1;
+        std::fs::write(other.with_extension("idx"), index_data)?;
+
+        let handle = git_odb::at(tmp.path())?;
+        let pack_checksum = |path: &std::path::Path| -> crate::Result<git_hash::ObjectId> {
+            Ok(git_odb::pack::data::File::at(path, git_hash::Kind::Sha1)?.checksum())
+        };
+        let mut mismatches = 0;
+        let mut checksum_errors = 0;
+        for id in all_index_ids(&handle)? {
+            match handle
+                .store_ref()
+                .verify_index(id, progress::Discard, &AtomicBool::new(false))
+            {
+                Ok(()) => {}
+                Err(index::Error::PackMismatch { path, expected, actual }) => {
+                    assert_eq!(path, replaced);
+                    assert_eq!(
+                        expected,
+                        pack_checksum(&fixture_path(
+                            "objects/pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b.pack"
+                        ))?
+                    );
+                    assert_eq!(actual, pack_checksum(&other.with_extension("pack"))?);
+                    mismatches += 1;
+                }
+                Err(index::Error::Checksum { path, .. }) => {
+                    assert_eq!(path, other.with_extension("idx"));
+                    checksum_errors += 1;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        assert_eq!(
+            (mismatches, checksum_errors),
+            (1, 1),
+            "the pack not belonging to its index, and the altered index are detected"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn multi_indices_interruption() -> crate::Result {
+        let (handle, _tmp) = db_with_all_object_sources()?;
+        assert!(matches!(
+            handle
+                .store_ref()
+                .verify_multi_indices(progress::Discard, &AtomicBool::new(true)),
+            Err(multi_index::Error::Interrupted)
+        ));
+        Ok(())
+    }
+}
+
+mod count {
+    use std::sync::atomic::AtomicBool;
+
+    use git_features::progress;
+    use git_odb::store::count::by_kind;
+
+    use crate::store::dynamic::db;
+
+    #[test]
+    fn objects_by_kind() {
+        let handle = db();
+        let precise = handle
+            .store_ref()
+            .count_objects_by_kind(progress::Discard, &AtomicBool::new(false), by_kind::Mode::ResolveDeltas)
+            .unwrap();
+        let fast = handle
+            .store_ref()
+            .count_objects_by_kind(
+                progress::Discard,
+                &AtomicBool::new(false),
+                by_kind::Mode::DeclaredKindOnly,
+            )
+            .unwrap();
+        assert_eq!(
+            precise,
+            by_kind::Outcome {
+                commits: 27,
+                trees: 33,
+                blobs: 85,
+                tags: 1,
+                deltas: 0,
+            },
+            "all delta bases are in their own pack, so all kinds can be determined"
+        );
+        assert_eq!(
+            fast,
+            by_kind::Outcome {
+                commits: 27,
+                trees: 20,
+                blobs: 71,
+                tags: 1,
+                deltas: 27,
+            },
+            "delta objects remain undetermined"
+        );
+        assert_eq!(precise.total(), fast.total(), "both modes see the same objects");
+    }
+
+    #[test]
+    fn interruption() {
+        let handle = db();
+        let err = handle
+            .store_ref()
+            .count_objects_by_kind(progress::Discard, &AtomicBool::new(true), Default::default())
+            .unwrap_err();
+        assert!(matches!(err, by_kind::Error::Interrupted));
+    }
+}
+
+mod savings {
+    use std::sync::atomic::AtomicBool;
+
+    use git_features::progress;
+    use git_odb::store::savings;
+    use git_testtools::fixture_path;
+
+    use crate::store::dynamic::db;
+
+    #[test]
+    fn nothing_to_save_without_duplicates() {
+        let handle = db();
+        let outcome = handle
+            .store_ref()
+            .estimate_repack_savings(progress::Discard, &AtomicBool::new(false))
+            .unwrap();
+        assert_eq!(outcome, savings::Outcome::default(), "all objects are stored only once");
+    }
+
+    #[test]
+    fn duplicate_packs_are_redundant() -> crate::Result {
+        let tmp = git_testtools::tempfile::TempDir::new()?;
+        git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), tmp.path())?;
+        let pack = tmp.path().join("pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b");
+        for ext in ["idx", "pack"] {
+            std::fs::copy(
+                pack.with_extension(ext),
+                tmp.path().join("pack/pack-duplicate").with_extension(ext),
+            )?;
+        }
+        let pack_len = std::fs::metadata(pack.with_extension("pack"))?.len();
+
+        let handle = git_odb::at(tmp.path())?;
+        let outcome = handle
+            .store_ref()
+            .estimate_repack_savings(progress::Discard, &AtomicBool::new(false))
+            .unwrap();
+        assert_eq!(outcome.packs.len(), 1, "only the second copy is redundant");
+        let pack = &outcome.packs[0];
+        assert_eq!(pack.duplicate_objects, 67, "all of its objects");
+        assert_eq!(
+            pack.duplicate_bytes,
+            pack_len - 12 - 20,
+            "all entries, without header and trailer"
+        );
+        assert_eq!(pack.cross_pack_deltas, 0);
+        assert_eq!(outcome.total_redundant_bytes, pack.duplicate_bytes);
+        Ok(())
+    }
+
+    #[test]
+    fn interruption() {
+        let handle = db();
+        let err = handle
+            .store_ref()
+            .estimate_repack_savings(progress::Discard, &AtomicBool::new(true))
+            .unwrap_err();
+        assert!(matches!(err, savings::Error::Interrupted));
+    }
+}
+
+mod overlap {
+    use std::{
+        io::Write,
+        path::{Path, PathBuf},
+        sync::atomic::AtomicBool,
+    };
+
+    use git_features::progress;
+    use git_odb::{store::overlap, FindExt};
+    use git_testtools::{fixture_path, hex_to_id};
+
+    use crate::store::dynamic::db;
+
+    /// Write a pack with a single object stored as delta against `base`, which is stored elsewhere, along with its index.
+    /// Return the path to the pack.
+    fn write_thin_pack(pack_dir: &Path, base: git_hash::ObjectId, base_data: &[u8]) -> crate::Result<PathBuf> {
+        fn size(mut n: usize, out: &mut Vec<u8>) {
+            loop {
+                let byte = (n & 0x7f) as u8;
+                n >>= 7;
+                if n == 0 {
+                    out.push(byte);
+                    break;
+                }
+                out.push(byte | 0x80);
+            }
+        }
+        let appended = b"appended\n";
+        let mut delta = Vec::new();
+        size(base_data.len(), &mut delta);
+        size(base_data.len() + appended.len(), &mut delta);
+        let copy_size = (base_data.len() as u32).to_le_bytes();
+        let mut copy = vec![0x80];
+        for (bit, byte) in copy_size.iter().take(3).enumerate().filter(|(_, b)| **b != 0) {
+            copy[0] |= 0x10 << bit;
+            copy.push(*byte);
+        }
+        delta.extend(copy);
+        delta.push(appended.len() as u8);
+        delta.extend_from_slice(appended);
+
+        let mut entry = Vec::new();
+        git_odb::pack::data::entry::Header::RefDelta { base_id: base }.write_to(delta.len() as u64, &mut entry)?;
+        let mut compressed = git_features::zlib::stream::deflate::Write::new(Vec::new());
+        compressed.write_all(&delta)?;
+        compressed.flush()?;
+        entry.extend(compressed.into_inner());
+
+        let mut pack = git_features::hash::Write::new(Vec::new(), git_hash::Kind::Sha1);
+        pack.write_all(b"PACK")?;
+        pack.write_all(&2_u32.to_be_bytes())?;
+        pack.write_all(&1_u32.to_be_bytes())?;
+        pack.write_all(&entry)?;
+        let pack_checksum = pack.hash.digest();
+        let mut pack = pack.inner;
+        pack.extend_from_slice(&pack_checksum);
+
+        let mut object = base_data.to_vec();
+        object.extend_from_slice(appended);
+        let id = git_odb::Write::write_buf(&git_odb::sink(git_hash::Kind::Sha1), git_object::Kind::Blob, &object)?;
+        let mut index = git_features::hash::Write::new(Vec::new(), git_hash::Kind::Sha1);
+        index.write_all(b"\xfftOc")?;
+        index.write_all(&2_u32.to_be_bytes())?;
+        for first_byte in 0..=255_u8 {
+            index.write_all(&u32::from(first_byte >= id.as_slice()[0]).to_be_bytes())?;
+        }
+        index.write_all(id.as_slice())?;
+        index.write_all(&git_features::hash::crc32(&entry).to_be_bytes())?;
+        index.write_all(&12_u32.to_be_bytes())?;
+        index.write_all(&pack_checksum)?;
+        let index_checksum = index.hash.digest();
+        let mut index = index.inner;
+        index.extend_from_slice(&index_checksum);
+
+        let path = pack_dir.join("pack-thin.pack");
+        std::fs::write(&path, pack)?;
+        std::fs::write(path.with_extension("idx"), index)?;
+        Ok(path)
+    }
+
+    #[test]
+    fn packs_without_cross_pack_deltas_have_no_candidates() -> crate::Result {
+        let handle = db();
+        let pack = fixture_path("objects/pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack");
+        let outcome = handle
+            .store_ref()
+            .delta_base_overlap(&pack, progress::Discard, &AtomicBool::new(false))?;
+        assert_eq!(
+            outcome,
+            overlap::Outcome {
+                num_objects: 30,
+                cross_pack_deltas: 0,
+                unresolved_bases: 0,
+                candidates: Vec::new(),
+            },
+            "its deltas all have their base in the same pack"
+        );
+        assert_eq!(outcome.nearest(), None);
+
+        assert!(matches!(
+            handle.store_ref().delta_base_overlap(
+                fixture_path("objects/pack/does-not-exist.pack"),
+                progress::Discard,
+                &AtomicBool::new(false)
+            ),
+            Err(overlap::Error::PackNotFound { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn bases_of_cross_pack_deltas_are_attributed_to_their_pack() -> crate::Result {
+        let tmp = git_testtools::tempfile::TempDir::new()?;
+        git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), tmp.path())?;
+        let base = hex_to_id("dd25c539efbb0ab018caa4cda2d133285634e9b5");
+        let mut buf = Vec::new();
+        let base_data = db().find_blob(base, &mut buf)?.data.to_owned();
+        let thin_pack = write_thin_pack(&tmp.path().join("pack"), base, &base_data)?;
+
+        let handle = git_odb::at(tmp.path())?;
+        let outcome = handle
+            .store_ref()
+            .delta_base_overlap(&thin_pack, progress::Discard, &AtomicBool::new(false))?;
+        let base_pack = tmp
+            .path()
+            .join("pack/pack-c0438c19fb16422b6bbcce24387b3264416d485b.pack");
+        assert_eq!(
+            outcome,
+            overlap::Outcome {
+                num_objects: 1,
+                cross_pack_deltas: 1,
+                unresolved_bases: 0,
+                candidates: vec![overlap::Candidate {
+                    path: base_pack.clone(),
+                    delta_objects: 1
+                }],
+            }
+        );
+        assert_eq!(outcome.nearest().map(|c| c.path.as_path()), Some(base_pack.as_path()));
+
+        std::fs::remove_file(base_pack.with_extension("idx"))?;
+        std::fs::remove_file(&base_pack)?;
+        let handle = git_odb::at(tmp.path())?;
+        let outcome = handle
+            .store_ref()
+            .delta_base_overlap(&thin_pack, progress::Discard, &AtomicBool::new(false))?;
+        assert_eq!(outcome.unresolved_bases, 1, "the base isn't in any pack anymore");
+        assert_eq!(outcome.nearest(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn interruption() {
+        let pack = fixture_path("objects/pack/pack-11fdfa9e156ab73caae3b6da867192221f2089c2.pack");
+        assert!(matches!(
+            db().store_ref()
+                .delta_base_overlap(&pack, progress::Discard, &AtomicBool::new(true)),
+            Err(overlap::Error::Interrupted)
+        ));
+    }
 }
@@ -41,6 +41,37 @@ fn verify_integrity() {
     assert_eq!(outcome.num_objects, 7);
 }
 
+#[test]
+fn verify_object_names() -> crate::Result {
+    let db = ldb();
+    let outcome = db.verify_object_names(progress::Discard, &AtomicBool::new(false))?;
+    assert_eq!(outcome.num_objects, 7);
+    assert!(outcome.mismatches.is_empty());
+
+    let tmp = tempfile::tempdir()?;
+    git_testtools::copy_recursively_into_existing_dir(fixture_path("objects"), &tmp)?;
+    let (source, renamed) = (
+        tmp.path().join("37/d4e6c5c48ba0d245164c4e10d5f41140cab980"),
+        tmp.path().join("59/5dfd62fc1ad283d61bb47a24e7a1f66398f84d"),
+    );
+    std::fs::remove_file(&renamed)?;
+    std::fs::copy(source, &renamed)?;
+
+    let db = Store::at(tmp.path(), git_hash::Kind::Sha1);
+    let outcome = db.verify_object_names(progress::Discard, &AtomicBool::new(false))?;
+    assert_eq!(outcome.num_objects, 7, "mismatching objects are counted as well");
+    assert_eq!(
+        outcome.mismatches,
+        vec![git_odb::loose::verify::names::Mismatch {
+            path: renamed.clone(),
+            expected: hex_to_id("595dfd62fc1ad283d61bb47a24e7a1f66398f84d"),
+            actual: hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab980"),
+        }]
+    );
+    assert!(renamed.is_file(), "mismatching objects are only reported");
+    Ok(())
+}
+
 mod write {
     use git_odb::{loose, Write};
 
@@ -70,6 +101,71 @@ mod write {
         }
         Ok(())
     }
+
+    #[test]
+    fn existing_objects_are_not_rewritten() -> crate::Result {
+        let dir = tempfile::tempdir()?;
+        let db = loose::Store::at(dir.path(), git_hash::Kind::Sha1);
+        let id = db.write_buf(git_object::Kind::Blob, b"hello")?;
+        let hex = id.to_hex().to_string();
+        let object_path = dir.path().join(&hex[..2]).join(&hex[2..]);
+        let past = filetime::FileTime::from_unix_time(1, 0);
+        filetime::set_file_mtime(&object_path, past)?;
+
+        assert_eq!(db.write_buf(git_object::Kind::Blob, b"hello")?, id);
+        assert_eq!(
+            filetime::FileTime::from_last_modification_time(&std::fs::metadata(&object_path)?),
+            past,
+            "the object was left untouched"
+        );
+        assert_eq!(
+            std::fs::read_dir(dir.path())?.count(),
+            1,
+            "the temporary file was removed, leaving only the directory of the object"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_writers_of_the_same_object_succeed() -> crate::Result {
+        let dir = tempfile::tempdir()?;
+        let db = std::sync::Arc::new(loose::Store::at(dir.path(), git_hash::Kind::Sha1));
+        let data = b"written by many".repeat(1000);
+        let num_threads = 8;
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(num_threads));
+        let threads: Vec<_> = (0..num_threads)
+            .map(|_| {
+                std::thread::spawn({
+                    let db = db.clone();
+                    let data = data.clone();
+                    let barrier = barrier.clone();
+                    move || {
+                        barrier.wait();
+                        db.write_buf(git_object::Kind::Blob, &data)
+                    }
+                })
+            })
+            .collect();
+        let ids = threads
+            .into_iter()
+            .map(|thread| thread.join().expect("no panic"))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(
+            ids.windows(2).all(|ids| ids[0] == ids[1]),
+            "all writers agree on the id"
+        );
+
+        let mut buf = Vec::new();
+        let obj = db.try_find(ids[0], &mut buf)?.expect("object was written");
+        assert_eq!(obj.kind, git_object::Kind::Blob);
+        assert_eq!(obj.data, data, "the object wasn't corrupted");
+        assert_eq!(
+            std::fs::read_dir(dir.path())?.count(),
+            1,
+            "no temporary file was left behind"
+        );
+        Ok(())
+    }
 }
 
 mod contains {
@@ -170,7 +266,7 @@ mod find {
 
     use crate::{
         hex_to_id,
-        store::loose::{ldb, locate_oid, signature},
+        store::loose::{ldb, locate_oid, object_ids, signature},
     };
 
     fn find<'a>(hex: &str, buf: &'a mut Vec<u8>) -> git_object::Data<'a> {
@@ -291,6 +387,36 @@ cjHJZXWmV4CcRfmLsXzU8s2cR9A0DBvOxhPD1TlKC2JhBFXigjuL9U4Rbq9tdegB
         Ok(())
     }
 
+    #[test]
+    fn small_read_buffers_yield_the_same_objects() -> crate::Result {
+        let mut expected = Vec::new();
+        let mut actual = Vec::new();
+        for read_buffer_size in [1, 7, 64, 8192] {
+            let db = ldb().with_read_buffer_size(Some(read_buffer_size));
+            for id in object_ids() {
+                let expected = ldb().try_find(id, &mut expected)?.expect("present");
+                let actual = db.try_find(id, &mut actual)?.expect("present");
+                assert_eq!(actual.kind, expected.kind);
+                assert_eq!(actual.data, expected.data, "buffer size {}", read_buffer_size);
+            }
+            assert_eq!(
+                db.try_find(hex_to_id("37d4e6c5c48ba0d245164c4e10d5f41140cab989"), &mut actual)?,
+                None
+            );
+        }
+        let tmp = git_testtools::tempfile::tempdir()?;
+        let base = tmp.path().join("aa");
+        std::fs::create_dir(&base)?;
+        std::fs::write(base.join("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), [])?;
+        let db = loose::Store::at(tmp.path(), git_hash::Kind::Sha1).with_read_buffer_size(Some(16));
+        assert!(
+            db.try_find(hex_to_id("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), &mut actual)
+                .is_err(),
+            "invalid objects are errors"
+        );
+        Ok(())
+    }
+
     fn try_locate<'a>(hex: &str, buf: &'a mut Vec<u8>) -> Option<git_object::Data<'a>> {
         ldb().try_find(hex_to_id(hex), buf).ok().flatten()
     }
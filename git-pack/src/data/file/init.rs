@@ -1,4 +1,8 @@
-use std::{convert::TryInto, path::Path};
+use std::{
+    convert::TryInto,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::data;
 
@@ -12,14 +16,49 @@ impl data::File {
         Self::at_inner(path.as_ref(), object_hash)
     }
 
-    fn at_inner(path: &Path, object_hash: git_hash::Kind) -> Result<data::File, data::header::decode::Error> {
-        use crate::data::header::N32_SIZE;
-        let hash_len = object_hash.len_in_bytes();
+    /// Return a new instance identified by `id` which shares the memory map of this one instead of opening the file again.
+    ///
+    /// This allows object stores which assign their own ids to packs to use the same pack without mapping it multiple times.
+    pub fn clone_with_id(&self, id: data::Id) -> data::File {
+        data::File {
+            data: std::sync::Arc::clone(&self.data),
+            path: self.path.clone(),
+            id,
+            version: self.version,
+            num_objects: self.num_objects,
+            hash_len: self.hash_len,
+            object_hash: self.object_hash,
+        }
+    }
+
+    /// Instantiate a pack data file from its `data` in memory, which is identified by `path` as if it was read from there.
+    ///
+    /// This allows to read packs that don't exist as a file on the local filesystem. `object_hash` is used as in
+    /// [`at()`][data::File::at()], and the [`id`][data::File::id] is derived from `path` as well.
+    pub fn from_bytes(
+        data: impl Into<Arc<[u8]>>,
+        path: impl Into<PathBuf>,
+        object_hash: git_hash::Kind,
+    ) -> Result<data::File, data::header::decode::Error> {
+        Self::from_data(crate::mmap::Data::InMemory(data.into()), path.into(), object_hash)
+    }
 
+    fn at_inner(path: &Path, object_hash: git_hash::Kind) -> Result<data::File, data::header::decode::Error> {
         let data = crate::mmap::read_only(path).map_err(|e| data::header::decode::Error::Io {
             source: e,
             path: path.to_owned(),
         })?;
+        Self::from_data(data.into(), path.to_owned(), object_hash)
+    }
+
+    fn from_data(
+        data: crate::mmap::Data,
+        path: PathBuf,
+        object_hash: git_hash::Kind,
+    ) -> Result<data::File, data::header::decode::Error> {
+        use crate::data::header::N32_SIZE;
+        let hash_len = object_hash.len_in_bytes();
+
         let pack_len = data.len();
         if pack_len < N32_SIZE * 3 + hash_len {
             return Err(data::header::decode::Error::Corrupt(format!(
@@ -30,9 +69,9 @@ impl data::File {
         let (kind, num_objects) =
             data::header::decode(&data[..12].try_into().expect("enough data after previous check"))?;
         Ok(data::File {
-            data,
-            path: path.to_owned(),
+            data: Arc::new(data),
             id: git_features::hash::crc32(path.as_os_str().to_string_lossy().as_bytes()),
+            path,
             version: kind,
             num_objects,
             hash_len,
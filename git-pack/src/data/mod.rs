@@ -7,8 +7,6 @@ pub type Offset = u64;
 /// An identifier to uniquely identify all packs loaded within a known context or namespace.
 pub type Id = u32;
 
-use memmap2::Mmap;
-
 /// An representing an full- or delta-object within a pack
 #[derive(PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone)]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
@@ -64,7 +62,7 @@ impl Default for Version {
 
 /// A pack data file
 pub struct File {
-    data: Mmap,
+    data: std::sync::Arc<crate::mmap::Data>,
     path: std::path::PathBuf,
     /// A value to represent this pack uniquely when used with cache lookup, or a way to identify this pack by its location on disk.
     /// The same location on disk should yield the same id.
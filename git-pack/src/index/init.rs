@@ -1,4 +1,8 @@
-use std::{mem::size_of, path::Path};
+use std::{
+    mem::size_of,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::index::{self, Version, FAN_LEN, V2_SIGNATURE};
 
@@ -13,7 +17,7 @@ pub enum Error {
     },
     #[error("{message}")]
     Corrupt { message: String },
-    #[error("Unsupported index version: {version})")]
+    #[error("Unsupported index version: {version}")]
     UnsupportedVersion { version: u32 },
 }
 
@@ -29,11 +33,27 @@ impl index::File {
         Self::at_inner(path.as_ref(), object_hash)
     }
 
+    /// Instantiate a pack index from its `data` in memory, which is identified by `path` as if it was read from there.
+    ///
+    /// This allows to read pack indices that don't exist as a file on the local filesystem. `object_hash` is used as in
+    /// [`at()`][index::File::at()].
+    pub fn from_bytes(
+        data: impl Into<Arc<[u8]>>,
+        path: impl Into<PathBuf>,
+        object_hash: git_hash::Kind,
+    ) -> Result<index::File, Error> {
+        Self::from_data(crate::mmap::Data::InMemory(data.into()), path.into(), object_hash)
+    }
+
     fn at_inner(path: &Path, object_hash: git_hash::Kind) -> Result<index::File, Error> {
         let data = crate::mmap::read_only(path).map_err(|source| Error::Io {
             source,
             path: path.to_owned(),
         })?;
+        Self::from_data(data.into(), path.to_owned(), object_hash)
+    }
+
+    fn from_data(data: crate::mmap::Data, path: PathBuf, object_hash: git_hash::Kind) -> Result<index::File, Error> {
         let idx_len = data.len();
         let hash_len = object_hash.len_in_bytes();
 
@@ -72,7 +92,7 @@ impl index::File {
         };
         Ok(index::File {
             data,
-            path: path.to_owned(),
+            path,
             version: kind,
             num_objects,
             fan,
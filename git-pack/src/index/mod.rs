@@ -73,8 +73,6 @@ macro_rules! izip {
     };
 }
 
-use memmap2::Mmap;
-
 /// The version of an index file
 #[derive(PartialEq, Eq, Ord, PartialOrd, Debug, Hash, Clone, Copy)]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
@@ -110,7 +108,7 @@ const FAN_LEN: usize = 256;
 
 /// A representation of a pack index file
 pub struct File {
-    data: Mmap,
+    data: crate::mmap::Data,
     path: std::path::PathBuf,
     version: Version,
     num_objects: u32,
@@ -137,6 +135,10 @@ impl File {
     pub fn object_hash(&self) -> git_hash::Kind {
         self.object_hash
     }
+    /// The length of all mapped data, including the header and the trailing checksums.
+    pub fn data_len(&self) -> usize {
+        self.data.len()
+    }
 }
 
 const V2_SIGNATURE: &[u8] = b"\xfftOc";
@@ -146,6 +148,8 @@ pub mod init;
 pub(crate) mod access;
 pub use access::Entry;
 
+pub mod reverse;
+
 ///
 pub mod traverse;
 mod util;
@@ -0,0 +1,205 @@
+//! A reverse index maps pack offsets to the position of their entries in the pack index, as stored in `.rev` files by git.
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{data, index, index::EntryIndex};
+
+const SIGNATURE: &[u8] = b"RIDX";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = SIGNATURE.len() + 4 /*version*/ + 4 /*hash id*/;
+const N32_SIZE: usize = std::mem::size_of::<u32>();
+
+/// Returned by [`reverse::File::at()`][File::at()].
+#[derive(thiserror::Error, Debug)]
+#[allow(missing_docs)]
+pub enum Error {
+    #[error("Could not open reverse index file at '{path}'")]
+    Io {
+        source: std::io::Error,
+        path: std::path::PathBuf,
+    },
+    #[error("{message}")]
+    Corrupt { message: String },
+    #[error("Unsupported reverse index version: {version}")]
+    UnsupportedVersion { version: u32 },
+}
+
+enum Positions {
+    Mapped(crate::mmap::Data),
+    Computed(Vec<EntryIndex>),
+}
+
+/// The positions of all entries of a pack index in the order of their pack offsets, either read from a `.rev` file or computed
+/// from the pack index itself.
+pub struct File {
+    positions: Positions,
+    path: Option<PathBuf>,
+    num_objects: u32,
+}
+
+/// Instantiation
+impl File {
+    /// Open the reverse index file at `path` which belongs to `index`.
+    ///
+    /// All entries are validated to refer to entries of `index` in the order of their pack offsets, and the reverse index must
+    /// be for the same pack as `index`, so a corrupt or stale file is reported as [`Error::Corrupt`] instead of producing
+    /// wrong answers later.
+    pub fn at(path: impl AsRef<Path>, index: &index::File) -> Result<File, Error> {
+        Self::at_inner(path.as_ref(), index)
+    }
+
+    /// Instantiate the reverse index of `index` from its `data` in memory, identified by `path` as if it was read from there.
+    ///
+    /// It's validated just like in [`at()`][File::at()].
+    pub fn from_bytes(
+        data: impl Into<Arc<[u8]>>,
+        path: impl Into<PathBuf>,
+        index: &index::File,
+    ) -> Result<File, Error> {
+        Self::from_data(crate::mmap::Data::InMemory(data.into()), path.into(), index)
+    }
+
+    fn at_inner(path: &Path, index: &index::File) -> Result<File, Error> {
+        let data = crate::mmap::read_only(path).map_err(|source| Error::Io {
+            source,
+            path: path.to_owned(),
+        })?;
+        Self::from_data(data.into(), path.to_owned(), index)
+    }
+
+    fn from_data(data: crate::mmap::Data, path: PathBuf, index: &index::File) -> Result<File, Error> {
+        let (object_hash, num_objects) = (index.object_hash(), index.num_objects());
+        let expected_len = HEADER_LEN + num_objects as usize * N32_SIZE + object_hash.len_in_bytes() * 2;
+        if data.len() != expected_len {
+            return Err(Error::Corrupt {
+                message: format!(
+                    "Reverse index of size {} doesn't have the expected size of {} for {} objects",
+                    data.len(),
+                    expected_len,
+                    num_objects
+                ),
+            });
+        }
+        if &data[..SIGNATURE.len()] != SIGNATURE {
+            return Err(Error::Corrupt {
+                message: "Reverse index signature is invalid".into(),
+            });
+        }
+        let version = crate::read_u32(&data[SIGNATURE.len()..][..N32_SIZE]);
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion { version });
+        }
+        let hash_id = crate::read_u32(&data[SIGNATURE.len() + N32_SIZE..][..N32_SIZE]);
+        let expected_hash_id = match object_hash {
+            git_hash::Kind::Sha1 => 1,
+        };
+        if hash_id != expected_hash_id {
+            return Err(Error::Corrupt {
+                message: format!(
+                    "Reverse index is for hash with id {}, but {} was expected",
+                    hash_id, expected_hash_id
+                ),
+            });
+        }
+        let pack_checksum = &data[HEADER_LEN + num_objects as usize * N32_SIZE..][..object_hash.len_in_bytes()];
+        if pack_checksum != index.pack_checksum().as_slice() {
+            return Err(Error::Corrupt {
+                message: format!(
+                    "Reverse index is for pack {}, but its index is for pack {}",
+                    git_hash::ObjectId::from(pack_checksum),
+                    index.pack_checksum()
+                ),
+            });
+        }
+        let mut previous_pack_offset = None;
+        for entry in data[HEADER_LEN..][..num_objects as usize * N32_SIZE].chunks(N32_SIZE) {
+            let entry_index = crate::read_u32(entry);
+            if entry_index >= num_objects {
+                return Err(Error::Corrupt {
+                    message: format!(
+                        "Reverse index refers to entry {} of an index with only {} objects",
+                        entry_index, num_objects
+                    ),
+                });
+            }
+            let pack_offset = index.pack_offset_at_index(entry_index);
+            if previous_pack_offset.map_or(false, |previous| previous >= pack_offset) {
+                return Err(Error::Corrupt {
+                    message: format!(
+                        "Reverse index lists the entry at pack offset {} out of order",
+                        pack_offset
+                    ),
+                });
+            }
+            previous_pack_offset = Some(pack_offset);
+        }
+        Ok(File {
+            positions: Positions::Mapped(data),
+            path: Some(path),
+            num_objects,
+        })
+    }
+
+    /// Compute the reverse index of `index` in memory, which is what git does as well if there is no `.rev` file.
+    pub fn from_index(index: &index::File) -> File {
+        let mut positions: Vec<EntryIndex> = (0..index.num_objects()).collect();
+        positions.sort_by_key(|entry_index| index.pack_offset_at_index(*entry_index));
+        File {
+            positions: Positions::Computed(positions),
+            path: None,
+            num_objects: index.num_objects(),
+        }
+    }
+}
+
+/// Access
+impl File {
+    /// The path of the `.rev` file we were read from, or `None` if we were computed from the pack index.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The amount of entries, which is the same as the amount of objects in the pack index.
+    pub fn num_objects(&self) -> u32 {
+        self.num_objects
+    }
+
+    /// Return the position of the entry in the pack index that is the `n`th entry in the pack.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is out of bounds.
+    pub fn index_at_position(&self, n: u32) -> EntryIndex {
+        match &self.positions {
+            Positions::Mapped(data) => crate::read_u32(&data[HEADER_LEN + n as usize * N32_SIZE..][..N32_SIZE]),
+            Positions::Computed(positions) => positions[n as usize],
+        }
+    }
+
+    /// Return the position in `index` of the entry that starts at `pack_offset`, or `None` if no entry starts there.
+    ///
+    /// `index` must be the pack index we belong to. This is a binary search, as opposed to a linear search through the pack index.
+    pub fn lookup(&self, index: &index::File, pack_offset: data::Offset) -> Option<EntryIndex> {
+        self.position(index, pack_offset).map(|n| self.index_at_position(n))
+    }
+
+    /// Return the position in the pack of the entry that starts at `pack_offset`, so that `0` is the first entry in the pack,
+    /// or `None` if no entry starts there.
+    ///
+    /// `index` must be the pack index we belong to. The entry following it in the pack is at the returned position plus one.
+    pub fn position(&self, index: &index::File, pack_offset: data::Offset) -> Option<u32> {
+        let (mut low, mut high) = (0, self.num_objects);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let entry_index = self.index_at_position(mid);
+            match index.pack_offset_at_index(entry_index).cmp(&pack_offset) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+}
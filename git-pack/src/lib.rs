@@ -49,7 +49,7 @@ pub mod multi_index;
 pub mod verify;
 
 mod mmap {
-    use std::path::Path;
+    use std::{path::Path, sync::Arc};
 
     pub fn read_only(path: &Path) -> std::io::Result<memmap2::Mmap> {
         let file = std::fs::File::open(path)?;
@@ -59,6 +59,29 @@ mod mmap {
             memmap2::Mmap::map(&file)
         }
     }
+
+    /// The content of a file, either memory-mapped from disk or provided as bytes in memory.
+    pub enum Data {
+        Mapped(memmap2::Mmap),
+        InMemory(Arc<[u8]>),
+    }
+
+    impl std::ops::Deref for Data {
+        type Target = [u8];
+
+        fn deref(&self) -> &Self::Target {
+            match self {
+                Data::Mapped(map) => map,
+                Data::InMemory(bytes) => bytes,
+            }
+        }
+    }
+
+    impl From<memmap2::Mmap> for Data {
+        fn from(map: memmap2::Mmap) -> Self {
+            Data::Mapped(map)
+        }
+    }
 }
 
 use std::convert::TryInto;
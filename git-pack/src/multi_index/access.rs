@@ -59,6 +59,10 @@ impl File {
     pub fn index_names(&self) -> &[PathBuf] {
         &self.index_names
     }
+    /// Returns the length of all mapped data, including the header, all chunks and the trailing checksum.
+    pub fn data_len(&self) -> usize {
+        self.data.len()
+    }
 }
 
 impl File {
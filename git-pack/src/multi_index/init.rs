@@ -1,4 +1,8 @@
-use std::{convert::TryFrom, path::Path};
+use std::{
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::multi_index::{chunk, File, Version};
 
@@ -16,9 +20,9 @@ mod error {
         },
         #[error("{message}")]
         Corrupt { message: &'static str },
-        #[error("Unsupported multi-index version: {version})")]
+        #[error("Unsupported multi-index version: {version}")]
         UnsupportedVersion { version: u8 },
-        #[error("Unsupported hash kind: {kind})")]
+        #[error("Unsupported hash kind: {kind}")]
         UnsupportedObjectHash { kind: u8 },
         #[error(transparent)]
         ChunkFileDecode(#[from] git_chunk::file::decode::Error),
@@ -43,6 +47,13 @@ impl File {
     pub fn at(path: impl AsRef<Path>) -> Result<Self, Error> {
         Self::try_from(path.as_ref())
     }
+
+    /// Instantiate a multi-index from its `data` in memory, which is identified by `path` as if it was read from there.
+    ///
+    /// This allows to read multi-indices that don't exist as a file on the local filesystem.
+    pub fn from_bytes(data: impl Into<Arc<[u8]>>, path: impl Into<PathBuf>) -> Result<Self, Error> {
+        Self::from_data(crate::mmap::Data::InMemory(data.into()), path.into())
+    }
 }
 
 impl TryFrom<&Path> for File {
@@ -53,7 +64,12 @@ impl TryFrom<&Path> for File {
             source,
             path: path.to_owned(),
         })?;
+        Self::from_data(data.into(), path.to_owned())
+    }
+}
 
+impl File {
+    fn from_data(data: crate::mmap::Data, path: PathBuf) -> Result<Self, Error> {
         const TRAILER_LEN: usize = git_hash::Kind::shortest().len_in_bytes(); /* trailing hash */
         if data.len()
             < Self::HEADER_LEN
@@ -141,7 +157,7 @@ impl TryFrom<&Path> for File {
 
         Ok(File {
             data,
-            path: path.to_owned(),
+            path,
             version,
             hash_len: object_hash.len_in_bytes(),
             object_hash,
@@ -1,7 +1,5 @@
 use std::path::PathBuf;
 
-use memmap2::Mmap;
-
 /// Known multi-index file versions
 #[derive(PartialEq, Eq, Ord, PartialOrd, Debug, Hash, Clone, Copy)]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
@@ -25,7 +23,7 @@ pub type EntryIndex = u32;
 /// A representation of an index file for multiple packs at the same time, typically stored in a file
 /// named 'multi-pack-index'.
 pub struct File {
-    data: Mmap,
+    data: crate::mmap::Data,
     path: std::path::PathBuf,
     version: Version,
     hash_len: usize,